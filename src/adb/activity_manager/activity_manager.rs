@@ -0,0 +1,55 @@
+use std::error::Error;
+use crate::adb::activity_manager::intent_builder::IntentBuilder;
+use crate::adb::client::Client;
+use crate::constants::{AM_BROADCAST, AM_COMMAND, AM_FORCE_STOP, AM_START, AM_START_SERVICE};
+use crate::enums::device_transport::DeviceTransport;
+use crate::models::broadcast_result::BroadcastResult;
+use crate::models::launch_result::LaunchResult;
+use crate::utils::shell_quote;
+
+const AM_WAIT_OPTION: &str = "-W";
+const AM_ERROR_PREFIX: &str = "Error";
+
+impl Client {
+    pub async fn adb_am_start(&mut self, device_transport: DeviceTransport, intent: &IntentBuilder, wait: bool) -> Result<LaunchResult, Box<dyn Error>> {
+        let options: &[&str] = if wait { &[AM_WAIT_OPTION] } else { &[] };
+        let command = intent.render(AM_START, options);
+        let output = self.adb_shell_output(device_transport, &command).await?;
+        LaunchResult::from_output(&output)
+    }
+
+    pub async fn adb_am_broadcast(&mut self, device_transport: DeviceTransport, intent: &IntentBuilder) -> Result<BroadcastResult, Box<dyn Error>> {
+        let command = intent.render(AM_BROADCAST, &[]);
+        let output = self.adb_shell_output(device_transport, &command).await?;
+        BroadcastResult::from_output(&output)
+    }
+
+    pub async fn adb_am_start_service(&mut self, device_transport: DeviceTransport, intent: &IntentBuilder) -> Result<String, Box<dyn Error>> {
+        let command = intent.render(AM_START_SERVICE, &[]);
+        let output = self.adb_shell_output(device_transport, &command).await?;
+        Self::check_am_output(output)
+    }
+
+    pub async fn adb_am_force_stop(&mut self, device_transport: DeviceTransport, package_name: &str) -> Result<(), Box<dyn Error>> {
+        if package_name.is_empty() {
+            return Err("Package name is required".into());
+        }
+
+        let command = format!("{} {} {}", AM_COMMAND, AM_FORCE_STOP, shell_quote(package_name));
+        let output = self.adb_shell_output(device_transport, &command).await?;
+        Self::check_am_output(output).map(|_| ())
+    }
+
+    fn check_am_output(output: String) -> Result<String, Box<dyn Error>> {
+        let errors: Vec<&str> = output.lines()
+            .map(str::trim)
+            .filter(|line| line.starts_with(AM_ERROR_PREFIX))
+            .collect();
+
+        if errors.is_empty() {
+            Ok(output)
+        } else {
+            Err(errors.join("\n").into())
+        }
+    }
+}
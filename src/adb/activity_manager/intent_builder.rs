@@ -0,0 +1,160 @@
+use crate::constants::AM_COMMAND;
+use crate::enums::intent_extra::IntentExtra;
+use crate::utils::shell_quote;
+
+const ACTION_FLAG: &str = "-a";
+const DATA_URI_FLAG: &str = "-d";
+const MIME_TYPE_FLAG: &str = "-t";
+const CATEGORY_FLAG: &str = "-c";
+const COMPONENT_FLAG: &str = "-n";
+const FLAGS_FLAG: &str = "-f";
+const USER_FLAG: &str = "--user";
+
+#[derive(Clone, Debug, Default)]
+pub struct IntentBuilder {
+    action: Option<String>,
+    component: Option<String>,
+    data_uri: Option<String>,
+    mime_type: Option<String>,
+    categories: Vec<String>,
+    extras: Vec<IntentExtra>,
+    flags: u32,
+    user: Option<String>,
+}
+
+impl IntentBuilder {
+    pub fn new() -> Self {
+        IntentBuilder::default()
+    }
+
+    pub fn action(mut self, action: &str) -> Self {
+        self.action = Some(action.to_string());
+        self
+    }
+
+    pub fn component(mut self, component: &str) -> Self {
+        self.component = Some(component.to_string());
+        self
+    }
+
+    pub fn data_uri(mut self, data_uri: &str) -> Self {
+        self.data_uri = Some(data_uri.to_string());
+        self
+    }
+
+    pub fn mime_type(mut self, mime_type: &str) -> Self {
+        self.mime_type = Some(mime_type.to_string());
+        self
+    }
+
+    pub fn category(mut self, category: &str) -> Self {
+        self.categories.push(category.to_string());
+        self
+    }
+
+    pub fn extra(mut self, extra: IntentExtra) -> Self {
+        self.extras.push(extra);
+        self
+    }
+
+    pub fn extra_string(self, key: &str, value: &str) -> Self {
+        self.extra(IntentExtra::String(key.to_string(), value.to_string()))
+    }
+
+    pub fn extra_int(self, key: &str, value: i32) -> Self {
+        self.extra(IntentExtra::Int(key.to_string(), value))
+    }
+
+    pub fn extra_long(self, key: &str, value: i64) -> Self {
+        self.extra(IntentExtra::Long(key.to_string(), value))
+    }
+
+    pub fn extra_bool(self, key: &str, value: bool) -> Self {
+        self.extra(IntentExtra::Bool(key.to_string(), value))
+    }
+
+    pub fn extra_float(self, key: &str, value: f32) -> Self {
+        self.extra(IntentExtra::Float(key.to_string(), value))
+    }
+
+    pub fn extra_uri(self, key: &str, value: &str) -> Self {
+        self.extra(IntentExtra::Uri(key.to_string(), value.to_string()))
+    }
+
+    pub fn extra_string_array(self, key: &str, values: &[&str]) -> Self {
+        self.extra(IntentExtra::StringArray(key.to_string(), values.iter().map(|v| v.to_string()).collect()))
+    }
+
+    pub fn extra_int_array(self, key: &str, values: &[i32]) -> Self {
+        self.extra(IntentExtra::IntArray(key.to_string(), values.to_vec()))
+    }
+
+    pub fn extra_long_array(self, key: &str, values: &[i64]) -> Self {
+        self.extra(IntentExtra::LongArray(key.to_string(), values.to_vec()))
+    }
+
+    pub fn extra_float_array(self, key: &str, values: &[f32]) -> Self {
+        self.extra(IntentExtra::FloatArray(key.to_string(), values.to_vec()))
+    }
+
+    pub fn flags(mut self, flags: u32) -> Self {
+        self.flags |= flags;
+        self
+    }
+
+    pub fn user(mut self, user: &str) -> Self {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Intent arguments, each already quoted for the device shell.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+
+        if let Some(user) = &self.user {
+            args.push(USER_FLAG.to_string());
+            args.push(shell_quote(user));
+        }
+        if let Some(action) = &self.action {
+            args.push(ACTION_FLAG.to_string());
+            args.push(shell_quote(action));
+        }
+        if let Some(data_uri) = &self.data_uri {
+            args.push(DATA_URI_FLAG.to_string());
+            args.push(shell_quote(data_uri));
+        }
+        if let Some(mime_type) = &self.mime_type {
+            args.push(MIME_TYPE_FLAG.to_string());
+            args.push(shell_quote(mime_type));
+        }
+        for category in &self.categories {
+            args.push(CATEGORY_FLAG.to_string());
+            args.push(shell_quote(category));
+        }
+        for extra in &self.extras {
+            args.push(extra.flag().to_string());
+            args.push(shell_quote(extra.key()));
+            if let Some(value) = extra.value() {
+                args.push(shell_quote(&value));
+            }
+        }
+        if self.flags != 0 {
+            args.push(FLAGS_FLAG.to_string());
+            args.push(format!("0x{:x}", self.flags));
+        }
+        if let Some(component) = &self.component {
+            args.push(COMPONENT_FLAG.to_string());
+            args.push(shell_quote(component));
+        }
+
+        args
+    }
+
+    /// Renders a full `am <subcommand> [options] <intent>` command line.
+    pub fn render(&self, subcommand: &str, options: &[&str]) -> String {
+        let mut parts = vec![AM_COMMAND.to_string(), subcommand.to_string()];
+        parts.extend(options.iter().map(|option| option.to_string()));
+        parts.extend(self.to_args());
+        parts.join(" ")
+    }
+}
@@ -0,0 +1,2 @@
+pub mod activity_manager;
+pub mod intent_builder;
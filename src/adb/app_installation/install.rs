@@ -21,7 +21,7 @@ impl Client {
         }
 
         if flags.contains(&INSTALL_FLAG_DOWNGRADE.to_string()) && flags.contains(&INSTALL_FLAG_REPLACE.to_string()) {
-            println!("Warning: {} and {} flags may not work together on some Android versions", INSTALL_FLAG_DOWNGRADE, INSTALL_FLAG_REPLACE);
+            tracing::warn!("{} and {} flags may not work together on some Android versions", INSTALL_FLAG_DOWNGRADE, INSTALL_FLAG_REPLACE);
         }
 
         let apk_filename = Path::new(local_apk_path)
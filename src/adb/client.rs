@@ -1,65 +1,167 @@
-use std::{env};
 use std::error::Error;
-use tokio::net::TcpStream;
-use tokio::time::{timeout, Duration};
+use std::path::PathBuf;
+use std::sync::Arc;
+use futures::FutureExt;
+use tokio::time::{timeout, Duration, Instant};
 use std::io::{ErrorKind};
-use tokio::io::{AsyncWriteExt};
-use crate::constants::{DEFAULT_ADB_SERVER_IP, DEFAULT_ADB_SERVER_PORT, ADB_ADDRESS_ENV, ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio_util::sync::CancellationToken;
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::io::io_limits::IoLimits;
+use crate::adb::transport::transport::{AdbStream, Transport};
+use crate::constants::{ADB_ADDRESS_ENV, ADB_SERVER_SOCKET_ENV};
+use crate::enums::device_transport::DeviceTransport;
+
+/// Connection to the server; reads are buffered so data can be looked at without consuming it.
+pub(crate) type ClientStream = BufReader<Box<dyn AdbStream>>;
 
 pub struct Client {
-    pub adb_stream: TcpStream,
-    server_address: Option<String>,
-    server_port: Option<u16>,
+    pub(crate) adb_stream: ClientStream,
+    transport: Arc<dyn Transport>,
+    config: ClientBuilder,
+    pub(crate) transport_id: Option<u64>,
+    pub(crate) limits: IoLimits,
 }
 
 impl Client {
+    /// Connects to the server at the given address. When neither part is given, `ADB_ADDRESS` or
+    /// `ADB_SERVER_SOCKET` picks the server; a missing part defaults to 127.0.0.1:5037. Use
+    /// [`ClientBuilder`] for the other settings.
     pub async fn new(server_address: Option<String>, server_port: Option<u16>) -> Result<Self, Box<dyn Error>> {
-        let (adb_stream, _adb_server_addr) = Self::connect(server_address.clone(), server_port).await?;
+        let mut builder = ClientBuilder::new().apply_env(server_address.is_none() && server_port.is_none())?;
+        if let Some(server_address) = server_address {
+            builder = builder.server_address(&server_address);
+        }
+        if let Some(server_port) = server_port {
+            builder = builder.server_port(server_port);
+        }
+        builder.build().await
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::new()
+    }
+
+    pub(crate) async fn connect_with(config: ClientBuilder) -> Result<Self, Box<dyn Error>> {
+        let transport = config.resolve_transport();
+        let adb_stream = Self::connect(transport.as_ref(), &config).await?;
 
         Ok(Client {
             adb_stream,
-            server_address,
-            server_port,
+            transport,
+            transport_id: None,
+            limits: IoLimits { io_timeout: config.io_timeout, ..IoLimits::default() },
+            config,
         })
     }
 
-    async fn connect(server_address: Option<String>, server_port: Option<u16>) -> Result<(TcpStream, String), Box<dyn Error>> {
-        let adb_server_addr = match (server_address, server_port) {
-            (Some(addr), Some(port)) => format!("{}:{}", addr, port),
-            (Some(addr), None) => format!("{}:{}", addr, DEFAULT_ADB_SERVER_PORT),
-            (None, Some(port)) => format!("{}:{}", DEFAULT_ADB_SERVER_IP, port),
-            (None, None) => env::var(ADB_ADDRESS_ENV).unwrap_or_else(|_| {
-                format!("{}:{}", DEFAULT_ADB_SERVER_IP, DEFAULT_ADB_SERVER_PORT)
-            }),
-        };
+    /// Opens another connection to the same server that shares this client's timeouts, deadline
+    /// and cancellation token, for services that need a connection of their own.
+    pub async fn new_connection(&self) -> Result<Client, Box<dyn Error>> {
+        Self::connect_with_limits(self.config.clone(), self.limits.clone()).await
+    }
 
+    /// [`Client::new_connection`] for callers that can't borrow the client while its stream is in use.
+    pub(crate) async fn connect_with_limits(config: ClientBuilder, limits: IoLimits) -> Result<Client, Box<dyn Error>> {
+        let mut client = Self::connect_with(config).await?;
+        client.limits = limits;
+        Ok(client)
+    }
+
+    /// Connects, retrying as the retry policy allows.
+    async fn connect(transport: &dyn Transport, config: &ClientBuilder) -> Result<ClientStream, Box<dyn Error>> {
+        let mut retries_left = config.retry_policy.retries;
+        loop {
+            match Self::connect_once(transport, config.connect_timeout).await {
+                Err(err) if retries_left > 0 => {
+                    retries_left -= 1;
+                    tracing::debug!("{}; retrying in {:?}", err, config.retry_policy.delay);
+                    tokio::time::sleep(config.retry_policy.delay).await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn connect_once(transport: &dyn Transport, connect_timeout: Duration) -> Result<ClientStream, Box<dyn Error>> {
         let change_env_message = format!(
-            "You can change the ADB server address by setting the {} environment variable (e.g., export {}=127.0.0.1:5037)",
-            ADB_ADDRESS_ENV, ADB_ADDRESS_ENV
+            "You can change the ADB server address by setting the {} or {} environment variable (e.g., export {}=127.0.0.1:5037)",
+            ADB_ADDRESS_ENV, ADB_SERVER_SOCKET_ENV, ADB_ADDRESS_ENV
         );
 
-        let adb_stream = match timeout(
-            Duration::from_secs(ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION),
-            TcpStream::connect(&adb_server_addr),
-        ).await {
+        let adb_stream = match timeout(connect_timeout, transport.connect()).await {
             Ok(Ok(stream)) => stream,
             Ok(Err(e)) => return Err(format!(
                 "Failed to connect to ADB server at address {}: {}. {}",
-                adb_server_addr, e, change_env_message
+                transport, e, change_env_message
             ).into()),
             Err(_) => return Err(format!(
                 "Connection attempt to ADB server at address {} timed out. {}",
-                adb_server_addr, change_env_message
+                transport, change_env_message
             ).into()),
         };
 
-        Ok((adb_stream, adb_server_addr))
+        Ok(BufReader::new(adb_stream))
+    }
+
+    /// Where this client connects, e.g. `127.0.0.1:5037` or `localfilesystem:/tmp/adb.sock`.
+    pub fn server(&self) -> String {
+        self.transport.to_string()
+    }
+
+    pub fn server_address(&self) -> Option<String> {
+        self.config.server_address.clone()
+    }
+
+    pub fn server_port(&self) -> Option<u16> {
+        self.config.server_port
+    }
+
+    /// Settings this client was connected with, e.g. to connect more clients like it.
+    pub fn config(&self) -> &ClientBuilder {
+        &self.config
+    }
+
+    /// Device configured with [`ClientBuilder::device`] (or `ANDROID_SERIAL`), else any single device.
+    pub fn default_device(&self) -> DeviceTransport {
+        self.config.default_device()
+    }
+
+    /// Private keys configured with [`ClientBuilder::key_path`].
+    pub fn key_paths(&self) -> &[PathBuf] {
+        &self.config.key_paths
+    }
+
+    /// Sets how long a single protocol read or write may take before failing with
+    /// [`Interrupted::TimedOut`](crate::Interrupted::TimedOut); `None` waits forever.
+    pub fn set_io_timeout(&mut self, io_timeout: Option<Duration>) {
+        self.limits.io_timeout = io_timeout;
+    }
+
+    /// Makes every following operation fail with `Interrupted::TimedOut` once `deadline` passes,
+    /// e.g. `Some(Instant::now() + Duration::from_secs(30))` to bound the next call.
+    pub fn set_deadline(&mut self, deadline: Option<Instant>) {
+        self.limits.deadline = deadline;
+    }
+
+    /// Makes every following operation fail with [`Interrupted::Cancelled`](crate::Interrupted::Cancelled)
+    /// once `token` is cancelled. Files being pulled when that happens are removed.
+    pub fn set_cancellation_token(&mut self, token: Option<CancellationToken>) {
+        self.limits.cancellation = token;
+    }
+
+    pub fn limits(&self) -> &IoLimits {
+        &self.limits
+    }
+
+    /// Transport id reported by the server on the last `send_tport` call.
+    pub fn transport_id(&self) -> Option<u64> {
+        self.transport_id
     }
 
     pub async fn reconnect(&mut self) -> Result<(), Box<dyn Error>> {
         self.close().await;
-        let (new_stream, _) = Self::connect(self.server_address.clone(), self.server_port).await?;
-        self.adb_stream = new_stream;
+        self.adb_stream = Self::connect(self.transport.as_ref(), &self.config).await?;
         Ok(())
     }
 
@@ -68,17 +170,16 @@ impl Client {
             Ok(_) => {}
             Err(e) if e.kind() == ErrorKind::NotConnected => {}
             Err(e) => {
-                eprintln!("Error shutting down ADB stream: {}", e);
+                tracing::debug!("Error shutting down ADB stream: {}", e);
             }
         }
     }
-    pub async fn is_connected(&self) -> bool {
-        let mut buf = [0; 1];
-        match self.adb_stream.try_read(&mut buf) {
-            Ok(0) => false,
-            Ok(_) => true,
-            Err(ref e) if e.kind() == ErrorKind::WouldBlock => true,
-            Err(_) => false,
+    /// False once the server closed the connection; does not wait for data.
+    pub async fn is_connected(&mut self) -> bool {
+        match self.adb_stream.fill_buf().now_or_never() {
+            Some(Ok(data)) => !data.is_empty(),
+            Some(Err(_)) => false,
+            None => true,
         }
     }
 
@@ -0,0 +1,263 @@
+use std::error::Error;
+use std::path::PathBuf;
+use std::sync::Arc;
+use dirs::home_dir;
+use tokio::time::Duration;
+use crate::adb::client::Client;
+use crate::adb::recording::recording_transport::RecordingTransport;
+use crate::adb::recording::session_recorder::SessionRecorder;
+use crate::adb::transport::tcp_transport::TcpTransport;
+use crate::adb::transport::transport::Transport;
+use crate::adb::transport::unix_transport::UnixTransport;
+use crate::constants::{ADB_ADDRESS_ENV, ADBR_RECORD_ENV, ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION, ADB_SERVER_SOCKET_ENV, ANDROID_SERIAL_ENV, DEFAULT_ADB_SERVER_IP, DEFAULT_ADB_SERVER_PORT, DEFAULT_IO_TIMEOUT_SECS, LOCAL_ABSTRACT_PREFIX, LOCAL_FILESYSTEM_PREFIX, TCP_SOCKET_PREFIX};
+use crate::enums::device_transport::DeviceTransport;
+use crate::models::config_profile::ConfigProfile;
+use crate::models::retry_policy::RetryPolicy;
+use crate::utils::parse_duration;
+
+/// Settings for connecting a [`Client`].
+///
+/// Each call overrides what earlier calls set, so sources are layered by applying them in order.
+/// The `adbr` CLI uses defaults, then the config profile, then [`ClientBuilder::env`], then its
+/// command-line flags.
+#[derive(Debug, Clone)]
+pub struct ClientBuilder {
+    pub(crate) server_address: Option<String>,
+    pub(crate) server_port: Option<u16>,
+    pub(crate) transport: Option<Arc<dyn Transport>>,
+    pub(crate) connect_timeout: Duration,
+    pub(crate) io_timeout: Option<Duration>,
+    pub(crate) device: Option<DeviceTransport>,
+    pub(crate) key_paths: Vec<PathBuf>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) recorder: Option<Arc<SessionRecorder>>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            server_address: None,
+            server_port: None,
+            transport: None,
+            connect_timeout: Duration::from_secs(ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION),
+            io_timeout: Some(Duration::from_secs(DEFAULT_IO_TIMEOUT_SECS)),
+            device: None,
+            key_paths: Vec::new(),
+            retry_policy: RetryPolicy::default(),
+            recorder: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn server_address(mut self, server_address: &str) -> Self {
+        self.server_address = Some(server_address.to_string());
+        self.transport = None;
+        self
+    }
+
+    pub fn server_port(mut self, server_port: u16) -> Self {
+        self.server_port = Some(server_port);
+        self.transport = None;
+        self
+    }
+
+    /// Reaches the server on a unix socket instead of TCP.
+    pub fn unix_socket(self, path: impl Into<PathBuf>) -> Self {
+        self.transport(UnixTransport::new(path))
+    }
+
+    /// Reaches the server through `transport` instead of TCP, e.g. a tunnel or a
+    /// [`MemoryTransport`](crate::MemoryTransport) in tests.
+    pub fn transport(mut self, transport: impl Transport + 'static) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self.server_address = None;
+        self.server_port = None;
+        self
+    }
+
+    /// Selects the server with an adb socket spec: `tcp:[HOST:]PORT`, `localfilesystem:PATH` or
+    /// `localabstract:NAME`.
+    pub fn server_socket(self, spec: &str) -> Result<Self, Box<dyn Error>> {
+        if let Some(address) = spec.strip_prefix(TCP_SOCKET_PREFIX) {
+            return match address.parse::<u16>() {
+                Ok(port) => Ok(self.server_address(DEFAULT_ADB_SERVER_IP).server_port(port)),
+                Err(_) => match split_address(address) {
+                    Some((host, Some(port))) => Ok(self.server_address(host).server_port(port)),
+                    _ => Err(format!("Invalid server socket: {} (expected tcp:[HOST:]PORT)", spec).into()),
+                },
+            };
+        }
+        if let Some(path) = spec.strip_prefix(LOCAL_FILESYSTEM_PREFIX) {
+            return Ok(self.unix_socket(path));
+        }
+        if let Some(name) = spec.strip_prefix(LOCAL_ABSTRACT_PREFIX) {
+            return Ok(self.transport(UnixTransport::Abstract(name.to_string())));
+        }
+        Err(format!("Unsupported server socket: {} (expected tcp:, localfilesystem: or localabstract:)", spec).into())
+    }
+
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.connect_timeout = connect_timeout;
+        self
+    }
+
+    /// See [`Client::set_io_timeout`].
+    pub fn io_timeout(mut self, io_timeout: Option<Duration>) -> Self {
+        self.io_timeout = io_timeout;
+        self
+    }
+
+    /// Device used by commands that do not name one, see [`Client::default_device`].
+    pub fn device(mut self, device: DeviceTransport) -> Self {
+        self.device = Some(device);
+        self
+    }
+
+    /// Adds a private key to authenticate to devices with.
+    pub fn key_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.key_paths.push(path.into());
+        self
+    }
+
+    /// Keys added with [`ClientBuilder::key_path`] or by a profile.
+    pub fn key_paths(&self) -> &[PathBuf] {
+        &self.key_paths
+    }
+
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Records every byte exchanged with the server to `path`, see
+    /// [`ReplayServer`](crate::ReplayServer). Clients built from clones of this builder append to
+    /// the same recording.
+    pub fn record(mut self, path: impl AsRef<std::path::Path>) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let recorder = SessionRecorder::create(path).map_err(|err| format!("Cannot create recording {}: {}", path.display(), err))?;
+        self.recorder = Some(Arc::new(recorder));
+        Ok(self)
+    }
+
+    /// Applies the settings a config profile sets, leaving the others alone.
+    pub fn profile(mut self, profile: &ConfigProfile) -> Result<Self, Box<dyn Error>> {
+        if let Some(host) = &profile.host {
+            self = self.server_address(host);
+        }
+        if let Some(port) = profile.port {
+            self = self.server_port(port);
+        }
+        if let Some(socket) = &profile.socket {
+            self = self.unix_socket(expand_home(socket));
+        }
+        if let Some(connect_timeout) = &profile.connect_timeout {
+            self.connect_timeout = parse_duration(connect_timeout)?;
+        }
+        if let Some(io_timeout) = &profile.io_timeout {
+            self.io_timeout = Some(parse_duration(io_timeout)?).filter(|timeout| !timeout.is_zero());
+        }
+
+        let selectors = [profile.serial.is_some(), profile.transport_id.is_some(), profile.usb, profile.emulator];
+        if selectors.iter().filter(|selected| **selected).count() > 1 {
+            return Err("A profile can select a device by only one of serial, transport_id, usb and emulator".into());
+        }
+        if let Some(serial) = &profile.serial {
+            self.device = Some(DeviceTransport::serial(serial.clone()));
+        } else if let Some(transport_id) = profile.transport_id {
+            self.device = Some(DeviceTransport::transport_id(transport_id));
+        } else if profile.usb {
+            self.device = Some(DeviceTransport::default_usb());
+        } else if profile.emulator {
+            self.device = Some(DeviceTransport::default_emulator());
+        }
+
+        self.key_paths.extend(profile.keys.iter().map(|key| expand_home(key)));
+        if let Some(retries) = profile.retries {
+            self.retry_policy.retries = retries;
+        }
+        if let Some(retry_delay) = &profile.retry_delay {
+            self.retry_policy.delay = parse_duration(retry_delay)?;
+        }
+        Ok(self)
+    }
+
+    /// Applies `ADB_ADDRESS` (`host[:port]`), `ADB_SERVER_SOCKET` (see [`ClientBuilder::server_socket`],
+    /// wins over `ADB_ADDRESS`), `ANDROID_SERIAL` and `ADBR_RECORD` (see [`ClientBuilder::record`])
+    /// when they are set.
+    pub fn env(self) -> Result<Self, Box<dyn Error>> {
+        self.apply_env(true)
+    }
+
+    /// [`ClientBuilder::env`], leaving out `ADB_ADDRESS` and `ADB_SERVER_SOCKET` unless `server` is set.
+    pub(crate) fn apply_env(mut self, server: bool) -> Result<Self, Box<dyn Error>> {
+        if let Some(address) = std::env::var(ADB_ADDRESS_ENV).ok().filter(|address| server && !address.is_empty()) {
+            let (host, port) = split_address(&address)
+                .ok_or_else(|| format!("Invalid {}: {} (expected HOST or HOST:PORT)", ADB_ADDRESS_ENV, address))?;
+            self = self.server_address(host);
+            if let Some(port) = port {
+                self = self.server_port(port);
+            }
+        }
+        if let Some(spec) = std::env::var(ADB_SERVER_SOCKET_ENV).ok().filter(|spec| server && !spec.is_empty()) {
+            self = self.server_socket(&spec)?;
+        }
+        if let Some(serial) = std::env::var(ANDROID_SERIAL_ENV).ok().filter(|serial| !serial.is_empty()) {
+            self.device = Some(DeviceTransport::serial(serial));
+        }
+        if let Some(path) = std::env::var_os(ADBR_RECORD_ENV).filter(|path| !path.is_empty()) {
+            self = self.record(path)?;
+        }
+        Ok(self)
+    }
+
+    /// The device set with [`ClientBuilder::device`], or any single device.
+    pub fn default_device(&self) -> DeviceTransport {
+        self.device.clone().unwrap_or_else(DeviceTransport::default)
+    }
+
+    /// The TCP port clients of these settings connect to, where `adbr server` listens; `None`
+    /// when they use a unix socket or another transport.
+    pub fn tcp_port(&self) -> Option<u16> {
+        self.transport.is_none().then(|| self.server_port.unwrap_or(DEFAULT_ADB_SERVER_PORT))
+    }
+
+    /// The transport set on this builder, else TCP to the configured address; recorded when
+    /// [`ClientBuilder::record`] was called.
+    pub(crate) fn resolve_transport(&self) -> Arc<dyn Transport> {
+        let transport: Arc<dyn Transport> = match &self.transport {
+            Some(transport) => transport.clone(),
+            None => Arc::new(TcpTransport::new(
+                self.server_address.as_deref().unwrap_or(DEFAULT_ADB_SERVER_IP),
+                self.server_port.unwrap_or(DEFAULT_ADB_SERVER_PORT),
+            )),
+        };
+        match &self.recorder {
+            Some(recorder) => Arc::new(RecordingTransport::new(transport, recorder.clone())),
+            None => transport,
+        }
+    }
+
+    pub async fn build(self) -> Result<Client, Box<dyn Error>> {
+        Client::connect_with(self).await
+    }
+}
+
+fn split_address(address: &str) -> Option<(&str, Option<u16>)> {
+    match address.rsplit_once(':') {
+        // A bare IPv6 address has colons but no port.
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => Some((host, Some(port.parse().ok()?))),
+        _ => Some((address, None)),
+    }
+}
+
+fn expand_home(path: &std::path::Path) -> PathBuf {
+    match (path.strip_prefix("~"), home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
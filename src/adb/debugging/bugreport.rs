@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::Path;
+use serde::Serialize;
+use zip::ZipArchive;
+use crate::models::bugreport_section::BugreportSection;
+
+pub const CATEGORY_DUMPSYS: &str = "dumpsys";
+pub const CATEGORY_LOGCAT: &str = "logcat";
+pub const CATEGORY_ANR: &str = "anr";
+pub const CATEGORY_OTHER: &str = "other";
+
+const SECTION_MARKER: &str = "------ ";
+const SECTION_MARKER_END: &str = " ------";
+const DURATION_MARKER: &str = "was the duration of";
+const SERVICE_PREFIX: &str = "DUMP OF SERVICE ";
+const DUMPSYS_TITLE_PREFIX: &str = "DUMPSYS";
+const DUMPSYS_COMMAND: &str = "dumpsys";
+const LOGCAT_COMMAND: &str = "logcat";
+const ANR_TITLE_MARKERS: [&str; 2] = ["ANR", "TRACES"];
+const MAIN_ENTRY_FILE: &str = "main_entry.txt";
+const BUGREPORT_TEXT_PREFIX: &str = "bugreport";
+pub(crate) const ANR_DIR_PREFIX: &str = "FS/data/anr/";
+const SECTIONS_DIR: &str = "sections";
+const INDEX_FILE_NAME: &str = "index.json";
+
+/// What `extract_bugreport` found in a bugreport zip; also written to `index.json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct BugreportIndex {
+    pub main_file: String,
+    pub sections: Vec<BugreportSection>,
+    pub anr_files: Vec<String>,
+}
+
+/// Splits the main bugreport text into its `------ TITLE (command) ------` sections and the
+/// `DUMP OF SERVICE name:` blocks inside the dumpsys sections.
+pub fn parse_bugreport_sections(text: &str) -> Vec<BugreportSection> {
+    let mut sections: Vec<BugreportSection> = Vec::new();
+    let mut open_section: Option<usize> = None;
+    let mut open_service: Option<usize> = None;
+    let mut line_count = 0;
+
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        line_count = line_number;
+
+        if let Some(section) = parse_section_header(line) {
+            for open in [open_service.take(), open_section.take()].into_iter().flatten() {
+                sections[open].end_line = line_number - 1;
+            }
+            open_section = Some(sections.len());
+            sections.push(BugreportSection { start_line: line_number + 1, ..section });
+        } else if let Some(service) = parse_service_header(line) {
+            if let Some(open) = open_service.take() {
+                sections[open].end_line = line_number - 1;
+            }
+            open_service = Some(sections.len());
+            sections.push(BugreportSection { start_line: line_number + 1, ..service });
+        }
+    }
+
+    for open in [open_service, open_section].into_iter().flatten() {
+        sections[open].end_line = line_count;
+    }
+    sections
+}
+
+fn parse_section_header(line: &str) -> Option<BugreportSection> {
+    let inner = line.trim_end().strip_prefix(SECTION_MARKER)?.strip_suffix(SECTION_MARKER_END)?.trim();
+    if inner.contains(DURATION_MARKER) {
+        return None;
+    }
+
+    let (title, command) = match inner.split_once(" (") {
+        Some((title, command)) if inner.ends_with(')') => (title.trim(), Some(command[..command.len() - 1].to_string())),
+        _ => (inner, None),
+    };
+    let category = if title.starts_with(DUMPSYS_TITLE_PREFIX) {
+        CATEGORY_DUMPSYS
+    } else if ANR_TITLE_MARKERS.iter().any(|marker| title.contains(marker)) {
+        CATEGORY_ANR
+    } else if command.as_deref().is_some_and(|command| command.starts_with(LOGCAT_COMMAND)) {
+        CATEGORY_LOGCAT
+    } else {
+        CATEGORY_OTHER
+    };
+
+    Some(BugreportSection {
+        title: title.to_string(),
+        command,
+        category: category.to_string(),
+        start_line: 0,
+        end_line: 0,
+        file: None,
+    })
+}
+
+fn parse_service_header(line: &str) -> Option<BugreportSection> {
+    // "DUMP OF SERVICE activity:" or, on newer releases, "DUMP OF SERVICE CRITICAL SurfaceFlinger:"
+    let rest = line.trim_end().strip_prefix(SERVICE_PREFIX)?.strip_suffix(':')?;
+    let service = rest.split_whitespace().last()?;
+
+    Some(BugreportSection {
+        title: format!("{}{}", SERVICE_PREFIX, service),
+        command: Some(format!("{} {}", DUMPSYS_COMMAND, service)),
+        category: CATEGORY_DUMPSYS.to_string(),
+        start_line: 0,
+        end_line: 0,
+        file: None,
+    })
+}
+
+/// Reads the main text file of a bugreport zip, returning its name and contents.
+pub fn read_bugreport_text(zip_path: &Path) -> Result<(String, String), Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+    let main_file = main_entry_name(&mut archive)?;
+    let mut text = Vec::new();
+    archive.by_name(&main_file)?.read_to_end(&mut text)?;
+    Ok((main_file, String::from_utf8_lossy(&text).into_owned()))
+}
+
+fn main_entry_name<R: Read + io::Seek>(archive: &mut ZipArchive<R>) -> Result<String, Box<dyn Error>> {
+    if let Ok(mut entry) = archive.by_name(MAIN_ENTRY_FILE) {
+        let mut name = String::new();
+        entry.read_to_string(&mut name)?;
+        return Ok(name.trim().to_string());
+    }
+    archive.file_names()
+        .find(|name| !name.contains('/') && name.starts_with(BUGREPORT_TEXT_PREFIX) && name.ends_with(".txt"))
+        .map(str::to_string)
+        .ok_or_else(|| "No bugreport text file found in the archive".into())
+}
+
+/// Unzips a bugreport into `out_dir`, writes the dumpsys, logcat and ANR sections of the main
+/// text to `out_dir/sections/` and an `index.json` describing them.
+pub fn extract_bugreport(zip_path: &Path, out_dir: &Path) -> Result<BugreportIndex, Box<dyn Error>> {
+    let mut archive = ZipArchive::new(File::open(zip_path)?)?;
+    fs::create_dir_all(out_dir)?;
+
+    let mut anr_files = Vec::new();
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index)?;
+        let Some(relative_path) = entry.enclosed_name().map(Path::to_path_buf) else {
+            continue;
+        };
+        let target = out_dir.join(&relative_path);
+        if entry.is_dir() {
+            fs::create_dir_all(&target)?;
+            continue;
+        }
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        io::copy(&mut entry, &mut File::create(&target)?)?;
+        if entry.name().starts_with(ANR_DIR_PREFIX) {
+            anr_files.push(entry.name().to_string());
+        }
+    }
+
+    let main_file = main_entry_name(&mut archive)?;
+    let text = String::from_utf8_lossy(&fs::read(out_dir.join(&main_file))?).into_owned();
+    let lines: Vec<&str> = text.lines().collect();
+
+    let sections_dir = out_dir.join(SECTIONS_DIR);
+    fs::create_dir_all(&sections_dir)?;
+    let mut used_names = HashSet::new();
+    let mut sections = parse_bugreport_sections(&text);
+    for section in sections.iter_mut() {
+        // The top-level DUMPSYS blocks are covered by their per-service sections.
+        if section.category == CATEGORY_OTHER || section.title.starts_with(DUMPSYS_TITLE_PREFIX) || section.start_line > section.end_line {
+            continue;
+        }
+        let mut file_name = format!("{}-{}", section.category, slugify(section.title.trim_start_matches(SERVICE_PREFIX)));
+        let base_name = file_name.clone();
+        let mut suffix = 2;
+        while !used_names.insert(file_name.clone()) {
+            file_name = format!("{}-{}", base_name, suffix);
+            suffix += 1;
+        }
+
+        let relative = format!("{}/{}.txt", SECTIONS_DIR, file_name);
+        let body = lines[section.start_line - 1..section.end_line.min(lines.len())].join("\n");
+        fs::write(out_dir.join(&relative), body)?;
+        section.file = Some(relative);
+    }
+
+    let index = BugreportIndex { main_file, sections, anr_files };
+    fs::write(out_dir.join(INDEX_FILE_NAME), serde_json::to_string_pretty(&index)?)?;
+    Ok(index)
+}
+
+fn slugify(title: &str) -> String {
+    let slug: String = title.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-")
+}
@@ -0,0 +1,324 @@
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fs::{self, File};
+use std::path::Path;
+use chrono::{Datelike, Local};
+use regex::Regex;
+use zip::ZipArchive;
+use crate::adb::debugging::bugreport::{parse_bugreport_sections, read_bugreport_text, ANR_DIR_PREFIX, CATEGORY_LOGCAT};
+use crate::adb::debugging::crash_detector::CrashDetector;
+use crate::enums::crash_kind::CrashKind;
+use crate::models::bugreport_section::BugreportSection;
+use crate::models::bugreport_summary::{BatterySummary, BugreportSummary, KernelPanic, LowMemoryKill, WakelockUsage};
+use crate::models::crash_incident::CrashIncident;
+use crate::models::log_entry::LogEntry;
+
+const TEXT_EXTENSION: &str = "txt";
+
+const DUMPSTATE_HEADER_PREFIX: &str = "== dumpstate: ";
+const BUILD_FINGERPRINT_PREFIX: &str = "Build fingerprint: ";
+const FINGERPRINT_PROPERTY_PREFIX: &str = "[ro.build.fingerprint]: ";
+const UPTIME_HEADER_PREFIX: &str = "Uptime: ";
+const UPTIME_SECTION_TITLE: &str = "UPTIME";
+const UPTIME_MARKER: &str = "up ";
+const LOAD_AVERAGE_MARKER: &str = "load average";
+const USERS_MARKER: &str = "user";
+
+const BATTERY_SERVICE_TITLE: &str = "DUMP OF SERVICE battery";
+const BATTERY_LEVEL_KEY: &str = "level";
+const BATTERY_SCALE_KEY: &str = "scale";
+const BATTERY_STATUS_KEY: &str = "status";
+const BATTERY_HEALTH_KEY: &str = "health";
+const BATTERY_TEMPERATURE_KEY: &str = "temperature";
+const BATTERY_HISTORY_PREFIX: &str = "Battery History (";
+
+const KERNEL_WAKELOCKS_HEADER: &str = "All kernel wake locks:";
+const PARTIAL_WAKELOCKS_HEADER: &str = "All partial wake locks:";
+const KERNEL_WAKELOCK_PREFIX: &str = "Kernel Wake lock ";
+const PARTIAL_WAKELOCK_PREFIX: &str = "Wake lock ";
+const TOP_WAKELOCK_COUNT: usize = 10;
+
+const LMK_MARKERS: [&str; 2] = ["lowmemorykiller", "lmkd"];
+// lmkd: "Kill 'com.foo' (1234), uid 10123, oom_score_adj 900 to free 51200kB"
+// kernel: "lowmemorykiller: Killing 'com.foo' (1234) (tgid 1234), adj 906,"
+const LMK_KILL_PATTERN: &str = r"Kill(?:ing)? '([^']+)' \((\d+)\)(?:.*?(?:oom_score_)?adj (-?\d+))?";
+
+const KERNEL_PANIC_MARKER: &str = "Kernel panic - not syncing";
+
+/// Summarizes a bugreport zip, or the plain-text report older devices produce, without a device.
+pub fn summarize_bugreport(path: &Path) -> Result<BugreportSummary, Box<dyn Error>> {
+    if path.extension().is_some_and(|extension| extension == TEXT_EXTENSION) {
+        let text = String::from_utf8_lossy(&fs::read(path)?).into_owned();
+        return Ok(summarize_bugreport_text(&text));
+    }
+
+    let (main_file, text) = read_bugreport_text(path)?;
+    let mut summary = summarize_bugreport_text(&text);
+    summary.main_file = Some(main_file);
+    summary.anr_files = ZipArchive::new(File::open(path)?)?
+        .file_names()
+        .filter(|name| name.starts_with(ANR_DIR_PREFIX) && !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    summary.anr_files.sort();
+    Ok(summary)
+}
+
+/// Summarizes the main text of a bugreport.
+pub fn summarize_bugreport_text(text: &str) -> BugreportSummary {
+    let lines: Vec<&str> = text.lines().collect();
+    let sections = parse_bugreport_sections(text);
+    let generated_at = lines.iter()
+        .find_map(|line| line.strip_prefix(DUMPSTATE_HEADER_PREFIX))
+        .map(|value| value.trim().to_string());
+    // Logcat timestamps omit the year; take it from the dumpstate header.
+    let year = generated_at.as_deref()
+        .and_then(|value| value.get(..4))
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| Local::now().year());
+    let (anrs, crashes) = find_crashes(&lines, &sections, year);
+
+    BugreportSummary {
+        main_file: None,
+        generated_at,
+        build_fingerprint: find_build_fingerprint(&lines),
+        uptime: find_uptime(&lines, &sections),
+        battery: find_battery(&lines, &sections),
+        anrs,
+        crashes,
+        anr_files: Vec::new(),
+        top_wakelocks: find_top_wakelocks(&lines),
+        low_memory_kills: find_low_memory_kills(&lines, year),
+        kernel_panics: find_kernel_panics(&lines, &sections),
+        section_count: sections.len(),
+    }
+}
+
+fn section_lines<'a, 'b>(lines: &'b [&'a str], section: &BugreportSection) -> &'b [&'a str] {
+    let end = section.end_line.min(lines.len());
+    if section.start_line == 0 || section.start_line > end {
+        return &[];
+    }
+    &lines[section.start_line - 1..end]
+}
+
+fn find_build_fingerprint(lines: &[&str]) -> Option<String> {
+    lines.iter().find_map(|line| {
+        let line = line.trim();
+        let value = line.strip_prefix(BUILD_FINGERPRINT_PREFIX).or_else(|| line.strip_prefix(FINGERPRINT_PROPERTY_PREFIX))?;
+        Some(value.trim_matches(|c| c == '\'' || c == '[' || c == ']').to_string())
+    })
+}
+
+fn find_uptime(lines: &[&str], sections: &[BugreportSection]) -> Option<String> {
+    let header = lines.iter().find_map(|line| line.strip_prefix(UPTIME_HEADER_PREFIX));
+    let section = sections.iter()
+        .find(|section| section.title == UPTIME_SECTION_TITLE)
+        .and_then(|section| section_lines(lines, section).iter().find(|line| !line.trim().is_empty()).copied());
+    header.or(section).and_then(parse_uptime)
+}
+
+/// Turns " 10:00:00 up 2 days,  3:04,  0 users,  load average: 1.0" into "2 days, 3:04".
+fn parse_uptime(line: &str) -> Option<String> {
+    let start = line.find(UPTIME_MARKER)? + UPTIME_MARKER.len();
+    let rest = &line[start..];
+    let rest = rest.find(LOAD_AVERAGE_MARKER).map_or(rest, |end| &rest[..end]);
+    let parts: Vec<&str> = rest.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty() && !part.contains(USERS_MARKER))
+        .collect();
+    (!parts.is_empty()).then(|| parts.join(", "))
+}
+
+fn find_battery(lines: &[&str], sections: &[BugreportSection]) -> Option<BatterySummary> {
+    let mut battery = BatterySummary {
+        history: lines.iter()
+            .map(|line| line.trim())
+            .find(|line| line.starts_with(BATTERY_HISTORY_PREFIX))
+            .map(|line| line.trim_end_matches(':').to_string()),
+        ..BatterySummary::default()
+    };
+
+    let mut scale = None;
+    if let Some(section) = sections.iter().find(|section| section.title == BATTERY_SERVICE_TITLE) {
+        for line in section_lines(lines, section) {
+            let Some((key, value)) = line.trim().split_once(": ") else {
+                continue;
+            };
+            let value = value.trim();
+            match key {
+                BATTERY_LEVEL_KEY => battery.level = value.parse().ok(),
+                BATTERY_SCALE_KEY => scale = value.parse::<u32>().ok(),
+                BATTERY_STATUS_KEY => battery.status = value.parse().ok().map(battery_status_name),
+                BATTERY_HEALTH_KEY => battery.health = value.parse().ok().map(battery_health_name),
+                BATTERY_TEMPERATURE_KEY => battery.temperature_celsius = value.parse::<f32>().ok().map(|tenths| tenths / 10.0),
+                _ => {}
+            }
+        }
+    }
+
+    // Report the level as a percentage when the scale is not the usual 100.
+    if let (Some(level), Some(scale)) = (battery.level, scale.filter(|scale| *scale > 0)) {
+        battery.level = Some(level * 100 / scale);
+    }
+
+    let found = battery.level.is_some() || battery.status.is_some() || battery.history.is_some();
+    found.then_some(battery)
+}
+
+fn battery_status_name(status: u32) -> String {
+    match status {
+        2 => "charging",
+        3 => "discharging",
+        4 => "not charging",
+        5 => "full",
+        _ => "unknown",
+    }.to_string()
+}
+
+fn battery_health_name(health: u32) -> String {
+    match health {
+        2 => "good",
+        3 => "overheat",
+        4 => "dead",
+        5 => "over voltage",
+        6 => "failure",
+        7 => "cold",
+        _ => "unknown",
+    }.to_string()
+}
+
+/// Runs the logcat sections through [`CrashDetector`], returning the ANRs and the crashes.
+fn find_crashes(lines: &[&str], sections: &[BugreportSection], year: i32) -> (Vec<CrashIncident>, Vec<CrashIncident>) {
+    let mut detector = CrashDetector::new();
+    let mut incidents = Vec::new();
+    for section in sections.iter().filter(|section| section.category == CATEGORY_LOGCAT) {
+        for line in section_lines(lines, section) {
+            if let Some(entry) = LogEntry::from_threadtime_line(line, year) {
+                incidents.extend(detector.feed(&entry));
+            }
+        }
+        incidents.extend(detector.flush());
+    }
+
+    // The same report can appear in more than one buffer dump.
+    let mut seen = HashSet::new();
+    incidents.retain(|incident| seen.insert((incident.kind, incident.pid, incident.timestamp, incident.summary.clone())));
+    incidents.sort_by_key(|incident| incident.timestamp);
+    incidents.into_iter().partition(|incident| incident.kind == CrashKind::Anr)
+}
+
+fn find_top_wakelocks(lines: &[&str]) -> Vec<WakelockUsage> {
+    let mut wakelocks: HashMap<(String, bool), WakelockUsage> = HashMap::new();
+    let mut in_table = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed == KERNEL_WAKELOCKS_HEADER || trimmed == PARTIAL_WAKELOCKS_HEADER {
+            in_table = true;
+            continue;
+        }
+        if !in_table {
+            continue;
+        }
+        match parse_wakelock(trimmed) {
+            Some(wakelock) => {
+                let key = (wakelock.name.clone(), wakelock.kernel);
+                if wakelocks.get(&key).is_none_or(|existing| existing.duration_ms < wakelock.duration_ms) {
+                    wakelocks.insert(key, wakelock);
+                }
+            }
+            None => in_table = false,
+        }
+    }
+
+    let mut wakelocks: Vec<WakelockUsage> = wakelocks.into_values().collect();
+    wakelocks.sort_by(|a, b| b.duration_ms.cmp(&a.duration_ms).then_with(|| a.name.cmp(&b.name)));
+    wakelocks.truncate(TOP_WAKELOCK_COUNT);
+    wakelocks
+}
+
+/// Parses "Kernel Wake lock PowerManagerService.Display: 1h 4m 2s 71ms (12 times) realtime"
+/// and "Wake lock u0a57 NlpWakeLock: 1m 23s 456ms (789 times) max=3000 realtime".
+fn parse_wakelock(line: &str) -> Option<WakelockUsage> {
+    let (kernel, rest) = match line.strip_prefix(KERNEL_WAKELOCK_PREFIX) {
+        Some(rest) => (true, rest),
+        None => (false, line.strip_prefix(PARTIAL_WAKELOCK_PREFIX)?),
+    };
+    let (name, stats) = rest.rsplit_once(": ")?;
+    let (duration, counts) = stats.split_once(" (")?;
+    Some(WakelockUsage {
+        name: name.to_string(),
+        kernel,
+        duration_ms: parse_batterystats_duration(duration)?,
+        count: counts.split_whitespace().next()?.parse().ok()?,
+    })
+}
+
+/// Parses batterystats durations such as "1d 2h 3m 4s 5ms".
+fn parse_batterystats_duration(value: &str) -> Option<u64> {
+    value.split_whitespace().try_fold(0u64, |total, part| {
+        let (amount, multiplier) = if let Some(amount) = part.strip_suffix("ms") {
+            (amount, 1)
+        } else {
+            let (amount, unit) = part.split_at(part.len().checked_sub(1)?);
+            let multiplier = match unit {
+                "s" => 1_000,
+                "m" => 60_000,
+                "h" => 3_600_000,
+                "d" => 86_400_000,
+                _ => return None,
+            };
+            (amount, multiplier)
+        };
+        Some(total + amount.parse::<u64>().ok()? * multiplier)
+    })
+}
+
+fn find_low_memory_kills(lines: &[&str], year: i32) -> Vec<LowMemoryKill> {
+    let Ok(pattern) = Regex::new(LMK_KILL_PATTERN) else {
+        return Vec::new();
+    };
+
+    let mut seen = HashSet::new();
+    let mut kills = Vec::new();
+    for line in lines.iter().filter(|line| LMK_MARKERS.iter().any(|marker| line.contains(marker))) {
+        let Some(captures) = pattern.captures(line) else {
+            continue;
+        };
+        let Ok(pid) = captures[2].parse::<i32>() else {
+            continue;
+        };
+        let process = captures[1].to_string();
+        if !seen.insert((pid, process.clone())) {
+            continue;
+        }
+        kills.push(LowMemoryKill {
+            timestamp: LogEntry::from_threadtime_line(line, year).map(|entry| entry.timestamp),
+            process,
+            pid,
+            oom_score_adj: captures.get(3).and_then(|adj| adj.as_str().parse().ok()),
+        });
+    }
+    kills
+}
+
+fn find_kernel_panics(lines: &[&str], sections: &[BugreportSection]) -> Vec<KernelPanic> {
+    let mut seen = HashSet::new();
+    let mut panics = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let Some(start) = line.find(KERNEL_PANIC_MARKER) else {
+            continue;
+        };
+        let message = line[start..].trim().to_string();
+        // Sections nest (dumpsys services inside DUMPSYS), so take the innermost one.
+        let section = sections.iter()
+            .rev()
+            .find(|section| section.start_line <= index + 1 && index < section.end_line)
+            .map(|section| section.title.clone())
+            .unwrap_or_default();
+        if seen.insert((section.clone(), message.clone())) {
+            panics.push(KernelPanic { section, message });
+        }
+    }
+    panics
+}
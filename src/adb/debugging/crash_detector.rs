@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, Utc};
+use crate::enums::crash_kind::CrashKind;
+use crate::models::crash_incident::CrashIncident;
+use crate::models::log_entry::LogEntry;
+
+const ANDROID_RUNTIME_TAG: &str = "AndroidRuntime";
+const ACTIVITY_MANAGER_TAG: &str = "ActivityManager";
+
+const FATAL_EXCEPTION_PREFIX: &str = "FATAL EXCEPTION";
+const JAVA_PROCESS_PREFIX: &str = "Process: ";
+const JAVA_PID_SEPARATOR: &str = ", PID: ";
+const JAVA_FRAME_PREFIX: &str = "at ";
+const JAVA_CAUSE_PREFIX: &str = "Caused by: ";
+
+const NATIVE_CRASH_MARKER: &str = "*** *** ***";
+const NATIVE_PID_PREFIX: &str = "pid: ";
+const NATIVE_PROCESS_START: &str = ">>> ";
+const NATIVE_PROCESS_END: &str = " <<<";
+const NATIVE_SIGNAL_PREFIX: &str = "signal ";
+const NATIVE_ABORT_PREFIX: &str = "Abort message: ";
+const TOMBSTONE_WRITTEN_PREFIX: &str = "Tombstone written to: ";
+
+const ANR_PREFIX: &str = "ANR in ";
+const ANR_PID_PREFIX: &str = "PID: ";
+const ANR_REASON_PREFIX: &str = "Reason: ";
+
+/// Lines from the same writer further apart than this belong to different incidents.
+const GROUP_WINDOW_MS: i64 = 1000;
+
+struct PendingIncident {
+    pid: i32,
+    tag: String,
+    last_timestamp: DateTime<Utc>,
+    incident: CrashIncident,
+}
+
+/// Incremental detector that turns log entries into [`CrashIncident`]s.
+///
+/// Crash reports span many log lines (or one multi-line entry). Lines are grouped by the pid and
+/// tag that wrote them until that writer goes quiet for a moment, a new report starts, or
+/// [`CrashDetector::flush`] is called.
+#[derive(Default)]
+pub struct CrashDetector {
+    pending: Vec<PendingIncident>,
+}
+
+impl CrashDetector {
+    pub fn new() -> Self {
+        CrashDetector::default()
+    }
+
+    pub fn has_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Feeds one entry and returns the incidents it completed.
+    pub fn feed(&mut self, entry: &LogEntry) -> Vec<CrashIncident> {
+        let window = Duration::milliseconds(GROUP_WINDOW_MS);
+        let (expired, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut self.pending)
+            .into_iter()
+            .partition(|pending| entry.timestamp - pending.last_timestamp > window);
+        self.pending = pending;
+        let mut finished: Vec<CrashIncident> = expired.into_iter().map(Self::finish).collect();
+
+        for line in entry.message.lines() {
+            self.feed_line(entry, line, &mut finished);
+        }
+        finished
+    }
+
+    /// Completes every incident still being assembled.
+    pub fn flush(&mut self) -> Vec<CrashIncident> {
+        std::mem::take(&mut self.pending).into_iter().map(Self::finish).collect()
+    }
+
+    fn feed_line(&mut self, entry: &LogEntry, line: &str, finished: &mut Vec<CrashIncident>) {
+        let trimmed = line.trim();
+
+        if let Some(kind) = Self::start_kind(&entry.tag, trimmed) {
+            if let Some(index) = self.find_pending(entry) {
+                finished.push(Self::finish(self.pending.remove(index)));
+            }
+            let mut incident = CrashIncident::new(kind, entry.timestamp);
+            if kind == CrashKind::Anr {
+                incident.process = trimmed[ANR_PREFIX.len()..].split_whitespace().next().map(str::to_string);
+            }
+            incident.trace.push(line.to_string());
+            self.pending.push(PendingIncident { pid: entry.pid, tag: entry.tag.clone(), last_timestamp: entry.timestamp, incident });
+            return;
+        }
+
+        // tombstoned reports the file from its own process once the dump is complete.
+        if let Some(path) = trimmed.strip_prefix(TOMBSTONE_WRITTEN_PREFIX) {
+            let native = self.pending.iter_mut().rev().map(|pending| &mut pending.incident)
+                .chain(finished.iter_mut().rev())
+                .find(|incident| incident.kind == CrashKind::NativeCrash && incident.remote_artifact.is_none());
+            if let Some(incident) = native {
+                incident.remote_artifact = Some(path.trim().to_string());
+            }
+            return;
+        }
+
+        let Some(index) = self.find_pending(entry) else {
+            return;
+        };
+        let pending = &mut self.pending[index];
+        pending.last_timestamp = entry.timestamp;
+        let incident = &mut pending.incident;
+        incident.trace.push(line.to_string());
+
+        match incident.kind {
+            CrashKind::JavaCrash => Self::parse_java_line(incident, trimmed),
+            CrashKind::NativeCrash => Self::parse_native_line(incident, trimmed),
+            CrashKind::Anr => Self::parse_anr_line(incident, trimmed),
+        }
+    }
+
+    fn start_kind(tag: &str, line: &str) -> Option<CrashKind> {
+        if tag == ANDROID_RUNTIME_TAG && line.starts_with(FATAL_EXCEPTION_PREFIX) {
+            Some(CrashKind::JavaCrash)
+        } else if line.starts_with(NATIVE_CRASH_MARKER) {
+            Some(CrashKind::NativeCrash)
+        } else if tag == ACTIVITY_MANAGER_TAG && line.starts_with(ANR_PREFIX) {
+            Some(CrashKind::Anr)
+        } else {
+            None
+        }
+    }
+
+    fn find_pending(&self, entry: &LogEntry) -> Option<usize> {
+        self.pending.iter().position(|pending| pending.pid == entry.pid && pending.tag == entry.tag)
+    }
+
+    fn parse_java_line(incident: &mut CrashIncident, line: &str) {
+        if let Some(process) = line.strip_prefix(JAVA_PROCESS_PREFIX) {
+            let (process, pid) = process.split_once(JAVA_PID_SEPARATOR).unwrap_or((process, ""));
+            incident.process = Some(process.to_string());
+            incident.pid = pid.trim().parse().ok();
+        } else if incident.summary.is_empty() && incident.process.is_some()
+            && !line.starts_with(JAVA_FRAME_PREFIX) && !line.starts_with(JAVA_CAUSE_PREFIX) && !line.is_empty() {
+            incident.summary = line.to_string();
+        }
+    }
+
+    fn parse_native_line(incident: &mut CrashIncident, line: &str) {
+        // "pid: 1234, tid: 1250, name: RenderThread  >>> com.foo <<<"
+        if let Some(rest) = line.strip_prefix(NATIVE_PID_PREFIX) {
+            incident.pid = rest.split(',').next().and_then(|pid| pid.trim().parse().ok());
+            incident.process = rest.split_once(NATIVE_PROCESS_START)
+                .and_then(|(_, process)| process.split_once(NATIVE_PROCESS_END))
+                .map(|(process, _)| process.to_string());
+        } else if line.starts_with(NATIVE_SIGNAL_PREFIX) && incident.summary.is_empty() {
+            incident.summary = line.to_string();
+        } else if let Some(message) = line.strip_prefix(NATIVE_ABORT_PREFIX) {
+            let message = message.trim_matches('\'');
+            incident.summary = if incident.summary.is_empty() { message.to_string() } else { format!("{} ({})", incident.summary, message) };
+        }
+    }
+
+    fn parse_anr_line(incident: &mut CrashIncident, line: &str) {
+        if let Some(pid) = line.strip_prefix(ANR_PID_PREFIX) {
+            incident.pid = pid.trim().parse().ok();
+        } else if let Some(reason) = line.strip_prefix(ANR_REASON_PREFIX) {
+            incident.summary = reason.to_string();
+        }
+    }
+
+    fn finish(pending: PendingIncident) -> CrashIncident {
+        let mut incident = pending.incident;
+        if incident.summary.is_empty() {
+            incident.summary = incident.trace.first().map(|line| line.trim().to_string()).unwrap_or_default();
+        }
+        incident
+    }
+}
@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::path::Path;
+use futures::StreamExt;
+use tokio::time::{timeout, Duration};
+use crate::adb::client::Client;
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::io::io_limits::IoLimits;
+use crate::adb::debugging::crash_detector::CrashDetector;
+use crate::enums::crash_kind::CrashKind;
+use crate::enums::device_transport::DeviceTransport;
+use crate::models::crash_incident::CrashIncident;
+
+// Follow new entries only, including the crash buffer where AndroidRuntime and debuggerd report.
+const CRASH_LOGCAT_ARGS: &str = "-b main -b system -b crash -T 1";
+const TOMBSTONE_DIR: &str = "/data/tombstones";
+const ANR_DIR: &str = "/data/anr";
+const LATEST_TOMBSTONE_COMMAND: &str = "ls -t /data/tombstones 2>/dev/null | grep -v '\\.pb$' | head -n 1";
+const LATEST_ANR_TRACE_COMMAND: &str = "ls -t /data/anr 2>/dev/null | head -n 1";
+const ARTIFACT_DATE_FORMAT: &str = "%Y%m%d-%H%M%S";
+const IDLE_FLUSH_MS: u64 = 1000;
+
+impl Client {
+    /// Watches the device log for Java crashes, native crashes and ANRs until logcat ends.
+    ///
+    /// For native crashes and ANRs the matching tombstone or ANR trace is pulled into `artifact_dir`
+    /// over a separate connection before `on_incident` is called; a failed pull (these directories
+    /// usually need root) is recorded in the incident instead of stopping the watcher.
+    pub async fn adb_watch_crashes<F>(&mut self, device: DeviceTransport, artifact_dir: Option<&Path>, mut on_incident: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&CrashIncident),
+    {
+        let (config, limits) = (self.config().clone(), self.limits.clone());
+        let entries = self.adb_logcat_entries(device.clone(), CRASH_LOGCAT_ARGS).await?;
+        futures::pin_mut!(entries);
+        let mut detector = CrashDetector::new();
+
+        loop {
+            // Polling the stream again after a timeout is safe: the pending read lives in the stream.
+            let next = if detector.has_pending() {
+                timeout(Duration::from_millis(IDLE_FLUSH_MS), entries.next()).await.ok()
+            } else {
+                Some(entries.next().await)
+            };
+
+            let (incidents, ended) = match next {
+                None => (detector.flush(), false),
+                Some(None) => (detector.flush(), true),
+                Some(Some(entry)) => (detector.feed(&entry?), false),
+            };
+            for mut incident in incidents {
+                if let Some(dir) = artifact_dir {
+                    Self::pull_crash_artifact(&config, &limits, device.clone(), &mut incident, dir).await;
+                }
+                on_incident(&incident);
+            }
+            if ended {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn pull_crash_artifact(config: &ClientBuilder, limits: &IoLimits, device: DeviceTransport, incident: &mut CrashIncident, artifact_dir: &Path) {
+        if incident.kind == CrashKind::JavaCrash {
+            return;
+        }
+        match Self::try_pull_crash_artifact(config, limits, device, incident, artifact_dir).await {
+            Ok(local_path) => incident.artifact = Some(local_path),
+            Err(err) => incident.artifact_error = Some(err.to_string()),
+        }
+    }
+
+    async fn try_pull_crash_artifact(config: &ClientBuilder, limits: &IoLimits, device: DeviceTransport, incident: &mut CrashIncident, artifact_dir: &Path) -> Result<String, Box<dyn Error>> {
+        let mut client = Client::connect_with_limits(config.clone(), limits.clone()).await?;
+
+        let remote_path = match &incident.remote_artifact {
+            Some(path) => path.clone(),
+            None => {
+                let (dir, command) = match incident.kind {
+                    CrashKind::NativeCrash => (TOMBSTONE_DIR, LATEST_TOMBSTONE_COMMAND),
+                    _ => (ANR_DIR, LATEST_ANR_TRACE_COMMAND),
+                };
+                let name = client.adb_shell_output(device.clone(), command).await?.trim().to_string();
+                if name.is_empty() {
+                    return Err(format!("No readable files in {}", dir).into());
+                }
+                client.reconnect().await?;
+                format!("{}/{}", dir, name)
+            }
+        };
+        incident.remote_artifact = Some(remote_path.clone());
+
+        std::fs::create_dir_all(artifact_dir)?;
+        let file_name = remote_path.rsplit('/').next().unwrap_or(&remote_path);
+        let local_path = artifact_dir.join(format!("{}-{}", incident.timestamp.format(ARTIFACT_DATE_FORMAT), file_name));
+        let local_path = local_path.to_str().ok_or("Invalid artifact path")?.to_string();
+
+        client.adb_pull(device, &[remote_path], &local_path, false).await?;
+        Ok(local_path)
+    }
+}
@@ -1,27 +1,61 @@
 use std::error::Error;
 use crate::adb::client::Client;
 use crate::enums::device_transport::DeviceTransport;
-use crate::constants::{OKAY};
-use std::path::Path;
-use tokio::io::{AsyncBufReadExt, BufReader};
+use crate::constants::{ADB_SHELL_COMMAND, OKAY};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, BufReader};
 use chrono::Local;
-use indicatif::{ProgressBar, ProgressStyle};
+use crate::enums::bugreport_event::BugreportEvent;
+use crate::adb::io::partial_file::PartialFile;
 
 const LOGCAT_COMMAND_FORMAT: &str = "export ANDROID_LOG_TAGS=\"''\"; exec logcat {}";
-const BUGREPORT_COMMAND: &str = "shell:bugreportz -p";
+const BUGREPORTZ_VERSION_COMMAND: &str = "bugreportz -v 2>&1";
+const BUGREPORTZ_COMMAND: &str = "bugreportz";
+const BUGREPORTZ_PROGRESS_COMMAND: &str = "bugreportz -p";
+// bugreportz 1.0 (Android 7.0) does not support -p.
+const BUGREPORTZ_VERSION_WITHOUT_PROGRESS: &str = "1.0";
+const PLAIN_BUGREPORT_COMMAND: &str = "bugreport";
 const DEFAULT_BUGREPORT_FILENAME: &str = "bugreport.zip";
 const BUGREPORT_PREFIX: &str = "bugreport";
+const ZIP_EXTENSION: &str = "zip";
+const TEXT_EXTENSION: &str = "txt";
 const PROGRESS_PREFIX: &str = "PROGRESS:";
 const OK_PREFIX: &str = "OK:";
 const XOK_PREFIX: &str = "XOK:";
+const FAIL_PREFIX: &str = "FAIL:";
+const BEGIN_PREFIX: &str = "BEGIN:";
 const INFO_PREFIX: &str = "INFO:";
 const DATE_FORMAT: &str = "%Y-%m-%d-%H-%M-%S";
-const PROGRESS_BAR_LENGTH: u64 = 5000;
+const GENERATING_BUGREPORT: &str = "generating the bugreport";
 
 impl Client {
-    pub async fn adb_bugreport(&mut self, device_transport: DeviceTransport, path: Option<&str>) -> Result<(), Box<dyn Error>> {
+    /// Generates a bugreport and saves it to `path` (a file or a directory), returning the saved file.
+    /// A file name without an extension gets `.zip` or `.txt` for the kind of report the device made.
+    ///
+    /// Devices with `bugreportz` (Android 7.0+) produce a zip that is pulled afterwards; older devices
+    /// fall back to streaming the plain-text `bugreport` into a `.txt` file. `on_event` reports progress.
+    pub async fn adb_bugreport<F>(&mut self, device_transport: DeviceTransport, path: Option<&str>, mut on_event: F) -> Result<PathBuf, Box<dyn Error>>
+    where
+        F: FnMut(&BugreportEvent),
+    {
+        let version = self.adb_shell_output(device_transport.clone(), BUGREPORTZ_VERSION_COMMAND).await?;
+        let version = version.trim();
+        let supports_zip = version.starts_with(|c: char| c.is_ascii_digit());
+        self.reconnect().await?;
+
+        let extension = if supports_zip { ZIP_EXTENSION } else { TEXT_EXTENSION };
+        let final_path = Self::bugreport_path(path, extension);
+
+        if !supports_zip {
+            on_event(&BugreportEvent::PlainTextFallback);
+            self.save_plain_bugreport_to_file(device_transport, &final_path, &mut on_event).await?;
+            return Ok(final_path);
+        }
+
+        let progress = version != BUGREPORTZ_VERSION_WITHOUT_PROGRESS;
+        let command = if progress { BUGREPORTZ_PROGRESS_COMMAND } else { BUGREPORTZ_COMMAND };
         self.send_transport(device_transport.clone()).await?;
-        self.send_adb_command(BUGREPORT_COMMAND.as_ref()).await?;
+        self.send_adb_command(&format!("{}{}", ADB_SHELL_COMMAND, command)).await?;
 
         let response = self.read_first_four_bytes_response().await?;
         if response != OKAY {
@@ -29,30 +63,56 @@ impl Client {
             return Err(format!("Failed to initiate bugreport: {}", error_msg).into());
         }
 
-        let output_path = path.unwrap_or(DEFAULT_BUGREPORT_FILENAME);
-        let path = Path::new(output_path);
+        on_event(&BugreportEvent::Generating { progress });
+        self.save_bugreportz_to_file(device_transport, &final_path, &mut on_event).await?;
+        Ok(final_path)
+    }
 
-        let final_path = if path.is_dir() {
-            let filename = format!("{}-{}.zip", BUGREPORT_PREFIX, Local::now().format(DATE_FORMAT));
-            path.join(filename)
+    fn bugreport_path(path: Option<&str>, extension: &str) -> PathBuf {
+        let path = Path::new(path.unwrap_or(DEFAULT_BUGREPORT_FILENAME));
+        if path.is_dir() {
+            path.join(format!("{}-{}.{}", BUGREPORT_PREFIX, Local::now().format(DATE_FORMAT), extension))
+        } else if path.extension().is_none_or(|current| current == ZIP_EXTENSION && extension != ZIP_EXTENSION) {
+            path.with_extension(extension)
         } else {
             path.to_path_buf()
-        };
-        self.save_bugreportz_to_file(device_transport.clone(), &final_path).await
+        }
     }
 
-    async fn save_bugreportz_to_file(&mut self, device: DeviceTransport, path: &Path) -> Result<(), Box<dyn Error>> {
-        println!("Generating bugreport. This may take a while...");
+    async fn save_plain_bugreport_to_file<F>(&mut self, device: DeviceTransport, path: &Path, on_event: &mut F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&BugreportEvent),
+    {
+        self.send_transport(device).await?;
+        self.send_adb_command(&format!("{}{}", ADB_SHELL_COMMAND, PLAIN_BUGREPORT_COMMAND)).await?;
+        if self.read_first_four_bytes_response().await? != OKAY {
+            let error_msg = self.read_adb_full_response().await?;
+            return Err(format!("Failed to initiate bugreport: {}", error_msg).into());
+        }
+
+        on_event(&BugreportEvent::Generating { progress: false });
+        let mut file = tokio::fs::File::create(path).await?;
+        let partial = PartialFile::new(path);
+        let bytes = self.limits.run(GENERATING_BUGREPORT, false, tokio::io::copy(&mut self.adb_stream, &mut file)).await?;
 
-        let pb = self.create_progress_bar();
+        if bytes == 0 {
+            return Err("Failed to generate bugreport: the device returned no output".into());
+        }
+        partial.keep();
+        Ok(())
+    }
 
+    async fn save_bugreportz_to_file<F>(&mut self, device: DeviceTransport, path: &Path, on_event: &mut F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&BugreportEvent),
+    {
         let mut reader = BufReader::new(&mut self.adb_stream);
         let mut line = String::new();
         let mut zip_file = String::new();
 
         loop {
             line.clear();
-            let bytes_read = reader.read_line(&mut line).await?;
+            let bytes_read = self.limits.run(GENERATING_BUGREPORT, false, reader.read_line(&mut line)).await?;
 
             if bytes_read == 0 {
                 break;
@@ -60,67 +120,49 @@ impl Client {
 
             let trimmed_line = line.trim();
 
-            if let Some(progress_str) = Self::extract_progress(trimmed_line) {
-                if let Ok(progress) = progress_str.parse::<u64>() {
-                    pb.set_position(progress);
-                }
+            if let Some((done, total)) = Self::extract_progress(trimmed_line) {
+                on_event(&BugreportEvent::Progress { done, total });
             } else if let Some(path_str) = Self::extract_zip_path(trimmed_line) {
                 zip_file = path_str.to_string();
                 break;
-            } else if !trimmed_line.is_empty() && !trimmed_line.starts_with(INFO_PREFIX) {
-                pb.suspend(|| println!("Info: {}", trimmed_line));
+            } else if let Some(reason) = trimmed_line.strip_prefix(FAIL_PREFIX) {
+                return Err(format!("Failed to generate bugreport: {}", reason.trim()).into());
+            } else if !trimmed_line.is_empty() && !trimmed_line.starts_with(INFO_PREFIX) && !trimmed_line.starts_with(BEGIN_PREFIX) {
+                on_event(&BugreportEvent::Message(trimmed_line.to_string()));
             }
         }
 
-        pb.finish_with_message("Bugreport generated");
-
         if zip_file.is_empty() {
             return Err("Failed to generate bugreport: No zip file path received".into());
         }
 
-        println!("\nPulling bugreport file...");
-
-        let mut pull_client = Client::new(None, None).await?;
-        pull_client.send_transport(device.clone()).await?;
-
-        pull_client.adb_pull(device.clone(), &[zip_file], path.to_str().unwrap(), false).await?;
+        on_event(&BugreportEvent::Pulling);
 
-        println!("Bugreport saved to: {}", path.display());
+        let mut pull_client = self.new_connection().await?;
+        pull_client.adb_pull(device, &[zip_file], path.to_str().ok_or("Invalid bugreport path")?, false).await?;
         Ok(())
     }
 
-    fn create_progress_bar(&self) -> ProgressBar {
-        let pb = ProgressBar::new(PROGRESS_BAR_LENGTH);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-                .unwrap_or_else(|_| ProgressStyle::default_bar())
-                .progress_chars("#>-")
-        );
-        pb
-    }
-
-    fn extract_progress(line: &str) -> Option<&str> {
-        if let Some(idx) = line.find(PROGRESS_PREFIX) {
-            let progress_part = &line[idx + PROGRESS_PREFIX.len()..];
-            progress_part.split('/').next()
-        } else {
-            None
-        }
+    /// Parses `PROGRESS:<done>/<total>` lines written by `bugreportz -p`.
+    fn extract_progress(line: &str) -> Option<(u64, u64)> {
+        let idx = line.find(PROGRESS_PREFIX)?;
+        let (done, total) = line[idx + PROGRESS_PREFIX.len()..].split_once('/')?;
+        Some((done.trim().parse().ok()?, total.trim().parse().ok()?))
     }
 
     fn extract_zip_path(line: &str) -> Option<&str> {
-        if let Some(idx) = line.find(OK_PREFIX) {
-            Some(line[idx + OK_PREFIX.len()..].trim())
-        } else if let Some(idx) = line.find(XOK_PREFIX) {
+        if let Some(idx) = line.find(XOK_PREFIX) {
             Some(line[idx + XOK_PREFIX.len()..].trim())
+        } else if let Some(idx) = line.find(OK_PREFIX) {
+            Some(line[idx + OK_PREFIX.len()..].trim())
         } else {
             None
         }
     }
 
-    pub async fn adb_logcat(&mut self, device: DeviceTransport, args: &str) -> Result<String, Box<dyn Error>> {
-        let logcat_command = format!("{} {}", LOGCAT_COMMAND_FORMAT, args);
-        self.adb_shell(device, &logcat_command).await
+    /// Streams `logcat` with `args` to `out` until the device closes the log.
+    pub async fn adb_logcat<W: AsyncWrite + Unpin>(&mut self, device: DeviceTransport, args: &str, out: &mut W) -> Result<(), Box<dyn Error>> {
+        let logcat_command = LOGCAT_COMMAND_FORMAT.replace("{}", args);
+        self.adb_shell_to_writer(device, &logcat_command, out).await
     }
 }
\ No newline at end of file
@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use tokio::time::sleep;
+use crate::adb::client::Client;
+use crate::constants::DEFAULT_WAIT_STATE;
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::log_format::LogFormat;
+use crate::enums::record_event::RecordEvent;
+use crate::models::boot_session::BootSession;
+use crate::models::log_entry::LogEntry;
+use crate::models::log_filter::LogFilter;
+
+const INDEX_FILE_NAME: &str = "index.json";
+const INDEX_SAVE_INTERVAL_SECS: u64 = 10;
+const RECONNECT_DELAY_SECS: u64 = 1;
+const BOOT_ID_COMMAND: &str = "cat /proc/sys/kernel/random/boot_id";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RecordIndex {
+    sessions: Vec<BootSession>,
+}
+
+/// Writes log entries into size/time rotated files under one directory and keeps an
+/// `index.json` listing every boot session and the files that belong to it.
+pub struct LogRecorder {
+    dir: PathBuf,
+    max_file_size: u64,
+    max_file_age: Duration,
+    format: LogFormat,
+    filter: LogFilter,
+    index: RecordIndex,
+    current_file: Option<File>,
+    current_file_size: u64,
+    current_file_opened: Instant,
+    last_entry: Option<LogEntry>,
+    last_index_save: Instant,
+}
+
+impl LogRecorder {
+    /// Opens `dir` for recording, continuing its existing index if there is one.
+    pub fn open(dir: &Path, max_file_size: u64, max_file_age: Duration, format: LogFormat) -> Result<Self, Box<dyn Error>> {
+        fs::create_dir_all(dir)?;
+        let index_path = dir.join(INDEX_FILE_NAME);
+        let index = if index_path.exists() {
+            serde_json::from_str(&fs::read_to_string(&index_path)?)
+                .map_err(|err| format!("Invalid recording index {}: {}", index_path.display(), err))?
+        } else {
+            RecordIndex::default()
+        };
+
+        Ok(LogRecorder {
+            dir: dir.to_path_buf(),
+            max_file_size,
+            max_file_age,
+            format,
+            filter: LogFilter::new(),
+            index,
+            current_file: None,
+            current_file_size: 0,
+            current_file_opened: Instant::now(),
+            last_entry: None,
+            last_index_save: Instant::now(),
+        })
+    }
+
+    /// Only entries accepted by `filter` are written.
+    pub fn with_filter(mut self, filter: LogFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn sessions(&self) -> &[BootSession] {
+        &self.index.sessions
+    }
+
+    /// Starts recording `boot_id`; returns `true` when it continues the latest session
+    /// (a reconnect without a reboot) rather than opening a new one.
+    pub fn start_session(&mut self, boot_id: &str) -> Result<bool, Box<dyn Error>> {
+        let resumed = match self.index.sessions.last_mut() {
+            Some(session) if session.boot_id == boot_id => {
+                session.reconnects += 1;
+                true
+            }
+            _ => {
+                self.close_file()?;
+                self.last_entry = None;
+                self.index.sessions.push(BootSession::new(boot_id));
+                false
+            }
+        };
+        self.save_index()?;
+        Ok(resumed)
+    }
+
+    /// Timestamp to pass to `logcat -T` so a resumed session does not start from the whole buffer.
+    pub fn resume_time(&self) -> Option<DateTime<Utc>> {
+        self.index.sessions.last().and_then(|session| session.last_entry)
+    }
+
+    /// Writes `entry` unless it is filtered out or was already recorded before a reconnect.
+    /// Returns the path of a newly opened file when the entry caused a rotation.
+    pub fn record(&mut self, entry: &LogEntry) -> Result<Option<PathBuf>, Box<dyn Error>> {
+        if self.is_duplicate(entry) || !self.filter.accept(entry) {
+            return Ok(None);
+        }
+
+        let rotate = self.current_file.is_none()
+            || self.current_file_size >= self.max_file_size
+            || self.current_file_opened.elapsed() >= self.max_file_age;
+        let opened = if rotate { Some(self.open_next_file()?) } else { None };
+
+        let line = format!("{}\n", self.format.render(entry));
+        if let Some(file) = self.current_file.as_mut() {
+            file.write_all(line.as_bytes())?;
+        }
+        self.current_file_size += line.len() as u64;
+
+        if let Some(session) = self.index.sessions.last_mut() {
+            session.first_entry.get_or_insert(entry.timestamp);
+            session.last_entry = Some(entry.timestamp);
+            session.entries += 1;
+        }
+        self.last_entry = Some(entry.clone());
+
+        if opened.is_some() || self.last_index_save.elapsed() >= Duration::from_secs(INDEX_SAVE_INTERVAL_SECS) {
+            self.save_index()?;
+        }
+        Ok(opened)
+    }
+
+    /// Flushes the current file and writes the index; call when the stream ends.
+    pub fn finish(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(file) = self.current_file.as_mut() {
+            file.flush()?;
+        }
+        self.save_index()
+    }
+
+    fn is_duplicate(&self, entry: &LogEntry) -> bool {
+        let Some(last_timestamp) = self.resume_time() else {
+            return false;
+        };
+        if entry.timestamp != last_timestamp {
+            return entry.timestamp < last_timestamp;
+        }
+        // -T is inclusive, so the last recorded entry comes back once more.
+        self.last_entry.as_ref().is_some_and(|last| {
+            last.pid == entry.pid && last.tid == entry.tid && last.tag == entry.tag && last.message == entry.message
+        })
+    }
+
+    fn open_next_file(&mut self) -> Result<PathBuf, Box<dyn Error>> {
+        self.close_file()?;
+        let session_number = self.index.sessions.len().max(1);
+        let session = self.index.sessions.last_mut().ok_or("No recording session started")?;
+        let file_name = format!("logcat-{:03}-{:04}.log", session_number, session.files.len() + 1);
+        let path = self.dir.join(&file_name);
+
+        self.current_file = Some(File::create(&path)?);
+        self.current_file_size = 0;
+        self.current_file_opened = Instant::now();
+        session.files.push(file_name);
+        Ok(path)
+    }
+
+    fn close_file(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(mut file) = self.current_file.take() {
+            file.flush()?;
+        }
+        Ok(())
+    }
+
+    fn save_index(&mut self) -> Result<(), Box<dyn Error>> {
+        let index_path = self.dir.join(INDEX_FILE_NAME);
+        let temp_path = index_path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(&self.index)?)?;
+        fs::rename(&temp_path, &index_path)?;
+        self.last_index_save = Instant::now();
+        Ok(())
+    }
+}
+
+impl Client {
+    /// Records logcat into `recorder` until a file can no longer be written.
+    ///
+    /// When the device disconnects or reboots the recorder waits for it with `adb_wait_for`,
+    /// compares the kernel boot id to decide between resuming the session with `-T <last timestamp>`
+    /// and starting a new one, and keeps going.
+    pub async fn adb_logcat_record<F>(&mut self, device: DeviceTransport, args: &str, recorder: &mut LogRecorder, mut on_event: F) -> Result<(), Box<dyn Error>>
+    where
+        F: FnMut(&RecordEvent),
+    {
+        loop {
+            let reason = match self.start_record_session(device.clone(), recorder, &mut on_event).await? {
+                Ok(logcat_args) => {
+                    let logcat_args = format!("{} {}", logcat_args, args);
+                    match self.adb_logcat_entries(device.clone(), logcat_args.trim()).await {
+                        Ok(entries) => {
+                            futures::pin_mut!(entries);
+                            let mut reason = String::from("logcat exited");
+                            while let Some(entry) = entries.next().await {
+                                match entry {
+                                    Ok(entry) => {
+                                        if let Some(path) = recorder.record(&entry)? {
+                                            on_event(&RecordEvent::FileOpened(path));
+                                        }
+                                    }
+                                    Err(err) => {
+                                        reason = err.to_string();
+                                        break;
+                                    }
+                                }
+                            }
+                            reason
+                        }
+                        Err(err) => err.to_string(),
+                    }
+                }
+                Err(err) => err,
+            };
+
+            recorder.finish()?;
+            on_event(&RecordEvent::Disconnected { reason });
+            sleep(Duration::from_secs(RECONNECT_DELAY_SECS)).await;
+        }
+    }
+
+    /// Waits for the device and opens or resumes its boot session. Connection problems are returned
+    /// in the inner result so the caller can retry; only recorder errors are fatal.
+    async fn start_record_session<F>(&mut self, device: DeviceTransport, recorder: &mut LogRecorder, on_event: &mut F) -> Result<Result<String, String>, Box<dyn Error>>
+    where
+        F: FnMut(&RecordEvent),
+    {
+        on_event(&RecordEvent::WaitingForDevice);
+        let boot_id = match self.wait_for_boot_id(device).await {
+            Ok(boot_id) => boot_id,
+            Err(err) => return Ok(Err(err.to_string())),
+        };
+
+        let resumed = recorder.start_session(&boot_id)?;
+        on_event(&RecordEvent::SessionStarted { boot_id, resumed });
+
+        if let Err(err) = self.reconnect().await {
+            return Ok(Err(err.to_string()));
+        }
+        Ok(Ok(match recorder.resume_time().filter(|_| resumed) {
+            Some(since) => format!("-T {}.{:03}", since.timestamp(), since.timestamp_subsec_millis()),
+            None => String::new(),
+        }))
+    }
+
+    async fn wait_for_boot_id(&mut self, device: DeviceTransport) -> Result<String, Box<dyn Error>> {
+        self.reconnect().await?;
+        self.adb_wait_for(device.clone(), DEFAULT_WAIT_STATE, None).await?;
+        self.reconnect().await?;
+        let boot_id = self.adb_shell_output(device, BOOT_ID_COMMAND).await?.trim().to_string();
+        if boot_id.is_empty() {
+            return Err("Could not read the device boot id".into());
+        }
+        Ok(boot_id)
+    }
+}
@@ -0,0 +1,201 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::error::Error;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use futures::{Stream, StreamExt};
+use futures::stream::select_all;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::adb::client::Client;
+use crate::adb::io::io_limits::IoLimits;
+use crate::constants::{ADB_EXEC_COMMAND, OKAY};
+use crate::enums::device_transport::DeviceTransport;
+use crate::models::log_entry::LogEntry;
+use crate::models::log_filter::is_package_process;
+use crate::utils::shell_quote;
+
+const LOGCAT_BINARY_COMMAND_FORMAT: &str = "export ANDROID_LOG_TAGS=\"''\"; exec logcat -B {}";
+const ENTRY_PREFIX_SIZE: usize = 4;
+const READING_LOGCAT: &str = "reading logcat";
+// toybox ps (O+) needs -A to list every process; older toolbox ps lists them by default.
+const PS_COMMAND: &str = "ps -A -o PID,NAME 2>/dev/null || ps";
+const PIDOF_COMMAND: &str = "pidof";
+const PS_PID_COLUMN: &str = "PID";
+const DEFAULT_PS_PID_COLUMN: usize = 1;
+
+impl Client {
+    /// Runs `logcat -B` on the device and decodes its binary output into [`LogEntry`] records.
+    ///
+    /// `args` are passed to logcat unchanged (for example `-b all` or `-T 100`). The stream ends
+    /// when logcat exits or the connection closes, and stops after the first decoding error.
+    pub async fn adb_logcat_entries(&mut self, device: DeviceTransport, args: &str) -> Result<impl Stream<Item = Result<LogEntry, Box<dyn Error>>> + '_, Box<dyn Error>> {
+        self.send_transport(device).await?;
+        // exec: gives a raw channel, a pty would mangle the binary records.
+        let logcat_command = format!("{}{}", ADB_EXEC_COMMAND, LOGCAT_BINARY_COMMAND_FORMAT.replace("{}", args));
+        self.send_adb_command(&logcat_command).await?;
+
+        if self.read_first_four_bytes_response().await? != OKAY {
+            let error_message = self.read_adb_full_response().await?;
+            return Err(format!("Failed to start logcat: {}", error_message).into());
+        }
+
+        Ok(limited_log_entry_stream(&mut self.adb_stream, self.limits.clone()))
+    }
+
+    /// Resolves the pids of a package's processes, using `ps` and falling back to `pidof`.
+    pub async fn adb_package_pids(&mut self, device: DeviceTransport, package: &str) -> Result<Vec<i32>, Box<dyn Error>> {
+        let ps_output = self.adb_shell_output(device.clone(), PS_COMMAND).await?;
+        let pids = parse_package_pids(&ps_output, package);
+        if !pids.is_empty() {
+            return Ok(pids);
+        }
+
+        self.reconnect().await?;
+        let pidof_output = self.adb_shell_output(device, &format!("{} {}", PIDOF_COMMAND, shell_quote(package))).await?;
+        Ok(pidof_output.split_whitespace().filter_map(|pid| pid.parse::<i32>().ok()).collect())
+    }
+}
+
+/// Decodes a `logcat -B` byte stream from any reader.
+pub fn log_entry_stream<R: AsyncRead + Unpin>(reader: R) -> impl Stream<Item = Result<LogEntry, Box<dyn Error>>> {
+    limited_log_entry_stream(reader, IoLimits::default())
+}
+
+/// [`log_entry_stream`] with every read bound by the deadline and cancellation of `limits`; a
+/// quiet log is not an I/O timeout.
+fn limited_log_entry_stream<R: AsyncRead + Unpin>(reader: R, limits: IoLimits) -> impl Stream<Item = Result<LogEntry, Box<dyn Error>>> {
+    futures::stream::unfold(Some((reader, limits)), |state| async move {
+        let (mut reader, limits) = state?;
+        let entry = limits.run(READING_LOGCAT, false, async { Ok(read_log_entry(&mut reader).await) }).await;
+        match entry.and_then(|entry| entry) {
+            Ok(Some(entry)) => Some((Ok(entry), Some((reader, limits)))),
+            Ok(None) => None,
+            Err(err) => Some((Err(err), None)),
+        }
+    })
+}
+
+struct BufferedEntry {
+    timestamp: DateTime<Utc>,
+    sequence: u64,
+    arrived: Instant,
+    source: usize,
+    entry: LogEntry,
+}
+
+impl PartialEq for BufferedEntry {
+    fn eq(&self, other: &Self) -> bool {
+        (self.timestamp, self.sequence) == (other.timestamp, other.sequence)
+    }
+}
+
+impl Eq for BufferedEntry {}
+
+impl PartialOrd for BufferedEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BufferedEntry {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.timestamp, self.sequence).cmp(&(other.timestamp, other.sequence))
+    }
+}
+
+struct MergeState<S> {
+    sources: futures::stream::SelectAll<S>,
+    buffer: BinaryHeap<Reverse<BufferedEntry>>,
+    sequence: u64,
+    ended: bool,
+}
+
+/// Merges several log streams (typically one per device) into one ordered by entry timestamp.
+///
+/// Items carry the index of the stream they came from. Live streams never end, so entries are held
+/// back for `window` after arrival and released oldest timestamp first; entries from different
+/// sources that arrive within the window of each other come out in timestamp order. Errors are
+/// passed through immediately.
+pub fn merge_log_streams<S>(streams: Vec<S>, window: Duration) -> impl Stream<Item = (usize, Result<LogEntry, Box<dyn Error>>)>
+where
+    S: Stream<Item = Result<LogEntry, Box<dyn Error>>> + Unpin,
+{
+    let tagged = streams.into_iter().enumerate()
+        .map(|(source, stream)| stream.map(move |entry| (source, entry)))
+        .collect::<Vec<_>>();
+    let state = MergeState { sources: select_all(tagged), buffer: BinaryHeap::new(), sequence: 0, ended: false };
+
+    futures::stream::unfold(state, move |mut state| async move {
+        loop {
+            let oldest_arrival = state.buffer.iter().map(|Reverse(buffered)| buffered.arrived).min();
+            let release = match oldest_arrival {
+                Some(_) if state.ended => true,
+                Some(arrived) => arrived.elapsed() >= window,
+                None if state.ended => return None,
+                None => false,
+            };
+            if release {
+                let Reverse(buffered) = state.buffer.pop()?;
+                return Some(((buffered.source, Ok(buffered.entry)), state));
+            }
+
+            // Waiting on the stream again after a timeout is safe: pending reads live in the sources.
+            let next = match oldest_arrival {
+                Some(arrived) => match tokio::time::timeout(window.saturating_sub(arrived.elapsed()), state.sources.next()).await {
+                    Ok(next) => next,
+                    Err(_) => continue,
+                },
+                None => state.sources.next().await,
+            };
+
+            match next {
+                Some((source, Ok(entry))) => {
+                    state.sequence += 1;
+                    let buffered = BufferedEntry { timestamp: entry.timestamp, sequence: state.sequence, arrived: Instant::now(), source, entry };
+                    state.buffer.push(Reverse(buffered));
+                }
+                Some((source, Err(err))) => return Some(((source, Err(err)), state)),
+                None => state.ended = true,
+            }
+        }
+    })
+}
+
+/// Reads a single entry, returning `None` on a clean end of stream between entries.
+pub async fn read_log_entry<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Option<LogEntry>, Box<dyn Error>> {
+    let mut prefix = [0u8; ENTRY_PREFIX_SIZE];
+    let mut filled = 0;
+    while filled < ENTRY_PREFIX_SIZE {
+        let n = reader.read(&mut prefix[filled..]).await?;
+        if n == 0 {
+            if filled == 0 {
+                return Ok(None);
+            }
+            return Err("Truncated logger entry header".into());
+        }
+        filled += n;
+    }
+
+    let mut entry = vec![0u8; LogEntry::entry_size(&prefix)?];
+    entry[..ENTRY_PREFIX_SIZE].copy_from_slice(&prefix);
+    reader.read_exact(&mut entry[ENTRY_PREFIX_SIZE..]).await?;
+
+    LogEntry::from_bytes(&entry).map(Some)
+}
+
+/// Extracts the pids of `package` and its `package:suffix` processes from `ps` output.
+pub fn parse_package_pids(ps_output: &str, package: &str) -> Vec<i32> {
+    let mut lines = ps_output.lines();
+    let pid_column = lines.next()
+        .and_then(|header| header.split_whitespace().position(|column| column == PS_PID_COLUMN))
+        .unwrap_or(DEFAULT_PS_PID_COLUMN);
+
+    lines.filter_map(|line| {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+        let name = columns.last()?;
+        if !is_package_process(name, package) {
+            return None;
+        }
+        columns.get(pid_column)?.parse::<i32>().ok()
+    }).collect()
+}
@@ -1 +1,7 @@
 mod debugging;
+pub mod logcat;
+pub mod log_recorder;
+pub mod crash_detector;
+pub mod crash_watcher;
+pub mod bugreport;
+pub mod bugreport_analyzer;
@@ -0,0 +1,162 @@
+use std::error::Error;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use crate::adb::client::Client;
+use crate::adb::shell::shell_session::{ShellSession, ShellStdin};
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::expect_pattern::ExpectPattern;
+use crate::models::expect_match::ExpectMatch;
+
+const DEFAULT_EXPECT_TIMEOUT_SECS: u64 = 10;
+const OUTPUT_CHANNEL_CAPACITY: usize = 64;
+const READ_BUFFER_SIZE: usize = 4096;
+
+type OutputHandler = Box<dyn FnMut(&[u8]) + Send>;
+
+/// Expect-style driver for a [`ShellSession`]: send input, then wait for output matching a
+/// literal or a regex.
+///
+/// stdout and stderr are merged in arrival order. Each successful expect consumes the output up
+/// to the end of its match, so the next expect only sees newer output.
+pub struct ExpectSession {
+    session: ShellSession,
+    stdin: Option<ShellStdin>,
+    output: mpsc::Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+    eof: bool,
+    timeout: Duration,
+    output_handler: Option<OutputHandler>,
+}
+
+impl ExpectSession {
+    pub fn new(mut session: ShellSession) -> Self {
+        let (sender, output) = mpsc::channel(OUTPUT_CHANNEL_CAPACITY);
+        if let Some(stdout) = session.stdout.take() {
+            tokio::spawn(forward_output(stdout, sender.clone()));
+        }
+        if let Some(stderr) = session.stderr.take() {
+            tokio::spawn(forward_output(stderr, sender));
+        }
+
+        ExpectSession {
+            stdin: session.stdin.take(),
+            session,
+            output,
+            buffer: Vec::new(),
+            eof: false,
+            timeout: Duration::from_secs(DEFAULT_EXPECT_TIMEOUT_SECS),
+            output_handler: None,
+        }
+    }
+
+    /// Calls `handler` with every chunk of output as it arrives, e.g. to show a live transcript.
+    pub fn with_output_handler(mut self, handler: impl FnMut(&[u8]) + Send + 'static) -> Self {
+        self.output_handler = Some(Box::new(handler));
+        self
+    }
+
+    /// Sets the timeout used by expects that do not pass their own.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    pub async fn send(&mut self, data: impl AsRef<[u8]>) -> Result<(), Box<dyn Error>> {
+        let stdin = self.stdin.as_mut().ok_or("Shell session stdin is closed")?;
+        stdin.write_all(data.as_ref()).await?;
+        stdin.flush().await?;
+        Ok(())
+    }
+
+    pub async fn send_line(&mut self, line: &str) -> Result<(), Box<dyn Error>> {
+        self.send(format!("{}\n", line)).await
+    }
+
+    /// Waits until `pattern` appears in the output not consumed by earlier expects.
+    pub async fn expect(&mut self, pattern: &ExpectPattern, timeout: Option<Duration>) -> Result<ExpectMatch, Box<dyn Error>> {
+        let timeout = timeout.unwrap_or(self.timeout);
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some((start, end, captures)) = pattern.find(&self.buffer) {
+                let consumed: Vec<u8> = self.buffer.drain(..end).collect();
+                return Ok(ExpectMatch {
+                    before: String::from_utf8_lossy(&consumed[..start]).into_owned(),
+                    matched: String::from_utf8_lossy(&consumed[start..]).into_owned(),
+                    captures,
+                });
+            }
+            if self.eof {
+                return Err(format!("Output ended while waiting for {}; unmatched output: {:?}", pattern, self.pending_output()).into());
+            }
+            if !self.receive_until(deadline).await {
+                return Err(format!("Timed out after {:.1}s waiting for {}; unmatched output: {:?}", timeout.as_secs_f64(), pattern, self.pending_output()).into());
+            }
+        }
+    }
+
+    /// Waits for the command to close its output and returns what was not consumed yet.
+    pub async fn expect_eof(&mut self, timeout: Option<Duration>) -> Result<String, Box<dyn Error>> {
+        let timeout = timeout.unwrap_or(self.timeout);
+        let deadline = Instant::now() + timeout;
+        while !self.eof {
+            if !self.receive_until(deadline).await {
+                return Err(format!("Timed out after {:.1}s waiting for end of output", timeout.as_secs_f64()).into());
+            }
+        }
+        let rest = self.pending_output();
+        self.buffer.clear();
+        Ok(rest)
+    }
+
+    /// Closes stdin and waits for the command to exit, returning its exit status when known.
+    pub async fn wait(&mut self) -> Result<Option<u8>, Box<dyn Error>> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.shutdown().await;
+        }
+        self.session.wait().await
+    }
+
+    /// Hangs up on the remote command without waiting for it.
+    pub fn kill(&mut self) {
+        self.session.kill();
+    }
+
+    fn pending_output(&self) -> String {
+        String::from_utf8_lossy(&self.buffer).into_owned()
+    }
+
+    /// Moves the next chunk of output into the buffer; false when `deadline` passed first.
+    async fn receive_until(&mut self, deadline: Instant) -> bool {
+        match tokio::time::timeout_at(deadline, self.output.recv()).await {
+            Ok(Some(chunk)) => {
+                if let Some(handler) = self.output_handler.as_mut() {
+                    handler(&chunk);
+                }
+                self.buffer.extend_from_slice(&chunk);
+                true
+            }
+            Ok(None) => {
+                self.eof = true;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+async fn forward_output<R: AsyncRead + Unpin>(mut reader: R, sender: mpsc::Sender<Vec<u8>>) {
+    let mut buffer = [0u8; READ_BUFFER_SIZE];
+    while let Ok(n) = reader.read(&mut buffer).await {
+        if n == 0 || sender.send(buffer[..n].to_vec()).await.is_err() {
+            break;
+        }
+    }
+}
+
+impl Client {
+    /// Starts `command` with [`Client::open_shell`] and wraps it in an [`ExpectSession`].
+    pub async fn open_expect(&self, device_transport: DeviceTransport, command: &str, pty: bool) -> Result<ExpectSession, Box<dyn Error>> {
+        Ok(ExpectSession::new(self.open_shell(device_transport, command, pty).await?))
+    }
+}
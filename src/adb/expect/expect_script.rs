@@ -0,0 +1,213 @@
+use std::error::Error;
+use std::time::Duration;
+use crate::adb::client::Client;
+use crate::adb::expect::expect::ExpectSession;
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::expect_pattern::ExpectPattern;
+use crate::enums::expect_step::ExpectStep;
+use crate::models::expect_match::ExpectMatch;
+
+const COMMENT_PREFIX: char = '#';
+const TIMEOUT_OPTION: &str = "-t";
+const PTY_OPTION: &str = "-t";
+const EOF_KEYWORD: &str = "eof";
+const DEFAULT_SPAWN_COMMAND: &str = "sh";
+
+/// A parsed `adbr expect` script.
+///
+/// One statement per line; blank lines and lines starting with `#` are ignored:
+///
+/// ```text
+/// spawn [-t] COMMAND          command to drive (default: sh), -t on a PTY; only before other statements
+/// send TEXT                   send TEXT as is
+/// sendline TEXT               send TEXT and a newline
+/// expect [-t SECS] PATTERN    wait for "literal", /regex/ or eof
+/// timeout SECS                default timeout of later expects (10s)
+/// sleep SECS
+/// ```
+///
+/// TEXT and literals may be double-quoted to use `\n`, `\r`, `\t`, `\\`, `\"` and `\xHH` (ASCII);
+/// unquoted TEXT runs to the end of the line. In send TEXT, `$0`..`$9` are replaced with the
+/// capture groups of the last successful expect and `$$` is a literal `$`.
+#[derive(Debug, Clone)]
+pub struct ExpectScript {
+    pub steps: Vec<ExpectStep>,
+}
+
+impl ExpectScript {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut steps = Vec::new();
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(COMMENT_PREFIX) {
+                continue;
+            }
+            let step = parse_statement(line).map_err(|err| format!("line {}: {}", index + 1, err))?;
+            if matches!(step, ExpectStep::Spawn { .. }) && !steps.is_empty() {
+                return Err(format!("line {}: spawn must come before other statements", index + 1).into());
+            }
+            steps.push(step);
+        }
+        Ok(ExpectScript { steps })
+    }
+
+    /// The command given by `spawn`, or a plain shell.
+    pub fn command(&self) -> &str {
+        match self.steps.first() {
+            Some(ExpectStep::Spawn { command, .. }) => command,
+            _ => DEFAULT_SPAWN_COMMAND,
+        }
+    }
+
+    /// Whether `spawn -t` asked for a PTY.
+    pub fn pty(&self) -> bool {
+        matches!(self.steps.first(), Some(ExpectStep::Spawn { pty: true, .. }))
+    }
+
+    /// Runs the remaining steps against an open session. Stops at the first failed expect.
+    pub async fn run(&self, session: &mut ExpectSession) -> Result<(), Box<dyn Error>> {
+        let mut last_match: Option<ExpectMatch> = None;
+        for step in &self.steps {
+            match step {
+                ExpectStep::Spawn { .. } => {}
+                ExpectStep::Send(text) => session.send(substitute_captures(text, last_match.as_ref())).await?,
+                ExpectStep::Expect { pattern, timeout } => last_match = Some(session.expect(pattern, *timeout).await?),
+                ExpectStep::ExpectEof { timeout } => {
+                    session.expect_eof(*timeout).await?;
+                }
+                ExpectStep::SetTimeout(timeout) => session.set_timeout(*timeout),
+                ExpectStep::Sleep(duration) => tokio::time::sleep(*duration).await,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_statement(line: &str) -> Result<ExpectStep, String> {
+    let (keyword, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+    let rest = rest.trim_start();
+    match keyword {
+        "spawn" => parse_spawn(rest),
+        "send" => Ok(ExpectStep::Send(parse_text(rest)?)),
+        "sendline" => Ok(ExpectStep::Send(format!("{}\n", parse_text(rest)?))),
+        "expect" => parse_expect(rest),
+        "timeout" => Ok(ExpectStep::SetTimeout(parse_seconds(rest)?)),
+        "sleep" => Ok(ExpectStep::Sleep(parse_seconds(rest)?)),
+        _ => Err(format!("unknown statement: {}", keyword)),
+    }
+}
+
+fn parse_spawn(args: &str) -> Result<ExpectStep, String> {
+    match args.strip_prefix(PTY_OPTION) {
+        Some(rest) if rest.starts_with(char::is_whitespace) => Ok(ExpectStep::Spawn { command: parse_text(rest.trim_start())?, pty: true }),
+        _ => Ok(ExpectStep::Spawn { command: parse_text(args)?, pty: false }),
+    }
+}
+
+fn parse_expect(args: &str) -> Result<ExpectStep, String> {
+    let (timeout, pattern) = match args.strip_prefix(TIMEOUT_OPTION) {
+        Some(rest) if rest.starts_with(char::is_whitespace) => {
+            let rest = rest.trim_start();
+            let (seconds, pattern) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            (Some(parse_seconds(seconds)?), pattern.trim_start())
+        }
+        _ => (None, args),
+    };
+
+    if pattern == EOF_KEYWORD {
+        return Ok(ExpectStep::ExpectEof { timeout });
+    }
+    let pattern = if let Some(regex) = pattern.strip_prefix('/') {
+        let regex = regex.strip_suffix('/').ok_or("regex must end with /")?;
+        ExpectPattern::regex(regex).map_err(|err| err.to_string())?
+    } else {
+        ExpectPattern::Literal(parse_text(pattern)?)
+    };
+    Ok(ExpectStep::Expect { pattern, timeout })
+}
+
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value.trim().parse::<f64>().ok()
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| format!("invalid number of seconds: {}", value.trim()))
+}
+
+/// Text is taken raw unless it is a double-quoted string, which supports C-style escapes.
+fn parse_text(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("missing argument".to_string());
+    }
+    let Some(quoted) = value.strip_prefix('"') else {
+        return Ok(value.to_string());
+    };
+
+    let mut text = String::new();
+    let mut chars = quoted.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' => {
+                return match chars.as_str().trim() {
+                    "" => Ok(text),
+                    trailing => Err(format!("unexpected text after closing quote: {}", trailing)),
+                };
+            }
+            '\\' => match chars.next() {
+                Some('n') => text.push('\n'),
+                Some('r') => text.push('\r'),
+                Some('t') => text.push('\t'),
+                Some('\\') => text.push('\\'),
+                Some('"') => text.push('"'),
+                Some('x') => {
+                    let hex: String = chars.by_ref().take(2).collect();
+                    let byte = u8::from_str_radix(&hex, 16).ok().filter(|byte| hex.len() == 2 && byte.is_ascii())
+                        .ok_or_else(|| format!("invalid escape: \\x{}", hex))?;
+                    text.push(byte as char);
+                }
+                Some(other) => return Err(format!("invalid escape: \\{}", other)),
+                None => break,
+            },
+            _ => text.push(c),
+        }
+    }
+    Err("missing closing quote".to_string())
+}
+
+/// Replaces `$0`..`$9` with capture groups of `last_match` (empty when missing) and `$$` with `$`.
+pub fn substitute_captures(text: &str, last_match: Option<&ExpectMatch>) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => {
+                chars.next();
+                result.push('$');
+            }
+            Some(digit @ '0'..='9') => {
+                chars.next();
+                let index = digit as usize - '0' as usize;
+                result.push_str(last_match.and_then(|m| m.group(index)).unwrap_or(""));
+            }
+            _ => result.push('$'),
+        }
+    }
+    result
+}
+
+impl Client {
+    /// Runs `script` on the device and returns the command's exit status when known.
+    ///
+    /// `on_output` receives the command's output as it arrives.
+    pub async fn run_expect_script(&self, device_transport: DeviceTransport, script: &ExpectScript, on_output: impl FnMut(&[u8]) + Send + 'static) -> Result<Option<u8>, Box<dyn Error>> {
+        let mut session = self.open_expect(device_transport, script.command(), script.pty()).await?.with_output_handler(on_output);
+        if let Err(err) = script.run(&mut session).await {
+            session.kill();
+            return Err(err);
+        }
+        session.wait().await
+    }
+}
@@ -7,6 +7,7 @@ use std::path::{Path};
 use std::time::{Instant, UNIX_EPOCH};
 use filetime::{FileTime, set_file_times};
 use crate::adb::client::Client;
+use crate::adb::io::partial_file::PartialFile;
 use crate::enums::pull_result::PullResult;
 use crate::constants::{RECV_COMMAND, DATA_COMMAND, DONE_COMMAND, FAIL, OKAY, S_IFDIR, SYNC_COMMAND, QUIT_COMMAND, LIST_COMMAND, DENT_COMMAND};
 use crate::enums::device_transport::DeviceTransport;
@@ -97,6 +98,7 @@ impl Client {
         }
 
         let mut file = File::create(local_path).await?;
+        let partial = PartialFile::new(local_path);
         let mut total_bytes = 0u64;
 
         let data = self.get_exact_bytes(size as usize).await?;
@@ -115,6 +117,8 @@ impl Client {
                     total_bytes += size as u64;
                 }
                 DONE_COMMAND => {
+                    file.flush().await?;
+                    partial.keep();
                     break;
                 }
                 FAIL => {
@@ -141,7 +145,7 @@ impl Client {
                     set_file_times(local_path, FileTime::from_system_time(system_time), FileTime::from_system_time(system_time))?;
                 }
                 Err(e) => {
-                    eprintln!("Warning: Failed to preserve metadata for '{}': {}", remote_path, e);
+                    tracing::warn!("Failed to preserve metadata for '{}': {}", remote_path, e);
                 }
             }
         }
@@ -213,6 +217,8 @@ impl Client {
 
 
             if cmd == DONE_COMMAND {
+                // DONE carries an empty dirent: mode, size, mtime and name length.
+                self.get_exact_bytes(16).await?;
                 break;
             } else if cmd == FAIL {
                 let size_bytes = self.get_exact_bytes(4).await?;
@@ -232,6 +238,9 @@ impl Client {
 
                 let name_bytes = self.get_exact_bytes(namelen as usize).await?;
                 let name = String::from_utf8_lossy(&name_bytes).to_string();
+                if name == "." || name == ".." {
+                    continue;
+                }
 
                 entries.push(RemoteDirEntry {
                     name,
@@ -18,8 +18,8 @@ impl Client {
         let response = self.read_first_four_bytes_response().await?;
 
         if response != OKAY {
-            println!("{}", self.read_adb_full_response().await?);
-            return Err(format!("adbr push failed: Unexpected response: {}", response).into());
+            let error_message = self.read_adb_full_response().await?;
+            return Err(format!("adbr push failed: Unexpected response: {} {}", response, error_message).into());
         }
         let mut full_remote_path = remote_path.to_string();
         let should_be_directory = local_paths.len() > 1 || full_remote_path.ends_with('/') || full_remote_path.ends_with('\\');
@@ -144,10 +144,12 @@ impl Client {
 
         self.send_last_modified_time(local_path).await?;
 
-        let response = self.read_first_four_bytes_response().await?;
-        if response == FAIL {
-            let adb_full_response = self.read_adb_full_response().await?;
-            return Ok(PushResult::FailedAllPush(format!("adbr: error: failed to copy '{}' to '{}': remote {}", local_path.display(), full_remote_path.display(), adb_full_response)));
+        // The status is a sync header: OKAY or FAIL and a little-endian message length.
+        let status = self.get_exact_bytes(8).await?;
+        let message_length = u32::from_le_bytes(status[4..8].try_into()?) as usize;
+        let message = self.get_exact_bytes(message_length).await?;
+        if &status[..4] == FAIL.as_bytes() {
+            return Ok(PushResult::FailedAllPush(format!("adbr: error: failed to copy '{}' to '{}': remote {}", local_path.display(), full_remote_path.display(), String::from_utf8_lossy(&message))));
         }
 
         let duration = push_start_time.elapsed();
@@ -0,0 +1,68 @@
+use std::error::Error;
+use std::time::Instant;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use crate::adb::client::Client;
+use crate::adb::instrument::instrumentation_parser::InstrumentationParser;
+use crate::constants::{ADB_SHELL_COMMAND, AM_COMMAND, AM_INSTRUMENT, OKAY};
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::test_event::TestEvent;
+use crate::models::instrumentation_report::InstrumentationReport;
+use crate::utils::shell_quote;
+
+const INSTRUMENT_WAIT_RAW_OPTIONS: &str = "-w -r";
+const INSTRUMENT_ARG_OPTION: &str = "-e";
+const RUNNING_INSTRUMENTATION: &str = "running instrumentation";
+
+impl Client {
+    /// Runs `am instrument -w -r` and reports each parsed event to `on_event` as it arrives.
+    pub async fn adb_instrument<F>(&mut self, device_transport: DeviceTransport, runner: &str, instrument_args: &[(String, String)], mut on_event: F) -> Result<InstrumentationReport, Box<dyn Error>>
+    where
+        F: FnMut(&TestEvent),
+    {
+        if runner.is_empty() {
+            return Err("Instrumentation runner is required (e.g. com.example.test/androidx.test.runner.AndroidJUnitRunner)".into());
+        }
+
+        let mut command = format!("{} {} {}", AM_COMMAND, AM_INSTRUMENT, INSTRUMENT_WAIT_RAW_OPTIONS);
+        for (key, value) in instrument_args {
+            command.push_str(&format!(" {} {} {}", INSTRUMENT_ARG_OPTION, shell_quote(key), shell_quote(value)));
+        }
+        command.push_str(&format!(" {}", shell_quote(runner)));
+
+        self.send_transport(device_transport).await?;
+        self.send_adb_command(&format!("{}{}", ADB_SHELL_COMMAND, command)).await?;
+        let response = self.read_first_four_bytes_response().await?;
+        if response != OKAY {
+            let error_msg = self.read_adb_full_response().await?;
+            return Err(format!("Failed to start instrumentation: {}", error_msg).into());
+        }
+
+        let start_time = Instant::now();
+        let mut parser = InstrumentationParser::new();
+        let mut report = InstrumentationReport::new(runner);
+
+        let mut reader = BufReader::new(&mut self.adb_stream);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let bytes_read = self.limits.run(RUNNING_INSTRUMENTATION, false, reader.read_until(b'\n', &mut line)).await?;
+            let events = if bytes_read == 0 {
+                parser.finish()
+            } else {
+                parser.feed_line(&String::from_utf8_lossy(&line))
+            };
+
+            for event in &events {
+                on_event(event);
+                report.record(event);
+            }
+
+            if parser.is_finished() {
+                break;
+            }
+        }
+
+        report.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use crate::enums::test_event::TestEvent;
+use crate::enums::test_status::TestStatus;
+use crate::models::test_case_result::TestCaseResult;
+
+const STATUS_PREFIX: &str = "INSTRUMENTATION_STATUS: ";
+const STATUS_CODE_PREFIX: &str = "INSTRUMENTATION_STATUS_CODE: ";
+const RESULT_PREFIX: &str = "INSTRUMENTATION_RESULT: ";
+const CODE_PREFIX: &str = "INSTRUMENTATION_CODE: ";
+const FAILED_PREFIX: &str = "INSTRUMENTATION_FAILED: ";
+const ABORTED_PREFIX: &str = "INSTRUMENTATION_ABORTED: ";
+
+const KEY_CLASS: &str = "class";
+const KEY_TEST: &str = "test";
+const KEY_STACK: &str = "stack";
+const KEY_CURRENT: &str = "current";
+const KEY_NUM_TESTS: &str = "numtests";
+const KEY_SHORT_MSG: &str = "shortMsg";
+const KEY_LONG_MSG: &str = "longMsg";
+const KEY_STREAM: &str = "stream";
+
+const UNEXPECTED_END_MESSAGE: &str = "Instrumentation output ended unexpectedly";
+
+/// Incremental parser for the `am instrument -r` raw output stream.
+#[derive(Default)]
+pub struct InstrumentationParser {
+    status_bundle: HashMap<String, String>,
+    result_bundle: HashMap<String, String>,
+    last_key: Option<String>,
+    in_result: bool,
+    failure_message: Option<String>,
+    running: HashMap<String, (String, String, Instant)>,
+    total: usize,
+    finished: bool,
+}
+
+impl InstrumentationParser {
+    pub fn new() -> Self {
+        InstrumentationParser::default()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn feed_line(&mut self, line: &str) -> Vec<TestEvent> {
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        if self.finished {
+            return Vec::new();
+        }
+
+        if let Some(pair) = line.strip_prefix(STATUS_PREFIX) {
+            self.in_result = false;
+            self.insert_pair(pair);
+            Vec::new()
+        } else if let Some(code) = line.strip_prefix(STATUS_CODE_PREFIX) {
+            self.last_key = None;
+            let bundle = std::mem::take(&mut self.status_bundle);
+            match code.trim().parse::<i32>().ok().and_then(TestStatus::from_status_code) {
+                Some(status) => self.handle_status(status, bundle),
+                None => Vec::new(),
+            }
+        } else if let Some(pair) = line.strip_prefix(RESULT_PREFIX) {
+            self.in_result = true;
+            self.insert_pair(pair);
+            Vec::new()
+        } else if let Some(code) = line.strip_prefix(CODE_PREFIX) {
+            self.finish_run(code.trim().parse::<i32>().ok())
+        } else if let Some(message) = line.strip_prefix(FAILED_PREFIX).or_else(|| line.strip_prefix(ABORTED_PREFIX)) {
+            self.failure_message = Some(message.trim().to_string());
+            Vec::new()
+        } else {
+            self.append_continuation(line);
+            Vec::new()
+        }
+    }
+
+    /// Flushes the parser at end of stream, reporting a crash if the run never completed.
+    pub fn finish(&mut self) -> Vec<TestEvent> {
+        if self.finished {
+            return Vec::new();
+        }
+        if self.failure_message.is_none() {
+            self.failure_message = Some(UNEXPECTED_END_MESSAGE.to_string());
+        }
+        self.finish_run(None)
+    }
+
+    fn insert_pair(&mut self, pair: &str) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let bundle = if self.in_result { &mut self.result_bundle } else { &mut self.status_bundle };
+        bundle.insert(key.to_string(), value.to_string());
+        self.last_key = Some(key.to_string());
+    }
+
+    fn append_continuation(&mut self, line: &str) {
+        if let Some(key) = &self.last_key {
+            let bundle = if self.in_result { &mut self.result_bundle } else { &mut self.status_bundle };
+            if let Some(value) = bundle.get_mut(key) {
+                value.push('\n');
+                value.push_str(line);
+            }
+        }
+    }
+
+    fn handle_status(&mut self, status: TestStatus, bundle: HashMap<String, String>) -> Vec<TestEvent> {
+        let class_name = bundle.get(KEY_CLASS).cloned().unwrap_or_default();
+        let test_name = bundle.get(KEY_TEST).cloned().unwrap_or_default();
+        if class_name.is_empty() && test_name.is_empty() {
+            return Vec::new();
+        }
+
+        if let Some(total) = bundle.get(KEY_NUM_TESTS).and_then(|n| n.trim().parse().ok()) {
+            self.total = total;
+        }
+        let full_name = format!("{}#{}", class_name, test_name);
+
+        if status == TestStatus::Started {
+            let current = bundle.get(KEY_CURRENT).and_then(|n| n.trim().parse().ok()).unwrap_or(0);
+            self.running.insert(full_name, (class_name.clone(), test_name.clone(), Instant::now()));
+            return vec![TestEvent::Started { class_name, test_name, current, total: self.total }];
+        }
+
+        let duration_ms = self.running.remove(&full_name)
+            .map(|(_, _, started)| started.elapsed().as_millis() as u64)
+            .unwrap_or(0);
+        let stack_trace = bundle.get(KEY_STACK)
+            .map(|stack| stack.trim_end().to_string())
+            .filter(|stack| !stack.is_empty());
+
+        vec![TestEvent::Finished(TestCaseResult { class_name, test_name, status, stack_trace, duration_ms, device: None, retries: 0 })]
+    }
+
+    fn finish_run(&mut self, code: Option<i32>) -> Vec<TestEvent> {
+        self.finished = true;
+
+        let message = self.failure_message.clone()
+            .or_else(|| self.result_bundle.get(KEY_LONG_MSG).cloned())
+            .or_else(|| self.result_bundle.get(KEY_SHORT_MSG).cloned())
+            .map(|message| message.trim().to_string());
+        let crashed = message.is_some() || !self.running.is_empty();
+        let message = message.or_else(|| crashed.then(|| UNEXPECTED_END_MESSAGE.to_string()));
+
+        let mut events: Vec<TestEvent> = self.running.drain()
+            .map(|(_, (class_name, test_name, started))| TestEvent::Finished(TestCaseResult {
+                class_name,
+                test_name,
+                status: TestStatus::Error,
+                stack_trace: message.clone(),
+                duration_ms: started.elapsed().as_millis() as u64,
+                device: None,
+                retries: 0,
+            }))
+            .collect();
+
+        let stream_message = self.result_bundle.get(KEY_STREAM).map(|stream| stream.trim().to_string());
+        events.push(TestEvent::RunFinished {
+            code,
+            crashed,
+            message: if crashed { message } else { stream_message },
+        });
+        events
+    }
+}
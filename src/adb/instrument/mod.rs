@@ -0,0 +1,3 @@
+pub mod instrument;
+pub mod instrumentation_parser;
+pub mod sharding;
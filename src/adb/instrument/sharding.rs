@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::time::Instant;
+use futures::future::join_all;
+use tracing::Instrument;
+use crate::adb::client::Client;
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::test_event::TestEvent;
+use crate::models::instrumentation_report::InstrumentationReport;
+
+const NUM_SHARDS_ARG: &str = "numShards";
+const SHARD_INDEX_ARG: &str = "shardIndex";
+const CLASS_ARG: &str = "class";
+
+impl Client {
+    /// Splits an instrumentation run into `num_shards` shards spread over `serials`, runs them
+    /// concurrently on separate connections, retries failures on other devices and merges the results.
+    /// The connection of `self` is not used; only its server address is.
+    pub async fn adb_instrument_sharded<F>(&self, serials: &[String], runner: &str, instrument_args: &[(String, String)], num_shards: usize, max_retries: u32, on_event: F) -> Result<InstrumentationReport, Box<dyn Error>>
+    where
+        F: Fn(&str, &TestEvent),
+    {
+        if serials.is_empty() {
+            return Err("No ready devices found for sharded instrumentation".into());
+        }
+
+        let start_time = Instant::now();
+        let num_shards = num_shards.max(1);
+        let on_event = &on_event;
+
+        let device_runs = serials.iter().enumerate().map(|(device_index, serial)| async move {
+            let mut shard_reports = Vec::new();
+            for shard_index in (device_index..num_shards).step_by(serials.len()) {
+                let result = self.run_instrumentation_on(serial, runner, &Self::shard_args(instrument_args, num_shards, shard_index), on_event).await;
+                shard_reports.push((shard_index, serial.clone(), result));
+            }
+            shard_reports
+        });
+        let shard_results: Vec<_> = join_all(device_runs).await.into_iter().flatten().collect();
+
+        let mut merged = InstrumentationReport::new(runner);
+        for (shard_index, serial, result) in shard_results {
+            let shard_report = match result {
+                Ok(report) if !report.crashed => report,
+                first_attempt => {
+                    let retry_serial = Self::pick_other_device(serials, &serial, shard_index);
+                    let retry = self.run_instrumentation_on(retry_serial, runner, &Self::shard_args(instrument_args, num_shards, shard_index), on_event).await;
+                    match (retry, first_attempt) {
+                        (Ok(report), _) if !report.crashed => report,
+                        (_, Ok(report)) => report,
+                        (_, Err(err)) => Self::failed_run(runner, format!("Shard {} on {} failed: {}", shard_index, serial, err)),
+                    }
+                }
+            };
+            merged.merge(shard_report);
+        }
+
+        for attempt in 1..=max_retries {
+            let mut retries_by_device: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+            for (index, failed) in merged.failed_tests().iter().enumerate() {
+                let original = failed.device.as_deref().unwrap_or_default();
+                let retry_serial = Self::pick_other_device(serials, original, index);
+                retries_by_device.entry(retry_serial).or_default().push(failed.full_name());
+            }
+            if retries_by_device.is_empty() {
+                break;
+            }
+
+            let retry_runs = retries_by_device.iter().map(|(serial, tests)| {
+                // The failed tests replace whatever classes the caller selected.
+                let mut args: Vec<(String, String)> = instrument_args.iter().filter(|(key, _)| key != CLASS_ARG).cloned().collect();
+                args.push((CLASS_ARG.to_string(), tests.join(",")));
+                async move { self.run_instrumentation_on(serial, runner, &args, on_event).await }
+            });
+
+            let mut retries_completed = true;
+            for (serial, retry) in retries_by_device.keys().zip(join_all(retry_runs).await) {
+                match retry {
+                    Ok(retry) => {
+                        if retry.crashed {
+                            retries_completed = false;
+                            let message = retry.crash_message.clone().unwrap_or_else(|| "instrumentation crashed".to_string());
+                            merged.merge(Self::failed_run(runner, format!("Retry {} on {} crashed: {}", attempt, serial, message)));
+                        }
+                        for mut test in retry.tests {
+                            test.retries = attempt;
+                            merged.replace_test(test);
+                        }
+                    }
+                    Err(err) => {
+                        retries_completed = false;
+                        merged.merge(Self::failed_run(runner, format!("Retry {} on {} failed: {}", attempt, serial, err)));
+                    }
+                }
+            }
+            // Every failure was retried to a pass, so earlier crashes no longer decide the result.
+            if retries_completed && merged.failed_tests().is_empty() {
+                merged.crashed = false;
+                merged.crash_message = None;
+            }
+        }
+
+        merged.duration_ms = start_time.elapsed().as_millis() as u64;
+        Ok(merged)
+    }
+
+    async fn run_instrumentation_on<F>(&self, serial: &str, runner: &str, instrument_args: &[(String, String)], on_event: &F) -> Result<InstrumentationReport, Box<dyn Error>>
+    where
+        F: Fn(&str, &TestEvent),
+    {
+        let span = tracing::debug_span!("device", serial);
+        let result = async {
+            let mut client = self.new_connection().await?;
+            let result = client.adb_instrument(DeviceTransport::serial(serial.to_string()), runner, instrument_args, |event| on_event(serial, event)).await;
+            client.close().await;
+            result
+        }.instrument(span).await;
+
+        let mut report = result?;
+        for test in report.tests.iter_mut() {
+            test.device = Some(serial.to_string());
+        }
+        Ok(report)
+    }
+
+    /// A report for a run that produced no results.
+    fn failed_run(runner: &str, message: String) -> InstrumentationReport {
+        let mut report = InstrumentationReport::new(runner);
+        report.crashed = true;
+        report.crash_message = Some(message);
+        report
+    }
+
+    fn shard_args(instrument_args: &[(String, String)], num_shards: usize, shard_index: usize) -> Vec<(String, String)> {
+        let mut args = instrument_args.to_vec();
+        if num_shards > 1 {
+            args.push((NUM_SHARDS_ARG.to_string(), num_shards.to_string()));
+            args.push((SHARD_INDEX_ARG.to_string(), shard_index.to_string()));
+        }
+        args
+    }
+
+    fn pick_other_device<'a>(serials: &'a [String], current: &str, offset: usize) -> &'a str {
+        let others: Vec<&String> = serials.iter().filter(|serial| serial.as_str() != current).collect();
+        if others.is_empty() {
+            &serials[0]
+        } else {
+            others[offset % others.len()]
+        }
+    }
+}
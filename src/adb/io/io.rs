@@ -1,12 +1,29 @@
 use std::error::Error;
 use std::io::ErrorKind;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use crate::adb::client::Client;
-use std::io::{Write};
+use crate::constants::FRAME_TRACE_TARGET;
+use crate::utils::escape_bytes;
+
+const MAX_TRACED_FRAME_BYTES: usize = 256;
+const SENDING: &str = "sending to the ADB server";
+const WAITING: &str = "waiting for the ADB server";
+
+/// Dumps bytes sent (`>>`) or received (`<<`) on the server connection when frame tracing is on.
+pub(crate) fn trace_frame(direction: &str, data: &[u8]) {
+    if tracing::enabled!(target: FRAME_TRACE_TARGET, tracing::Level::TRACE) {
+        tracing::trace!(target: FRAME_TRACE_TARGET, "{} {}", direction, escape_bytes(data, MAX_TRACED_FRAME_BYTES));
+    }
+}
+
+fn is_io_error(err: &(dyn Error + 'static), kind: ErrorKind) -> bool {
+    err.downcast_ref::<std::io::Error>().is_some_and(|err| err.kind() == kind)
+}
 
 impl Client {
     pub async fn send_command(&mut self, data: &[u8]) -> Result<(), Box<dyn Error>> {
-        self.adb_stream.write_all(data).await?;
+        trace_frame(">>", data);
+        self.limits.run(SENDING, true, self.adb_stream.write_all(data)).await?;
         Ok(())
     }
 
@@ -15,11 +32,14 @@ impl Client {
         let mut temp_buffer = [0u8; 1024];
 
         loop {
-            match self.adb_stream.read(&mut temp_buffer).await {
+            match self.limits.run(WAITING, false, self.adb_stream.read(&mut temp_buffer)).await {
                 Ok(0) => break,
-                Ok(n) => data.extend_from_slice(&temp_buffer[..n]),
-                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
-                Err(err) => return Err(err.into()),
+                Ok(n) => {
+                    trace_frame("<<", &temp_buffer[..n]);
+                    data.extend_from_slice(&temp_buffer[..n]);
+                }
+                Err(err) if is_io_error(err.as_ref(), ErrorKind::Interrupted) => continue,
+                Err(err) => return Err(err),
             }
         }
 
@@ -37,16 +57,20 @@ impl Client {
         }
 
         let mut data = vec![0u8; length];
-        match self.adb_stream.read_exact(&mut data).await {
-            Ok(_) => Ok(String::from_utf8(data)?),
-            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(String::new()),
-            Err(e) => Err(Box::new(e))
+        match self.limits.run(WAITING, true, self.adb_stream.read_exact(&mut data)).await {
+            Ok(_) => {
+                trace_frame("<<", &data);
+                Ok(String::from_utf8(data)?)
+            }
+            Err(e) if is_io_error(e.as_ref(), ErrorKind::UnexpectedEof) => Ok(String::new()),
+            Err(e) => Err(e)
         }
     }
 
     pub async fn get_exact_bytes(&mut self, num_bytes: usize) -> Result<Vec<u8>, Box<dyn Error>> {
         let mut buffer = vec![0u8; num_bytes];
-        self.adb_stream.read_exact(&mut buffer).await.map_err(|e| Box::new(e) as Box<dyn Error>)?;
+        self.limits.run(WAITING, true, self.adb_stream.read_exact(&mut buffer)).await?;
+        trace_frame("<<", &buffer);
         Ok(buffer)
     }
 
@@ -55,12 +79,15 @@ impl Client {
         let mut temp_buffer = [0u8; 1024];
 
         loop {
-            match self.adb_stream.read(&mut temp_buffer).await {
+            match self.limits.run(WAITING, false, self.adb_stream.read(&mut temp_buffer)).await {
                 Ok(0) => break,
-                Ok(n) => buffer.extend_from_slice(&temp_buffer[..n]),
-                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e.into()),
+                Ok(n) => {
+                    trace_frame("<<", &temp_buffer[..n]);
+                    buffer.extend_from_slice(&temp_buffer[..n]);
+                }
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::WouldBlock) => break,
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(e),
             }
         }
 
@@ -68,52 +95,53 @@ impl Client {
     }
 
 
-    pub async fn read_and_print_data(&mut self) -> Result<(), Box<dyn Error>> {
+    /// Copies the rest of the stream to `out` as it arrives.
+    pub async fn read_and_write_data<W: AsyncWrite + Unpin>(&mut self, out: &mut W) -> Result<(), Box<dyn Error>> {
         let mut temp_buffer = [0u8; 1024];
 
         loop {
-            match self.adb_stream.read(&mut temp_buffer).await {
+            match self.limits.run(WAITING, false, self.adb_stream.read(&mut temp_buffer)).await {
                 Ok(0) => break,
                 Ok(n) => {
-                    let chunk = String::from_utf8_lossy(&temp_buffer[..n]);
-                    print!("{}", chunk);
-                    std::io::stdout().flush()?;
+                    trace_frame("<<", &temp_buffer[..n]);
+                    out.write_all(&temp_buffer[..n]).await?;
+                    out.flush().await?;
                 },
-                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e.into()),
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::WouldBlock) => break,
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(e),
             }
         }
 
         Ok(())
     }
 
-    pub async fn read_print_and_collect_output(&mut self) -> Result<String, Box<dyn Error>> {
+    /// Like [`Client::read_and_write_data`], also returning everything read (invalid UTF-8 replaced).
+    pub async fn read_write_and_collect_output<W: AsyncWrite + Unpin>(&mut self, out: &mut W) -> Result<String, Box<dyn Error>> {
         let mut temp_buffer = [0u8; 1024];
-        let mut full_output = String::new();
+        let mut full_output = Vec::new();
 
         loop {
-            match self.adb_stream.read(&mut temp_buffer).await {
+            match self.limits.run(WAITING, false, self.adb_stream.read(&mut temp_buffer)).await {
                 Ok(0) => break,
                 Ok(n) => {
-                    let chunk = String::from_utf8_lossy(&temp_buffer[..n]);
-                    print!("{}", chunk);
-                    std::io::stdout().flush()?;
-                    full_output.push_str(&chunk);
+                    trace_frame("<<", &temp_buffer[..n]);
+                    out.write_all(&temp_buffer[..n]).await?;
+                    out.flush().await?;
+                    full_output.extend_from_slice(&temp_buffer[..n]);
                 },
-                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
-                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
-                Err(e) => return Err(e.into()),
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::WouldBlock) => break,
+                Err(e) if is_io_error(e.as_ref(), ErrorKind::Interrupted) => continue,
+                Err(e) => return Err(e),
             }
         }
 
-        Ok(full_output)
+        Ok(String::from_utf8_lossy(&full_output).into_owned())
     }
 
     pub async fn has_more_data(&mut self) -> Result<bool, Box<dyn Error>> {
-        let mut buf = [0u8; 1];
-        let bytes_read = self.adb_stream.peek(&mut buf).await?;
-        Ok(bytes_read > 0)
+        let buffered = self.limits.run(WAITING, true, async { Ok(self.adb_stream.fill_buf().await?.len()) }).await?;
+        Ok(buffered > 0)
     }
 
 }
@@ -0,0 +1,59 @@
+use std::error::Error;
+use std::future::Future;
+use tokio::time::{Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use crate::enums::interrupted::Interrupted;
+
+/// Limits applied to every read and write a [`Client`](crate::Client) makes on its connection.
+#[derive(Debug, Clone, Default)]
+pub struct IoLimits {
+    /// Longest wait for a single protocol read or write. Streams of command output (shell, logcat)
+    /// may legitimately stay quiet for long and are only bound by the deadline.
+    pub io_timeout: Option<Duration>,
+    /// Point in time after which every operation fails.
+    pub deadline: Option<Instant>,
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl IoLimits {
+    /// Runs one I/O step, failing with [`Interrupted`] when it is cancelled or runs out of time.
+    /// `idle` steps are also bound by the I/O timeout.
+    pub(crate) async fn run<T, F>(&self, operation: &str, idle: bool, io: F) -> Result<T, Box<dyn Error>>
+    where
+        F: Future<Output = std::io::Result<T>>,
+    {
+        let timeout = self.io_timeout.filter(|_| idle).map(|timeout| Instant::now() + timeout);
+        let limit = match (timeout, self.deadline) {
+            (Some(timeout), Some(deadline)) => Some(timeout.min(deadline)),
+            (timeout, deadline) => timeout.or(deadline),
+        };
+
+        tokio::select! {
+            result = io => Ok(result?),
+            _ = Self::cancelled(self.cancellation.as_ref()) => Err(Interrupted::Cancelled.into()),
+            _ = Self::expired(limit) => Err(Interrupted::TimedOut { operation: operation.to_string() }.into()),
+        }
+    }
+
+    /// Sleeps for `duration`, waking early with an error on cancellation or at the deadline.
+    pub(crate) async fn sleep(&self, operation: &str, duration: Duration) -> Result<(), Box<dyn Error>> {
+        self.run(operation, false, async {
+            tokio::time::sleep(duration).await;
+            Ok(())
+        }).await
+    }
+
+    async fn cancelled(token: Option<&CancellationToken>) {
+        match token {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    }
+
+    async fn expired(limit: Option<Instant>) {
+        match limit {
+            Some(limit) => tokio::time::sleep_until(limit).await,
+            None => std::future::pending().await,
+        }
+    }
+}
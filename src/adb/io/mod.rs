@@ -1 +1,3 @@
 mod io;
+pub mod io_limits;
+pub(crate) mod partial_file;
@@ -0,0 +1,27 @@
+use std::path::{Path, PathBuf};
+
+/// Removes a file that is being written unless [`PartialFile::keep`] is called, so a transfer that
+/// fails, times out or is dropped halfway does not leave a truncated file behind.
+pub(crate) struct PartialFile {
+    path: Option<PathBuf>,
+}
+
+impl PartialFile {
+    pub(crate) fn new(path: &Path) -> Self {
+        PartialFile { path: Some(path.to_path_buf()) }
+    }
+
+    pub(crate) fn keep(mut self) {
+        self.path = None;
+    }
+}
+
+impl Drop for PartialFile {
+    fn drop(&mut self) {
+        if let Some(path) = self.path.take() {
+            if std::fs::remove_file(&path).is_ok() {
+                tracing::debug!("Removed partially written {}", path.display());
+            }
+        }
+    }
+}
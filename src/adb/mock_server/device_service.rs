@@ -0,0 +1,176 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::adb::server::host_request::{fail, okay, okay_string, parse_forward_spec};
+use crate::adb::mock_server::shell_service::{run_shell, InteractiveCommand};
+use crate::adb::mock_server::sync_service::serve_sync;
+use crate::adb::mock_server::virtual_device::VirtualDevice;
+use crate::adb::shell::shell_protocol::{encode_shell_packet, ShellPacketDecoder};
+use crate::constants::{ADB_EXEC_COMMAND, ADB_REBOOT_COMMAND, ADB_ROOT_COMMAND, ADB_SHELL_COMMAND, ADB_SHELL_V2_SERVICE, ADB_UNROOT_COMMAND, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_LIST_SERVICE, HOST_FORWARD_SERVICE, OKAY, SHELL_PTY_ARG, SYNC_COMMAND};
+use crate::enums::shell_packet_id::ShellPacketId;
+
+const REVERSE_PREFIX: &str = "reverse:";
+
+/// Serves one service on a connection already switched to `device`, then returns so the
+/// connection is closed like adbd closes finished streams.
+pub(crate) async fn serve_device<S: AsyncRead + AsyncWrite + Unpin>(device: &VirtualDevice, service: &str, stream: &mut S) -> io::Result<()> {
+    if service == SYNC_COMMAND {
+        stream.write_all(OKAY.as_bytes()).await?;
+        return serve_sync(device, stream).await;
+    }
+
+    if let Some((command, shell_v2, pty)) = shell_command(service) {
+        let session = {
+            let mut device = device.lock();
+            let session = device.interactive_commands.get(command).cloned();
+            if session.is_some() {
+                device.shell_history.push(command.to_string());
+            }
+            session
+        };
+        if let Some(mut session) = session {
+            stream.write_all(OKAY.as_bytes()).await?;
+            if session.needs_pty && !pty {
+                session.greeting.clear();
+            }
+            return match shell_v2 {
+                true => serve_interactive_v2(&session, stream).await,
+                false => serve_interactive_raw(&session, !service.starts_with(ADB_EXEC_COMMAND), stream).await,
+            };
+        }
+    }
+
+    let reply = if let Some(command) = service.strip_prefix(ADB_SHELL_COMMAND) {
+        [okay(), run_shell(&mut device.lock(), command).merged()].concat()
+    } else if let Some(command) = service.strip_prefix(ADB_EXEC_COMMAND) {
+        [okay(), run_shell(&mut device.lock(), command).stdout].concat()
+    } else if let Some((_, command)) = service.strip_prefix(ADB_SHELL_V2_SERVICE).and_then(|options| options.split_once(':')) {
+        let output = run_shell(&mut device.lock(), command);
+        let mut reply = okay();
+        if !output.stdout.is_empty() {
+            reply.extend(encode_shell_packet(ShellPacketId::Stdout, &output.stdout));
+        }
+        if !output.stderr.is_empty() {
+            reply.extend(encode_shell_packet(ShellPacketId::Stderr, &output.stderr));
+        }
+        reply.extend(encode_shell_packet(ShellPacketId::Exit, &[output.exit_code]));
+        reply
+    } else if let Some(target) = service.strip_prefix(ADB_REBOOT_COMMAND) {
+        device.lock().reboot(target);
+        okay()
+    } else if service == ADB_ROOT_COMMAND || service == ADB_UNROOT_COMMAND {
+        let root = service == ADB_ROOT_COMMAND;
+        let mut device = device.lock();
+        let message = match (root, device.root) {
+            (true, true) => "adbd is already running as root\n",
+            (true, false) => "restarting adbd as root\n",
+            (false, true) => "restarting adbd as non root\n",
+            (false, false) => "adbd not running as root\n",
+        };
+        device.root = root;
+        [okay(), message.as_bytes().to_vec()].concat()
+    } else if let Some(request) = service.strip_prefix(REVERSE_PREFIX) {
+        reverse(device, request)
+    } else {
+        fail(&format!("unsupported device service: {}", service))
+    };
+    stream.write_all(&reply).await
+}
+
+/// The command of a `shell:`, `exec:` or shell v2 service, whether it is shell v2 and whether it
+/// has a PTY (`shell:` always does).
+fn shell_command(service: &str) -> Option<(&str, bool, bool)> {
+    if let Some((options, command)) = service.strip_prefix(ADB_SHELL_V2_SERVICE).and_then(|options| options.split_once(':')) {
+        return Some((command, true, options.split(',').any(|option| option == SHELL_PTY_ARG)));
+    }
+    if let Some(command) = service.strip_prefix(ADB_SHELL_COMMAND) {
+        return Some((command, false, true));
+    }
+    service.strip_prefix(ADB_EXEC_COMMAND).map(|command| (command, false, false))
+}
+
+/// Runs an interactive command over shell v2 packets until the client closes stdin or disconnects.
+async fn serve_interactive_v2<S: AsyncRead + AsyncWrite + Unpin>(session: &InteractiveCommand, stream: &mut S) -> io::Result<()> {
+    if !session.greeting.is_empty() {
+        stream.write_all(&encode_shell_packet(ShellPacketId::Stdout, &session.greeting)).await?;
+    }
+    let mut decoder = ShellPacketDecoder::new();
+    let mut buffer = [0u8; 4096];
+    loop {
+        let count = stream.read(&mut buffer).await?;
+        if count == 0 {
+            return Ok(());
+        }
+        decoder.feed(&buffer[..count]);
+        while let Some((id, data)) = decoder.next_packet() {
+            match id {
+                ShellPacketId::Stdin => {
+                    let (stdout, stderr) = (session.respond)(&data);
+                    for (id, output) in [(ShellPacketId::Stdout, stdout), (ShellPacketId::Stderr, stderr)] {
+                        if !output.is_empty() {
+                            stream.write_all(&encode_shell_packet(id, &output)).await?;
+                        }
+                    }
+                }
+                ShellPacketId::CloseStdin => return stream.write_all(&encode_shell_packet(ShellPacketId::Exit, &[session.exit_code])).await,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Runs an interactive command over a raw `shell:` or `exec:` stream until the client stops
+/// writing; `exec:` has no stderr. There is no exit status to send.
+async fn serve_interactive_raw<S: AsyncRead + AsyncWrite + Unpin>(session: &InteractiveCommand, with_stderr: bool, stream: &mut S) -> io::Result<()> {
+    stream.write_all(&session.greeting).await?;
+    let mut buffer = [0u8; 4096];
+    loop {
+        let count = stream.read(&mut buffer).await?;
+        if count == 0 {
+            return Ok(());
+        }
+        let (stdout, stderr) = (session.respond)(&buffer[..count]);
+        stream.write_all(&stdout).await?;
+        if with_stderr {
+            stream.write_all(&stderr).await?;
+        }
+    }
+}
+
+/// `reverse:` requests, answered like adbd after the stream is opened: a second OKAY or a FAIL.
+fn reverse(device: &VirtualDevice, request: &str) -> Vec<u8> {
+    let mut device = device.lock();
+    let result = if request == HOST_FORWARD_LIST_SERVICE {
+        let listing: String = device.reverse_forwards.iter()
+            .map(|(remote, local)| format!("{} {} {}\n", device.serial, remote, local))
+            .collect();
+        return okay_string(&listing);
+    } else if request == HOST_FORWARD_KILL_ALL_SERVICE {
+        device.reverse_forwards.clear();
+        Ok(())
+    } else if let Some(remote) = request.strip_prefix(HOST_FORWARD_KILL_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+        let count = device.reverse_forwards.len();
+        device.reverse_forwards.retain(|(existing, _)| existing != remote);
+        if device.reverse_forwards.len() < count { Ok(()) } else { Err(format!("listener '{}' not found", remote)) }
+    } else if let Some(spec) = request.strip_prefix(HOST_FORWARD_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+        parse_forward_spec(spec).and_then(|(no_rebind, remote, local)| {
+            match device.reverse_forwards.iter_mut().find(|(existing, _)| *existing == remote) {
+                Some(_) if no_rebind => Err("cannot rebind existing socket".to_string()),
+                Some(existing) => {
+                    existing.1 = local;
+                    Ok(())
+                }
+                None => {
+                    device.reverse_forwards.push((remote, local));
+                    Ok(())
+                }
+            }
+        })
+    } else {
+        Err(format!("unsupported reverse request: {}", request))
+    };
+
+    match result {
+        Ok(()) => [okay(), okay()].concat(),
+        Err(message) => [okay(), fail(&message)].concat(),
+    }
+}
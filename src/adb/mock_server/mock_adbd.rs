@@ -0,0 +1,131 @@
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::adb::mock_server::device_service::serve_device;
+use crate::adb::mock_server::virtual_device::VirtualDevice;
+use crate::adb::wire::auth::DeviceAuth;
+use crate::adb::wire::device_link::{DeviceLink, IncomingStream};
+use crate::constants::{DEFAULT_ADB_SERVER_IP, DELAYED_ACK_FEATURE, OKAY, TCP_SOCKET_PREFIX};
+use crate::models::device_banner::DeviceBanner;
+
+const SERVICE_BUFFER_SIZE: usize = 64 * 1024;
+const BANNER_PROPS: [&str; 3] = ["ro.product.name", "ro.product.model", "ro.product.device"];
+
+/// A stand-in adbd for a [`VirtualDevice`], reachable over TCP like a device after `adb tcpip` or
+/// an emulator. Speaks the device protocol to an [`AdbServer`](crate::adb::server::adb_server::AdbServer)
+/// and serves the same services as the [`MockServer`](crate::adb::mock_server::mock_server::MockServer),
+/// plus `tcp:PORT` streams to ports of the local machine. Hosts only have to authenticate when
+/// [`MockAdbd::auth`] was set.
+#[derive(Debug, Clone)]
+pub struct MockAdbd {
+    device: VirtualDevice,
+    auth: Option<DeviceAuth>,
+    links: Arc<Mutex<Vec<DeviceLink>>>,
+}
+
+impl MockAdbd {
+    pub fn new(device: &VirtualDevice) -> Self {
+        MockAdbd { device: device.clone(), auth: None, links: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    /// Makes hosts pass AUTH against the keys of `auth`, like adbd with `ro.adb.secure=1`.
+    pub fn auth(mut self, auth: DeviceAuth) -> Self {
+        self.auth = Some(auth);
+        self
+    }
+
+    pub fn device(&self) -> VirtualDevice {
+        self.device.clone()
+    }
+
+    fn links(&self) -> MutexGuard<'_, Vec<DeviceLink>> {
+        self.links.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Accepts servers on a free port of the loopback interface, e.g. for `adbr connect`.
+    pub async fn listen(&self) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind((DEFAULT_ADB_SERVER_IP, 0)).await?;
+        let address = listener.local_addr()?;
+        let adbd = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let adbd = adbd.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = adbd.serve(stream).await {
+                        tracing::debug!("mock adbd connection failed: {}", err);
+                    }
+                });
+            }
+        });
+        Ok(address)
+    }
+
+    /// Serves one server connection until either end closes it.
+    pub async fn serve<S: AsyncRead + AsyncWrite + Send + 'static>(&self, stream: S) -> io::Result<()> {
+        let (link, mut streams) = DeviceLink::accept(stream, &self.banner(), self.auth.as_ref()).await?;
+        self.links().push(link);
+        while let Some(incoming) = streams.recv().await {
+            let device = self.device.clone();
+            tokio::spawn(async move { serve_stream(device, incoming).await });
+        }
+        self.links().retain(|link| !link.is_closed());
+        Ok(())
+    }
+
+    /// Drops every server connection, like a device going out of Wi-Fi range.
+    pub fn disconnect(&self) {
+        for link in self.links().drain(..) {
+            link.close();
+        }
+    }
+
+    fn banner(&self) -> DeviceBanner {
+        let device = self.device.lock();
+        let mut features = device.features.clone();
+        features.push(DELAYED_ACK_FEATURE.to_string());
+        let mut banner = DeviceBanner::new(device.connection_state()).features(&features);
+        for key in BANNER_PROPS {
+            if let Some(value) = device.props.get(key) {
+                banner = banner.prop(key, value);
+            }
+        }
+        banner
+    }
+}
+
+async fn serve_stream(device: VirtualDevice, incoming: IncomingStream) {
+    if let Some(port) = incoming.service.strip_prefix(TCP_SOCKET_PREFIX) {
+        let connected = match port.parse::<u16>() {
+            Ok(port) => TcpStream::connect((DEFAULT_ADB_SERVER_IP, port)).await.ok(),
+            Err(_) => None,
+        };
+        match connected {
+            Some(mut socket) => {
+                let mut stream = incoming.accept();
+                let _ = tokio::io::copy_bidirectional(&mut stream, &mut socket).await;
+            }
+            None => incoming.reject(),
+        }
+        return;
+    }
+
+    // The mock services answer like the server would, OKAY or FAIL first; adbd says the same by
+    // accepting or refusing the stream.
+    let (mut service_end, mut reply) = tokio::io::duplex(SERVICE_BUFFER_SIZE);
+    let service = incoming.service.clone();
+    tokio::spawn(async move {
+        if let Err(err) = serve_device(&device, &service, &mut service_end).await {
+            tracing::debug!("mock adbd service {} failed: {}", service, err);
+        }
+        let _ = service_end.shutdown().await;
+    });
+    let mut status = [0u8; 4];
+    if reply.read_exact(&mut status).await.is_err() || status != OKAY.as_bytes() {
+        incoming.reject();
+        return;
+    }
+    let mut stream = incoming.accept();
+    let _ = tokio::io::copy_bidirectional(&mut stream, &mut reply).await;
+}
@@ -0,0 +1,274 @@
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, ToSocketAddrs};
+use crate::adb::client::Client;
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::mock_server::device_service::serve_device;
+use crate::adb::mock_server::virtual_device::{DeviceState, VirtualDevice};
+use crate::adb::transport::memory_transport::MemoryTransport;
+use crate::adb::server::host_request::{self, fail, okay, okay_string, parse_forward_spec, parse_host_request, parse_transport_request, read_request, Selector, TransportRequest, SERVER_VERSION};
+use crate::constants::{DEFAULT_ADB_SERVER_IP, HOST_FEATURES_SERVICE, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_LIST_SERVICE, HOST_FORWARD_SERVICE, HOST_GET_DEVPATH_SERVICE, HOST_GET_SERIALNO_SERVICE, HOST_GET_STATE_SERVICE, OKAY};
+
+const UNKNOWN_DEVPATH: &str = "unknown";
+const UNAUTHORIZED_STATE: &str = "unauthorized";
+const UNAUTHORIZED_MESSAGE: &str = "device unauthorized.\nThis adb server's $ADB_VENDOR_KEYS is not set\nTry 'adb kill-server' if that seems wrong.\nOtherwise check for a confirmation dialog on your device.";
+
+/// An in-process ADB server for tests: speaks the smart-socket host protocol and serves
+/// `shell`, `exec`, `sync`, `reboot`, `root` and `reverse` services from [`VirtualDevice`]s.
+///
+/// Forwards are only recorded; no ports are opened. Cloning gives another handle to the same server.
+#[derive(Debug, Clone, Default)]
+pub struct MockServer {
+    inner: Arc<Mutex<ServerState>>,
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    devices: Vec<(u64, VirtualDevice)>,
+    forwards: Vec<Forward>,
+    requests: Vec<String>,
+    stalled_services: Vec<String>,
+    last_transport_id: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Forward {
+    serial: String,
+    local: String,
+    remote: String,
+}
+
+enum HostReply {
+    /// Switch the connection to a device; `tport` requests also get the transport id.
+    Transport(VirtualDevice, Option<u64>),
+    Respond(Vec<u8>),
+}
+
+fn device_line(transport_id: u64, device: &DeviceState, long: bool) -> String {
+    let details = device.is_online().then(|| (device.product(), device.model(), device.device()));
+    host_request::device_line(transport_id, &device.serial, device.connection_state(), device.usb.as_deref(), details, long)
+}
+
+impl MockServer {
+    pub fn new() -> Self {
+        MockServer::default()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ServerState> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Attaches a device and returns its transport id.
+    pub fn add_device(&self, device: &VirtualDevice) -> u64 {
+        let mut state = self.lock();
+        state.last_transport_id += 1;
+        let transport_id = state.last_transport_id;
+        state.devices.push((transport_id, device.clone()));
+        transport_id
+    }
+
+    /// Detaches a device and drops its forwards; false if no device has that serial.
+    pub fn remove_device(&self, serial: &str) -> bool {
+        let mut state = self.lock();
+        let count = state.devices.len();
+        state.devices.retain(|(_, device)| device.serial() != serial);
+        state.forwards.retain(|forward| forward.serial != serial);
+        state.devices.len() < count
+    }
+
+    pub fn device(&self, serial: &str) -> Option<VirtualDevice> {
+        self.lock().devices.iter().find(|(_, device)| device.serial() == serial).map(|(_, device)| device.clone())
+    }
+
+    /// Leaves requests for the host service `service` (e.g. `host:devices`) unanswered with the
+    /// connection open, like a wedged server.
+    pub fn stall(&self, service: &str) {
+        self.lock().stalled_services.push(service.to_string());
+    }
+
+    /// Every service requested so far, in order, e.g. `host:transport-any` then `shell:ls`.
+    pub fn requests(&self) -> Vec<String> {
+        self.lock().requests.clone()
+    }
+
+    /// A transport whose connections are served by this server in-process.
+    pub fn transport(&self) -> MemoryTransport {
+        let (transport, mut listener) = MemoryTransport::pair();
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Some(stream) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        transport
+    }
+
+    /// Connects a client over [`MockServer::transport`].
+    pub async fn client(&self) -> Result<Client, Box<dyn Error>> {
+        ClientBuilder::new().transport(self.transport()).build().await
+    }
+
+    /// Serves on a local TCP port, e.g. for running the `adbr` binary with `-P`.
+    pub async fn listen(&self) -> io::Result<SocketAddr> {
+        self.listen_on((DEFAULT_ADB_SERVER_IP, 0)).await
+    }
+
+    /// Serves on `address`, e.g. a port a client is already retrying.
+    pub async fn listen_on(&self, address: impl ToSocketAddrs) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address).await?;
+        let address = listener.local_addr()?;
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        Ok(address)
+    }
+
+    /// Serves one client connection until its service finishes.
+    pub async fn serve<S: AsyncRead + AsyncWrite + Unpin>(&self, mut stream: S) {
+        if let Err(err) = self.serve_connection(&mut stream).await {
+            tracing::debug!("mock server connection failed: {}", err);
+        }
+        stream.shutdown().await.ok();
+    }
+
+    async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut S) -> io::Result<()> {
+        let mut selected: Option<VirtualDevice> = None;
+        while let Some(request) = read_request(stream).await? {
+            let stalled = {
+                let mut state = self.lock();
+                state.requests.push(request.clone());
+                selected.is_none() && state.stalled_services.contains(&request)
+            };
+            if stalled {
+                std::future::pending::<()>().await;
+            }
+            if let Some(device) = &selected {
+                return serve_device(device, &request, stream).await;
+            }
+            match self.host_service(&request) {
+                HostReply::Transport(device, tport_id) => {
+                    stream.write_all(OKAY.as_bytes()).await?;
+                    if let Some(transport_id) = tport_id {
+                        stream.write_all(&transport_id.to_le_bytes()).await?;
+                    }
+                    selected = Some(device);
+                }
+                HostReply::Respond(reply) => return stream.write_all(&reply).await,
+            }
+        }
+        Ok(())
+    }
+
+    /// Finds the single device `selector` names; `online` also requires it to accept services.
+    fn select(&self, selector: &Selector, online: bool) -> Result<(u64, VirtualDevice), String> {
+        let state = self.lock();
+        let candidates: Vec<&(u64, VirtualDevice)> = state.devices.iter().filter(|(transport_id, device)| {
+            let device = device.lock();
+            device.is_listed() && selector.matches(*transport_id, &device.serial, device.usb.is_some())
+        }).collect();
+        let (transport_id, device) = selector.pick(candidates)?.clone();
+        drop(state);
+
+        let state = device.connection_state();
+        match state.as_str() {
+            _ if !online || device.lock().is_online() => Ok((transport_id, device)),
+            UNAUTHORIZED_STATE => Err(UNAUTHORIZED_MESSAGE.to_string()),
+            state => Err(format!("device {}", state)),
+        }
+    }
+
+    fn host_service(&self, request: &str) -> HostReply {
+        let Some((selector, service)) = parse_host_request(request) else {
+            return HostReply::Respond(fail(&format!("unknown host service: {}", request)));
+        };
+
+        match parse_transport_request(service) {
+            Some(Ok(TransportRequest::Transport(selector))) => match self.select(&selector, true) {
+                Ok((_, device)) => HostReply::Transport(device, None),
+                Err(message) => HostReply::Respond(fail(&message)),
+            },
+            Some(Ok(TransportRequest::Tport(selector))) => match self.select(&selector, true) {
+                Ok((transport_id, device)) => HostReply::Transport(device, Some(transport_id)),
+                Err(message) => HostReply::Respond(fail(&message)),
+            },
+            Some(Err(message)) => HostReply::Respond(fail(&message)),
+            None => HostReply::Respond(match self.host_request(selector, service) {
+                Ok(reply) => reply,
+                Err(message) => fail(&message),
+            }),
+        }
+    }
+    fn host_request(&self, selector: Selector, service: &str) -> Result<Vec<u8>, String> {
+        match service {
+            "version" => Ok(okay_string(SERVER_VERSION)),
+            "devices" | "devices-l" => {
+                let state = self.lock();
+                let listing: String = state.devices.iter()
+                    .filter(|(_, device)| device.lock().is_listed())
+                    .map(|(transport_id, device)| device_line(*transport_id, &device.lock(), service == "devices-l"))
+                    .collect();
+                Ok(okay_string(&listing))
+            }
+            HOST_GET_STATE_SERVICE => {
+                let (_, device) = self.select(&selector, false)?;
+                Ok(okay_string(&device.connection_state()))
+            }
+            HOST_GET_SERIALNO_SERVICE => {
+                let (_, device) = self.select(&selector, false)?;
+                Ok(okay_string(&device.serial()))
+            }
+            HOST_GET_DEVPATH_SERVICE => {
+                let (_, device) = self.select(&selector, false)?;
+                let usb = device.lock().usb.clone();
+                Ok(okay_string(&usb.map(|path| format!("usb:{}", path)).unwrap_or_else(|| UNKNOWN_DEVPATH.to_string())))
+            }
+            HOST_FEATURES_SERVICE => {
+                let (_, device) = self.select(&selector, true)?;
+                let features = device.lock().features.join(",");
+                Ok(okay_string(&features))
+            }
+            HOST_FORWARD_LIST_SERVICE => {
+                let listing: String = self.lock().forwards.iter()
+                    .map(|forward| format!("{} {} {}\n", forward.serial, forward.local, forward.remote))
+                    .collect();
+                Ok(okay_string(&listing))
+            }
+            HOST_FORWARD_KILL_ALL_SERVICE => {
+                self.lock().forwards.clear();
+                Ok([okay(), okay()].concat())
+            }
+            service => {
+                if let Some(local) = service.strip_prefix(HOST_FORWARD_KILL_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+                    let mut state = self.lock();
+                    let count = state.forwards.len();
+                    state.forwards.retain(|forward| forward.local != local);
+                    return match state.forwards.len() < count {
+                        true => Ok([okay(), okay()].concat()),
+                        false => Err(format!("listener '{}' not found", local)),
+                    };
+                }
+                if let Some(spec) = service.strip_prefix(HOST_FORWARD_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+                    let (no_rebind, local, remote) = parse_forward_spec(spec)?;
+                    let (_, device) = self.select(&selector, true)?;
+                    let serial = device.serial();
+                    let mut state = self.lock();
+                    match state.forwards.iter_mut().find(|forward| forward.local == local) {
+                        Some(_) if no_rebind => return Err("cannot rebind existing socket".to_string()),
+                        Some(existing) => *existing = Forward { serial, local, remote },
+                        None => state.forwards.push(Forward { serial, local, remote }),
+                    }
+                    return Ok([okay(), okay()].concat());
+                }
+                Err(format!("unknown host service: {}", service))
+            }
+        }
+    }
+}
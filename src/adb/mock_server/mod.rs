@@ -0,0 +1,7 @@
+pub mod mock_server;
+pub mod mock_adbd;
+pub mod virtual_device;
+pub mod virtual_fs;
+pub mod device_service;
+pub mod shell_service;
+pub mod sync_service;
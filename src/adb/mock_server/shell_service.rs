@@ -0,0 +1,275 @@
+use std::fmt;
+use std::sync::Arc;
+use crate::adb::mock_server::virtual_device::DeviceState;
+
+const NOT_FOUND_EXIT_CODE: u8 = 127;
+const PACKAGE_PREFIX: &str = "package:";
+const INSTALLED_APK_PATH: &str = "/data/app/{}/base.apk";
+const SUCCESS: &str = "Success\n";
+
+/// Result of a shell command on a virtual device.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct CommandOutput {
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+    pub exit_code: u8,
+}
+
+impl CommandOutput {
+    pub fn new(stdout: Vec<u8>, stderr: Vec<u8>, exit_code: u8) -> Self {
+        CommandOutput { stdout, stderr, exit_code }
+    }
+
+    fn success(stdout: impl Into<String>) -> Self {
+        CommandOutput::new(stdout.into().into_bytes(), Vec::new(), 0)
+    }
+
+    fn failure(stderr: impl Into<String>, exit_code: u8) -> Self {
+        CommandOutput::new(Vec::new(), stderr.into().into_bytes(), exit_code)
+    }
+
+    fn with_exit_code(mut self, exit_code: u8) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    fn append(&mut self, other: CommandOutput) {
+        self.stdout.extend(other.stdout);
+        self.stderr.extend(other.stderr);
+        self.exit_code = other.exit_code;
+    }
+
+    /// Output as the legacy `shell:` service sends it, with stderr after stdout.
+    pub fn merged(&self) -> Vec<u8> {
+        [self.stdout.as_slice(), self.stderr.as_slice()].concat()
+    }
+}
+
+/// Answers one chunk of stdin with `(stdout, stderr)`.
+pub(crate) type StdinResponder = Arc<dyn Fn(&[u8]) -> (Vec<u8>, Vec<u8>) + Send + Sync>;
+
+/// A command that keeps running while the client has stdin open, see [`VirtualDevice::interactive`].
+///
+/// [`VirtualDevice::interactive`]: crate::adb::mock_server::virtual_device::VirtualDevice::interactive
+#[derive(Clone)]
+pub(crate) struct InteractiveCommand {
+    pub greeting: Vec<u8>,
+    pub respond: StdinResponder,
+    pub exit_code: u8,
+    /// Only greets when the service has a PTY, like programs that only prompt on a terminal.
+    pub needs_pty: bool,
+}
+
+impl fmt::Debug for InteractiveCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InteractiveCommand").field("greeting", &self.greeting).field("exit_code", &self.exit_code).field("needs_pty", &self.needs_pty).finish_non_exhaustive()
+    }
+}
+
+/// Runs `command` against the device: scripted responses first, then a handful of built-in
+/// commands (`echo`, `getprop`, `setprop`, `pm`, `cat`, `ls`, `rm`, `mkdir`, `whoami`).
+///
+/// Commands may be chained with `;` and `&&`; there are no pipes, redirections or variables.
+pub(crate) fn run_shell(device: &mut DeviceState, command: &str) -> CommandOutput {
+    device.shell_history.push(command.to_string());
+    if let Some(response) = device.shell_responses.get(command.trim()) {
+        return response.clone();
+    }
+
+    let mut output = CommandOutput::default();
+    for sequence in command.split(';') {
+        for (index, step) in sequence.split("&&").map(str::trim).filter(|step| !step.is_empty()).enumerate() {
+            if index > 0 && output.exit_code != 0 {
+                break;
+            }
+            let step_output = run_step(device, step);
+            output.append(step_output);
+        }
+    }
+    output
+}
+
+/// Splits a command into words, honouring single and double quotes and backslash escapes.
+fn tokenize(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    let mut chars = command.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('"'), '\\') | (None, '\\') => {
+                if let Some(escaped) = chars.next() {
+                    word.push(escaped);
+                }
+                in_word = true;
+            }
+            (Some(_), c) => word.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+fn run_step(device: &mut DeviceState, step: &str) -> CommandOutput {
+    if let Some(response) = device.shell_responses.get(step) {
+        return response.clone();
+    }
+
+    let words = tokenize(step);
+    let Some((program, args)) = words.split_first() else {
+        return CommandOutput::default();
+    };
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match program.as_str() {
+        "echo" => CommandOutput::success(format!("{}\n", args.join(" "))),
+        "true" => CommandOutput::default(),
+        "false" => CommandOutput::new(Vec::new(), Vec::new(), 1),
+        "whoami" => CommandOutput::success(if device.root { "root\n" } else { "shell\n" }),
+        "getprop" => getprop(device, &args),
+        "setprop" => setprop(device, &args),
+        "pm" => package_manager(device, &args),
+        "cat" => cat(device, &args),
+        "ls" => ls(device, &args),
+        "rm" => rm(device, &args),
+        "mkdir" => mkdir(device, &args),
+        _ => CommandOutput::failure(format!("/system/bin/sh: {}: inaccessible or not found\n", program), NOT_FOUND_EXIT_CODE),
+    }
+}
+
+fn split_flags<'a>(args: &[&'a str]) -> (Vec<&'a str>, Vec<&'a str>) {
+    args.iter().partition(|arg| arg.starts_with('-'))
+}
+
+fn getprop(device: &DeviceState, args: &[&str]) -> CommandOutput {
+    match args.first() {
+        Some(key) => CommandOutput::success(format!("{}\n", device.props.get(*key).map(String::as_str).unwrap_or_default())),
+        None => CommandOutput::success(device.props.iter().map(|(key, value)| format!("[{}]: [{}]\n", key, value)).collect::<String>()),
+    }
+}
+
+fn setprop(device: &mut DeviceState, args: &[&str]) -> CommandOutput {
+    match args {
+        [key, value] => {
+            device.props.insert(key.to_string(), value.to_string());
+            CommandOutput::default()
+        }
+        _ => CommandOutput::failure("usage: setprop NAME VALUE\n", 1),
+    }
+}
+
+fn package_manager(device: &mut DeviceState, args: &[&str]) -> CommandOutput {
+    match args.split_first() {
+        Some((&"list", ["packages", rest @ ..])) => {
+            let (flags, filters) = split_flags(rest);
+            let with_path = flags.contains(&"-f");
+            let listing: String = device.packages.iter()
+                .filter(|package| filters.first().is_none_or(|filter| package.contains(filter)))
+                .map(|package| match with_path {
+                    true => format!("{}{}={}\n", PACKAGE_PREFIX, INSTALLED_APK_PATH.replace("{}", package), package),
+                    false => format!("{}{}\n", PACKAGE_PREFIX, package),
+                })
+                .collect();
+            CommandOutput::success(listing)
+        }
+        Some((&"install", rest)) => install(device, rest),
+        Some((&"uninstall", rest)) => match rest.last().filter(|package| !package.starts_with('-')) {
+            Some(package) if device.packages.remove(*package) => CommandOutput::success(SUCCESS),
+            Some(_) => CommandOutput::success("Failure [DELETE_FAILED_INTERNAL_ERROR]\n").with_exit_code(1),
+            None => CommandOutput::failure("Error: package name not specified\n", 1),
+        },
+        Some((&"path", [package])) if device.packages.contains(*package) => {
+            CommandOutput::success(format!("{}{}\n", PACKAGE_PREFIX, INSTALLED_APK_PATH.replace("{}", package)))
+        }
+        Some((&"path", _)) => CommandOutput::new(Vec::new(), Vec::new(), 1),
+        Some((command, _)) => CommandOutput::failure(format!("Unknown command: {}\n", command), 1),
+        None => CommandOutput::failure("usage: pm [list|install|uninstall|path] ...\n", 1),
+    }
+}
+
+fn install(device: &mut DeviceState, args: &[&str]) -> CommandOutput {
+    let Some(path) = args.last().filter(|path| !path.starts_with('-')) else {
+        return CommandOutput::failure("Error: APK content must be streamed\n", 1);
+    };
+    if device.fs.read(path).is_err() {
+        return CommandOutput::failure(format!("Error: Can't open file: {}\n", path), 1);
+    }
+    let file_name = path.rsplit('/').next().unwrap_or(path);
+    match device.apks.get(file_name).cloned() {
+        Some(package) => {
+            device.packages.insert(package);
+            CommandOutput::success(SUCCESS)
+        }
+        None => CommandOutput::success(format!("Failure [INSTALL_PARSE_FAILED_NOT_APK: Failed to parse {}]\n", path)).with_exit_code(1),
+    }
+}
+
+fn cat(device: &DeviceState, args: &[&str]) -> CommandOutput {
+    let mut output = CommandOutput::default();
+    for path in args {
+        match device.fs.read(path) {
+            Ok(data) => output.stdout.extend_from_slice(data),
+            Err(error) => output.append(CommandOutput::failure(format!("cat: {}: {}\n", path, error), 1)),
+        }
+    }
+    output
+}
+
+fn ls(device: &DeviceState, args: &[&str]) -> CommandOutput {
+    let (_, paths) = split_flags(args);
+    let paths = if paths.is_empty() { vec!["/"] } else { paths };
+    let mut output = CommandOutput::default();
+    for path in paths {
+        match device.fs.list(path) {
+            Ok(entries) => entries.iter().for_each(|(name, _)| output.stdout.extend(format!("{}\n", name).into_bytes())),
+            Err(_) if device.fs.stat(path).is_some() => output.stdout.extend(format!("{}\n", path).into_bytes()),
+            Err(error) => output.append(CommandOutput::failure(format!("ls: {}: {}\n", path, error), 1)),
+        }
+    }
+    output
+}
+
+fn rm(device: &mut DeviceState, args: &[&str]) -> CommandOutput {
+    let (flags, paths) = split_flags(args);
+    let recursive = flags.iter().any(|flag| flag.contains('r') || flag.contains('R'));
+    let force = flags.iter().any(|flag| flag.contains('f'));
+    let mut output = CommandOutput::default();
+    for path in paths {
+        match device.fs.remove(path, recursive) {
+            Ok(()) => {}
+            Err(_) if force && device.fs.stat(path).is_none() => {}
+            Err(error) => output.append(CommandOutput::failure(format!("rm: {}: {}\n", path, error), 1)),
+        }
+    }
+    output
+}
+
+fn mkdir(device: &mut DeviceState, args: &[&str]) -> CommandOutput {
+    let (_, paths) = split_flags(args);
+    let mut output = CommandOutput::default();
+    for path in paths {
+        if let Err(error) = device.fs.create_dir_all(path) {
+            output.append(CommandOutput::failure(format!("mkdir: '{}': {}\n", path, error), 1));
+        }
+    }
+    output
+}
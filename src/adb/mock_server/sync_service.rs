@@ -0,0 +1,130 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::adb::mock_server::virtual_device::VirtualDevice;
+use crate::adb::mock_server::virtual_fs::FsStat;
+use crate::constants::{DATA_COMMAND, DEFAULT_PUSH_MODE, DENT_COMMAND, DONE_COMMAND, FAIL, LIST_COMMAND, OKAY, QUIT_COMMAND, RECV_COMMAND, SEND_COMMAND, STAT_COMMAND};
+
+const SYNC_DATA_MAX: usize = 64 * 1024;
+const DOT_ENTRIES: [&str; 2] = [".", ".."];
+
+async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<(String, u32)> {
+    let mut header = [0u8; 8];
+    stream.read_exact(&mut header).await?;
+    let id = String::from_utf8_lossy(&header[..4]).into_owned();
+    Ok((id, u32::from_le_bytes([header[4], header[5], header[6], header[7]])))
+}
+
+/// Reads a request path, dropping the NUL terminator some clients send.
+async fn read_path<S: AsyncRead + Unpin>(stream: &mut S, length: u32) -> io::Result<String> {
+    let mut path = vec![0u8; length as usize];
+    stream.read_exact(&mut path).await?;
+    Ok(String::from_utf8_lossy(&path).trim_end_matches('\0').to_string())
+}
+
+async fn write_status<S: AsyncWrite + Unpin>(stream: &mut S, id: &str, length: u32) -> io::Result<()> {
+    stream.write_all(id.as_bytes()).await?;
+    stream.write_all(&length.to_le_bytes()).await
+}
+
+async fn write_fail<S: AsyncWrite + Unpin>(stream: &mut S, message: &str) -> io::Result<()> {
+    write_status(stream, FAIL, message.len() as u32).await?;
+    stream.write_all(message.as_bytes()).await
+}
+
+async fn write_stat<S: AsyncWrite + Unpin>(stream: &mut S, stat: FsStat) -> io::Result<()> {
+    for value in [stat.mode, stat.size, stat.mtime] {
+        stream.write_all(&value.to_le_bytes()).await?;
+    }
+    Ok(())
+}
+
+/// Serves the `sync:` file transfer protocol (STAT, LIST, SEND, RECV) until QUIT or disconnect.
+pub(crate) async fn serve_sync<S: AsyncRead + AsyncWrite + Unpin>(device: &VirtualDevice, stream: &mut S) -> io::Result<()> {
+    loop {
+        let (id, length) = match read_header(stream).await {
+            Ok(header) => header,
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(()),
+            Err(err) => return Err(err),
+        };
+
+        match id.as_str() {
+            STAT_COMMAND => {
+                let path = read_path(stream, length).await?;
+                // adbd answers missing paths with an all-zero stat.
+                let stat = device.lock().fs.stat(&path).unwrap_or(FsStat { mode: 0, size: 0, mtime: 0 });
+                stream.write_all(STAT_COMMAND.as_bytes()).await?;
+                write_stat(stream, stat).await?;
+            }
+            LIST_COMMAND => {
+                let path = read_path(stream, length).await?;
+                let (own_stat, entries) = {
+                    let device = device.lock();
+                    (device.fs.stat(&path), device.fs.list(&path).unwrap_or_default())
+                };
+                let dot_entries = own_stat.into_iter().flat_map(|stat| DOT_ENTRIES.map(|name| (name.to_string(), stat)));
+                for (name, stat) in dot_entries.chain(entries) {
+                    stream.write_all(DENT_COMMAND.as_bytes()).await?;
+                    write_stat(stream, stat).await?;
+                    stream.write_all(&(name.len() as u32).to_le_bytes()).await?;
+                    stream.write_all(name.as_bytes()).await?;
+                }
+                write_status(stream, DONE_COMMAND, 0).await?;
+                stream.write_all(&[0u8; 12]).await?;
+            }
+            SEND_COMMAND => {
+                let spec = read_path(stream, length).await?;
+                let (path, mode) = match spec.rsplit_once(',') {
+                    Some((path, mode)) => (path.to_string(), mode.parse().unwrap_or(DEFAULT_PUSH_MODE)),
+                    None => (spec, DEFAULT_PUSH_MODE),
+                };
+
+                let mut data = Vec::new();
+                let mtime = loop {
+                    match read_header(stream).await? {
+                        (id, length) if id == DATA_COMMAND => {
+                            let start = data.len();
+                            data.resize(start + length as usize, 0);
+                            stream.read_exact(&mut data[start..]).await?;
+                        }
+                        (id, mtime) if id == DONE_COMMAND => break mtime,
+                        (id, _) => {
+                            write_fail(stream, &format!("unexpected '{}' while receiving {}", id, path)).await?;
+                            return Ok(());
+                        }
+                    }
+                };
+
+                let written = device.lock().fs.write(&path, data, mode, mtime);
+                match written {
+                    Ok(()) => write_status(stream, OKAY, 0).await?,
+                    Err(err) => write_fail(stream, &format!("couldn't create file: {}", err)).await?,
+                }
+            }
+            RECV_COMMAND => {
+                let path = read_path(stream, length).await?;
+                let (data, stalled) = {
+                    let device = device.lock();
+                    (device.fs.read(&path).map(<[u8]>::to_vec), device.stalled_files.contains(&path))
+                };
+                match data {
+                    Ok(data) => {
+                        for chunk in data.chunks(SYNC_DATA_MAX) {
+                            write_status(stream, DATA_COMMAND, chunk.len() as u32).await?;
+                            stream.write_all(chunk).await?;
+                            if stalled {
+                                std::future::pending::<()>().await;
+                            }
+                        }
+                        write_status(stream, DONE_COMMAND, 0).await?;
+                    }
+                    Err(err) => write_fail(stream, &format!("open failed: {}", err)).await?,
+                }
+            }
+            QUIT_COMMAND => return Ok(()),
+            _ => {
+                write_fail(stream, &format!("unknown sync request '{}'", id)).await?;
+                return Ok(());
+            }
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use crate::adb::mock_server::shell_service::{CommandOutput, InteractiveCommand};
+use crate::adb::mock_server::virtual_fs::VirtualFs;
+use crate::constants::{DEFAULT_WAIT_STATE, EMULATOR_SERIAL_PREFIX, REBOOT_BOOTLOADER, REBOOT_RECOVERY, REBOOT_SIDELOAD, REBOOT_SIDELOAD_AUTO_REBOOT, SHELL_V2_FEATURE};
+
+const OFFLINE_STATE: &str = "offline";
+const BOOTLOADER_STATE: &str = "bootloader";
+const RECOVERY_STATE: &str = "recovery";
+const SIDELOAD_STATE: &str = "sideload";
+const ONLINE_STATES: [&str; 4] = [DEFAULT_WAIT_STATE, RECOVERY_STATE, SIDELOAD_STATE, "rescue"];
+const DEFAULT_USB_PATH: &str = "1-1";
+const DEFAULT_FEATURES: [&str; 3] = [SHELL_V2_FEATURE, "cmd", "fixed_push_mkdir"];
+const DEFAULT_PACKAGES: [&str; 3] = ["android", "com.android.settings", "com.android.systemui"];
+const DEFAULT_PROPS: [(&str, &str); 5] = [
+    ("ro.product.model", "Mock_Device"),
+    ("ro.product.name", "mock"),
+    ("ro.product.device", "mock"),
+    ("ro.build.version.release", "14"),
+    ("ro.build.version.sdk", "34"),
+];
+const SERIALNO_PROP: &str = "ro.serialno";
+
+/// A device attached to a [`MockServer`](crate::adb::mock_server::mock_server::MockServer).
+///
+/// Cloning gives another handle to the same device, so a test can keep one to set up responses and
+/// inspect files, packages and reboots after the client ran.
+#[derive(Debug, Clone)]
+pub struct VirtualDevice {
+    inner: Arc<Mutex<DeviceState>>,
+}
+
+#[derive(Debug)]
+pub(crate) struct DeviceState {
+    pub serial: String,
+    pub state: String,
+    pub usb: Option<String>,
+    pub props: BTreeMap<String, String>,
+    pub fs: VirtualFs,
+    /// Files whose pull stops after the first chunk.
+    pub stalled_files: BTreeSet<String>,
+    pub packages: BTreeSet<String>,
+    /// Package name of each installable APK, by file name.
+    pub apks: HashMap<String, String>,
+    pub shell_responses: HashMap<String, CommandOutput>,
+    pub interactive_commands: HashMap<String, InteractiveCommand>,
+    pub shell_history: Vec<String>,
+    pub features: Vec<String>,
+    pub reverse_forwards: Vec<(String, String)>,
+    pub reboots: Vec<String>,
+    pub boot_duration: Duration,
+    pub booting_until: Option<Instant>,
+    pub root: bool,
+}
+
+impl DeviceState {
+    /// State as `adb devices` shows it; a rebooting device is offline until it has booted.
+    pub fn connection_state(&self) -> &str {
+        match self.booting_until {
+            Some(until) if Instant::now() < until => OFFLINE_STATE,
+            _ => &self.state,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        ONLINE_STATES.contains(&self.connection_state())
+    }
+
+    /// Devices in the bootloader talk fastboot, so the server does not list them.
+    pub fn is_listed(&self) -> bool {
+        self.state != BOOTLOADER_STATE
+    }
+
+    /// Restarts into `target` (empty for a normal boot), dropping adbd's reverse forwards and root.
+    pub fn reboot(&mut self, target: &str) {
+        self.reboots.push(target.to_string());
+        self.reverse_forwards.clear();
+        self.root = false;
+        self.state = match target {
+            REBOOT_BOOTLOADER => BOOTLOADER_STATE,
+            REBOOT_RECOVERY => RECOVERY_STATE,
+            REBOOT_SIDELOAD | REBOOT_SIDELOAD_AUTO_REBOOT => SIDELOAD_STATE,
+            _ => DEFAULT_WAIT_STATE,
+        }.to_string();
+        self.booting_until = Some(Instant::now() + self.boot_duration);
+    }
+
+    pub fn model(&self) -> String {
+        self.props.get(DEFAULT_PROPS[0].0).map(|model| model.replace(' ', "_")).unwrap_or_default()
+    }
+
+    pub fn product(&self) -> String {
+        self.props.get(DEFAULT_PROPS[1].0).cloned().unwrap_or_default()
+    }
+
+    pub fn device(&self) -> String {
+        self.props.get(DEFAULT_PROPS[2].0).cloned().unwrap_or_default()
+    }
+}
+
+impl VirtualDevice {
+    /// An online device with a few system packages and properties; serials starting with
+    /// `emulator-` or containing `:` are attached locally, everything else over USB.
+    pub fn new(serial: &str) -> Self {
+        let local = serial.starts_with(EMULATOR_SERIAL_PREFIX) || serial.contains(':');
+        let mut props: BTreeMap<String, String> = DEFAULT_PROPS.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        props.insert(SERIALNO_PROP.to_string(), serial.to_string());
+
+        let state = DeviceState {
+            serial: serial.to_string(),
+            state: DEFAULT_WAIT_STATE.to_string(),
+            usb: (!local).then(|| DEFAULT_USB_PATH.to_string()),
+            props,
+            fs: VirtualFs::default(),
+            stalled_files: BTreeSet::new(),
+            packages: DEFAULT_PACKAGES.iter().map(|package| package.to_string()).collect(),
+            apks: HashMap::new(),
+            shell_responses: HashMap::new(),
+            interactive_commands: HashMap::new(),
+            shell_history: Vec::new(),
+            features: DEFAULT_FEATURES.iter().map(|feature| feature.to_string()).collect(),
+            reverse_forwards: Vec::new(),
+            reboots: Vec::new(),
+            boot_duration: Duration::ZERO,
+            booting_until: None,
+            root: false,
+        };
+        VirtualDevice { inner: Arc::new(Mutex::new(state)) }
+    }
+
+    pub(crate) fn lock(&self) -> MutexGuard<'_, DeviceState> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Connection state, e.g. `offline` or `unauthorized`.
+    pub fn state(self, state: &str) -> Self {
+        self.set_state(state);
+        self
+    }
+
+    pub fn prop(self, key: &str, value: &str) -> Self {
+        self.lock().props.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn file(self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.lock().fs.write_default(path, data.into()).expect("virtual file path");
+        self
+    }
+
+    /// A file like [`VirtualDevice::file`] whose pull sends its first chunk and then hangs,
+    /// like a wedged device.
+    pub fn stalled_file(self, path: &str, data: impl Into<Vec<u8>>) -> Self {
+        self.lock().stalled_files.insert(path.to_string());
+        self.file(path, data)
+    }
+
+    pub fn package(self, package: &str) -> Self {
+        self.lock().packages.insert(package.to_string());
+        self
+    }
+
+    /// Lets `pm install` accept an APK pushed under `file_name` as `package`; other APKs fail to parse.
+    pub fn apk(self, file_name: &str, package: &str) -> Self {
+        self.lock().apks.insert(file_name.to_string(), package.to_string());
+        self
+    }
+
+    /// Answers the exact shell command `command` with `stdout` and exit status 0.
+    pub fn shell_output(self, command: &str, stdout: &str) -> Self {
+        self.lock().shell_responses.insert(command.to_string(), CommandOutput::new(stdout.as_bytes().to_vec(), Vec::new(), 0));
+        self
+    }
+
+    /// Answers the exact shell command `command` with `stderr` and `exit_code`.
+    pub fn shell_failure(self, command: &str, stderr: &str, exit_code: u8) -> Self {
+        self.lock().shell_responses.insert(command.to_string(), CommandOutput::new(Vec::new(), stderr.as_bytes().to_vec(), exit_code));
+        self
+    }
+
+    /// Runs the exact shell command `command` until the client closes stdin: `greeting` is written
+    /// when it starts, each chunk of stdin is answered with the `(stdout, stderr)` that `respond`
+    /// returns, and closing stdin ends it with `exit_code`. With a `respond` that ignores its input
+    /// this also stands in for endless commands such as `logcat`.
+    pub fn interactive(self, command: &str, greeting: &str, exit_code: u8, respond: impl Fn(&[u8]) -> (Vec<u8>, Vec<u8>) + Send + Sync + 'static) -> Self {
+        let session = InteractiveCommand { greeting: greeting.as_bytes().to_vec(), respond: Arc::new(respond), exit_code, needs_pty: false };
+        self.lock().interactive_commands.insert(command.to_string(), session);
+        self
+    }
+
+    /// [`VirtualDevice::interactive`] for a program such as `su` or `sqlite3` that only prints
+    /// `greeting`, its prompt, when it runs on a PTY.
+    pub fn tty_interactive(self, command: &str, greeting: &str, exit_code: u8, respond: impl Fn(&[u8]) -> (Vec<u8>, Vec<u8>) + Send + Sync + 'static) -> Self {
+        let session = InteractiveCommand { greeting: greeting.as_bytes().to_vec(), respond: Arc::new(respond), exit_code, needs_pty: true };
+        self.lock().interactive_commands.insert(command.to_string(), session);
+        self
+    }
+
+    /// Replaces the advertised features; without `shell_v2` clients fall back to the legacy shell.
+    pub fn features(self, features: &[&str]) -> Self {
+        self.lock().features = features.iter().map(|feature| feature.to_string()).collect();
+        self
+    }
+
+    /// How long the device stays offline after a reboot.
+    pub fn boot_duration(self, boot_duration: Duration) -> Self {
+        self.lock().boot_duration = boot_duration;
+        self
+    }
+
+    pub fn serial(&self) -> String {
+        self.lock().serial.clone()
+    }
+
+    pub fn connection_state(&self) -> String {
+        self.lock().connection_state().to_string()
+    }
+
+    pub fn set_state(&self, state: &str) {
+        let mut device = self.lock();
+        device.state = state.to_string();
+        device.booting_until = None;
+    }
+
+    pub fn get_prop(&self, key: &str) -> Option<String> {
+        self.lock().props.get(key).cloned()
+    }
+
+    pub fn read_file(&self, path: &str) -> Option<Vec<u8>> {
+        self.lock().fs.read(path).ok().map(<[u8]>::to_vec)
+    }
+
+    pub fn installed_packages(&self) -> Vec<String> {
+        self.lock().packages.iter().cloned().collect()
+    }
+
+    /// Every shell command run on the device, in order.
+    pub fn shell_history(&self) -> Vec<String> {
+        self.lock().shell_history.clone()
+    }
+
+    /// Reboot targets in order; a normal reboot is recorded as an empty string.
+    pub fn reboots(&self) -> Vec<String> {
+        self.lock().reboots.clone()
+    }
+
+    /// `(remote, local)` pairs set up with `adb reverse`.
+    pub fn reverse_forwards(&self) -> Vec<(String, String)> {
+        self.lock().reverse_forwards.clone()
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.lock().root
+    }
+}
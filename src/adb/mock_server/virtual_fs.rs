@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::constants::{DEFAULT_PUSH_MODE, S_IFDIR, S_IFREG};
+
+const DIRECTORY_MODE: u32 = 0o771;
+const DEFAULT_DIRECTORIES: [&str; 5] = ["/data/local/tmp", "/sdcard/Download", "/sdcard/DCIM", "/storage/emulated/0", "/system"];
+const NO_SUCH_FILE: &str = "No such file or directory";
+const NOT_A_DIRECTORY: &str = "Not a directory";
+const IS_A_DIRECTORY: &str = "Is a directory";
+
+#[derive(Debug, Clone)]
+enum FsNode {
+    File { data: Vec<u8>, mode: u32, mtime: u32 },
+    Directory { mtime: u32 },
+}
+
+/// `stat` of a virtual file: mode with file type bits, size and modification time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct FsStat {
+    pub mode: u32,
+    pub size: u32,
+    pub mtime: u32,
+}
+
+/// In-memory file tree of a virtual device, keyed by normalized absolute path.
+#[derive(Debug, Clone)]
+pub(crate) struct VirtualFs {
+    nodes: BTreeMap<String, FsNode>,
+}
+
+pub(crate) fn now_secs() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_secs() as u32).unwrap_or_default()
+}
+
+/// Resolves `.`, `..` and repeated slashes; relative paths are taken from `/`.
+pub(crate) fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    format!("/{}", parts.join("/"))
+}
+
+fn parent(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        Some(0) if path.len() > 1 => Some("/"),
+        Some(0) | None => None,
+        Some(index) => Some(&path[..index]),
+    }
+}
+
+impl Default for VirtualFs {
+    fn default() -> Self {
+        let mut fs = VirtualFs { nodes: BTreeMap::new() };
+        fs.nodes.insert("/".to_string(), FsNode::Directory { mtime: now_secs() });
+        for directory in DEFAULT_DIRECTORIES {
+            fs.create_dir_all(directory).expect("default directories");
+        }
+        fs
+    }
+}
+
+impl VirtualFs {
+    pub fn stat(&self, path: &str) -> Option<FsStat> {
+        match self.nodes.get(&normalize(path))? {
+            FsNode::File { data, mode, mtime } => Some(FsStat { mode: S_IFREG | mode, size: data.len() as u32, mtime: *mtime }),
+            FsNode::Directory { mtime } => Some(FsStat { mode: S_IFDIR | DIRECTORY_MODE, size: 0, mtime: *mtime }),
+        }
+    }
+
+    /// Names and stats of the entries of a directory, in name order.
+    pub fn list(&self, path: &str) -> Result<Vec<(String, FsStat)>, String> {
+        let path = normalize(path);
+        match self.nodes.get(&path) {
+            Some(FsNode::Directory { .. }) => {}
+            Some(FsNode::File { .. }) => return Err(NOT_A_DIRECTORY.to_string()),
+            None => return Err(NO_SUCH_FILE.to_string()),
+        }
+        Ok(self.nodes.keys()
+            .filter(|key| *key != &path && parent(key) == Some(path.as_str()))
+            .filter_map(|key| Some((key.rsplit('/').next()?.to_string(), self.stat(key)?)))
+            .collect())
+    }
+
+    pub fn read(&self, path: &str) -> Result<&[u8], String> {
+        match self.nodes.get(&normalize(path)) {
+            Some(FsNode::File { data, .. }) => Ok(data),
+            Some(FsNode::Directory { .. }) => Err(IS_A_DIRECTORY.to_string()),
+            None => Err(NO_SUCH_FILE.to_string()),
+        }
+    }
+
+    /// Creates or replaces a file, creating missing parent directories like adbd does for pushes.
+    pub fn write(&mut self, path: &str, data: Vec<u8>, mode: u32, mtime: u32) -> Result<(), String> {
+        let path = normalize(path);
+        if let Some(FsNode::Directory { .. }) = self.nodes.get(&path) {
+            return Err(IS_A_DIRECTORY.to_string());
+        }
+        if let Some(parent) = parent(&path) {
+            self.create_dir_all(parent)?;
+        }
+        self.nodes.insert(path, FsNode::File { data, mode: mode & 0o7777, mtime });
+        Ok(())
+    }
+
+    pub fn write_default(&mut self, path: &str, data: Vec<u8>) -> Result<(), String> {
+        self.write(path, data, DEFAULT_PUSH_MODE, now_secs())
+    }
+
+    pub fn create_dir_all(&mut self, path: &str) -> Result<(), String> {
+        let path = normalize(path);
+        match self.nodes.get(&path) {
+            Some(FsNode::Directory { .. }) => return Ok(()),
+            Some(FsNode::File { .. }) => return Err(NOT_A_DIRECTORY.to_string()),
+            None => {}
+        }
+        if let Some(parent) = parent(&path) {
+            self.create_dir_all(parent)?;
+        }
+        self.nodes.insert(path, FsNode::Directory { mtime: now_secs() });
+        Ok(())
+    }
+
+    /// Removes a file, or a directory with everything below it when `recursive`.
+    pub fn remove(&mut self, path: &str, recursive: bool) -> Result<(), String> {
+        let path = normalize(path);
+        match self.nodes.get(&path) {
+            None => Err(NO_SUCH_FILE.to_string()),
+            Some(FsNode::Directory { .. }) if !recursive || path == "/" => Err(IS_A_DIRECTORY.to_string()),
+            Some(FsNode::Directory { .. }) => {
+                let prefix = format!("{}/", path);
+                self.nodes.retain(|key, _| key != &path && !key.starts_with(&prefix));
+                Ok(())
+            }
+            Some(FsNode::File { .. }) => {
+                self.nodes.remove(&path);
+                Ok(())
+            }
+        }
+    }
+}
@@ -1,4 +1,5 @@
 pub mod client;
+pub mod client_builder;
 pub mod file_transfer;
 pub mod protocol;
 pub mod shell;
@@ -8,3 +9,12 @@ pub mod security;
 pub mod network;
 pub mod scripting;
 pub mod app_installation;
+pub mod activity_manager;
+pub mod instrument;
+pub mod expect;
+pub mod transport;
+#[cfg(feature = "mock")]
+pub mod mock_server;
+pub mod recording;
+pub mod server;
+pub mod wire;
@@ -0,0 +1,26 @@
+use std::error::Error;
+use crate::adb::client::Client;
+use crate::constants::{HOST_CONNECT_COMMAND, HOST_DISCONNECT_COMMAND};
+
+const CONNECTED_PREFIXES: [&str; 2] = ["connected to ", "already connected to "];
+
+impl Client {
+    /// Asks the server to connect to adbd at `HOST[:PORT]` and returns its message, e.g.
+    /// `connected to 10.0.0.2:5555`; a message saying why it could not connect becomes the error.
+    pub async fn adb_connect(&mut self, address: &str) -> Result<String, Box<dyn Error>> {
+        let connect_message = format!("{}{}", HOST_CONNECT_COMMAND, address);
+        let message = self.send_adb_and_return_response(&connect_message, &connect_message).await?;
+        if CONNECTED_PREFIXES.iter().any(|prefix| message.starts_with(prefix)) {
+            Ok(message)
+        } else {
+            Err(message.into())
+        }
+    }
+
+    /// Disconnects a device connected with [`Client::adb_connect`], or every one of them when
+    /// `address` is empty.
+    pub async fn adb_disconnect(&mut self, address: &str) -> Result<String, Box<dyn Error>> {
+        let disconnect_message = format!("{}{}", HOST_DISCONNECT_COMMAND, address);
+        self.send_adb_and_return_response(&disconnect_message, &disconnect_message).await
+    }
+}
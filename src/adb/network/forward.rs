@@ -1,40 +1,34 @@
 use std::error::Error;
 use crate::adb::client::Client;
-use crate::constants::{FAIL, HOST_FORWARD_COMMAND, HOST_FORWARD_KILL_ALL_COMMAND, HOST_FORWARD_KILL_COMMAND, HOST_FORWARD_LIST_COMMAND, NO_REBIND_OPTION, OKAY};
+use crate::constants::{FAIL, HOST_FORWARD_SERVICE, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_LIST_SERVICE, NO_REBIND_OPTION, OKAY};
 use crate::enums::device_transport::DeviceTransport;
 
 
 impl Client {
     pub async fn send_forward_command_set(&mut self, device_transport: DeviceTransport, local: &str, remote: &str, no_rebind: bool) -> Result<(), Box<dyn Error>> {
-        self.send_transport(device_transport).await?;
+        let host_prefix = device_transport.host_prefix();
 
         let forward_message = if no_rebind {
-            format!("{}:{}:{};{}", HOST_FORWARD_COMMAND, NO_REBIND_OPTION, local, remote)
+            format!("{}{}:{}:{};{}", host_prefix, HOST_FORWARD_SERVICE, NO_REBIND_OPTION, local, remote)
         } else {
-            format!("{}:{};{}", HOST_FORWARD_COMMAND, local, remote)
+            format!("{}{}:{};{}", host_prefix, HOST_FORWARD_SERVICE, local, remote)
         };
         self.send_adb_command_and_check_if_fail(&forward_message, &forward_message).await
     }
 
     pub async fn send_forward_command_remove(&mut self, device_transport: DeviceTransport, local: &str) -> Result<(), Box<dyn Error>> {
-        self.send_transport(device_transport).await?;
-
-        let forward_message = format!("{}:{}", HOST_FORWARD_KILL_COMMAND, local);
+        let forward_message = format!("{}{}:{}", device_transport.host_prefix(), HOST_FORWARD_KILL_SERVICE, local);
         self.send_adb_command_and_check_if_fail(&forward_message, &forward_message).await
     }
 
     pub async fn send_forward_command_remove_all(&mut self, device_transport: DeviceTransport) -> Result<(), Box<dyn Error>> {
-        self.send_transport(device_transport).await?;
-
-        let forward_message = format!("{}", HOST_FORWARD_KILL_ALL_COMMAND);
+        let forward_message = format!("{}{}", device_transport.host_prefix(), HOST_FORWARD_KILL_ALL_SERVICE);
         self.send_adb_command_and_check_if_fail(&forward_message, &forward_message).await
     }
 
 
     pub async fn send_forward_command_list(&mut self, device_transport: DeviceTransport) -> Result<String, Box<dyn Error>> {
-        self.send_transport(device_transport).await?;
-
-        let forward_message = format!("{}", HOST_FORWARD_LIST_COMMAND);
+        let forward_message = format!("{}{}", device_transport.host_prefix(), HOST_FORWARD_LIST_SERVICE);
         self.send_adb_command(&forward_message).await?;
 
         let response = self.read_first_four_bytes_response().await?;
@@ -1,33 +1,122 @@
 use std::error::Error;
 use std::path::Path;
 use crate::adb::client::Client;
-use crate::constants::{ADB_DEVICES_COMMAND, FAIL, OKAY, S_IFDIR, STAT_COMMAND, STAT_DATA_SIZE, USER_TRANSPORT_COMMAND};
+use tokio::io::AsyncBufReadExt;
+use crate::constants::{ADB_DEVICES_COMMAND, ADB_DEVICES_LONG_COMMAND, ADB_TRACK_DEVICES_COMMAND, FAIL, HOST_KILL_COMMAND, HOST_VERSION_COMMAND, OKAY, S_IFDIR, STAT_COMMAND, STAT_DATA_SIZE, USER_TRANSPORT_COMMAND};
 use crate::enums::device_transport::DeviceTransport;
+use crate::models::device_info::DeviceInfo;
 use crate::models::remote_metadata::RemoteMetadata;
 use crate::models::stat_data::StatData;
 use crate::utils::strip_adb_prefix;
 
+const TRANSPORT_ID_SIZE: usize = 8;
+const LONG_LISTING_SUFFIX: &str = "-l";
+const WAITING_FOR_DEVICES: &str = "waiting for device changes";
+
 
 impl Client {
+    /// Switches this connection to the device, recording the transport id the server selected.
+    ///
+    /// Uses `host:tport:*`; servers that refuse it (before adb 1.0.41) get a new connection and
+    /// `host:transport*`, which leaves [`Client::transport_id`] unset unless the id was given.
     pub async fn send_transport(&mut self, device_transport: DeviceTransport) -> Result<(), Box<dyn Error>> {
+        if let Some(tport_command) = device_transport.tport_command() {
+            self.send_adb_command(&tport_command).await?;
+            if self.read_first_four_bytes_response().await? == OKAY {
+                self.transport_id = Some(self.read_transport_id().await?);
+                return Ok(());
+            }
+            let error_msg_str = self.read_adb_full_response().await?;
+            tracing::debug!("{} refused, falling back to host:transport: {}", tport_command, error_msg_str);
+            self.reconnect().await?;
+        }
         let transport_command = device_transport.get_device_transport();
-        self.send_adb_command_and_check_if_fail(&transport_command, USER_TRANSPORT_COMMAND).await
+        self.send_adb_command_and_check_if_fail(transport_command, USER_TRANSPORT_COMMAND).await?;
+        self.transport_id = device_transport.selected_transport_id();
+        Ok(())
+    }
+
+    /// Switches transports with `host:tport:*` and returns the transport id the server selected.
+    pub async fn send_tport(&mut self, device_transport: DeviceTransport) -> Result<u64, Box<dyn Error>> {
+        let transport_id = match device_transport.tport_command() {
+            Some(tport_command) => {
+                self.send_adb_command_and_check_if_fail(&tport_command, USER_TRANSPORT_COMMAND).await?;
+                self.read_transport_id().await?
+            }
+            None => {
+                self.send_transport(device_transport.clone()).await?;
+                device_transport.selected_transport_id().ok_or("Invalid transport id")?
+            }
+        };
+
+        self.transport_id = Some(transport_id);
+        Ok(transport_id)
+    }
+
+    async fn read_transport_id(&mut self) -> Result<u64, Box<dyn Error>> {
+        let id_bytes = self.get_exact_bytes(TRANSPORT_ID_SIZE).await?;
+        Ok(u64::from_le_bytes(id_bytes.as_slice().try_into()?))
     }
 
     pub async fn adb_devices(&mut self) -> Result<String, Box<dyn Error>> {
-        self.send_adb_command(ADB_DEVICES_COMMAND).await?;
+        self.request_device_list(ADB_DEVICES_COMMAND).await
+    }
+
+    pub async fn adb_devices_long(&mut self) -> Result<String, Box<dyn Error>> {
+        self.request_device_list(ADB_DEVICES_LONG_COMMAND).await
+    }
+
+    async fn request_device_list(&mut self, devices_command: &str) -> Result<String, Box<dyn Error>> {
+        self.send_adb_command(devices_command).await?;
         let response = self.read_first_four_bytes_response().await?;
         if response == OKAY {
             let device_list_str = self.read_adb_full_response().await?;
             Ok(format!("List of devices attached\n{}", device_list_str))
         } else {
             let error_msg_str = self.read_adb_full_response().await?;
-            println!("Received: {:?} Error message: {}", response, error_msg_str);
-            Err("Failed to get devices list".into())
+            Err(format!("Failed to get devices list: {} {}", response, error_msg_str).into())
+        }
+    }
+
+    /// Starts `host:track-devices` on this connection; read the listings with [`Client::next_device_list`].
+    pub async fn adb_track_devices(&mut self, long: bool) -> Result<(), Box<dyn Error>> {
+        let track_command = match long {
+            true => format!("{}{}", ADB_TRACK_DEVICES_COMMAND, LONG_LISTING_SUFFIX),
+            false => ADB_TRACK_DEVICES_COMMAND.to_string(),
+        };
+        self.send_adb_command_and_check_if_fail(&track_command, &track_command).await
+    }
+
+    /// Waits for the next device listing after [`Client::adb_track_devices`]: the current one
+    /// first, then one per change. Only the deadline bounds the wait.
+    pub async fn next_device_list(&mut self) -> Result<String, Box<dyn Error>> {
+        let buffered = self.limits.run(WAITING_FOR_DEVICES, false, async { Ok(self.adb_stream.fill_buf().await?.len()) }).await?;
+        if buffered == 0 {
+            return Err("The server stopped tracking devices".into());
         }
+        let length = self.read_first_four_bytes_response().await?;
+        let length = usize::from_str_radix(&length, 16).map_err(|_| format!("Invalid device listing length: {}", length))?;
+        self.read_exact_string(length).await
+    }
+
+    /// The protocol revision of the server, 41 for current platform tools.
+    pub async fn adb_server_version(&mut self) -> Result<u32, Box<dyn Error>> {
+        let version = self.send_adb_and_return_response(HOST_VERSION_COMMAND, HOST_VERSION_COMMAND).await?;
+        Ok(u32::from_str_radix(&version, 16).map_err(|_| format!("Invalid server version: {}", version))?)
+    }
+
+    /// Asks the server to exit, dropping its devices and forwards.
+    pub async fn adb_kill_server(&mut self) -> Result<(), Box<dyn Error>> {
+        self.send_adb_command_and_check_if_fail(HOST_KILL_COMMAND, HOST_KILL_COMMAND).await
+    }
+
+    pub async fn adb_list_devices(&mut self) -> Result<Vec<DeviceInfo>, Box<dyn Error>> {
+        let device_list_str = self.send_adb_and_return_response(ADB_DEVICES_LONG_COMMAND, ADB_DEVICES_LONG_COMMAND).await?;
+        Ok(DeviceInfo::parse_list(&device_list_str))
     }
 
     pub async fn send_adb_command(&mut self, command: &str) -> Result<(), Box<dyn Error>> {
+        tracing::debug!(service = command, "request");
         let msg_len = format!("{:04x}", command.len());
         let adb_message = format!("{}{}", msg_len, command);
         self.send_command(adb_message.as_bytes()).await?;
@@ -0,0 +1,4 @@
+pub mod session_recorder;
+pub mod recording_transport;
+pub mod replay_server;
+pub mod redact;
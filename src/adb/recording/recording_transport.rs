@@ -0,0 +1,103 @@
+use std::fmt;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use crate::adb::recording::session_recorder::SessionRecorder;
+use crate::adb::transport::transport::{AdbStream, Transport};
+use crate::enums::stream_direction::StreamDirection;
+
+/// Wraps another transport and records every byte its connections carry, as enabled by `ADBR_RECORD`.
+#[derive(Debug, Clone)]
+pub struct RecordingTransport {
+    inner: Arc<dyn Transport>,
+    recorder: Arc<SessionRecorder>,
+}
+
+impl RecordingTransport {
+    pub fn new(inner: Arc<dyn Transport>, recorder: Arc<SessionRecorder>) -> Self {
+        RecordingTransport { inner, recorder }
+    }
+}
+
+#[async_trait]
+impl Transport for RecordingTransport {
+    async fn connect(&self) -> io::Result<Box<dyn AdbStream>> {
+        let inner = self.inner.connect().await?;
+        let connection = self.recorder.next_connection();
+        self.recorder.record(connection, StreamDirection::Connect, &[]);
+        Ok(Box::new(RecordingStream { inner, connection, recorder: self.recorder.clone(), server_closed: false, client_closed: false }))
+    }
+}
+
+impl fmt::Display for RecordingTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+struct RecordingStream {
+    inner: Box<dyn AdbStream>,
+    connection: u64,
+    recorder: Arc<SessionRecorder>,
+    server_closed: bool,
+    client_closed: bool,
+}
+
+impl RecordingStream {
+    fn close(&mut self) {
+        if !self.client_closed {
+            self.client_closed = true;
+            self.recorder.record(self.connection, StreamDirection::ClientClosed, &[]);
+        }
+    }
+}
+
+impl AsyncRead for RecordingStream {
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let result = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if let Poll::Ready(Ok(())) = result {
+            let data = &buf.filled()[filled..];
+            if !data.is_empty() {
+                self.recorder.record(self.connection, StreamDirection::Receive, data);
+            } else if buf.remaining() > 0 && !self.server_closed {
+                self.server_closed = true;
+                self.recorder.record(self.connection, StreamDirection::ServerClosed, &[]);
+            }
+        }
+        result
+    }
+}
+
+impl AsyncWrite for RecordingStream {
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, data: &[u8]) -> Poll<io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, data);
+        if let Poll::Ready(Ok(written)) = result {
+            if written > 0 {
+                self.recorder.record(self.connection, StreamDirection::Send, &data[..written]);
+            }
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let result = Pin::new(&mut self.inner).poll_shutdown(cx);
+        if result.is_ready() {
+            self.close();
+        }
+        result
+    }
+}
+
+impl Drop for RecordingStream {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
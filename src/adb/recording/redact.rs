@@ -0,0 +1,111 @@
+use std::collections::BTreeMap;
+use regex::bytes::Regex;
+use crate::constants::{ADB_DEVICES_COMMAND, ADB_TRACK_DEVICES_COMMAND, OKAY};
+use crate::enums::stream_direction::StreamDirection;
+use crate::models::session_recording::SessionRecording;
+
+const REQUEST_LENGTH_SIZE: usize = 4;
+const PLACEHOLDER_STEM: &str = "SERIAL";
+/// Requests naming a device: `host:transport:S`, `host:tport:serial:S` and `host-serial:S:<service>`.
+const SERIAL_REQUEST_PATTERN: &str = r"^(?:host:transport:|host:tport:serial:)(.+)$|^host-serial:((?:[0-9.]+:\d+)|[^:]+):";
+
+/// Replaces device serials throughout a recording, so it can be attached to a bug report.
+///
+/// Serials are taken from device listings and transport requests in the recording, plus `extra`
+/// ones such as a serial only seen in shell output. Each is replaced by a placeholder of the same
+/// length (`SERIAL0001`, ...) so the length prefixes of the protocol stay valid and the redacted
+/// recording still replays. Returns the serials that were replaced and their placeholders.
+pub fn redact_serials(recording: &mut SessionRecording, extra: &[String]) -> BTreeMap<String, String> {
+    let mut serials = find_serials(recording);
+    serials.extend(extra.iter().filter(|serial| !serial.is_empty()).cloned());
+    serials.sort();
+    serials.dedup();
+
+    let replacements: BTreeMap<String, String> = serials.iter().enumerate()
+        .map(|(index, serial)| (serial.clone(), placeholder(index + 1, serial.len())))
+        .collect();
+    // Longer serials first, so a serial that contains another is replaced whole.
+    let mut ordered: Vec<(&String, &String)> = replacements.iter().collect();
+    ordered.sort_by_key(|(serial, _)| std::cmp::Reverse(serial.len()));
+
+    let connections: Vec<u64> = recording.connections().iter().filter_map(|events| events.first()).map(|event| event.connection).collect();
+    for connection in connections {
+        for direction in [StreamDirection::Send, StreamDirection::Receive] {
+            let mut data = recording.stream_data(connection, direction);
+            for (serial, replacement) in &ordered {
+                replace_all(&mut data, serial.as_bytes(), replacement.as_bytes());
+            }
+            // Replacements keep the length, so the data splits back into the recorded chunks.
+            let mut rest = data.as_slice();
+            for event in recording.events.iter_mut().filter(|event| event.connection == connection && event.direction == direction) {
+                let (chunk, tail) = rest.split_at(event.data.len());
+                event.data = chunk.to_vec();
+                rest = tail;
+            }
+        }
+    }
+    replacements
+}
+
+fn find_serials(recording: &SessionRecording) -> Vec<String> {
+    let request_pattern = Regex::new(SERIAL_REQUEST_PATTERN).expect("valid serial request pattern");
+    let mut serials = Vec::new();
+    for events in recording.connections() {
+        let Some(connection) = events.first().map(|event| event.connection) else {
+            continue;
+        };
+        let sent = recording.stream_data(connection, StreamDirection::Send);
+        let Some(request) = first_request(&sent) else {
+            continue;
+        };
+
+        if let Some(captures) = request_pattern.captures(request) {
+            if let Some(serial) = captures.get(1).or_else(|| captures.get(2)) {
+                serials.push(String::from_utf8_lossy(serial.as_bytes()).to_string());
+            }
+        } else if request.starts_with(ADB_DEVICES_COMMAND.as_bytes()) || request.starts_with(ADB_TRACK_DEVICES_COMMAND.as_bytes()) {
+            serials.extend(listed_serials(&recording.stream_data(connection, StreamDirection::Receive)));
+        }
+    }
+    serials
+}
+
+fn first_request(data: &[u8]) -> Option<&[u8]> {
+    let length = usize::from_str_radix(std::str::from_utf8(data.get(..REQUEST_LENGTH_SIZE)?).ok()?, 16).ok()?;
+    data.get(REQUEST_LENGTH_SIZE..REQUEST_LENGTH_SIZE + length)
+}
+
+/// Serials in a device listing reply: `OKAY`, then one or more length-prefixed listings.
+fn listed_serials(reply: &[u8]) -> Vec<String> {
+    let mut serials = Vec::new();
+    let mut rest = reply.strip_prefix(OKAY.as_bytes()).unwrap_or(reply);
+    while let Some(listing) = first_request(rest) {
+        rest = &rest[REQUEST_LENGTH_SIZE + listing.len()..];
+        serials.extend(String::from_utf8_lossy(listing).lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(str::to_string));
+    }
+    serials
+}
+
+/// `SERIAL0001` for a 10 character serial; shorter serials get a shorter stem.
+fn placeholder(number: usize, length: usize) -> String {
+    let number = number.to_string();
+    if length < number.len() {
+        return "X".repeat(length);
+    }
+    let stem = &PLACEHOLDER_STEM[..PLACEHOLDER_STEM.len().min(length - number.len())];
+    format!("{}{}{}", stem, "0".repeat(length - stem.len() - number.len()), number)
+}
+
+fn replace_all(data: &mut [u8], from: &[u8], to: &[u8]) {
+    let mut index = 0;
+    while index + from.len() <= data.len() {
+        if &data[index..index + from.len()] == from {
+            data[index..index + from.len()].copy_from_slice(to);
+            index += from.len();
+        } else {
+            index += 1;
+        }
+    }
+}
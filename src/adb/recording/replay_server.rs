@@ -0,0 +1,245 @@
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::time::Instant;
+use crate::adb::client::Client;
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::transport::memory_transport::MemoryTransport;
+use crate::constants::DEFAULT_ADB_SERVER_IP;
+use crate::enums::stream_direction::StreamDirection;
+use crate::models::recorded_event::RecordedEvent;
+use crate::models::session_recording::SessionRecording;
+use crate::utils::escape_bytes;
+
+const MAX_REPORTED_BYTES: usize = 128;
+const REQUEST_LENGTH_SIZE: usize = 4;
+
+#[derive(Debug, Default)]
+struct ReplayState {
+    /// Recorded connections not replayed yet.
+    pending: Vec<Vec<RecordedEvent>>,
+    /// Connections being replayed right now.
+    active: usize,
+    mismatches: Vec<String>,
+}
+
+/// Serves a [`SessionRecording`] back to a client, so a session recorded against a real device
+/// can be reproduced in a test.
+///
+/// Each connection the client opens is matched to the first recorded connection that started with
+/// the same request. Its bytes must then arrive exactly as recorded; the recorded replies are sent
+/// back in turn. A connection that strays from the recording is closed and reported in
+/// [`ReplayServer::mismatches`]. What the client sends has to match too, e.g. the mtime of pushed files.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayServer {
+    inner: Arc<Mutex<ReplayState>>,
+    realtime: bool,
+}
+
+impl ReplayServer {
+    pub fn new(recording: &SessionRecording) -> Self {
+        let state = ReplayState { pending: recording.connections(), active: 0, mismatches: Vec::new() };
+        ReplayServer { inner: Arc::new(Mutex::new(state)), realtime: false }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Ok(Self::new(&SessionRecording::load(path)?))
+    }
+
+    /// Sends replies with the delays seen while recording instead of as fast as possible.
+    pub fn realtime(mut self, realtime: bool) -> Self {
+        self.realtime = realtime;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ReplayState> {
+        self.inner.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// How the client strayed from the recording, one message per closed connection.
+    pub fn mismatches(&self) -> Vec<String> {
+        self.lock().mismatches.clone()
+    }
+
+    /// Number of recorded connections the client has not opened (yet).
+    pub fn remaining(&self) -> usize {
+        self.lock().pending.len()
+    }
+
+    /// True once every recorded connection that carried a request has been replayed.
+    pub fn is_done(&self) -> bool {
+        let state = self.lock();
+        state.active == 0 && !state.pending.iter().any(|events| sends_anything(events))
+    }
+
+    /// Succeeds when every recorded connection was replayed without a mismatch; connections the
+    /// client never sent anything on may be left over.
+    pub fn finish(&self) -> Result<(), Box<dyn Error>> {
+        let state = self.lock();
+        if let Some(mismatch) = state.mismatches.first() {
+            return Err(format!("{} replay mismatch(es), first: {}", state.mismatches.len(), mismatch).into());
+        }
+        let unreplayed = state.pending.iter().filter(|events| sends_anything(events)).count();
+        if unreplayed > 0 {
+            return Err(format!("{} recorded connection(s) were not replayed", unreplayed).into());
+        }
+        Ok(())
+    }
+
+    /// Connections for a client in the same process; see [`ReplayServer::client`].
+    pub fn transport(&self) -> MemoryTransport {
+        let (transport, mut listener) = MemoryTransport::pair();
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Some(stream) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        transport
+    }
+
+    pub async fn client(&self) -> Result<Client, Box<dyn Error>> {
+        ClientBuilder::new().transport(self.transport()).build().await
+    }
+
+    /// Serves on a free TCP port of the loopback interface, e.g. for `adbr -P <port>`.
+    pub async fn listen(&self) -> io::Result<SocketAddr> {
+        self.listen_on(0).await
+    }
+
+    pub async fn listen_on(&self, port: u16) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind((DEFAULT_ADB_SERVER_IP, port)).await?;
+        let address = listener.local_addr()?;
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        Ok(address)
+    }
+
+    /// Replays the recorded connection that matches the request the client sends first.
+    pub async fn serve<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: S) {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let request = match read_request(&mut reader).await {
+            Ok(request) => request,
+            // Clients open a connection up front and may close it unused, e.g. to reconnect.
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                let mut state = self.lock();
+                if let Some(index) = state.pending.iter().position(|events| !sends_anything(events)) {
+                    state.pending.remove(index);
+                }
+                return;
+            }
+            Err(err) => {
+                self.lock().mismatches.push(format!("no request on a new connection: {}", err));
+                return;
+            }
+        };
+
+        let Some(events) = self.take_connection(&request) else {
+            self.lock().mismatches.push(format!("no recorded connection starts with {}", escape_bytes(&request, MAX_REPORTED_BYTES)));
+            return;
+        };
+        let connection = events.first().map_or(0, |event| event.connection);
+        let mut reader = io::Cursor::new(request).chain(reader);
+        let result = self.replay(&events, &mut reader, &mut writer).await;
+        let mut state = self.lock();
+        state.active -= 1;
+        if let Err(mismatch) = result {
+            tracing::warn!("Replay of connection {} stopped: {}", connection, mismatch);
+            state.mismatches.push(format!("connection {}: {}", connection, mismatch));
+        }
+    }
+
+    fn take_connection(&self, request: &[u8]) -> Option<Vec<RecordedEvent>> {
+        let mut state = self.lock();
+        let index = state.pending.iter().position(|events| {
+            let sent: Vec<u8> = events.iter()
+                .filter(|event| event.direction == StreamDirection::Send)
+                .flat_map(|event| event.data.iter().copied())
+                .take(request.len())
+                .collect();
+            sent == request
+        })?;
+        state.active += 1;
+        Some(state.pending.remove(index))
+    }
+
+    async fn replay<R, W>(&self, events: &[RecordedEvent], reader: &mut R, writer: &mut W) -> Result<(), String>
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let started = Instant::now();
+        let recorded_start = events.first().map_or(0, |event| event.time_us);
+        for event in events {
+            match event.direction {
+                StreamDirection::Connect => {}
+                StreamDirection::Send => {
+                    let received = read_expected(reader, &event.data).await.map_err(|err| format!("reading from the client: {}", err))?;
+                    if received != event.data {
+                        return Err(format!(
+                            "expected {} but the client sent {}",
+                            escape_bytes(&event.data, MAX_REPORTED_BYTES),
+                            escape_bytes(&received, MAX_REPORTED_BYTES)
+                        ));
+                    }
+                }
+                StreamDirection::Receive => {
+                    if self.realtime {
+                        tokio::time::sleep_until(started + Duration::from_micros(event.time_us.saturating_sub(recorded_start))).await;
+                    }
+                    writer.write_all(&event.data).await.map_err(|err| format!("writing to the client: {}", err))?;
+                }
+                StreamDirection::ServerClosed => {
+                    let _ = writer.shutdown().await;
+                }
+                StreamDirection::ClientClosed => break,
+            }
+        }
+        Ok(())
+    }
+}
+
+fn sends_anything(events: &[RecordedEvent]) -> bool {
+    events.iter().any(|event| event.direction == StreamDirection::Send)
+}
+
+/// Reads the first smart-socket request: four hex digits of length, then the service name.
+async fn read_request<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut request = vec![0u8; REQUEST_LENGTH_SIZE];
+    reader.read_exact(&mut request).await?;
+    let length = std::str::from_utf8(&request).ok()
+        .and_then(|length| usize::from_str_radix(length, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("invalid request length {}", escape_bytes(&request, REQUEST_LENGTH_SIZE))))?;
+    request.resize(REQUEST_LENGTH_SIZE + length, 0);
+    reader.read_exact(&mut request[REQUEST_LENGTH_SIZE..]).await?;
+    Ok(request)
+}
+
+/// Reads as many bytes as `expected` holds, stopping early when the client closes the connection
+/// or sends something else, so a client waiting for a reply is not left hanging.
+async fn read_expected<R: AsyncRead + Unpin>(reader: &mut R, expected: &[u8]) -> io::Result<Vec<u8>> {
+    let mut received = vec![0u8; expected.len()];
+    let mut filled = 0;
+    while filled < received.len() {
+        match reader.read(&mut received[filled..]).await? {
+            0 => break,
+            read => filled += read,
+        }
+        if received[..filled] != expected[..filled] {
+            break;
+        }
+    }
+    received.truncate(filled);
+    Ok(received)
+}
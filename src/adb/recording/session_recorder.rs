@@ -0,0 +1,50 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+use crate::enums::stream_direction::StreamDirection;
+use crate::models::recorded_event::RecordedEvent;
+
+/// Appends the events of every connection of a session to one recording file.
+///
+/// Each event is flushed as it is written, so the recording is complete even when the process
+/// exits without dropping its clients.
+#[derive(Debug)]
+pub struct SessionRecorder {
+    started: Instant,
+    next_connection: AtomicU64,
+    file: Mutex<BufWriter<File>>,
+}
+
+impl SessionRecorder {
+    /// Creates (or truncates) the recording at `path`.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        Ok(SessionRecorder {
+            started: Instant::now(),
+            next_connection: AtomicU64::new(0),
+            file: Mutex::new(BufWriter::new(File::create(path)?)),
+        })
+    }
+
+    pub(crate) fn next_connection(&self) -> u64 {
+        self.next_connection.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub(crate) fn record(&self, connection: u64, direction: StreamDirection, data: &[u8]) {
+        let event = RecordedEvent {
+            time_us: self.started.elapsed().as_micros() as u64,
+            connection,
+            direction,
+            data: data.to_vec(),
+        };
+        let mut file = self.file.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let written = serde_json::to_writer(&mut *file, &event).map_err(io::Error::from)
+            .and_then(|_| file.write_all(b"\n"))
+            .and_then(|_| file.flush());
+        if let Err(err) = written {
+            tracing::warn!("Error writing session recording: {}", err);
+        }
+    }
+}
@@ -1,35 +1,43 @@
 use std::error::Error;
 use crate::adb::client::Client;
-use crate::constants::{HOST_GET_DEVPATH_COMMAND, REBOOT_BOOTLOADER, HOST_SERIALNO_COMMAND, REBOOT_RECOVERY, REBOOT_SIDELOAD, REBOOT_SIDELOAD_AUTO_REBOOT, OKAY, ADB_REBOOT_BOOTLOADER_COMMAND, ADB_REBOOT_RECOVERY_COMMAND, ADB_REBOOT_SIDELOAD_COMMAND, ADB_REBOOT_SIDELOAD_AUTO_REBOOT_COMMAND, ADB_REBOOT_COMMAND, ADB_ROOT_COMMAND, ADB_UNROOT_COMMAND, ADB_REMOUNT_COMMAND, ADB_USB_COMMAND, ADB_TCPIP_COMMAND, ADB_GET_STATE_COMMAND};
+use crate::constants::{HOST_GET_DEVPATH_SERVICE, REBOOT_BOOTLOADER, HOST_GET_SERIALNO_SERVICE, REBOOT_RECOVERY, REBOOT_SIDELOAD, REBOOT_SIDELOAD_AUTO_REBOOT, OKAY, ADB_REBOOT_BOOTLOADER_COMMAND, ADB_REBOOT_RECOVERY_COMMAND, ADB_REBOOT_SIDELOAD_COMMAND, ADB_REBOOT_SIDELOAD_AUTO_REBOOT_COMMAND, ADB_REBOOT_COMMAND, ADB_ROOT_COMMAND, ADB_UNROOT_COMMAND, ADB_REMOUNT_COMMAND, ADB_USB_COMMAND, ADB_TCPIP_COMMAND, HOST_GET_STATE_SERVICE, HOST_FEATURES_SERVICE};
 use crate::enums::device_transport::DeviceTransport;
-use tokio::time::{Duration, Instant, sleep};
+use crate::enums::interrupted::Interrupted;
+use tokio::time::{Duration, Instant};
 
 const WAIT_FOR_STATE_POLL_INTERVAL_SEC: u64 = 1;
+const WAITING_FOR_DEVICE: &str = "waiting for the device";
 
 impl Client {
     pub async fn adb_wait_for(&mut self, device_transport: DeviceTransport, desired_state: &str, timeout_duration: Option<Duration>) -> Result<(), Box<dyn Error>> {
         let start_time = Instant::now();
+        let mut first_poll = true;
 
         loop {
             if let Some(timeout) = timeout_duration {
                 if Instant::now().duration_since(start_time) >= timeout {
-                    return Err(format!(
-                        "Timeout while waiting for device to reach '{}' state",
-                        desired_state
-                    ).into());
+                    return Err(Interrupted::TimedOut {
+                        operation: format!("waiting for device to reach '{}' state", desired_state),
+                    }.into());
                 }
             }
 
+            if !first_poll && self.reconnect().await.is_err() {
+                self.limits.sleep(WAITING_FOR_DEVICE, Duration::from_secs(WAIT_FOR_STATE_POLL_INTERVAL_SEC)).await?;
+                continue;
+            }
+            first_poll = false;
+
             match self.adb_get_state(device_transport.clone()).await {
                 Ok(current_state) => {
                     if current_state == desired_state {
                         return Ok(());
                     } else {
-                        sleep(Duration::from_secs(WAIT_FOR_STATE_POLL_INTERVAL_SEC)).await;
+                        self.limits.sleep(WAITING_FOR_DEVICE, Duration::from_secs(WAIT_FOR_STATE_POLL_INTERVAL_SEC)).await?;
                     }
                 }
                 Err(_) => {
-                    sleep(Duration::from_secs(WAIT_FOR_STATE_POLL_INTERVAL_SEC)).await;
+                    self.limits.sleep(WAITING_FOR_DEVICE, Duration::from_secs(WAIT_FOR_STATE_POLL_INTERVAL_SEC)).await?;
                 }
             }
         }
@@ -37,8 +45,8 @@ impl Client {
 
 
     pub async fn adb_get_state(&mut self, device_transport: DeviceTransport) -> Result<String, Box<dyn Error>> {
-        self.send_transport(device_transport.clone()).await?;
-        self.send_adb_command(ADB_GET_STATE_COMMAND).await?;
+        let command = format!("{}{}", device_transport.host_prefix(), HOST_GET_STATE_SERVICE);
+        self.send_adb_command(&command).await?;
         let response = self.read_first_four_bytes_response().await?;
 
         if response != OKAY {
@@ -49,6 +57,20 @@ impl Client {
         self.read_adb_full_response().await
     }
 
+    pub async fn adb_features(&mut self, device_transport: DeviceTransport) -> Result<Vec<String>, Box<dyn Error>> {
+        let command = format!("{}{}", device_transport.host_prefix(), HOST_FEATURES_SERVICE);
+        self.send_adb_command(&command).await?;
+        let response = self.read_first_four_bytes_response().await?;
+
+        if response != OKAY {
+            let error_msg_str = self.read_adb_full_response().await?;
+            return Err(format!("Failed to get device features: {}", error_msg_str).into());
+        }
+
+        let features = self.read_adb_full_response().await?;
+        Ok(features.trim().split(',').filter(|feature| !feature.is_empty()).map(String::from).collect())
+    }
+
 
     pub async fn adb_reboot(&mut self, device_transport: DeviceTransport, reboot_target: Option<String>) -> Result<(), Box<dyn Error>> {
         self.send_transport(device_transport.clone()).await?;
@@ -72,8 +94,8 @@ impl Client {
         Ok(())
     }
     pub async fn adb_serialno(&mut self, device_transport: DeviceTransport) -> Result<String, Box<dyn Error>> {
-        self.send_transport(device_transport.clone()).await?;
-        self.send_adb_command(HOST_SERIALNO_COMMAND).await?;
+        let command = format!("{}{}", device_transport.host_prefix(), HOST_GET_SERIALNO_SERVICE);
+        self.send_adb_command(&command).await?;
 
         let response = self.read_first_four_bytes_response().await?;
 
@@ -123,8 +145,8 @@ impl Client {
     }
 
     pub async fn adb_get_devpath(&mut self, device_transport: DeviceTransport) -> Result<String, Box<dyn Error>> {
-        self.send_transport(device_transport.clone()).await?;
-        self.send_adb_command(HOST_GET_DEVPATH_COMMAND).await?;
+        let command = format!("{}{}", device_transport.host_prefix(), HOST_GET_DEVPATH_SERVICE);
+        self.send_adb_command(&command).await?;
 
         let response = self.read_first_four_bytes_response().await?;
 
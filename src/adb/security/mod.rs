@@ -1 +1 @@
-mod security;
+pub(crate) mod security;
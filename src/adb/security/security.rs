@@ -48,6 +48,8 @@ impl Client {
             } else {
                 path
             }
+        } else if let Some(path) = self.key_paths().first() {
+            path.clone()
         } else {
             let mut path = home_dir().ok_or("Unable to determine home directory")?;
             path.push(ADB_FOLDER_NAME);
@@ -0,0 +1,541 @@
+use std::error::Error;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tokio::sync::watch;
+use tokio_util::sync::CancellationToken;
+use crate::adb::client::Client;
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::server::host_request::{self, fail, okay, okay_string, parse_forward_spec, parse_host_request, parse_transport_request, read_request, Selector, TransportRequest, SERVER_VERSION};
+use crate::adb::transport::memory_transport::MemoryTransport;
+use crate::adb::wire::auth::HostAuth;
+use crate::adb::wire::device_link::DeviceLink;
+use crate::constants::{ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION, ADB_TRACK_DEVICES_COMMAND, DEFAULT_ADBD_PORT, DEFAULT_ADB_SERVER_IP, DELAYED_ACK_FEATURE, EMULATOR_FIRST_ADB_PORT, EMULATOR_LAST_ADB_PORT, EMULATOR_SERIAL_PREFIX, HOST_CONNECT_COMMAND, HOST_DISCONNECT_COMMAND, HOST_FEATURES_SERVICE, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_LIST_SERVICE, HOST_FORWARD_SERVICE, HOST_GET_DEVPATH_SERVICE, HOST_GET_SERIALNO_SERVICE, HOST_GET_STATE_SERVICE, HOST_KILL_COMMAND, OKAY, TCP_SOCKET_PREFIX};
+use crate::models::device_banner::DeviceBanner;
+
+const UNKNOWN_DEVPATH: &str = "unknown";
+const TRACK_DEVICES_LONG_COMMAND: &str = "host:track-devices-l";
+/// Features this server announces to devices; services are relayed as is, so these are what adbr
+/// clients know how to speak.
+const HOST_FEATURES: [&str; 13] = [
+    "shell_v2", "cmd", "stat_v2", "ls_v2", "fixed_push_mkdir", "apex", "abb", "fixed_push_symlink_timestamp",
+    "abb_exec", "remount_shell", "track_app", "sendrecv_v2", DELAYED_ACK_FEATURE,
+];
+const PRODUCT_NAME_PROP: &str = "ro.product.name";
+const PRODUCT_MODEL_PROP: &str = "ro.product.model";
+const PRODUCT_DEVICE_PROP: &str = "ro.product.device";
+
+#[derive(Debug, Clone)]
+struct AttachedDevice {
+    transport_id: u64,
+    serial: String,
+    link: DeviceLink,
+}
+
+impl AttachedDevice {
+    /// `device`, `recovery`, ... as the device announced itself, until the link drops.
+    fn state(&self) -> String {
+        if self.link.is_closed() { "offline".to_string() } else { self.link.peer().kind.clone() }
+    }
+
+    fn line(&self, long: bool) -> String {
+        let peer = self.link.peer();
+        let prop = |key: &str| peer.get(key).unwrap_or_default().to_string();
+        let details = (prop(PRODUCT_NAME_PROP), prop(PRODUCT_MODEL_PROP).replace(' ', "_"), prop(PRODUCT_DEVICE_PROP));
+        host_request::device_line(self.transport_id, &self.serial, &self.state(), None, Some(details), long)
+    }
+}
+
+#[derive(Debug)]
+struct Forward {
+    serial: String,
+    local: String,
+    remote: String,
+    stop: CancellationToken,
+}
+
+#[derive(Debug, Default)]
+struct ServerState {
+    devices: Vec<AttachedDevice>,
+    forwards: Vec<Forward>,
+    last_transport_id: u64,
+}
+
+#[derive(Debug)]
+struct ServerShared {
+    state: Mutex<ServerState>,
+    /// Bumped whenever the device list changes, for `host:track-devices`.
+    changes: watch::Sender<()>,
+    shutdown: CancellationToken,
+    auth: HostAuth,
+}
+
+enum HostReply {
+    /// Switch the connection to a device; `tport` requests also get the transport id.
+    Transport(AttachedDevice, Option<u64>),
+    Respond(Vec<u8>),
+    TrackDevices(bool),
+    Kill,
+}
+
+/// A native ADB server: speaks the smart-socket protocol to clients and the device protocol to
+/// devices reached over TCP, i.e. `adb connect` targets and emulators. Device services are relayed
+/// byte for byte once the client switched its connection to a device.
+///
+/// Cloning gives another handle to the same server.
+#[derive(Debug, Clone)]
+pub struct AdbServer {
+    shared: Arc<ServerShared>,
+}
+
+impl Default for AdbServer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AdbServer {
+    /// A server without keys, for devices that do not ask for AUTH such as emulators.
+    pub fn new() -> Self {
+        Self::with_auth(HostAuth::new())
+    }
+
+    /// A server that authenticates to devices with the keys of `auth`.
+    pub fn with_auth(auth: HostAuth) -> Self {
+        let shared = ServerShared {
+            state: Mutex::new(ServerState::default()),
+            changes: watch::Sender::new(()),
+            shutdown: CancellationToken::new(),
+            auth,
+        };
+        AdbServer { shared: Arc::new(shared) }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, ServerState> {
+        self.shared.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn devices_changed(&self) {
+        self.shared.changes.send_replace(());
+    }
+
+    /// A transport whose connections are served by this server in-process.
+    pub fn transport(&self) -> MemoryTransport {
+        let (transport, mut listener) = MemoryTransport::pair();
+        let server = self.clone();
+        tokio::spawn(async move {
+            while let Some(stream) = listener.accept().await {
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        transport
+    }
+
+    pub async fn client(&self) -> Result<Client, Box<dyn Error>> {
+        ClientBuilder::new().transport(self.transport()).build().await
+    }
+
+    /// Serves on a free TCP port of the loopback interface.
+    pub async fn listen(&self) -> io::Result<SocketAddr> {
+        self.listen_on(0).await
+    }
+
+    /// Serves on `port` of the loopback interface until [`AdbServer::shutdown`].
+    pub async fn listen_on(&self, port: u16) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind((DEFAULT_ADB_SERVER_IP, port)).await?;
+        let address = listener.local_addr()?;
+        let server = self.clone();
+        tokio::spawn(async move {
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = server.shared.shutdown.cancelled() => break,
+                };
+                let Ok((stream, _)) = accepted else { break };
+                let _ = stream.set_nodelay(true);
+                let server = server.clone();
+                tokio::spawn(async move { server.serve(stream).await });
+            }
+        });
+        Ok(address)
+    }
+
+    /// Stops listening and drops every device and forward, as for `adb kill-server`.
+    pub fn shutdown(&self) {
+        self.shared.shutdown.cancel();
+        let devices = std::mem::take(&mut self.lock().devices);
+        for device in devices {
+            device.link.close();
+        }
+        self.lock().forwards.clear();
+        self.devices_changed();
+    }
+
+    /// Resolves once the server was shut down, e.g. by a `host:kill` request.
+    pub async fn wait(&self) {
+        self.shared.shutdown.cancelled().await
+    }
+
+    /// Connects to adbd at `HOST[:PORT]` (port 5555 by default) and returns the message `adb connect`
+    /// prints, e.g. `connected to 10.0.0.2:5555`.
+    pub async fn connect_device(&self, address: &str) -> Result<String, String> {
+        let serial = normalize_address(address);
+        if self.find(&serial).is_some() {
+            return Ok(format!("already connected to {}", serial));
+        }
+        match self.dial(&serial).await {
+            Ok(link) => {
+                self.attach(&serial, link);
+                Ok(format!("connected to {}", serial))
+            }
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => Err(format!("failed to authenticate to {}", serial)),
+            Err(err) => Err(format!("failed to connect to '{}': {}", serial, err)),
+        }
+    }
+
+    /// Attaches the emulator whose adbd listens on local `adb_port`, as `emulator-<console port>`.
+    pub async fn connect_emulator(&self, adb_port: u16) -> io::Result<String> {
+        if adb_port < 1 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid emulator adb port {}", adb_port)));
+        }
+        let serial = format!("{}{}", EMULATOR_SERIAL_PREFIX, adb_port - 1);
+        if self.find(&serial).is_none() {
+            let link = self.dial(&format!("{}:{}", DEFAULT_ADB_SERVER_IP, adb_port)).await?;
+            self.attach(&serial, link);
+        }
+        Ok(serial)
+    }
+
+    /// Looks for emulators on the ports they use by default, like adb does when it starts.
+    pub async fn scan_emulators(&self) -> Vec<String> {
+        let mut serials = Vec::new();
+        for port in (EMULATOR_FIRST_ADB_PORT..=EMULATOR_LAST_ADB_PORT).step_by(2) {
+            if let Ok(serial) = self.connect_emulator(port).await {
+                serials.push(serial);
+            }
+        }
+        serials
+    }
+
+    /// Lists a device connected over `link` until the link drops; returns its transport id.
+    pub fn attach(&self, serial: &str, link: DeviceLink) -> u64 {
+        let transport_id = {
+            let mut state = self.lock();
+            state.last_transport_id += 1;
+            let transport_id = state.last_transport_id;
+            state.devices.push(AttachedDevice { transport_id, serial: serial.to_string(), link: link.clone() });
+            transport_id
+        };
+        tracing::debug!("Attached {} as transport {}", serial, transport_id);
+        self.devices_changed();
+
+        let server = self.clone();
+        tokio::spawn(async move {
+            link.closed().await;
+            server.detach(transport_id);
+        });
+        transport_id
+    }
+
+    /// Closes the connection to a device and drops its forwards; false if no device has that serial.
+    pub fn disconnect_device(&self, serial: &str) -> bool {
+        match self.find(serial) {
+            Some(device) => {
+                device.link.close();
+                self.detach(device.transport_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn detach(&self, transport_id: u64) {
+        let mut state = self.lock();
+        let Some(index) = state.devices.iter().position(|device| device.transport_id == transport_id) else {
+            return;
+        };
+        let device = state.devices.remove(index);
+        state.forwards.retain(|forward| {
+            let keep = forward.serial != device.serial;
+            if !keep {
+                forward.stop.cancel();
+            }
+            keep
+        });
+        drop(state);
+        tracing::debug!("Detached {}", device.serial);
+        self.devices_changed();
+    }
+
+    /// Connects and completes the handshake within the usual connect timeout, plus the time the
+    /// user has to allow our key on the device.
+    async fn dial(&self, address: &str) -> io::Result<DeviceLink> {
+        let banner = DeviceBanner::new("host").features(&HOST_FEATURES.map(str::to_string));
+        let timeout = Duration::from_secs(ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION) + self.shared.auth.prompt_timeout;
+        tokio::time::timeout(timeout, DeviceLink::dial(address, &banner, &self.shared.auth)).await
+            .map_err(|_| io::Error::new(io::ErrorKind::TimedOut, "timed out"))?
+    }
+
+    fn find(&self, serial: &str) -> Option<AttachedDevice> {
+        self.lock().devices.iter().find(|device| device.serial == serial).cloned()
+    }
+
+    /// Serves one client connection until its service finishes.
+    pub async fn serve<S: AsyncRead + AsyncWrite + Unpin>(&self, mut stream: S) {
+        if let Err(err) = self.serve_connection(&mut stream).await {
+            tracing::debug!("server connection failed: {}", err);
+        }
+        stream.shutdown().await.ok();
+    }
+
+    async fn serve_connection<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut S) -> io::Result<()> {
+        let mut selected: Option<AttachedDevice> = None;
+        while let Some(request) = read_request(stream).await? {
+            tracing::debug!(service = request.as_str(), "host request");
+            if let Some(device) = &selected {
+                return relay(device, &request, stream).await;
+            }
+            match self.host_service(&request).await {
+                HostReply::Transport(device, tport_id) => {
+                    stream.write_all(OKAY.as_bytes()).await?;
+                    if let Some(transport_id) = tport_id {
+                        stream.write_all(&transport_id.to_le_bytes()).await?;
+                    }
+                    selected = Some(device);
+                }
+                HostReply::Respond(reply) => return stream.write_all(&reply).await,
+                HostReply::TrackDevices(long) => return self.track_devices(stream, long).await,
+                HostReply::Kill => {
+                    stream.write_all(OKAY.as_bytes()).await?;
+                    self.shutdown();
+                    return Ok(());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends the device list, then again whenever it changes, until the client hangs up.
+    async fn track_devices<S: AsyncRead + AsyncWrite + Unpin>(&self, stream: &mut S, long: bool) -> io::Result<()> {
+        let mut changes = self.shared.changes.subscribe();
+        stream.write_all(OKAY.as_bytes()).await?;
+        let mut probe = [0u8; 1];
+        loop {
+            changes.borrow_and_update();
+            let listing = self.device_listing(long);
+            stream.write_all(format!("{:04x}{}", listing.len(), listing).as_bytes()).await?;
+            tokio::select! {
+                changed = changes.changed() => if changed.is_err() { return Ok(()) },
+                read = stream.read(&mut probe) => if matches!(read, Ok(0) | Err(_)) { return Ok(()) },
+                _ = self.shared.shutdown.cancelled() => return Ok(()),
+            }
+        }
+    }
+
+    fn device_listing(&self, long: bool) -> String {
+        self.lock().devices.iter().map(|device| device.line(long)).collect()
+    }
+
+    /// Finds the single device `selector` names; TCP devices never match `-d`.
+    fn select(&self, selector: &Selector) -> Result<AttachedDevice, String> {
+        let state = self.lock();
+        let candidates: Vec<&AttachedDevice> = state.devices.iter()
+            .filter(|device| selector.matches(device.transport_id, &device.serial, false))
+            .collect();
+        let device = selector.pick(candidates)?.clone();
+        drop(state);
+        match device.link.is_closed() {
+            true => Err("device offline".to_string()),
+            false => Ok(device),
+        }
+    }
+
+    async fn host_service(&self, request: &str) -> HostReply {
+        match request {
+            HOST_KILL_COMMAND => return HostReply::Kill,
+            ADB_TRACK_DEVICES_COMMAND => return HostReply::TrackDevices(false),
+            TRACK_DEVICES_LONG_COMMAND => return HostReply::TrackDevices(true),
+            _ => {}
+        }
+        if let Some(address) = request.strip_prefix(HOST_CONNECT_COMMAND) {
+            // adb reports failures to connect as a message too.
+            let (Ok(message) | Err(message)) = self.connect_device(address).await;
+            return HostReply::Respond(okay_string(&message));
+        }
+        if let Some(address) = request.strip_prefix(HOST_DISCONNECT_COMMAND) {
+            return HostReply::Respond(self.disconnect_request(address));
+        }
+
+        let Some((selector, service)) = parse_host_request(request) else {
+            return HostReply::Respond(fail(&format!("unknown host service: {}", request)));
+        };
+        match parse_transport_request(service) {
+            Some(Ok(TransportRequest::Transport(selector))) => match self.select(&selector) {
+                Ok(device) => HostReply::Transport(device, None),
+                Err(message) => HostReply::Respond(fail(&message)),
+            },
+            Some(Ok(TransportRequest::Tport(selector))) => match self.select(&selector) {
+                Ok(device) => {
+                    let transport_id = device.transport_id;
+                    HostReply::Transport(device, Some(transport_id))
+                }
+                Err(message) => HostReply::Respond(fail(&message)),
+            },
+            Some(Err(message)) => HostReply::Respond(fail(&message)),
+            None => HostReply::Respond(match self.host_request(selector, service).await {
+                Ok(reply) => reply,
+                Err(message) => fail(&message),
+            }),
+        }
+    }
+
+    fn disconnect_request(&self, address: &str) -> Vec<u8> {
+        if address.is_empty() {
+            let serials: Vec<String> = self.lock().devices.iter().map(|device| device.serial.clone()).collect();
+            for serial in serials {
+                self.disconnect_device(&serial);
+            }
+            return okay_string("disconnected everything");
+        }
+        let serial = normalize_address(address);
+        match self.disconnect_device(&serial) {
+            true => okay_string(&format!("disconnected {}", serial)),
+            false => fail(&format!("no such device '{}'", serial)),
+        }
+    }
+
+    async fn host_request(&self, selector: Selector, service: &str) -> Result<Vec<u8>, String> {
+        match service {
+            "version" => Ok(okay_string(SERVER_VERSION)),
+            "devices" | "devices-l" => Ok(okay_string(&self.device_listing(service == "devices-l"))),
+            HOST_GET_STATE_SERVICE => Ok(okay_string(&self.select(&selector)?.state())),
+            HOST_GET_SERIALNO_SERVICE => Ok(okay_string(&self.select(&selector)?.serial)),
+            HOST_GET_DEVPATH_SERVICE => {
+                self.select(&selector)?;
+                Ok(okay_string(UNKNOWN_DEVPATH))
+            }
+            HOST_FEATURES_SERVICE => {
+                let device = self.select(&selector)?;
+                let features: Vec<&str> = device.link.peer().features.iter()
+                    .map(String::as_str)
+                    .filter(|feature| HOST_FEATURES.contains(feature))
+                    .collect();
+                Ok(okay_string(&features.join(",")))
+            }
+            HOST_FORWARD_LIST_SERVICE => {
+                let listing: String = self.lock().forwards.iter()
+                    .map(|forward| format!("{} {} {}\n", forward.serial, forward.local, forward.remote))
+                    .collect();
+                Ok(okay_string(&listing))
+            }
+            HOST_FORWARD_KILL_ALL_SERVICE => {
+                let serial = match selector {
+                    Selector::Any => None,
+                    selector => Some(self.select(&selector)?.serial),
+                };
+                self.lock().forwards.retain(|forward| {
+                    let keep = serial.as_ref().is_some_and(|serial| *serial != forward.serial);
+                    if !keep {
+                        forward.stop.cancel();
+                    }
+                    keep
+                });
+                Ok([okay(), okay()].concat())
+            }
+            service => {
+                if let Some(local) = service.strip_prefix(HOST_FORWARD_KILL_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+                    let mut state = self.lock();
+                    let Some(index) = state.forwards.iter().position(|forward| forward.local == local) else {
+                        return Err(format!("listener '{}' not found", local));
+                    };
+                    state.forwards.remove(index).stop.cancel();
+                    return Ok([okay(), okay()].concat());
+                }
+                if let Some(spec) = service.strip_prefix(HOST_FORWARD_SERVICE).and_then(|rest| rest.strip_prefix(':')) {
+                    return self.forward(&selector, spec).await;
+                }
+                Err(format!("unknown host service: {}", service))
+            }
+        }
+    }
+
+    /// Listens on a local port and relays every connection to `remote` on the device.
+    async fn forward(&self, selector: &Selector, spec: &str) -> Result<Vec<u8>, String> {
+        let (no_rebind, local, remote) = parse_forward_spec(spec)?;
+        let device = self.select(selector)?;
+        if let Some(existing) = self.lock().forwards.iter_mut().find(|forward| forward.local == local) {
+            if no_rebind {
+                return Err("cannot rebind existing socket".to_string());
+            }
+            existing.serial = device.serial;
+            existing.remote = remote;
+            return Ok([okay(), okay()].concat());
+        }
+
+        let port = local.strip_prefix(TCP_SOCKET_PREFIX).and_then(|port| port.parse::<u16>().ok())
+            .ok_or_else(|| format!("cannot bind listener: unsupported socket spec '{}'", local))?;
+        let listener = TcpListener::bind((DEFAULT_ADB_SERVER_IP, port)).await.map_err(|err| format!("cannot bind listener: {}", err))?;
+        let bound = listener.local_addr().map_err(|err| format!("cannot bind listener: {}", err))?.port();
+        // `tcp:0` is listed under the port it got.
+        let local = format!("{}{}", TCP_SOCKET_PREFIX, bound);
+        let stop = self.shared.shutdown.child_token();
+        self.lock().forwards.push(Forward { serial: device.serial, local: local.clone(), remote, stop: stop.clone() });
+        tokio::spawn(self.clone().accept_forwarded(listener, local, stop));
+        Ok(match port {
+            0 => [okay(), okay_string(&bound.to_string())].concat(),
+            _ => [okay(), okay()].concat(),
+        })
+    }
+
+    async fn accept_forwarded(self, listener: TcpListener, local: String, stop: CancellationToken) {
+        loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = stop.cancelled() => break,
+            };
+            let Ok((mut socket, _)) = accepted else { break };
+            // Looked up per connection, as the forward may have been rebound to another device.
+            let target = self.lock().forwards.iter()
+                .find(|forward| forward.local == local)
+                .map(|forward| (forward.serial.clone(), forward.remote.clone()));
+            let Some(device) = target.as_ref().and_then(|(serial, _)| self.find(serial)) else { break };
+            let remote = target.map(|(_, remote)| remote).unwrap_or_default();
+            let local = local.clone();
+            tokio::spawn(async move {
+                match device.link.open(&remote).await {
+                    Ok(mut stream) => {
+                        let _ = tokio::io::copy_bidirectional(&mut socket, &mut stream).await;
+                    }
+                    Err(err) => tracing::debug!("Forward {} -> {} failed: {}", local, remote, err),
+                }
+            });
+        }
+    }
+}
+
+/// Opens `service` on the device and, once it accepted, copies bytes both ways until either end closes.
+async fn relay<S: AsyncRead + AsyncWrite + Unpin>(device: &AttachedDevice, service: &str, stream: &mut S) -> io::Result<()> {
+    let mut device_stream = match device.link.open(service).await {
+        Ok(device_stream) => device_stream,
+        Err(err) => {
+            tracing::debug!("{} refused {}: {}", device.serial, service, err);
+            return stream.write_all(&fail("closed")).await;
+        }
+    };
+    stream.write_all(OKAY.as_bytes()).await?;
+    tokio::io::copy_bidirectional(stream, &mut device_stream).await?;
+    Ok(())
+}
+
+
+/// `HOST` becomes `HOST:5555`; an address with a port is kept as is.
+fn normalize_address(address: &str) -> String {
+    match address.rsplit_once(':') {
+        Some((_, port)) if port.parse::<u16>().is_ok() => address.to_string(),
+        _ => format!("{}:{}", address, DEFAULT_ADBD_PORT),
+    }
+}
@@ -0,0 +1,179 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::constants::{FAIL, HOST_FEATURES_SERVICE, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_LIST_SERVICE, HOST_FORWARD_SERVICE, HOST_GET_DEVPATH_SERVICE, HOST_GET_SERIALNO_SERVICE, HOST_GET_STATE_SERVICE, NO_REBIND_OPTION, OKAY};
+
+const HOST_PREFIX: &str = "host:";
+const HOST_USB_PREFIX: &str = "host-usb:";
+const HOST_LOCAL_PREFIX: &str = "host-local:";
+const HOST_SERIAL_PREFIX: &str = "host-serial:";
+const HOST_TRANSPORT_ID_PREFIX: &str = "host-transport-id:";
+const DEVICE_SCOPED_SERVICES: [&str; 8] = [
+    HOST_GET_STATE_SERVICE, HOST_GET_SERIALNO_SERVICE, HOST_GET_DEVPATH_SERVICE, HOST_FEATURES_SERVICE,
+    HOST_FORWARD_SERVICE, HOST_FORWARD_KILL_SERVICE, HOST_FORWARD_KILL_ALL_SERVICE, HOST_FORWARD_LIST_SERVICE,
+];
+/// `adb version` 1.0.41, the protocol revision of current platform tools.
+pub(crate) const SERVER_VERSION: &str = "0029";
+
+/// Which device a host request is about, from its `host[-usb|-local|-serial|-transport-id]:` prefix
+/// or a `transport*` service.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Selector {
+    Any,
+    Usb,
+    Local,
+    Serial(String),
+    TransportId(u64),
+}
+
+/// A request to switch the connection to a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum TransportRequest {
+    /// `host:transport*`: OKAY, then the connection belongs to the device.
+    Transport(Selector),
+    /// `host:tport:*`: like `Transport`, but the transport id follows the OKAY.
+    Tport(Selector),
+}
+
+impl Selector {
+    pub fn matches(&self, transport_id: u64, serial: &str, usb: bool) -> bool {
+        match self {
+            Selector::Any => true,
+            Selector::Usb => usb,
+            Selector::Local => !usb,
+            Selector::Serial(wanted) => serial == wanted,
+            Selector::TransportId(id) => transport_id == *id,
+        }
+    }
+
+    /// Picks the single matching candidate, failing with the messages adb uses.
+    pub fn pick<T>(&self, candidates: Vec<T>) -> Result<T, String> {
+        let count = candidates.len();
+        let mut candidates = candidates.into_iter();
+        match (candidates.next(), count, self) {
+            (Some(found), 1, _) | (Some(found), _, Selector::Serial(_) | Selector::TransportId(_)) => Ok(found),
+            (None, _, Selector::Any) => Err("no devices/emulators found".to_string()),
+            (None, _, Selector::Usb) => Err("no devices found".to_string()),
+            (None, _, Selector::Local) => Err("no emulators found".to_string()),
+            (None, _, Selector::Serial(serial)) => Err(format!("device '{}' not found", serial)),
+            (None, _, Selector::TransportId(id)) => Err(format!("no device with transport id '{}'", id)),
+            (Some(_), _, Selector::Usb) => Err("more than one device".to_string()),
+            (Some(_), _, Selector::Local) => Err("more than one emulator".to_string()),
+            (Some(_), _, _) => Err("more than one device/emulator".to_string()),
+        }
+    }
+}
+
+pub(crate) fn okay() -> Vec<u8> {
+    OKAY.as_bytes().to_vec()
+}
+
+/// OKAY followed by a length-prefixed string.
+pub(crate) fn okay_string(value: &str) -> Vec<u8> {
+    format!("{}{:04x}{}", OKAY, value.len(), value).into_bytes()
+}
+
+pub(crate) fn fail(message: &str) -> Vec<u8> {
+    format!("{}{:04x}{}", FAIL, message.len(), message).into_bytes()
+}
+
+/// Reads one length-prefixed request; `None` when the client closed the connection instead.
+pub(crate) async fn read_request<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<Option<String>> {
+    let mut length = [0u8; 4];
+    match stream.read_exact(&mut length).await {
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let length = std::str::from_utf8(&length).ok()
+        .and_then(|length| usize::from_str_radix(length, 16).ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid request length"))?;
+    let mut request = vec![0u8; length];
+    stream.read_exact(&mut request).await?;
+    Ok(Some(String::from_utf8_lossy(&request).into_owned()))
+}
+
+/// Splits `[norebind:]LOCAL;REMOTE` into the no-rebind flag and both socket specs.
+pub(crate) fn parse_forward_spec(spec: &str) -> Result<(bool, String, String), String> {
+    let (no_rebind, spec) = match spec.strip_prefix(NO_REBIND_OPTION).and_then(|rest| rest.strip_prefix(':')) {
+        Some(rest) => (true, rest),
+        None => (false, spec),
+    };
+    match spec.split_once(';') {
+        Some((local, remote)) if !local.is_empty() && !remote.is_empty() => Ok((no_rebind, local.to_string(), remote.to_string())),
+        _ => Err(format!("malformed forward spec '{}'", spec)),
+    }
+}
+
+/// Splits `<serial>:<service>` where the serial itself may contain colons, e.g. `10.0.0.2:5555`.
+fn split_serial(rest: &str) -> Option<(&str, &str)> {
+    rest.match_indices(':')
+        .map(|(index, _)| (&rest[..index], &rest[index + 1..]))
+        .find(|(_, service)| DEVICE_SCOPED_SERVICES.iter().any(|known| service.split(':').next() == Some(known)))
+}
+
+/// Splits a host request into the device it is scoped to and the service, e.g.
+/// `host-serial:R58M123ABC:get-state` into `Serial("R58M123ABC")` and `get-state`.
+pub(crate) fn parse_host_request(request: &str) -> Option<(Selector, &str)> {
+    if let Some(service) = request.strip_prefix(HOST_PREFIX) {
+        Some((Selector::Any, service))
+    } else if let Some(service) = request.strip_prefix(HOST_USB_PREFIX) {
+        Some((Selector::Usb, service))
+    } else if let Some(service) = request.strip_prefix(HOST_LOCAL_PREFIX) {
+        Some((Selector::Local, service))
+    } else if let Some(rest) = request.strip_prefix(HOST_SERIAL_PREFIX) {
+        split_serial(rest).map(|(serial, service)| (Selector::Serial(serial.to_string()), service))
+    } else if let Some(rest) = request.strip_prefix(HOST_TRANSPORT_ID_PREFIX) {
+        let (id, service) = rest.split_once(':')?;
+        Some((Selector::TransportId(id.parse().ok()?), service))
+    } else {
+        None
+    }
+}
+
+/// Recognizes `transport*` and `tport:*` host services; `None` for other services.
+pub(crate) fn parse_transport_request(service: &str) -> Option<Result<TransportRequest, String>> {
+    if let Some(target) = service.strip_prefix("tport:") {
+        let selector = match target {
+            "any" => Selector::Any,
+            "usb" => Selector::Usb,
+            "local" => Selector::Local,
+            target => match target.strip_prefix("serial:") {
+                Some(serial) => Selector::Serial(serial.to_string()),
+                None => return Some(Err(format!("unknown tport target: {}", target))),
+            },
+        };
+        return Some(Ok(TransportRequest::Tport(selector)));
+    }
+
+    let selector = match service {
+        "transport-any" => Selector::Any,
+        "transport-usb" => Selector::Usb,
+        "transport-local" => Selector::Local,
+        service => match (service.strip_prefix("transport:"), service.strip_prefix("transport-id:")) {
+            (Some(serial), _) => Selector::Serial(serial.to_string()),
+            (_, Some(id)) => match id.parse() {
+                Ok(id) => Selector::TransportId(id),
+                Err(_) => return Some(Err(format!("invalid transport id '{}'", id))),
+            },
+            _ => return None,
+        },
+    };
+    Some(Ok(TransportRequest::Transport(selector)))
+}
+
+/// One line of `host:devices` or, when `long`, `host:devices-l`. `details` are the
+/// product, model and device names, shown for online devices.
+pub(crate) fn device_line(transport_id: u64, serial: &str, state: &str, usb: Option<&str>, details: Option<(String, String, String)>, long: bool) -> String {
+    if !long {
+        return format!("{}\t{}\n", serial, state);
+    }
+    let mut line = format!("{:<22} {}", serial, state);
+    if let Some(usb) = usb {
+        line.push_str(&format!(" usb:{}", usb));
+    }
+    if let Some((product, model, device)) = details {
+        line.push_str(&format!(" product:{} model:{} device:{}", product, model, device));
+    }
+    line.push_str(&format!(" transport_id:{}\n", transport_id));
+    line
+}
@@ -0,0 +1,2 @@
+pub mod adb_server;
+pub(crate) mod host_request;
@@ -1 +1,5 @@
 pub mod shell;
+pub mod shell_protocol;
+pub mod raw_terminal;
+pub mod shell_escape;
+pub mod shell_session;
@@ -0,0 +1,81 @@
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::{Mutex, Once};
+use nix::libc;
+use termios::{Termios, TCSADRAIN, TCSAFLUSH};
+
+/// Settings to put back if the process panics while the terminal is raw.
+static SAVED_TERMINAL: Mutex<Option<(RawFd, Termios)>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+/// Puts a terminal into raw mode (`cfmakeraw`) and restores it when dropped or on panic.
+///
+/// In raw mode Ctrl-C, Ctrl-Z and escape sequences reach the remote PTY as plain bytes instead of
+/// being handled by the local terminal.
+pub struct RawTerminal {
+    fd: RawFd,
+    original: Termios,
+    raw: Termios,
+}
+
+impl RawTerminal {
+    pub fn enter(fd: RawFd) -> io::Result<Self> {
+        let original = Termios::from_fd(fd)?;
+        let mut raw = original;
+        termios::cfmakeraw(&mut raw);
+
+        PANIC_HOOK.call_once(|| {
+            let previous = std::panic::take_hook();
+            std::panic::set_hook(Box::new(move |info| {
+                restore_saved_terminal();
+                previous(info);
+            }));
+        });
+        if let Ok(mut saved) = SAVED_TERMINAL.lock() {
+            *saved = Some((fd, original));
+        }
+
+        termios::tcsetattr(fd, TCSAFLUSH, &raw)?;
+        Ok(RawTerminal { fd, original, raw })
+    }
+
+    /// Temporarily restores the original settings, e.g. for a local prompt or before suspending.
+    pub fn suspend(&self) -> io::Result<()> {
+        termios::tcsetattr(self.fd, TCSADRAIN, &self.original)
+    }
+
+    /// Switches back to raw mode after [`RawTerminal::suspend`].
+    pub fn resume(&self) -> io::Result<()> {
+        termios::tcsetattr(self.fd, TCSAFLUSH, &self.raw)
+    }
+
+    /// The terminal size as (rows, cols, x pixels, y pixels), if `fd` is a terminal.
+    pub fn window_size(&self) -> Option<(u16, u16, u16, u16)> {
+        window_size(self.fd)
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let _ = termios::tcsetattr(self.fd, TCSADRAIN, &self.original);
+        if let Ok(mut saved) = SAVED_TERMINAL.lock() {
+            *saved = None;
+        }
+    }
+}
+
+fn restore_saved_terminal() {
+    if let Ok(mut saved) = SAVED_TERMINAL.lock() {
+        if let Some((fd, original)) = saved.take() {
+            let _ = termios::tcsetattr(fd, TCSADRAIN, &original);
+        }
+    }
+}
+
+/// Reads the window size of the terminal on `fd` with `TIOCGWINSZ`.
+pub fn window_size(fd: RawFd) -> Option<(u16, u16, u16, u16)> {
+    let mut size = libc::winsize { ws_row: 0, ws_col: 0, ws_xpixel: 0, ws_ypixel: 0 };
+    // SAFETY: TIOCGWINSZ only writes a `winsize` into the pointer it is given.
+    let result = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut size) };
+    (result == 0).then_some((size.ws_row, size.ws_col, size.ws_xpixel, size.ws_ypixel))
+}
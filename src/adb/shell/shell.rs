@@ -4,20 +4,64 @@ use std::io;
 use tokio::time::{Duration};
 use std::io::{Write};
 use std::os::fd::BorrowedFd;
-use std::os::unix::io::{AsRawFd};
-use termios::{Termios, TCSAFLUSH, ICANON, ECHO};
+use std::os::unix::io::{AsRawFd, RawFd};
 use nix::sys::select::{select, FdSet};
 use nix::sys::time::TimeVal;
 use nix::unistd::read;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use nix::libc;
+use tokio::io::{AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc;
 use crate::adb::client::Client;
-use crate::constants::{ADB_SHELL_COMMAND, FAIL, OKAY, SELECT_TIMEOUT_USEC, USER_EXIT_COMMAND};
+use crate::adb::client_builder::ClientBuilder;
+use crate::adb::io::io_limits::IoLimits;
+use crate::adb::shell::raw_terminal::RawTerminal;
+use crate::adb::shell::shell_escape::{escape_help, EscapeParser};
+use crate::adb::shell::shell_protocol::{encode_shell_packet, window_size_payload, ShellPacketDecoder};
+use crate::constants::{ADB_EXEC_COMMAND, ADB_SHELL_COMMAND, ADB_SHELL_V2_SERVICE, DEFAULT_ESCAPE_CHAR, FAIL, OKAY, SELECT_TIMEOUT_USEC, SHELL_PTY_ARG, SHELL_RAW_ARG, SHELL_V2_FEATURE, TERM_ENV};
 use crate::enums::device_transport::DeviceTransport;
+use crate::enums::escape_action::EscapeAction;
+use crate::enums::pty_mode::PtyMode;
+use crate::enums::shell_packet_id::ShellPacketId;
 
 const WAIT_FOR_FIRST_CONNECTION_DURATION_MS: u64 = 300;
+const STDIN_CHUNK_SIZE: usize = 1024;
+const STDIN_CHANNEL_CAPACITY: usize = 16;
+const DISCONNECTED_MESSAGE: &str = "\r\nConnection to device closed.\r\n";
+const LOCAL_PROMPT: &str = "\r\nadbr> ";
+const LOCAL_PUSH_COMMAND: &str = "push";
+const LOCAL_PULL_COMMAND: &str = "pull";
+const LOCAL_COMMAND_HELP: &str = "Commands:\n  push LOCAL... REMOTE\n  pull REMOTE... LOCAL";
 
 impl Client {
+    /// Runs `shell_command` and returns its output; an empty command starts an interactive shell.
     pub async fn adb_shell(&mut self, device_transport: DeviceTransport, shell_command: &str) -> Result<String, Box<dyn Error>> {
+        if shell_command.is_empty() {
+            self.adb_interactive_shell(device_transport, shell_command, PtyMode::Auto, Some(DEFAULT_ESCAPE_CHAR)).await?;
+            return Ok(String::new());
+        }
+
+        match self.start_shell_command(device_transport, shell_command).await? {
+            Ok(()) => self.read_write_and_collect_output(&mut tokio::io::sink()).await,
+            Err(fail_response) => Ok(fail_response),
+        }
+    }
+
+    /// Runs `shell_command` and copies its output to `out` as it arrives, for commands that run
+    /// until interrupted or produce more output than should be kept in memory.
+    pub async fn adb_shell_to_writer<W: AsyncWrite + Unpin>(&mut self, device_transport: DeviceTransport, shell_command: &str, out: &mut W) -> Result<(), Box<dyn Error>> {
+        if shell_command.is_empty() {
+            return Err("Shell command is required".into());
+        }
+
+        match self.start_shell_command(device_transport, shell_command).await? {
+            Ok(()) => self.read_and_write_data(out).await,
+            Err(fail_response) => Err(format!("Failed to run shell command: {}", fail_response).into()),
+        }
+    }
+
+    /// Starts a `shell:` service; the inner error is the server's FAIL message.
+    async fn start_shell_command(&mut self, device_transport: DeviceTransport, shell_command: &str) -> Result<Result<(), String>, Box<dyn Error>> {
         self.send_transport(device_transport.clone()).await?;
         let adb_shell_command = format!("{}{}", ADB_SHELL_COMMAND, shell_command);
         self.send_adb_command(&adb_shell_command).await?;
@@ -25,20 +69,8 @@ impl Client {
         tokio::time::sleep(Duration::from_millis(WAIT_FOR_FIRST_CONNECTION_DURATION_MS)).await;
 
         match self.read_first_four_bytes_response().await?.as_str() {
-            OKAY => {
-                if shell_command.is_empty() {
-                    self.interactive_shell().await?;
-                    Ok(String::new())
-                } else {
-                    let output = self.read_print_and_collect_output().await?;
-                    io::stdout().flush()?;
-                    Ok(output)
-                }
-            }
-            FAIL => {
-                let fail_response = self.read_adb_full_response().await?;
-                Ok(fail_response)
-            }
+            OKAY => Ok(Ok(())),
+            FAIL => Ok(Err(self.read_adb_full_response().await?)),
             _ => {
                 let error_message = self.read_adb_full_response().await?;
                 Err(format!(
@@ -50,67 +82,283 @@ impl Client {
         }
     }
 
-    pub async fn interactive_shell(&mut self) -> Result<(), Box<dyn Error>> {
-        let stdin = io::stdin();
-        let stdin_fd = stdin.as_raw_fd();
-        let mut oldtty_attrs = None;
+    pub async fn adb_shell_output(&mut self, device_transport: DeviceTransport, shell_command: &str) -> Result<String, Box<dyn Error>> {
+        if shell_command.is_empty() {
+            return Err("Shell command is required".into());
+        }
+
+        self.send_transport(device_transport).await?;
+        let adb_shell_command = format!("{}{}", ADB_SHELL_COMMAND, shell_command);
+        self.send_adb_command(&adb_shell_command).await?;
+
+        match self.read_first_four_bytes_response().await?.as_str() {
+            OKAY => self.read_all_data().await,
+            _ => {
+                let error_message = self.read_adb_full_response().await?;
+                Err(format!("Failed to send shell command : {}", error_message).into())
+            }
+        }
+    }
+
+    /// Runs `shell_command` (or a login shell when empty) with stdin, stdout and stderr connected to
+    /// the local terminal, returning the remote exit status when the device supports shell v2.
+    ///
+    /// With a PTY the local terminal is switched to raw mode for the session, so keys such as
+    /// Ctrl-C reach the remote process, and terminal resizes are forwarded to the device.
+    /// `escape_char` (usually `~`) starts ssh-style escapes in raw mode; `None` disables them.
+    pub async fn adb_interactive_shell(&mut self, device_transport: DeviceTransport, shell_command: &str, pty_mode: PtyMode, escape_char: Option<u8>) -> Result<Option<u8>, Box<dyn Error>> {
+        let shell_v2 = self.supports_shell_v2(device_transport.clone()).await?;
+
+        let stdin_is_tty = atty::is(atty::Stream::Stdin);
+        let use_pty = match pty_mode {
+            PtyMode::Auto => shell_command.is_empty() && stdin_is_tty,
+            PtyMode::Force => true,
+            PtyMode::Disable => false,
+        };
 
-        if atty::is(atty::Stream::Stdin) {
-            oldtty_attrs = Some(Termios::from_fd(stdin_fd)?);
-            let mut new_termios = oldtty_attrs.unwrap();
-            new_termios.c_lflag &= !(ICANON | ECHO);
-            termios::tcsetattr(stdin_fd, TCSAFLUSH, &new_termios)?;
+        let service = if shell_v2 {
+            shell_v2_service(shell_command, use_pty)
+        } else if use_pty || shell_command.is_empty() {
+            // The legacy shell service always allocates a PTY for an interactive login shell.
+            format!("{}{}", ADB_SHELL_COMMAND, shell_command)
+        } else {
+            format!("{}{}", ADB_EXEC_COMMAND, shell_command)
+        };
+
+        self.send_transport(device_transport.clone()).await?;
+        self.send_adb_command(&service).await?;
+        if self.read_first_four_bytes_response().await? != OKAY {
+            let error_message = self.read_adb_full_response().await?;
+            return Err(format!("Failed to start shell: {}", error_message).into());
         }
 
-        let mut is_alive = true;
-        let mut input_buffer = String::new();
+        self.interactive_shell(device_transport, shell_v2, use_pty && stdin_is_tty, escape_char).await
+    }
 
-        while is_alive {
-            let mut read_fds = FdSet::new();
-            read_fds.insert(unsafe { BorrowedFd::borrow_raw(self.adb_stream.as_raw_fd()) });
-            read_fds.insert(unsafe { BorrowedFd::borrow_raw(stdin_fd) });
+    /// Asks the server on a separate connection whether the device speaks the shell v2 protocol.
+    pub(crate) async fn supports_shell_v2(&self, device_transport: DeviceTransport) -> Result<bool, Box<dyn Error>> {
+        let mut features_client = self.new_connection().await?;
+        let shell_v2 = features_client.adb_features(device_transport).await
+            .is_ok_and(|features| features.iter().any(|feature| feature == SHELL_V2_FEATURE));
+        features_client.close().await;
+        Ok(shell_v2)
+    }
 
-            match select(None, &mut read_fds, None, None, Some(&mut TimeVal::new(0, SELECT_TIMEOUT_USEC))) {
-                Ok(ready) if ready > 0 => {
-                    if read_fds.contains(unsafe { BorrowedFd::borrow_raw(self.adb_stream.as_raw_fd()) }) {
-                        let mut buf = [0; 8000];
-                        match self.adb_stream.read(&mut buf).await {
-                            Ok(0) => is_alive = false,
-                            Ok(n) => {
-                                let decoded_out = String::from_utf8_lossy(&buf[..n]);
-                                print!("{}", decoded_out);
-                                io::stdout().flush()?;
+    /// Relays an already opened shell service to the local terminal until either side closes it.
+    ///
+    /// `shell_v2` selects packet framing; `raw_terminal` switches the local terminal to raw mode,
+    /// forwards window size changes and enables escapes. `device_transport` is used by the `~>`
+    /// prompt to push and pull over separate connections.
+    pub async fn interactive_shell(&mut self, device_transport: DeviceTransport, shell_v2: bool, raw_terminal: bool, escape_char: Option<u8>) -> Result<Option<u8>, Box<dyn Error>> {
+        let stdin_fd = io::stdin().as_raw_fd();
+        let terminal = if raw_terminal { Some(RawTerminal::enter(stdin_fd)?) } else { None };
+        let escape_char = escape_char.filter(|_| raw_terminal);
+        let mut escapes = EscapeParser::new(escape_char);
+        let (config, limits) = (self.config().clone(), self.limits.clone());
+
+        let (stdin_sender, mut stdin_receiver) = mpsc::channel(STDIN_CHANNEL_CAPACITY);
+        spawn_stdin_reader(stdin_fd, stdin_sender);
+
+        let mut window_changes = signal(SignalKind::window_change())?;
+        let mut terminate = signal(SignalKind::terminate())?;
+        let mut hangup = signal(SignalKind::hangup())?;
+
+        let (mut reader, mut writer) = tokio::io::split(&mut self.adb_stream);
+        let send_window_size = |terminal: &Option<RawTerminal>| {
+            terminal.as_ref()
+                .and_then(RawTerminal::window_size)
+                .filter(|_| shell_v2)
+                .map(|(rows, cols, x_pixels, y_pixels)| encode_shell_packet(ShellPacketId::WindowSizeChange, &window_size_payload(rows, cols, x_pixels, y_pixels)))
+        };
+        if let Some(packet) = send_window_size(&terminal) {
+            writer.write_all(&packet).await?;
+        }
+
+        let mut decoder = ShellPacketDecoder::new();
+        let mut buffer = [0u8; 8192];
+        let mut stdin_open = true;
+        let mut exit_status = None;
+
+        'session: loop {
+            tokio::select! {
+                read = reader.read(&mut buffer) => {
+                    let n = match read {
+                        Ok(0) | Err(_) => break,
+                        Ok(n) => n,
+                    };
+                    if !shell_v2 {
+                        write_output(&mut io::stdout(), &buffer[..n])?;
+                        continue;
+                    }
+                    decoder.feed(&buffer[..n]);
+                    while let Some((id, data)) = decoder.next_packet() {
+                        match id {
+                            ShellPacketId::Stdout => write_output(&mut io::stdout(), &data)?,
+                            ShellPacketId::Stderr => write_output(&mut io::stderr(), &data)?,
+                            ShellPacketId::Exit => {
+                                exit_status = data.first().copied();
+                                break 'session;
                             }
-                            Err(_) => is_alive = false,
+                            _ => {}
                         }
                     }
-
-                    if read_fds.contains(unsafe { BorrowedFd::borrow_raw(stdin_fd) }) && is_alive {
-                        let mut char = [0; 1];
-                        match read(stdin_fd, &mut char) {
-                            Ok(0) => is_alive = false,
-                            Ok(_) => {
-                                self.adb_stream.write_all(&char).await?;
-                                input_buffer.push(char[0] as char);
-
-                                if input_buffer.ends_with(USER_EXIT_COMMAND) {
-                                    print!("\n");
-                                    is_alive = false;
+                }
+                input = stdin_receiver.recv(), if stdin_open => match input {
+                    Some(bytes) => {
+                        for action in escapes.feed(&bytes) {
+                            match action {
+                                EscapeAction::Send(bytes) => send_stdin(&mut writer, shell_v2, &bytes).await?,
+                                EscapeAction::Disconnect => {
+                                    write_output(&mut io::stderr(), DISCONNECTED_MESSAGE.as_bytes())?;
+                                    break 'session;
+                                }
+                                EscapeAction::Help => write_output(&mut io::stderr(), escape_help(escape_char.unwrap_or(DEFAULT_ESCAPE_CHAR)).as_bytes())?,
+                                EscapeAction::Suspend => {
+                                    if let Some(terminal) = &terminal {
+                                        terminal.suspend()?;
+                                    }
+                                    // SAFETY: raising a signal on the current process has no memory effects.
+                                    unsafe { libc::raise(libc::SIGTSTP) };
+                                    if let Some(terminal) = &terminal {
+                                        terminal.resume()?;
+                                    }
+                                    // The window may have been resized while stopped.
+                                    if let Some(packet) = send_window_size(&terminal) {
+                                        writer.write_all(&packet).await?;
+                                    }
+                                }
+                                EscapeAction::LocalCommand => {
+                                    if let Some(terminal) = &terminal {
+                                        terminal.suspend()?;
+                                    }
+                                    let line = read_local_command(&mut stdin_receiver).await;
+                                    if let Some(line) = &line {
+                                        let report = run_local_command(line, &config, &limits, device_transport.clone()).await;
+                                        write_output(&mut io::stderr(), report.as_bytes())?;
+                                    }
+                                    if let Some(terminal) = &terminal {
+                                        terminal.resume()?;
+                                    }
+                                    if line.is_none() {
+                                        stdin_open = false;
+                                    }
                                 }
                             }
-                            Err(_) => is_alive = false,
                         }
                     }
+                    None => {
+                        stdin_open = false;
+                        if shell_v2 {
+                            writer.write_all(&encode_shell_packet(ShellPacketId::CloseStdin, &[])).await?;
+                        } else if !raw_terminal {
+                            writer.shutdown().await?;
+                        }
+                    }
+                },
+                _ = window_changes.recv() => {
+                    if let Some(packet) = send_window_size(&terminal) {
+                        writer.write_all(&packet).await?;
+                    }
                 }
-                Ok(_) => {}
-                Err(e) => return Err(Box::new(e)),
+                _ = terminate.recv() => break,
+                _ = hangup.recv() => break,
             }
         }
 
-        if let Some(attrs) = oldtty_attrs {
-            termios::tcsetattr(stdin_fd, TCSAFLUSH, &attrs)?;
+        drop(terminal);
+        Ok(exit_status)
+    }
+}
+
+async fn send_stdin<W: AsyncWrite + Unpin>(writer: &mut W, shell_v2: bool, data: &[u8]) -> io::Result<()> {
+    if shell_v2 {
+        writer.write_all(&encode_shell_packet(ShellPacketId::Stdin, data)).await
+    } else {
+        writer.write_all(data).await
+    }
+}
+
+/// Shows the `~>` prompt and reads one line in cooked mode; `None` when stdin closed.
+async fn read_local_command(stdin_receiver: &mut mpsc::Receiver<Vec<u8>>) -> Option<String> {
+    let _ = write_output(&mut io::stderr(), LOCAL_PROMPT.as_bytes());
+    let mut line = Vec::new();
+    while !line.contains(&b'\n') {
+        line.extend(stdin_receiver.recv().await?);
+    }
+    Some(String::from_utf8_lossy(&line).trim().to_string())
+}
+
+/// The shell v2 service running `command`; a PTY also gets the local `TERM`.
+pub(crate) fn shell_v2_service(command: &str, use_pty: bool) -> String {
+    let mut args = vec![ADB_SHELL_V2_SERVICE.to_string()];
+    if use_pty {
+        if let Ok(term) = std::env::var(TERM_ENV) {
+            args.push(format!("{}={}", TERM_ENV, term));
         }
+    }
+    args.push(if use_pty { SHELL_PTY_ARG } else { SHELL_RAW_ARG }.to_string());
+    format!("{}:{}", args.join(","), command)
+}
 
-        Ok(())
+/// Runs a `~>` prompt command (`push LOCAL... REMOTE` or `pull REMOTE... LOCAL`) on its own connection
+/// and returns what to show the user: one line per path, or the usage or error.
+async fn run_local_command(line: &str, config: &ClientBuilder, limits: &IoLimits, device_transport: DeviceTransport) -> String {
+    let mut words: Vec<String> = line.split_whitespace().map(String::from).collect();
+    if words.is_empty() {
+        return String::new();
+    }
+    let command = words.remove(0);
+    if (command != LOCAL_PUSH_COMMAND && command != LOCAL_PULL_COMMAND) || words.len() < 2 {
+        return format!("{}\n", LOCAL_COMMAND_HELP);
     }
-}
\ No newline at end of file
+    let destination = words.pop().unwrap_or_default();
+
+    let mut client = match Client::connect_with_limits(config.clone(), limits.clone()).await {
+        Ok(client) => client,
+        Err(err) => return format!("{}\n", err),
+    };
+    let results: Result<Vec<(String, String)>, Box<dyn Error>> = if command == LOCAL_PUSH_COMMAND {
+        client.adb_push(device_transport, &words, &destination, false).await.map(|results| {
+            results.into_iter().map(|(path, result)| (path, result.map_or_else(|err| err.to_string(), |result| result.to_string()))).collect()
+        })
+    } else {
+        client.adb_pull(device_transport, &words, &destination, false).await.map(|results| {
+            results.into_iter().map(|(path, result)| (path, result.map_or_else(|err| err.to_string(), |result| result.to_string()))).collect()
+        })
+    };
+    client.close().await;
+    match results {
+        Ok(results) => results.into_iter().map(|(path, message)| format!("{}: {}\n", path, message)).collect(),
+        Err(err) => format!("{}\n", err),
+    }
+}
+
+fn write_output(output: &mut impl Write, data: &[u8]) -> io::Result<()> {
+    output.write_all(data)?;
+    output.flush()
+}
+
+/// Reads stdin on a plain thread and forwards it to `sender` until stdin closes or the receiver
+/// is dropped. Polling with a timeout lets the thread notice the end of the session instead of
+/// staying blocked in `read` and swallowing the next keystroke.
+fn spawn_stdin_reader(stdin_fd: RawFd, sender: mpsc::Sender<Vec<u8>>) {
+    std::thread::spawn(move || {
+        let mut buffer = [0u8; STDIN_CHUNK_SIZE];
+        while !sender.is_closed() {
+            let mut read_fds = FdSet::new();
+            read_fds.insert(unsafe { BorrowedFd::borrow_raw(stdin_fd) });
+            match select(None, &mut read_fds, None, None, Some(&mut TimeVal::new(0, SELECT_TIMEOUT_USEC))) {
+                Ok(ready) if ready > 0 => match read(stdin_fd, &mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => {
+                        if sender.blocking_send(buffer[..n].to_vec()).is_err() {
+                            break;
+                        }
+                    }
+                },
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+    });
+}
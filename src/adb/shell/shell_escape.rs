@@ -0,0 +1,110 @@
+use crate::enums::escape_action::EscapeAction;
+
+const ESCAPE_NONE: &str = "none";
+const DISCONNECT_KEY: u8 = b'.';
+const SUSPEND_KEY: u8 = 0x1a; // Ctrl-Z
+const HELP_KEY: u8 = b'?';
+const LOCAL_COMMAND_KEY: u8 = b'>';
+
+/// Parses the argument of `shell -e`: a single character, `^X` for a control character, or
+/// `none` to disable escapes.
+pub fn parse_escape_char(value: &str) -> Result<Option<u8>, String> {
+    if value == ESCAPE_NONE {
+        return Ok(None);
+    }
+    match value.as_bytes() {
+        [c] if c.is_ascii() => Ok(Some(*c)),
+        [b'^', c] if c.is_ascii_alphabetic() || b"@[\\]^_".contains(c) => Ok(Some(c.to_ascii_uppercase() & 0x1f)),
+        _ => Err(format!("Invalid escape character: {} (expected a single character, ^X or {})", value, ESCAPE_NONE)),
+    }
+}
+
+/// Help listing for `~?`, with `\r\n` line endings because the terminal is in raw mode.
+pub fn escape_help(escape_char: u8) -> String {
+    let escape = display_char(escape_char);
+    [
+        "Supported escape sequences:".to_string(),
+        format!(" {}.  - disconnect", escape),
+        format!(" {}^Z - suspend adbr", escape),
+        format!(" {}>  - local prompt (push/pull)", escape),
+        format!(" {}?  - this message", escape),
+        format!(" {}{} - send the escape character by typing it twice", escape, escape),
+        "(Note that escapes are only recognized immediately after newline.)".to_string(),
+    ].iter().map(|line| format!("{}\r\n", line)).collect()
+}
+
+fn display_char(c: u8) -> String {
+    if c < 0x20 {
+        format!("^{}", (c + b'@') as char)
+    } else {
+        (c as char).to_string()
+    }
+}
+
+/// ssh-style escape recognition on the keystrokes sent to an interactive shell.
+///
+/// The escape character only counts right after a newline (or at the start of the session), so
+/// it can still be typed normally inside a line.
+pub struct EscapeParser {
+    escape_char: Option<u8>,
+    at_line_start: bool,
+    pending_escape: bool,
+}
+
+impl EscapeParser {
+    pub fn new(escape_char: Option<u8>) -> Self {
+        EscapeParser { escape_char, at_line_start: true, pending_escape: false }
+    }
+
+    pub fn feed(&mut self, input: &[u8]) -> Vec<EscapeAction> {
+        let Some(escape_char) = self.escape_char else {
+            return vec![EscapeAction::Send(input.to_vec())];
+        };
+
+        let mut actions = Vec::new();
+        let mut send = Vec::new();
+        for &byte in input {
+            if self.pending_escape {
+                self.pending_escape = false;
+                let action = match byte {
+                    DISCONNECT_KEY => Some(EscapeAction::Disconnect),
+                    SUSPEND_KEY => Some(EscapeAction::Suspend),
+                    HELP_KEY => Some(EscapeAction::Help),
+                    LOCAL_COMMAND_KEY => Some(EscapeAction::LocalCommand),
+                    _ => None,
+                };
+                match action {
+                    Some(action) => {
+                        if !send.is_empty() {
+                            actions.push(EscapeAction::Send(std::mem::take(&mut send)));
+                        }
+                        actions.push(action);
+                        self.at_line_start = true;
+                    }
+                    None if byte == escape_char => {
+                        send.push(escape_char);
+                        self.at_line_start = false;
+                    }
+                    None => {
+                        send.extend_from_slice(&[escape_char, byte]);
+                        self.at_line_start = is_newline(byte);
+                    }
+                }
+            } else if self.at_line_start && byte == escape_char {
+                self.pending_escape = true;
+            } else {
+                send.push(byte);
+                self.at_line_start = is_newline(byte);
+            }
+        }
+
+        if !send.is_empty() {
+            actions.push(EscapeAction::Send(send));
+        }
+        actions
+    }
+}
+
+fn is_newline(byte: u8) -> bool {
+    byte == b'\r' || byte == b'\n'
+}
@@ -0,0 +1,57 @@
+use crate::enums::shell_packet_id::ShellPacketId;
+
+const PACKET_HEADER_SIZE: usize = 5;
+
+/// Frames `data` as a shell v2 packet: a one byte id, a little-endian u32 length and the payload.
+pub fn encode_shell_packet(id: ShellPacketId, data: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(PACKET_HEADER_SIZE + data.len());
+    packet.push(id.as_u8());
+    packet.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    packet.extend_from_slice(data);
+    packet
+}
+
+/// Payload of a window size change packet, "ROWSxCOLS,XPIXELSxYPIXELS" with a trailing NUL as adbd expects.
+pub fn window_size_payload(rows: u16, cols: u16, x_pixels: u16, y_pixels: u16) -> Vec<u8> {
+    let mut payload = format!("{}x{},{}x{}", rows, cols, x_pixels, y_pixels).into_bytes();
+    payload.push(0);
+    payload
+}
+
+/// Reassembles shell v2 packets from arbitrarily split reads.
+///
+/// Reads can be fed as they arrive, which keeps the caller's read cancel-safe inside `select!`.
+#[derive(Default)]
+pub struct ShellPacketDecoder {
+    buffer: Vec<u8>,
+}
+
+impl ShellPacketDecoder {
+    pub fn new() -> Self {
+        ShellPacketDecoder::default()
+    }
+
+    pub fn feed(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete packet, skipping packets with ids this client does not know.
+    pub fn next_packet(&mut self) -> Option<(ShellPacketId, Vec<u8>)> {
+        loop {
+            if self.buffer.len() < PACKET_HEADER_SIZE {
+                return None;
+            }
+            let length = u32::from_le_bytes([self.buffer[1], self.buffer[2], self.buffer[3], self.buffer[4]]) as usize;
+            if self.buffer.len() < PACKET_HEADER_SIZE + length {
+                return None;
+            }
+
+            let id = self.buffer[0];
+            let data = self.buffer[PACKET_HEADER_SIZE..PACKET_HEADER_SIZE + length].to_vec();
+            self.buffer.drain(..PACKET_HEADER_SIZE + length);
+            if let Some(id) = ShellPacketId::from_u8(id) {
+                return Some((id, data));
+            }
+        }
+    }
+}
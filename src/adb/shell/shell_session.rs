@@ -0,0 +1,159 @@
+use std::error::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio::task::JoinHandle;
+use crate::adb::client::Client;
+use crate::adb::shell::shell_protocol::{encode_shell_packet, ShellPacketDecoder};
+use crate::adb::shell::shell::shell_v2_service;
+use crate::constants::{ADB_EXEC_COMMAND, ADB_SHELL_COMMAND, OKAY};
+use crate::enums::device_transport::DeviceTransport;
+use crate::enums::shell_packet_id::ShellPacketId;
+
+const PIPE_CAPACITY: usize = 64 * 1024;
+const READ_BUFFER_SIZE: usize = 8192;
+
+/// Writing end of a session's stdin; shut it down or drop it to send EOF.
+pub type ShellStdin = WriteHalf<DuplexStream>;
+/// Reading end of a session's stdout or stderr.
+pub type ShellOutput = ReadHalf<DuplexStream>;
+
+/// A remote command started with [`Client::open_shell`], modelled on `tokio::process::Child`.
+///
+/// The command runs on its own connection and is relayed by a background task, so the process's
+/// own stdio is never touched. Take the pipes out of the `Option`s to use them; for
+/// line-by-line output wrap stdout in `tokio::io::BufReader` and call `lines()`.
+///
+/// Unread output is buffered up to a limit, after which the relay waits; drop a pipe you do not
+/// need and its output is discarded instead.
+pub struct ShellSession {
+    pub stdin: Option<ShellStdin>,
+    pub stdout: Option<ShellOutput>,
+    /// Only separate from stdout when the device supports shell v2; otherwise it stays empty.
+    pub stderr: Option<ShellOutput>,
+    relay: Option<JoinHandle<Result<Option<u8>, String>>>,
+    exit_status: Option<Option<u8>>,
+}
+
+impl ShellSession {
+    /// Waits for the remote command to finish, returning its exit status when the device supports
+    /// shell v2. Closes stdin first so commands reading it can finish.
+    pub async fn wait(&mut self) -> Result<Option<u8>, Box<dyn Error>> {
+        if let Some(mut stdin) = self.stdin.take() {
+            let _ = stdin.shutdown().await;
+        }
+        if let Some(status) = self.exit_status {
+            return Ok(status);
+        }
+        let relay = self.relay.take().ok_or("Shell session was killed")?;
+        let status = relay.await.map_err(|err| err.to_string())??;
+        self.exit_status = Some(status);
+        Ok(status)
+    }
+
+    /// Closes the connection, which makes adbd hang up on the remote command.
+    pub fn kill(&mut self) {
+        if let Some(relay) = self.relay.take() {
+            relay.abort();
+        }
+    }
+}
+
+impl Client {
+    /// Starts `command` (or a shell reading commands from stdin when empty) on a new connection and
+    /// returns its pipes.
+    ///
+    /// With `pty` the command gets a terminal, for programs such as `su` or `sqlite3` that only
+    /// prompt on one; the terminal echoes input and merges stderr into stdout.
+    pub async fn open_shell(&self, device_transport: DeviceTransport, command: &str, pty: bool) -> Result<ShellSession, Box<dyn Error>> {
+        let shell_v2 = self.supports_shell_v2(device_transport.clone()).await?;
+        let service = match (shell_v2, pty) {
+            (true, _) => shell_v2_service(command, pty),
+            // The legacy shell service always allocates a PTY.
+            (false, true) => format!("{}{}", ADB_SHELL_COMMAND, command),
+            (false, false) => format!("{}{}", ADB_EXEC_COMMAND, command),
+        };
+
+        let mut client = self.new_connection().await?;
+        client.send_transport(device_transport).await?;
+        client.send_adb_command(&service).await?;
+        if client.read_first_four_bytes_response().await? != OKAY {
+            let error_message = client.read_adb_full_response().await?;
+            return Err(format!("Failed to start shell: {}", error_message).into());
+        }
+
+        let (user_stdin, relay_stdin) = tokio::io::duplex(PIPE_CAPACITY);
+        let (user_stdout, relay_stdout) = tokio::io::duplex(PIPE_CAPACITY);
+        let (user_stderr, relay_stderr) = tokio::io::duplex(PIPE_CAPACITY);
+        let relay = tokio::spawn(relay_shell(client.adb_stream, shell_v2, relay_stdin, relay_stdout, relay_stderr));
+
+        Ok(ShellSession {
+            stdin: Some(tokio::io::split(user_stdin).1),
+            stdout: Some(tokio::io::split(user_stdout).0),
+            stderr: shell_v2.then(|| tokio::io::split(user_stderr).0),
+            relay: Some(relay),
+            exit_status: None,
+        })
+    }
+}
+
+/// Copies between the connection and the session's pipes until the remote side closes.
+async fn relay_shell<S, I>(stream: S, shell_v2: bool, mut stdin: I, mut stdout: DuplexStream, mut stderr: DuplexStream) -> Result<Option<u8>, String>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+    I: AsyncRead + Unpin,
+{
+    let (mut reader, mut writer) = tokio::io::split(stream);
+    let mut decoder = ShellPacketDecoder::new();
+    let mut buffer = [0u8; READ_BUFFER_SIZE];
+    let mut input = [0u8; READ_BUFFER_SIZE];
+    let mut stdin_open = true;
+    let (mut stdout_open, mut stderr_open) = (true, true);
+    let mut exit_status = None;
+
+    'relay: loop {
+        tokio::select! {
+            read = reader.read(&mut buffer) => {
+                let n = read.map_err(|err| err.to_string())?;
+                if n == 0 {
+                    break;
+                }
+                if !shell_v2 {
+                    stdout_open = stdout_open && stdout.write_all(&buffer[..n]).await.is_ok();
+                    continue;
+                }
+                decoder.feed(&buffer[..n]);
+                while let Some((id, data)) = decoder.next_packet() {
+                    match id {
+                        ShellPacketId::Stdout => stdout_open = stdout_open && stdout.write_all(&data).await.is_ok(),
+                        ShellPacketId::Stderr => stderr_open = stderr_open && stderr.write_all(&data).await.is_ok(),
+                        ShellPacketId::Exit => {
+                            exit_status = data.first().copied();
+                            break 'relay;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            read = stdin.read(&mut input), if stdin_open => {
+                match read {
+                    Ok(n) if n > 0 => {
+                        let data = if shell_v2 { encode_shell_packet(ShellPacketId::Stdin, &input[..n]) } else { input[..n].to_vec() };
+                        writer.write_all(&data).await.map_err(|err| err.to_string())?;
+                    }
+                    _ => {
+                        stdin_open = false;
+                        let closed = if shell_v2 {
+                            writer.write_all(&encode_shell_packet(ShellPacketId::CloseStdin, &[])).await
+                        } else {
+                            writer.shutdown().await
+                        };
+                        closed.map_err(|err| err.to_string())?;
+                    }
+                }
+            }
+        }
+    }
+
+    let _ = stdout.shutdown().await;
+    let _ = stderr.shutdown().await;
+    Ok(exit_status)
+}
@@ -0,0 +1,50 @@
+use std::fmt;
+use std::io;
+use async_trait::async_trait;
+use tokio::io::DuplexStream;
+use tokio::sync::mpsc;
+use crate::adb::transport::transport::{AdbStream, Transport};
+
+const MEMORY_PIPE_CAPACITY: usize = 64 * 1024;
+
+/// In-process transport, e.g. to run a client against a server in the same test: every connect
+/// creates a pipe and hands the other end to the paired [`MemoryListener`].
+#[derive(Debug, Clone)]
+pub struct MemoryTransport {
+    connections: mpsc::UnboundedSender<DuplexStream>,
+}
+
+/// Server side of a [`MemoryTransport`], used like a `TcpListener`.
+#[derive(Debug)]
+pub struct MemoryListener {
+    connections: mpsc::UnboundedReceiver<DuplexStream>,
+}
+
+impl MemoryTransport {
+    pub fn pair() -> (MemoryTransport, MemoryListener) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (MemoryTransport { connections: sender }, MemoryListener { connections: receiver })
+    }
+}
+
+impl MemoryListener {
+    /// Waits for the next connection; `None` once every transport is dropped.
+    pub async fn accept(&mut self) -> Option<DuplexStream> {
+        self.connections.recv().await
+    }
+}
+
+#[async_trait]
+impl Transport for MemoryTransport {
+    async fn connect(&self) -> io::Result<Box<dyn AdbStream>> {
+        let (client, server) = tokio::io::duplex(MEMORY_PIPE_CAPACITY);
+        self.connections.send(server).map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, "the memory listener is closed"))?;
+        Ok(Box::new(client))
+    }
+}
+
+impl fmt::Display for MemoryTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "memory")
+    }
+}
@@ -0,0 +1,4 @@
+pub mod transport;
+pub mod tcp_transport;
+pub mod unix_transport;
+pub mod memory_transport;
@@ -0,0 +1,30 @@
+use std::fmt;
+use std::io;
+use async_trait::async_trait;
+use tokio::net::TcpStream;
+use crate::adb::transport::transport::{AdbStream, Transport};
+
+/// Reaches the server over TCP, e.g. `127.0.0.1:5037`.
+#[derive(Debug, Clone)]
+pub struct TcpTransport {
+    address: String,
+}
+
+impl TcpTransport {
+    pub fn new(host: &str, port: u16) -> Self {
+        TcpTransport { address: format!("{}:{}", host, port) }
+    }
+}
+
+#[async_trait]
+impl Transport for TcpTransport {
+    async fn connect(&self) -> io::Result<Box<dyn AdbStream>> {
+        Ok(Box::new(TcpStream::connect(&self.address).await?))
+    }
+}
+
+impl fmt::Display for TcpTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.address)
+    }
+}
@@ -0,0 +1,19 @@
+use std::fmt::{Debug, Display};
+use std::io;
+use async_trait::async_trait;
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// A connection to the ADB server: any bidirectional byte stream.
+pub trait AdbStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> AdbStream for T {}
+
+/// Opens connections to an ADB server.
+///
+/// The server handles one service per connection, so a [`Client`](crate::Client) calls
+/// [`Transport::connect`] again to reconnect. Closing is `AsyncWriteExt::shutdown` on the stream;
+/// the client buffers reads itself, so streams need no peek support.
+#[async_trait]
+pub trait Transport: Debug + Display + Send + Sync {
+    async fn connect(&self) -> io::Result<Box<dyn AdbStream>>;
+}
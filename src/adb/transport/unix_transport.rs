@@ -0,0 +1,55 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use async_trait::async_trait;
+use tokio::net::UnixStream;
+use crate::adb::transport::transport::{AdbStream, Transport};
+use crate::constants::{LOCAL_ABSTRACT_PREFIX, LOCAL_FILESYSTEM_PREFIX};
+
+/// Reaches the server on a unix-domain socket, as selected by
+/// `ADB_SERVER_SOCKET=localfilesystem:<path>` or `localabstract:<name>`.
+#[derive(Debug, Clone)]
+pub enum UnixTransport {
+    Path(PathBuf),
+    /// A name in Linux's abstract socket namespace.
+    Abstract(String),
+}
+
+impl UnixTransport {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        UnixTransport::Path(path.into())
+    }
+}
+
+#[async_trait]
+impl Transport for UnixTransport {
+    async fn connect(&self) -> io::Result<Box<dyn AdbStream>> {
+        match self {
+            UnixTransport::Path(path) => Ok(Box::new(UnixStream::connect(path).await?)),
+            UnixTransport::Abstract(name) => connect_abstract(name),
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn connect_abstract(name: &str) -> io::Result<Box<dyn AdbStream>> {
+    use std::os::linux::net::SocketAddrExt;
+    let address = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+    let stream = std::os::unix::net::UnixStream::connect_addr(&address)?;
+    stream.set_nonblocking(true)?;
+    Ok(Box::new(UnixStream::from_std(stream)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn connect_abstract(_name: &str) -> io::Result<Box<dyn AdbStream>> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "abstract unix sockets are only available on Linux"))
+}
+
+impl fmt::Display for UnixTransport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UnixTransport::Path(path) => write!(f, "{}{}", LOCAL_FILESYSTEM_PREFIX, path.display()),
+            UnixTransport::Abstract(name) => write!(f, "{}{}", LOCAL_ABSTRACT_PREFIX, name),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use openssl::base64::{decode_block, encode_block};
+use openssl::bn::{BigNum, BigNumContext, BigNumRef};
+use openssl::pkey::{PKey, Private, Public};
+use openssl::rsa::{Padding, Rsa};
+use crate::constants::ADB_AUTH_TOKEN_SIZE;
+
+/// DER prefix of a SHA-1 DigestInfo: adb signs the token as if it were a SHA-1 digest.
+const SHA1_DIGEST_INFO: [u8; 15] = [0x30, 0x21, 0x30, 0x09, 0x06, 0x05, 0x2b, 0x0e, 0x03, 0x02, 0x1a, 0x05, 0x00, 0x04, 0x14];
+const KEY_BITS: u32 = 2048;
+const MODULUS_SIZE: usize = KEY_BITS as usize / 8;
+/// Modulus size in words, n0inv, modulus, R^2 mod modulus and exponent, all little-endian.
+const ANDROID_PUBKEY_SIZE: usize = 4 + 4 + MODULUS_SIZE + MODULUS_SIZE + 4;
+
+/// A private key the host signs AUTH tokens with, e.g. `~/.android/adbkey` from `adb keygen`.
+#[derive(Debug, Clone)]
+pub struct AdbKey {
+    rsa: Rsa<Private>,
+    public_key: AdbPublicKey,
+}
+
+/// The public half of an [`AdbKey`] in the format adbd keeps in `adb_keys`: base64 of Android's
+/// `RSAPublicKey` struct.
+#[derive(Debug, Clone)]
+pub struct AdbPublicKey {
+    rsa: Rsa<Public>,
+    encoded: String,
+}
+
+impl AdbKey {
+    pub fn generate() -> Result<Self, Box<dyn Error>> {
+        Self::from_rsa(Rsa::generate(KEY_BITS)?)
+    }
+
+    /// Reads a PEM private key, PKCS#8 as `adbr keygen` writes or PKCS#1 as older adb versions do.
+    pub fn from_pem(pem: &[u8]) -> Result<Self, Box<dyn Error>> {
+        Self::from_rsa(PKey::private_key_from_pem(pem)?.rsa()?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let pem = std::fs::read(path).map_err(|err| format!("Cannot read key {}: {}", path.display(), err))?;
+        Self::from_pem(&pem).map_err(|err| format!("Cannot load key {}: {}", path.display(), err).into())
+    }
+
+    fn from_rsa(rsa: Rsa<Private>) -> Result<Self, Box<dyn Error>> {
+        let public_key = AdbPublicKey::from_components(rsa.n(), rsa.e())?;
+        Ok(AdbKey { rsa, public_key })
+    }
+
+    /// The key as PKCS#8 PEM.
+    pub fn to_pem(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(PKey::from_rsa(self.rsa.clone())?.private_key_to_pem_pkcs8()?)
+    }
+
+    pub fn public_key(&self) -> &AdbPublicKey {
+        &self.public_key
+    }
+
+    /// Answers an AUTH token: an RSA PKCS#1 v1.5 signature over the token as a SHA-1 digest.
+    pub fn sign(&self, token: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+        if token.len() != ADB_AUTH_TOKEN_SIZE {
+            return Err(format!("AUTH token of {} bytes, expected {}", token.len(), ADB_AUTH_TOKEN_SIZE).into());
+        }
+        let mut signature = vec![0u8; self.rsa.size() as usize];
+        let length = self.rsa.private_encrypt(&[&SHA1_DIGEST_INFO[..], token].concat(), &mut signature, Padding::PKCS1)?;
+        signature.truncate(length);
+        Ok(signature)
+    }
+}
+
+impl AdbPublicKey {
+    /// Reads a line of `adb_keys` or `adbkey.pub`: the base64 key, optionally followed by a
+    /// comment such as `user@host`.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let encoded = text.trim_end_matches('\0').split_whitespace().next().ok_or("Empty public key")?;
+        let bytes = decode_block(encoded).map_err(|_| "Public key is not base64")?;
+        if bytes.len() < ANDROID_PUBKEY_SIZE {
+            return Err(format!("Public key of {} bytes, expected {}", bytes.len(), ANDROID_PUBKEY_SIZE).into());
+        }
+        let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().expect("4 bytes"));
+        if word(0) as usize != MODULUS_SIZE / 4 {
+            return Err(format!("Public key modulus of {} words, expected {}", word(0), MODULUS_SIZE / 4).into());
+        }
+        let mut modulus = bytes[8..8 + MODULUS_SIZE].to_vec();
+        modulus.reverse();
+        let (n, e) = (BigNum::from_slice(&modulus)?, BigNum::from_u32(word(ANDROID_PUBKEY_SIZE - 4))?);
+        Self::from_components(&n, &e)
+    }
+
+    fn from_components(n: &BigNumRef, e: &BigNumRef) -> Result<Self, Box<dyn Error>> {
+        if n.num_bits() != KEY_BITS as i32 {
+            return Err(format!("adb keys are {}-bit RSA, not {}-bit", KEY_BITS, n.num_bits()).into());
+        }
+        let exponent: u32 = e.to_dec_str()?.parse().map_err(|_| "RSA exponent does not fit in 32 bits")?;
+
+        let mut modulus = n.to_vec_padded(MODULUS_SIZE as i32)?;
+        modulus.reverse();
+        // Montgomery constants adbd's verifier expects: -1 / n mod 2^32 and (2^2048)^2 mod n.
+        let n0 = u32::from_le_bytes(modulus[..4].try_into().expect("4 bytes"));
+        let mut inverse = n0;
+        for _ in 0..5 {
+            inverse = inverse.wrapping_mul(2u32.wrapping_sub(n0.wrapping_mul(inverse)));
+        }
+        let mut r = BigNum::new()?;
+        r.set_bit(2 * KEY_BITS as i32)?;
+        let mut rr = BigNum::new()?;
+        let mut context = BigNumContext::new()?;
+        rr.nnmod(&r, n, &mut context)?;
+        let mut rr = rr.to_vec_padded(MODULUS_SIZE as i32)?;
+        rr.reverse();
+
+        let mut bytes = Vec::with_capacity(ANDROID_PUBKEY_SIZE);
+        bytes.extend_from_slice(&(MODULUS_SIZE as u32 / 4).to_le_bytes());
+        bytes.extend_from_slice(&inverse.wrapping_neg().to_le_bytes());
+        bytes.extend_from_slice(&modulus);
+        bytes.extend_from_slice(&rr);
+        bytes.extend_from_slice(&exponent.to_le_bytes());
+        let rsa = Rsa::from_public_components(n.to_owned()?, e.to_owned()?)?;
+        Ok(AdbPublicKey { rsa, encoded: encode_block(&bytes) })
+    }
+
+    /// Whether `signature` is this key's answer to `token`, see [`AdbKey::sign`].
+    pub fn verify(&self, token: &[u8], signature: &[u8]) -> bool {
+        let mut decrypted = vec![0u8; self.rsa.size() as usize];
+        match self.rsa.public_decrypt(signature, &mut decrypted, Padding::PKCS1) {
+            Ok(length) => decrypted[..length] == [&SHA1_DIGEST_INFO[..], token].concat(),
+            Err(_) => false,
+        }
+    }
+}
+
+impl PartialEq for AdbPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.encoded == other.encoded
+    }
+}
+
+impl Eq for AdbPublicKey {}
+
+impl fmt::Display for AdbPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.encoded)
+    }
+}
@@ -0,0 +1,140 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use dirs::home_dir;
+use crate::adb::security::security::{ADB_FOLDER_NAME, ADB_KEY_FILENAME};
+use crate::adb::wire::adb_key::{AdbKey, AdbPublicKey};
+use crate::constants::{ADB_AUTH_PROMPT_TIMEOUT_SECS, ADB_VENDOR_KEYS_ENV, ADB_VENDOR_KEY_EXTENSION, USER_ENV};
+
+/// The host side of AUTH: keys to sign the device's tokens with, tried in order. When the device
+/// knows none of them, the first is offered as a new key and the user has `prompt_timeout` to
+/// allow it on the device.
+#[derive(Debug, Clone)]
+pub struct HostAuth {
+    pub(crate) keys: Vec<AdbKey>,
+    pub(crate) prompt_timeout: Duration,
+}
+
+impl Default for HostAuth {
+    fn default() -> Self {
+        HostAuth { keys: Vec::new(), prompt_timeout: Duration::from_secs(ADB_AUTH_PROMPT_TIMEOUT_SECS) }
+    }
+}
+
+impl HostAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Loads the keys at `paths`, or `~/.android/adbkey` when there are none, plus the files and
+    /// `*.adb_key` files of directories listed in `ADB_VENDOR_KEYS`.
+    pub fn load(paths: &[PathBuf]) -> Result<Self, Box<dyn Error>> {
+        let mut auth = HostAuth::new();
+        if paths.is_empty() {
+            let default_path = home_dir().map(|home| home.join(ADB_FOLDER_NAME).join(ADB_KEY_FILENAME));
+            if let Some(path) = default_path.filter(|path| path.is_file()) {
+                auth = auth.key(AdbKey::load(&path)?);
+            }
+        }
+        for path in paths {
+            auth = auth.key(AdbKey::load(path)?);
+        }
+        if let Some(vendor_keys) = std::env::var_os(ADB_VENDOR_KEYS_ENV) {
+            for path in std::env::split_paths(&vendor_keys).filter(|path| !path.as_os_str().is_empty()) {
+                for path in vendor_key_files(&path)? {
+                    auth = auth.key(AdbKey::load(&path)?);
+                }
+            }
+        }
+        Ok(auth)
+    }
+
+    pub fn key(mut self, key: AdbKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    pub fn prompt_timeout(mut self, prompt_timeout: Duration) -> Self {
+        self.prompt_timeout = prompt_timeout;
+        self
+    }
+
+    pub fn keys(&self) -> &[AdbKey] {
+        &self.keys
+    }
+}
+
+/// The device side of AUTH, like adbd's `adb_keys`: the public keys it accepts signatures from.
+/// With `allow_new_keys` an offered key is accepted right away, as if the user allowed it.
+///
+/// Clones share the list of keys.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceAuth {
+    keys: Arc<Mutex<Vec<AdbPublicKey>>>,
+    allow_new_keys: bool,
+}
+
+impl DeviceAuth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow_new_keys(mut self, allow_new_keys: bool) -> Self {
+        self.allow_new_keys = allow_new_keys;
+        self
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<AdbPublicKey>> {
+        self.keys.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    pub fn authorize(&self, key: &AdbPublicKey) {
+        let mut keys = self.lock();
+        if !keys.contains(key) {
+            keys.push(key.clone());
+        }
+    }
+
+    pub fn authorized_keys(&self) -> Vec<AdbPublicKey> {
+        self.lock().clone()
+    }
+
+    /// Whether one of the authorized keys made `signature` for `token`.
+    pub fn verify(&self, token: &[u8], signature: &[u8]) -> bool {
+        self.lock().iter().any(|key| key.verify(token, signature))
+    }
+
+    /// Authorizes an offered key if new keys are allowed.
+    pub(crate) fn offer(&self, key: &AdbPublicKey) -> bool {
+        if self.allow_new_keys {
+            self.authorize(key);
+        }
+        self.allow_new_keys
+    }
+}
+
+/// `user@host`, sent after the public key so the device can show whose key it is.
+pub(crate) fn key_comment() -> String {
+    let user = std::env::var(USER_ENV).ok().filter(|user| !user.is_empty()).unwrap_or_else(|| "unknown".to_string());
+    let host = std::fs::read_to_string("/proc/sys/kernel/hostname").ok()
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+    format!("{}@{}", user, host)
+}
+
+fn vendor_key_files(path: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(path).map_err(|err| format!("Cannot read {}: {}", path.display(), err))? {
+        let file = entry?.path();
+        if file.extension().is_some_and(|extension| extension == ADB_VENDOR_KEY_EXTENSION) {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -0,0 +1,439 @@
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::error::TryRecvError;
+use tokio::sync::{mpsc, oneshot};
+use tokio_util::sync::CancellationToken;
+use crate::adb::wire::adb_key::AdbPublicKey;
+use crate::adb::wire::auth::{key_comment, DeviceAuth, HostAuth};
+use crate::adb::wire::packet::{command_name, Packet};
+use crate::constants::{A_AUTH, A_CLSE, A_CNXN, A_OKAY, A_OPEN, A_VERSION, A_VERSION_MIN, A_WRTE, ADB_AUTH_RSAPUBLICKEY, ADB_AUTH_SIGNATURE, ADB_AUTH_TOKEN, ADB_AUTH_TOKEN_SIZE, DELAYED_ACK_FEATURE, DELAYED_ACK_WINDOW, MAX_PAYLOAD};
+use crate::models::device_banner::DeviceBanner;
+
+/// Bytes buffered per stream between the link and its local end.
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Debug)]
+enum StreamSlot {
+    /// We sent OPEN and wait for the peer's OKAY or CLSE.
+    Opening(oneshot::Sender<DuplexStream>),
+    Open {
+        remote_id: u32,
+        inbound: mpsc::UnboundedSender<Vec<u8>>,
+        /// Bytes the peer acknowledged; 0 for a plain OKAY.
+        acks: mpsc::UnboundedSender<u32>,
+    },
+}
+
+#[derive(Debug)]
+struct LinkShared {
+    packets: mpsc::UnboundedSender<Packet>,
+    streams: Mutex<HashMap<u32, StreamSlot>>,
+    next_local_id: AtomicU32,
+    version: u32,
+    max_payload: usize,
+    delayed_ack: bool,
+    peer: DeviceBanner,
+    closed: CancellationToken,
+}
+
+/// One connection speaking the device protocol (CNXN/AUTH/OPEN/OKAY/WRTE/CLSE), carrying any number
+/// of streams. Each stream surfaces as a [`DuplexStream`]; incoming data is acknowledged once the
+/// local end has taken it. The link sends a WRTE only once the previous one was acknowledged, or
+/// with delayed acks while the bytes the peer still has to acknowledge fit its window.
+#[derive(Debug, Clone)]
+pub struct DeviceLink {
+    shared: Arc<LinkShared>,
+}
+
+/// A stream the peer asked to open; accept or reject it.
+#[derive(Debug)]
+pub struct IncomingStream {
+    pub service: String,
+    remote_id: u32,
+    /// Bytes we may send before the first ack, with delayed acks.
+    send_window: u32,
+    link: DeviceLink,
+}
+
+impl DeviceLink {
+    /// The host side of the handshake: sends our CNXN and waits for the device's, answering AUTH
+    /// tokens with the keys of `auth`.
+    pub async fn connect<S>(stream: S, banner: &DeviceBanner, auth: &HostAuth) -> io::Result<DeviceLink>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        writer.write_all(&connect_packet(banner, A_VERSION, MAX_PAYLOAD).encode()).await?;
+        let mut signing_keys = auth.keys.iter();
+        let mut offered_key = false;
+        loop {
+            let packet = if offered_key {
+                // The user has to allow the key on the device first.
+                tokio::time::timeout(auth.prompt_timeout, Packet::read(&mut reader, A_VERSION_MIN)).await
+                    .map_err(|_| unauthorized("the key was not allowed on the device"))??
+            } else {
+                Packet::read(&mut reader, A_VERSION_MIN).await?
+            };
+            let packet = packet.ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "device closed the connection during the handshake"))?;
+            match packet.command {
+                A_CNXN => return Self::start(reader, writer, banner, &packet, None),
+                A_AUTH if packet.arg0 == ADB_AUTH_TOKEN && !offered_key => {
+                    let reply = match signing_keys.next() {
+                        Some(key) => {
+                            let signature = key.sign(&packet.data).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+                            Packet::new(A_AUTH, ADB_AUTH_SIGNATURE, 0, signature)
+                        }
+                        None => {
+                            let key = auth.keys.first().ok_or_else(|| unauthorized("no adb key to authenticate with"))?;
+                            offered_key = true;
+                            Packet::new(A_AUTH, ADB_AUTH_RSAPUBLICKEY, 0, format!("{} {}\0", key.public_key(), key_comment()).into_bytes())
+                        }
+                    };
+                    writer.write_all(&reply.encode()).await?;
+                }
+                A_AUTH => return Err(unauthorized("the device rejected the key")),
+                other => tracing::debug!("Ignoring {} before the device's CNXN", command_name(other)),
+            }
+        }
+    }
+
+    /// Connects to adbd listening on `address`, e.g. a device after `adb tcpip 5555`, without an
+    /// ADB server in between.
+    pub async fn dial(address: &str, banner: &DeviceBanner, auth: &HostAuth) -> io::Result<DeviceLink> {
+        let stream = TcpStream::connect(address).await?;
+        stream.set_nodelay(true)?;
+        Self::connect(stream, banner, auth).await
+    }
+
+    /// The device side of the handshake: waits for the host's CNXN, makes it pass AUTH when `auth`
+    /// is given, then answers with `banner`. Streams the host opens arrive on the returned receiver,
+    /// which ends with the link.
+    pub async fn accept<S>(stream: S, banner: &DeviceBanner, auth: Option<&DeviceAuth>) -> io::Result<(DeviceLink, mpsc::UnboundedReceiver<IncomingStream>)>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let cnxn = loop {
+            let packet = Packet::read(&mut reader, A_VERSION_MIN).await?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "host closed the connection during the handshake"))?;
+            if packet.command == A_CNXN {
+                break packet;
+            }
+            tracing::debug!("Ignoring {} before the host's CNXN", command_name(packet.command));
+        };
+        let (version, max_payload) = negotiate(&cnxn)?;
+        if let Some(auth) = auth {
+            authenticate(&mut reader, &mut writer, auth, version).await?;
+        }
+        writer.write_all(&connect_packet(banner, version, max_payload).encode()).await?;
+        let (incoming, streams) = mpsc::unbounded_channel();
+        Ok((Self::start(reader, writer, banner, &cnxn, Some(incoming))?, streams))
+    }
+
+    fn start<S>(reader: ReadHalf<S>, writer: WriteHalf<S>, banner: &DeviceBanner, cnxn: &Packet, incoming: Option<mpsc::UnboundedSender<IncomingStream>>) -> io::Result<DeviceLink>
+    where
+        S: AsyncRead + AsyncWrite + Send + 'static,
+    {
+        let (version, max_payload) = negotiate(cnxn)?;
+        let peer = DeviceBanner::parse(&String::from_utf8_lossy(&cnxn.data));
+        let delayed_ack = [banner, &peer].iter().all(|banner| banner.features.iter().any(|feature| feature == DELAYED_ACK_FEATURE));
+        let (packets, outgoing) = mpsc::unbounded_channel();
+        let link = DeviceLink {
+            shared: Arc::new(LinkShared {
+                packets,
+                streams: Mutex::new(HashMap::new()),
+                next_local_id: AtomicU32::new(1),
+                version,
+                max_payload,
+                delayed_ack,
+                peer,
+                closed: CancellationToken::new(),
+            }),
+        };
+        tokio::spawn(link.clone().write_packets(writer, outgoing));
+        tokio::spawn(link.clone().read_packets(reader, incoming));
+        Ok(link)
+    }
+
+    /// What the peer said about itself in its CNXN.
+    pub fn peer(&self) -> &DeviceBanner {
+        &self.shared.peer
+    }
+
+    /// Protocol version both ends speak.
+    pub fn version(&self) -> u32 {
+        self.shared.version
+    }
+
+    /// Largest WRTE payload both ends accept.
+    pub fn max_payload(&self) -> usize {
+        self.shared.max_payload
+    }
+
+    /// Whether streams acknowledge bytes rather than every WRTE, i.e. both ends list the
+    /// `delayed_ack` feature.
+    pub fn delayed_ack(&self) -> bool {
+        self.shared.delayed_ack
+    }
+
+    /// Opens a stream to `service` on the peer, e.g. `shell:ls` or `tcp:8080`.
+    pub async fn open(&self, service: &str) -> io::Result<DuplexStream> {
+        if self.is_closed() {
+            return Err(io::Error::new(io::ErrorKind::NotConnected, "device is offline"));
+        }
+        let local_id = self.next_local_id();
+        let (reply, opened) = oneshot::channel();
+        self.streams().insert(local_id, StreamSlot::Opening(reply));
+        let mut data = service.as_bytes().to_vec();
+        data.push(0);
+        // With delayed acks, arg1 says how much the peer may send before our first ack.
+        let receive_window = if self.shared.delayed_ack { DELAYED_ACK_WINDOW } else { 0 };
+        self.send(Packet::new(A_OPEN, local_id, receive_window, data));
+        tokio::select! {
+            opened = opened => opened.map_err(|_| io::Error::new(io::ErrorKind::ConnectionRefused, format!("device refused to open '{}'", service))),
+            _ = self.shared.closed.cancelled() => Err(io::Error::new(io::ErrorKind::NotConnected, "device is offline")),
+        }
+    }
+
+    /// Drops the connection and every stream on it.
+    pub fn close(&self) {
+        self.shared.closed.cancel();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.shared.closed.is_cancelled()
+    }
+
+    /// Resolves once the connection is gone, closed by either end.
+    pub async fn closed(&self) {
+        self.shared.closed.cancelled().await
+    }
+
+    fn streams(&self) -> MutexGuard<'_, HashMap<u32, StreamSlot>> {
+        self.shared.streams.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn next_local_id(&self) -> u32 {
+        self.shared.next_local_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn send(&self, packet: Packet) {
+        let _ = self.shared.packets.send(packet);
+    }
+
+    /// An OKAY, carrying the acknowledged byte count with delayed acks.
+    fn okay(&self, local_id: u32, remote_id: u32, acked: u32) -> Packet {
+        let data = if self.shared.delayed_ack { acked.to_le_bytes().to_vec() } else { Vec::new() };
+        Packet::new(A_OKAY, local_id, remote_id, data)
+    }
+
+    async fn write_packets<W: AsyncWrite>(self, mut writer: WriteHalf<W>, mut outgoing: mpsc::UnboundedReceiver<Packet>) {
+        loop {
+            let packet = tokio::select! {
+                packet = outgoing.recv() => packet,
+                _ = self.shared.closed.cancelled() => None,
+            };
+            let Some(packet) = packet else { break };
+            if let Err(err) = writer.write_all(&packet.encode()).await {
+                tracing::debug!("Device link write failed: {}", err);
+                break;
+            }
+        }
+        self.close();
+        let _ = writer.shutdown().await;
+    }
+
+    async fn read_packets<R: AsyncRead>(self, mut reader: ReadHalf<R>, incoming: Option<mpsc::UnboundedSender<IncomingStream>>) {
+        loop {
+            let packet = tokio::select! {
+                packet = Packet::read(&mut reader, self.shared.version) => packet,
+                _ = self.shared.closed.cancelled() => break,
+            };
+            match packet {
+                Ok(Some(packet)) => self.dispatch(packet, incoming.as_ref()),
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::debug!("Device link read failed: {}", err);
+                    break;
+                }
+            }
+        }
+        self.close();
+        // Dropping the slots fails pending opens and ends every stream.
+        self.streams().clear();
+    }
+
+    fn dispatch(&self, packet: Packet, incoming: Option<&mpsc::UnboundedSender<IncomingStream>>) {
+        // For OKAY, WRTE and CLSE, arg0 is the sender's stream id and arg1 ours.
+        let (remote_id, local_id) = (packet.arg0, packet.arg1);
+        match packet.command {
+            A_OPEN => {
+                let service = String::from_utf8_lossy(&packet.data).trim_end_matches('\0').to_string();
+                let stream = IncomingStream { service, remote_id, send_window: packet.arg1, link: self.clone() };
+                match incoming {
+                    Some(incoming) => {
+                        if let Err(rejected) = incoming.send(stream) {
+                            rejected.0.reject();
+                        }
+                    }
+                    None => stream.reject(),
+                }
+            }
+            A_OKAY => {
+                let acked = match packet.data.get(..4) {
+                    Some(bytes) if self.shared.delayed_ack => u32::from_le_bytes(bytes.try_into().expect("4 bytes")),
+                    _ => 0,
+                };
+                let mut streams = self.streams();
+                match streams.get(&local_id) {
+                    Some(StreamSlot::Open { acks, .. }) => {
+                        let _ = acks.send(acked);
+                    }
+                    Some(StreamSlot::Opening(_)) => {
+                        let Some(StreamSlot::Opening(reply)) = streams.remove(&local_id) else { return };
+                        drop(streams);
+                        // If the opener gave up, dropping the stream closes it again.
+                        let _ = reply.send(self.start_stream(local_id, remote_id, acked));
+                    }
+                    None => {}
+                }
+            }
+            A_WRTE => {
+                if let Some(StreamSlot::Open { inbound, .. }) = self.streams().get(&local_id) {
+                    let _ = inbound.send(packet.data);
+                }
+            }
+            A_CLSE => {
+                self.streams().remove(&local_id);
+            }
+            other => tracing::debug!("Ignoring {} on an established link", command_name(other)),
+        }
+    }
+
+    /// Registers an open stream and starts moving its data; returns the local end. `send_window` is
+    /// what the peer lets us send before its first ack, with delayed acks.
+    fn start_stream(&self, local_id: u32, remote_id: u32, send_window: u32) -> DuplexStream {
+        let (ours, theirs) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+        let (inbound, mut inbound_data) = mpsc::unbounded_channel::<Vec<u8>>();
+        let (acks, mut ack_received) = mpsc::unbounded_channel();
+        self.streams().insert(local_id, StreamSlot::Open { remote_id, inbound, acks });
+        let (mut read_half, mut write_half) = tokio::io::split(ours);
+
+        let link = self.clone();
+        tokio::spawn(async move {
+            while let Some(data) = inbound_data.recv().await {
+                if write_half.write_all(&data).await.is_err() {
+                    link.close_stream(local_id);
+                    break;
+                }
+                link.send(link.okay(local_id, remote_id, data.len() as u32));
+            }
+            let _ = write_half.shutdown().await;
+        });
+
+        let link = self.clone();
+        tokio::spawn(async move {
+            let mut buffer = vec![0u8; link.shared.max_payload];
+            let mut available = send_window as i64;
+            loop {
+                match read_half.read(&mut buffer).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => {
+                        link.send(Packet::new(A_WRTE, local_id, remote_id, buffer[..read].to_vec()));
+                        if !link.shared.delayed_ack {
+                            if ack_received.recv().await.is_none() {
+                                // The peer closed the stream.
+                                return;
+                            }
+                            continue;
+                        }
+                        available -= read as i64;
+                        loop {
+                            match ack_received.try_recv() {
+                                Ok(acked) => available += acked as i64,
+                                Err(TryRecvError::Empty) => break,
+                                Err(TryRecvError::Disconnected) => return,
+                            }
+                        }
+                        while available <= 0 {
+                            match ack_received.recv().await {
+                                Some(acked) => available += acked as i64,
+                                None => return,
+                            }
+                        }
+                    }
+                }
+            }
+            link.close_stream(local_id);
+        });
+        theirs
+    }
+
+    /// Closes a stream from our side, unless the peer already did.
+    fn close_stream(&self, local_id: u32) {
+        if let Some(StreamSlot::Open { remote_id, .. }) = self.streams().remove(&local_id) {
+            self.send(Packet::new(A_CLSE, local_id, remote_id, Vec::new()));
+        }
+    }
+}
+
+impl IncomingStream {
+    pub fn accept(self) -> DuplexStream {
+        let local_id = self.link.next_local_id();
+        let stream = self.link.start_stream(local_id, self.remote_id, self.send_window);
+        self.link.send(self.link.okay(local_id, self.remote_id, DELAYED_ACK_WINDOW));
+        stream
+    }
+
+    pub fn reject(self) {
+        self.link.send(Packet::new(A_CLSE, 0, self.remote_id, Vec::new()));
+    }
+}
+
+fn connect_packet(banner: &DeviceBanner, version: u32, max_payload: usize) -> Packet {
+    Packet::new(A_CNXN, version, max_payload as u32, banner.to_string().into_bytes())
+}
+
+/// The version and payload size both ends support, from the peer's CNXN.
+fn negotiate(cnxn: &Packet) -> io::Result<(u32, usize)> {
+    if cnxn.arg0 < A_VERSION_MIN {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("unsupported protocol version {:#010x}", cnxn.arg0)));
+    }
+    Ok((cnxn.arg0.min(A_VERSION), (cnxn.arg1 as usize).clamp(1, MAX_PAYLOAD)))
+}
+
+/// Sends tokens until the host signs one with an authorized key or offers a key we accept.
+async fn authenticate<R, W>(reader: &mut R, writer: &mut W, auth: &DeviceAuth, version: u32) -> io::Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    loop {
+        let mut token = [0u8; ADB_AUTH_TOKEN_SIZE];
+        openssl::rand::rand_bytes(&mut token).map_err(io::Error::other)?;
+        writer.write_all(&Packet::new(A_AUTH, ADB_AUTH_TOKEN, 0, token.to_vec()).encode()).await?;
+        loop {
+            let packet = Packet::read(reader, version).await?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::UnexpectedEof, "host closed the connection during authentication"))?;
+            match (packet.command, packet.arg0) {
+                (A_AUTH, ADB_AUTH_SIGNATURE) if auth.verify(&token, &packet.data) => return Ok(()),
+                // A new token for the host's next key.
+                (A_AUTH, ADB_AUTH_SIGNATURE) => break,
+                (A_AUTH, ADB_AUTH_RSAPUBLICKEY) => match AdbPublicKey::parse(&String::from_utf8_lossy(&packet.data)) {
+                    Ok(key) if auth.offer(&key) => return Ok(()),
+                    Ok(_) => tracing::debug!("Host offered a key that is not allowed"),
+                    Err(err) => tracing::debug!("Host offered a bad key: {}", err),
+                },
+                (other, _) => tracing::debug!("Ignoring {} during authentication", command_name(other)),
+            }
+        }
+    }
+}
+
+fn unauthorized(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::PermissionDenied, format!("device unauthorized: {}", reason))
+}
@@ -0,0 +1,4 @@
+pub mod adb_key;
+pub mod auth;
+pub mod device_link;
+pub mod packet;
@@ -0,0 +1,73 @@
+use std::io;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::constants::{A_AUTH, A_CLSE, A_CNXN, A_OKAY, A_OPEN, A_VERSION_SKIP_CHECKSUM, A_WRTE, MAX_PAYLOAD};
+
+const HEADER_SIZE: usize = 24;
+
+/// One message of the device protocol: a 24-byte little-endian header (command, arg0, arg1, payload
+/// length, payload checksum, command ^ 0xffffffff), then `data`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Packet {
+    pub command: u32,
+    pub arg0: u32,
+    pub arg1: u32,
+    pub data: Vec<u8>,
+}
+
+impl Packet {
+    pub fn new(command: u32, arg0: u32, arg1: u32, data: Vec<u8>) -> Self {
+        Packet { command, arg0, arg1, data }
+    }
+
+    /// The header and payload; the checksum is always filled in, which every version accepts.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + self.data.len());
+        for word in [self.command, self.arg0, self.arg1, self.data.len() as u32, checksum(&self.data), self.command ^ 0xffff_ffff] {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.data);
+        bytes
+    }
+
+    /// Reads the next packet; `None` when the stream ended between packets. The magic is always
+    /// checked, the checksum only below [`A_VERSION_SKIP_CHECKSUM`] of the protocol `version`.
+    pub async fn read<S: AsyncRead + Unpin>(stream: &mut S, version: u32) -> io::Result<Option<Packet>> {
+        let mut header = [0u8; HEADER_SIZE];
+        match stream.read_exact(&mut header).await {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let word = |index: usize| u32::from_le_bytes(header[index * 4..index * 4 + 4].try_into().expect("4 bytes"));
+        let (command, length) = (word(0), word(3) as usize);
+        if word(5) != command ^ 0xffff_ffff {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad magic {:#010x} for {}", word(5), command_name(command))));
+        }
+        if length > MAX_PAYLOAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("{} packet of {} bytes exceeds the maximum payload", command_name(command), length)));
+        }
+        let mut data = vec![0u8; length];
+        stream.read_exact(&mut data).await?;
+        if version < A_VERSION_SKIP_CHECKSUM && word(4) != checksum(&data) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad checksum for {} packet", command_name(command))));
+        }
+        Ok(Some(Packet::new(command, word(1), word(2), data)))
+    }
+}
+
+/// Sum of the payload bytes, which older adbd versions still check.
+pub fn checksum(data: &[u8]) -> u32 {
+    data.iter().map(|&byte| byte as u32).fold(0, u32::wrapping_add)
+}
+
+pub fn command_name(command: u32) -> String {
+    match command {
+        A_CNXN => "CNXN".to_string(),
+        A_AUTH => "AUTH".to_string(),
+        A_OPEN => "OPEN".to_string(),
+        A_OKAY => "OKAY".to_string(),
+        A_CLSE => "CLSE".to_string(),
+        A_WRTE => "WRTE".to_string(),
+        other => format!("{:#010x}", other),
+    }
+}
@@ -5,35 +5,82 @@ pub const DEFAULT_ADB_SERVER_IP: &str = "127.0.0.1";
 pub const DEFAULT_ADB_SERVER_PORT: u16 = 5037;
 
 pub const ADB_ADDRESS_ENV: &str = "ADB_ADDRESS";
+pub const ADB_SERVER_SOCKET_ENV: &str = "ADB_SERVER_SOCKET";
+pub const ANDROID_SERIAL_ENV: &str = "ANDROID_SERIAL";
+pub const ADBR_TRACE_ENV: &str = "ADBR_TRACE";
+pub const ADBR_CONFIG_ENV: &str = "ADBR_CONFIG";
+pub const ADBR_PROFILE_ENV: &str = "ADBR_PROFILE";
+pub const ADBR_RECORD_ENV: &str = "ADBR_RECORD";
+/// Set on the per-device child processes of `--all`/`--match`.
+pub const ADBR_FAN_OUT_CHILD_ENV: &str = "ADBR_FAN_OUT_CHILD";
+pub const ADB_VENDOR_KEYS_ENV: &str = "ADB_VENDOR_KEYS";
+pub const XDG_CONFIG_HOME_ENV: &str = "XDG_CONFIG_HOME";
 pub const ADB_SERVER_CONNECT_TIMEOUT_SECONDS_DURATION: u64 = 5;
+pub const DEFAULT_IO_TIMEOUT_SECS: u64 = 60;
+pub const DEFAULT_CONNECT_RETRY_DELAY_MS: u64 = 500;
+
+pub const TCP_SOCKET_PREFIX: &str = "tcp:";
+pub const LOCAL_FILESYSTEM_PREFIX: &str = "localfilesystem:";
+pub const LOCAL_ABSTRACT_PREFIX: &str = "localabstract:";
+
+pub const CONFIG_DIR_NAME: &str = ".config";
+pub const ADBR_CONFIG_DIR_NAME: &str = "adbr";
+pub const CONFIG_FILE_NAME: &str = "config.toml";
 
 pub const FLAG_HELP_SHORT: &str = "-h";
 pub const FLAG_HELP_LONG: &str = "--help";
 pub const FLAG_VERSION: &str = "--version";
 pub const FLAG_SERVER_ADDRESS: &str = "-H";
 pub const FLAG_SERVER_PORT: &str = "-P";
+pub const FLAG_SERVER_SOCKET: &str = "-L";
 pub const FLAG_SERIAL: &str = "-s";
 pub const FLAG_USB: &str = "-d";
 pub const FLAG_EMULATOR: &str = "-e";
 pub const FLAG_WATCH_DEVICES: &str = "-w";
+pub const FLAG_LONG_DEVICES: &str = "-l";
 pub const FLAG_TIMEOUT: &str = "-t";
+pub const FLAG_TRANSPORT_ID: &str = "-t";
+pub const FLAG_ALL_DEVICES: &str = "--all";
+pub const FLAG_MATCH_DEVICES: &str = "--match";
+pub const FLAG_FORCE_PTY: &str = "-t";
+pub const FLAG_DISABLE_PTY: &str = "-T";
+pub const FLAG_ESCAPE_CHAR: &str = "-e";
+pub const FLAG_VERBOSE: &str = "-v";
+pub const FLAG_VERY_VERBOSE: &str = "-vv";
+pub const FLAG_LOG_LEVEL: &str = "--log-level";
+pub const FLAG_DEADLINE: &str = "--timeout";
+pub const FLAG_IO_TIMEOUT: &str = "--io-timeout";
+pub const FLAG_PROFILE: &str = "--profile";
+
+/// Tracing target of the raw protocol frame dumps enabled by `ADBR_TRACE`.
+pub const FRAME_TRACE_TARGET: &str = "adbr::frames";
 
 pub const ADB_SHELL_COMMAND: &str = "shell:";
+pub const ADB_EXEC_COMMAND: &str = "exec:";
+pub const ADB_SHELL_V2_SERVICE: &str = "shell,v2";
+pub const SHELL_PTY_ARG: &str = "pty";
+pub const SHELL_RAW_ARG: &str = "raw";
+pub const SHELL_V2_FEATURE: &str = "shell_v2";
+pub const TERM_ENV: &str = "TERM";
+pub const USER_ENV: &str = "USER";
+pub const DEFAULT_ESCAPE_CHAR: u8 = b'~';
 pub const ADB_DEVICES_COMMAND: &str = "host:devices";
-pub const HOST_FORWARD_COMMAND: &str = "host:forward";
-pub const HOST_FORWARD_KILL_COMMAND: &str = "host:killforward";
-pub const HOST_FORWARD_KILL_ALL_COMMAND: &str = "host:killforward-all";
-pub const HOST_FORWARD_LIST_COMMAND: &str = "host:list-forward";
+pub const ADB_DEVICES_LONG_COMMAND: &str = "host:devices-l";
+pub const ADB_TRACK_DEVICES_COMMAND: &str = "host:track-devices";
+pub const HOST_FORWARD_SERVICE: &str = "forward";
+pub const HOST_FORWARD_KILL_SERVICE: &str = "killforward";
+pub const HOST_FORWARD_KILL_ALL_SERVICE: &str = "killforward-all";
+pub const HOST_FORWARD_LIST_SERVICE: &str = "list-forward";
+pub const HOST_VERSION_COMMAND: &str = "host:version";
+pub const HOST_KILL_COMMAND: &str = "host:kill";
+pub const HOST_CONNECT_COMMAND: &str = "host:connect:";
+pub const HOST_DISCONNECT_COMMAND: &str = "host:disconnect:";
 
 pub const HOST_REVERSE_COMMAND: &str = "reverse:forward";
 pub const HOST_REVERSE_REMOVE_COMMAND: &str = "reverse:killforward";
 pub const HOST_REVERSE_REMOVE_ALL_COMMAND: &str = "reverse:killforward-all";
 pub const HOST_REVERSE_LIST_COMMAND: &str = "reverse:list-forward";
 
-
-pub const HOST_SERIALNO_COMMAND: &str = "host:get-serialno";
-pub const HOST_GET_DEVPATH_COMMAND: &str = "host:get-devpath";
-
 pub const USER_ENABLE_VERITY_COMMAND: &str = "enable-verity";
 pub const USER_KEYGEN_COMMAND: &str = "keygen";
 pub const USER_DISABLE_VERITY_COMMAND: &str = "disable-verity";
@@ -50,11 +97,11 @@ pub const USER_FORWARD_COMMAND: &str = "forward";
 pub const USER_REVERSE_COMMAND: &str = "reverse";
 pub const USER_PUSH_COMMAND: &str = "push";
 pub const USER_PULL_COMMAND: &str = "pull";
-pub const USER_EXIT_COMMAND: &str = "exit\n";
 pub const USER_USB_COMMAND: &str = "usb";
 pub const USER_TCPIP_COMMAND: &str = "tcpip";
 pub const USER_WAIT_FOR_COMMAND: &str = "wait-for";
 pub const USER_GET_STATE_COMMAND: &str = "get-state";
+pub const USER_FEATURES_COMMAND: &str = "features";
 
 pub const OPTION_NO_REBIND: &str = "--no-rebind";
 pub const OPTION_REMOVE: &str = "--remove";
@@ -80,7 +127,10 @@ pub const ADB_REBOOT_BOOTLOADER_COMMAND: &str = "reboot:bootloader";
 pub const ADB_REBOOT_RECOVERY_COMMAND: &str = "reboot:recovery";
 pub const ADB_REBOOT_SIDELOAD_COMMAND: &str = "reboot:sideload";
 pub const ADB_REBOOT_SIDELOAD_AUTO_REBOOT_COMMAND: &str = "reboot:sideload-auto-reboot";
-pub const ADB_GET_STATE_COMMAND: &str = "host:get-state";
+pub const HOST_GET_STATE_SERVICE: &str = "get-state";
+pub const HOST_GET_SERIALNO_SERVICE: &str = "get-serialno";
+pub const HOST_GET_DEVPATH_SERVICE: &str = "get-devpath";
+pub const HOST_FEATURES_SERVICE: &str = "features";
 pub const SEND_COMMAND: &str = "SEND";
 pub const STAT_COMMAND: &str = "STAT";
 pub const DATA_COMMAND: &str = "DATA";
@@ -95,6 +145,50 @@ pub const PM_INSTALL: &str = "pm install";
 pub const PM_UNINSTALL: &str = "pm uninstall";
 pub const USER_UNINSTALL_COMMAND: &str = "uninstall";
 
+pub const USER_AM_COMMAND: &str = "am";
+pub const AM_COMMAND: &str = "am";
+pub const AM_START: &str = "start";
+pub const AM_BROADCAST: &str = "broadcast";
+pub const AM_START_SERVICE: &str = "startservice";
+pub const AM_FORCE_STOP: &str = "force-stop";
+pub const AM_INSTRUMENT: &str = "instrument";
+
+pub const USER_INSTRUMENT_COMMAND: &str = "instrument";
+pub const OPTION_JUNIT: &str = "--junit";
+pub const OPTION_JSON: &str = "--json";
+pub const USER_TEST_COMMAND: &str = "test";
+pub const OPTION_SHARDS: &str = "--shards";
+pub const OPTION_RETRIES: &str = "--retries";
+pub const DEFAULT_TEST_RETRIES: u32 = 1;
+pub const OPTION_FORMAT: &str = "--format";
+pub const OPTION_PACKAGE: &str = "--package";
+pub const OPTION_GREP: &str = "--grep";
+pub const OPTION_EXCLUDE_TAG: &str = "--exclude-tag";
+pub const OPTION_MIN_LEVEL: &str = "--min-level";
+pub const OPTION_SINCE: &str = "--since";
+pub const PACKAGE_PID_REFRESH_SECS: u64 = 5;
+pub const LOGCAT_MERGE_WINDOW_MS: u64 = 250;
+pub const OPTION_RECORD: &str = "--record";
+pub const OPTION_ROTATE_SIZE: &str = "--rotate-size";
+pub const OPTION_ROTATE_TIME: &str = "--rotate-time";
+pub const DEFAULT_RECORD_FILE_SIZE: u64 = 16 * 1024 * 1024;
+pub const DEFAULT_RECORD_FILE_AGE_SECS: u64 = 3600;
+pub const USER_CRASHES_COMMAND: &str = "crashes";
+pub const OPTION_OUT: &str = "--out";
+pub const OPTION_NO_PULL: &str = "--no-pull";
+pub const DEFAULT_CRASH_ARTIFACT_DIR: &str = "crashes";
+pub const OPTION_EXTRACT: &str = "--extract";
+pub const USER_BUGREPORT_SUMMARY_COMMAND: &str = "bugreport-summary";
+pub const USER_EXPECT_COMMAND: &str = "expect";
+pub const USER_REPLAY_COMMAND: &str = "replay";
+pub const USER_REDACT_SERIALS_COMMAND: &str = "redact-serials";
+pub const OPTION_PORT: &str = "--port";
+pub const OPTION_REALTIME: &str = "--realtime";
+pub const OPTION_SERIAL: &str = "--serial";
+pub const USER_SERVER_COMMAND: &str = "server";
+pub const USER_KILL_SERVER_COMMAND: &str = "kill-server";
+pub const USER_DISCONNECT_COMMAND: &str = "disconnect";
+
 
 pub const UNINSTALL_FLAG_KEEP_DATA: &str = "-k";
 pub const INSTALL_FLAG_REPLACE: &str = "-r";
@@ -111,6 +205,7 @@ pub const USER_UNROOT_COMMAND: &str = "unroot";
 pub const DEFAULT_WAIT_STATE: &str = "device";
 
 pub const S_IFDIR: u32 = 0x4000;
+pub const S_IFREG: u32 = 0x8000;
 pub const DEFAULT_PUSH_MODE: u32 = 0o644;  // r
 pub const STAT_DATA_SIZE: usize = 12;
 
@@ -121,4 +216,32 @@ pub const DEVICE_TEMP_DIRECTORY: &str = "/data/local/tmp/";
 
 pub const REFRESH_INTERVAL_SECS: u64 = 1;
 
-pub const SELECT_TIMEOUT_USEC: i64 = 100_000;
\ No newline at end of file
+pub const SELECT_TIMEOUT_USEC: i64 = 100_000;
+
+/// Commands of the device protocol spoken between the ADB server and adbd.
+pub const A_CNXN: u32 = 0x4e584e43;
+pub const A_AUTH: u32 = 0x48545541;
+pub const A_OPEN: u32 = 0x4e45504f;
+pub const A_OKAY: u32 = 0x59414b4f;
+pub const A_CLSE: u32 = 0x45534c43;
+pub const A_WRTE: u32 = 0x45545257;
+pub const A_VERSION: u32 = 0x01000001;
+pub const A_VERSION_MIN: u32 = 0x01000000;
+/// From this version on, packet checksums are no longer verified.
+pub const A_VERSION_SKIP_CHECKSUM: u32 = 0x01000001;
+pub const MAX_PAYLOAD: usize = 256 * 1024;
+pub const ADB_AUTH_TOKEN: u32 = 1;
+pub const ADB_AUTH_SIGNATURE: u32 = 2;
+pub const ADB_AUTH_RSAPUBLICKEY: u32 = 3;
+pub const ADB_AUTH_TOKEN_SIZE: usize = 20;
+pub const ADB_AUTH_PROMPT_TIMEOUT_SECS: u64 = 30;
+pub const ADB_VENDOR_KEY_EXTENSION: &str = "adb_key";
+/// Streams acknowledge bytes instead of every WRTE when both ends list this feature.
+pub const DELAYED_ACK_FEATURE: &str = "delayed_ack";
+/// Bytes a delayed-ack stream lets its peer send before hearing back.
+pub const DELAYED_ACK_WINDOW: u32 = 4 * MAX_PAYLOAD as u32;
+pub const DEFAULT_ADBD_PORT: u16 = 5555;
+/// Emulators listen for adb on odd ports from 5555, named after their console port one below.
+pub const EMULATOR_FIRST_ADB_PORT: u16 = 5555;
+pub const EMULATOR_LAST_ADB_PORT: u16 = 5585;
+pub const EMULATOR_SERIAL_PREFIX: &str = "emulator-";
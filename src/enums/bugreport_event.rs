@@ -0,0 +1,13 @@
+/// Progress of [`Client::adb_bugreport`](crate::Client::adb_bugreport), for callers that show it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BugreportEvent {
+    /// `bugreportz` is missing (before Android 7.0); a plain-text report is streamed instead.
+    PlainTextFallback,
+    /// The device started generating the report; `progress` tells whether `Progress` events follow.
+    Generating { progress: bool },
+    Progress { done: u64, total: u64 },
+    /// A line from `bugreportz` that is not part of its protocol.
+    Message(String),
+    /// The zip is ready on the device and is being pulled.
+    Pulling,
+}
@@ -0,0 +1,19 @@
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CrashKind {
+    JavaCrash,
+    NativeCrash,
+    Anr,
+}
+
+impl std::fmt::Display for CrashKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CrashKind::JavaCrash => write!(f, "java crash"),
+            CrashKind::NativeCrash => write!(f, "native crash"),
+            CrashKind::Anr => write!(f, "ANR"),
+        }
+    }
+}
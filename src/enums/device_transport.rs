@@ -1,26 +1,53 @@
+const TRANSPORT_ANY: &str = "host:transport-any";
+const TRANSPORT_USB: &str = "host:transport-usb";
+const TRANSPORT_LOCAL: &str = "host:transport-local";
+const TRANSPORT_SERIAL_PREFIX: &str = "host:transport:";
+const TRANSPORT_ID_PREFIX: &str = "host:transport-id:";
+
+const HOST_PREFIX: &str = "host:";
+const HOST_USB_PREFIX: &str = "host-usb:";
+const HOST_LOCAL_PREFIX: &str = "host-local:";
+const HOST_SERIAL_PREFIX: &str = "host-serial:";
+const HOST_TRANSPORT_ID_PREFIX: &str = "host-transport-id:";
+
+const TPORT_ANY: &str = "host:tport:any";
+const TPORT_USB: &str = "host:tport:usb";
+const TPORT_LOCAL: &str = "host:tport:local";
+const TPORT_SERIAL_PREFIX: &str = "host:tport:serial:";
+
 #[derive(Clone, Debug)]
 pub enum DeviceTransport {
     Any(String),
     EmulatorAny(String),
     UsbAny(String),
-    Usb(String),
+    Serial(String),
+    TransportId(String),
 }
 
 impl DeviceTransport {
     pub fn default() -> Self {
-        DeviceTransport::Any(String::from("host:transport-any"))
+        DeviceTransport::Any(String::from(TRANSPORT_ANY))
     }
 
     pub fn default_usb() -> Self {
-        DeviceTransport::UsbAny(String::from("host:transport-usb"))
+        DeviceTransport::UsbAny(String::from(TRANSPORT_USB))
     }
 
     pub fn default_emulator() -> Self {
-        DeviceTransport::EmulatorAny(String::from("host:transport-local"))
+        DeviceTransport::EmulatorAny(String::from(TRANSPORT_LOCAL))
+    }
+
+    pub fn serial(serial: String) -> Self {
+        DeviceTransport::Serial(format!("{}{}", TRANSPORT_SERIAL_PREFIX, serial))
     }
 
+    #[deprecated(note = "selects a device by serial regardless of its connection type; use `DeviceTransport::serial`")]
     pub fn usb(serial: String) -> Self {
-        DeviceTransport::Usb(format!("host:transport:{}", serial))
+        Self::serial(serial)
+    }
+
+    pub fn transport_id(transport_id: u64) -> Self {
+        DeviceTransport::TransportId(format!("{}{}", TRANSPORT_ID_PREFIX, transport_id))
     }
 
     pub fn get_device_transport(&self) -> &str {
@@ -28,7 +55,39 @@ impl DeviceTransport {
             DeviceTransport::Any(s) => s,
             DeviceTransport::EmulatorAny(s) => s,
             DeviceTransport::UsbAny(s) => s,
-            DeviceTransport::Usb(s) => s,
+            DeviceTransport::Serial(s) => s,
+            DeviceTransport::TransportId(s) => s,
+        }
+    }
+
+    /// Prefix for host services addressed to this device without switching transports,
+    /// e.g. `host-serial:<serial>:` for `host-serial:<serial>:get-state`.
+    pub fn host_prefix(&self) -> String {
+        match self {
+            DeviceTransport::Any(_) => HOST_PREFIX.to_string(),
+            DeviceTransport::EmulatorAny(_) => HOST_LOCAL_PREFIX.to_string(),
+            DeviceTransport::UsbAny(_) => HOST_USB_PREFIX.to_string(),
+            DeviceTransport::Serial(s) => format!("{}{}:", HOST_SERIAL_PREFIX, s.trim_start_matches(TRANSPORT_SERIAL_PREFIX)),
+            DeviceTransport::TransportId(s) => format!("{}{}:", HOST_TRANSPORT_ID_PREFIX, s.trim_start_matches(TRANSPORT_ID_PREFIX)),
+        }
+    }
+
+    /// `host:tport:*` variant of the transport request, answered with the transport id.
+    /// Transport-id selection already names the id, so it has no tport form.
+    pub fn tport_command(&self) -> Option<String> {
+        match self {
+            DeviceTransport::Any(_) => Some(TPORT_ANY.to_string()),
+            DeviceTransport::EmulatorAny(_) => Some(TPORT_LOCAL.to_string()),
+            DeviceTransport::UsbAny(_) => Some(TPORT_USB.to_string()),
+            DeviceTransport::Serial(s) => Some(format!("{}{}", TPORT_SERIAL_PREFIX, s.trim_start_matches(TRANSPORT_SERIAL_PREFIX))),
+            DeviceTransport::TransportId(_) => None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn selected_transport_id(&self) -> Option<u64> {
+        match self {
+            DeviceTransport::TransportId(s) => s.trim_start_matches(TRANSPORT_ID_PREFIX).parse().ok(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,14 @@
+/// What the user asked for with an escape sequence in an interactive shell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EscapeAction {
+    /// Bytes to forward to the device.
+    Send(Vec<u8>),
+    /// `~.`: close the session.
+    Disconnect,
+    /// `~^Z`: stop adbr until it is resumed with `fg`.
+    Suspend,
+    /// `~?`: list the escape sequences.
+    Help,
+    /// `~>`: open the local prompt for push/pull.
+    LocalCommand,
+}
@@ -0,0 +1,52 @@
+use std::error::Error;
+use regex::bytes::Regex;
+
+/// What [`ExpectSession::expect`](crate::adb::expect::expect::ExpectSession::expect) waits for.
+#[derive(Debug, Clone)]
+pub enum ExpectPattern {
+    Literal(String),
+    /// Matched against raw output bytes, so a match is not disturbed by invalid UTF-8 elsewhere.
+    Regex(Regex),
+}
+
+impl ExpectPattern {
+    pub fn literal(text: &str) -> Self {
+        ExpectPattern::Literal(text.to_string())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ExpectPattern::Regex(Regex::new(pattern)?))
+    }
+
+    /// Finds the first match in `data`, returning its byte range and capture groups (group 0 is
+    /// the whole match).
+    pub fn find(&self, data: &[u8]) -> Option<(usize, usize, Vec<Option<String>>)> {
+        match self {
+            ExpectPattern::Literal(text) => {
+                let needle = text.as_bytes();
+                if needle.is_empty() {
+                    return Some((0, 0, vec![Some(String::new())]));
+                }
+                let start = data.windows(needle.len()).position(|window| window == needle)?;
+                Some((start, start + needle.len(), vec![Some(text.clone())]))
+            }
+            ExpectPattern::Regex(regex) => {
+                let captures = regex.captures(data)?;
+                let whole = captures.get(0)?;
+                let groups = captures.iter()
+                    .map(|group| group.map(|group| String::from_utf8_lossy(group.as_bytes()).into_owned()))
+                    .collect();
+                Some((whole.start(), whole.end(), groups))
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ExpectPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpectPattern::Literal(text) => write!(f, "{:?}", text),
+            ExpectPattern::Regex(regex) => write!(f, "/{}/", regex.as_str()),
+        }
+    }
+}
@@ -0,0 +1,20 @@
+use std::time::Duration;
+use crate::enums::expect_pattern::ExpectPattern;
+
+/// One statement of an expect script.
+#[derive(Debug, Clone)]
+pub enum ExpectStep {
+    /// `spawn [-t] COMMAND`: the command to run, on a PTY with `-t`; without it the script drives
+    /// a plain `sh`.
+    Spawn { command: String, pty: bool },
+    /// `send TEXT` / `sendline TEXT`; `$0`..`$9` are replaced with the last match's groups.
+    Send(String),
+    /// `expect [-t SECS] TEXT|/REGEX/`
+    Expect { pattern: ExpectPattern, timeout: Option<Duration> },
+    /// `expect [-t SECS] eof`
+    ExpectEof { timeout: Option<Duration> },
+    /// `timeout SECS`: default timeout of the following expects.
+    SetTimeout(Duration),
+    /// `sleep SECS`
+    Sleep(Duration),
+}
@@ -0,0 +1,73 @@
+#[derive(Clone, Debug)]
+pub enum IntentExtra {
+    String(String, String),
+    NullString(String),
+    Int(String, i32),
+    Long(String, i64),
+    Bool(String, bool),
+    Float(String, f32),
+    Uri(String, String),
+    StringArray(String, Vec<String>),
+    IntArray(String, Vec<i32>),
+    LongArray(String, Vec<i64>),
+    FloatArray(String, Vec<f32>),
+}
+
+impl IntentExtra {
+    pub fn flag(&self) -> &str {
+        match self {
+            IntentExtra::String(..) => "--es",
+            IntentExtra::NullString(..) => "--esn",
+            IntentExtra::Int(..) => "--ei",
+            IntentExtra::Long(..) => "--el",
+            IntentExtra::Bool(..) => "--ez",
+            IntentExtra::Float(..) => "--ef",
+            IntentExtra::Uri(..) => "--eu",
+            IntentExtra::StringArray(..) => "--esa",
+            IntentExtra::IntArray(..) => "--eia",
+            IntentExtra::LongArray(..) => "--ela",
+            IntentExtra::FloatArray(..) => "--efa",
+        }
+    }
+
+    pub fn key(&self) -> &str {
+        match self {
+            IntentExtra::String(key, _) => key,
+            IntentExtra::NullString(key) => key,
+            IntentExtra::Int(key, _) => key,
+            IntentExtra::Long(key, _) => key,
+            IntentExtra::Bool(key, _) => key,
+            IntentExtra::Float(key, _) => key,
+            IntentExtra::Uri(key, _) => key,
+            IntentExtra::StringArray(key, _) => key,
+            IntentExtra::IntArray(key, _) => key,
+            IntentExtra::LongArray(key, _) => key,
+            IntentExtra::FloatArray(key, _) => key,
+        }
+    }
+
+    /// Value as `am` expects it on the command line, before shell quoting.
+    /// Array elements are comma separated, so commas inside strings are escaped.
+    pub fn value(&self) -> Option<String> {
+        match self {
+            IntentExtra::String(_, value) => Some(value.clone()),
+            IntentExtra::NullString(_) => None,
+            IntentExtra::Int(_, value) => Some(value.to_string()),
+            IntentExtra::Long(_, value) => Some(value.to_string()),
+            IntentExtra::Bool(_, value) => Some(value.to_string()),
+            IntentExtra::Float(_, value) => Some(value.to_string()),
+            IntentExtra::Uri(_, value) => Some(value.clone()),
+            IntentExtra::StringArray(_, values) => Some(values.iter()
+                .map(|v| v.replace(',', "\\,"))
+                .collect::<Vec<String>>()
+                .join(",")),
+            IntentExtra::IntArray(_, values) => Some(join_values(values)),
+            IntentExtra::LongArray(_, values) => Some(join_values(values)),
+            IntentExtra::FloatArray(_, values) => Some(join_values(values)),
+        }
+    }
+}
+
+fn join_values<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<String>>().join(",")
+}
@@ -0,0 +1,20 @@
+/// Error returned when an operation is stopped before it finished, so callers can tell a wedged
+/// device or an expired deadline apart from a protocol failure with `downcast_ref::<Interrupted>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Interrupted {
+    /// The I/O timeout or the client's deadline passed while `operation` was in progress.
+    TimedOut { operation: String },
+    /// The client's cancellation token was cancelled.
+    Cancelled,
+}
+
+impl std::fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Interrupted::TimedOut { operation } => write!(f, "Timed out {}", operation),
+            Interrupted::Cancelled => write!(f, "Operation cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Interrupted {}
@@ -0,0 +1,68 @@
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LogBuffer {
+    Main,
+    Radio,
+    Events,
+    System,
+    Crash,
+    Stats,
+    Security,
+    Kernel,
+}
+
+impl LogBuffer {
+    /// Maps a `log_id_t` as stored in the `lid` field of v3/v4 entries.
+    pub fn from_id(id: u32) -> Option<Self> {
+        match id {
+            0 => Some(LogBuffer::Main),
+            1 => Some(LogBuffer::Radio),
+            2 => Some(LogBuffer::Events),
+            3 => Some(LogBuffer::System),
+            4 => Some(LogBuffer::Crash),
+            5 => Some(LogBuffer::Stats),
+            6 => Some(LogBuffer::Security),
+            7 => Some(LogBuffer::Kernel),
+            _ => None,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name {
+            "main" => Ok(LogBuffer::Main),
+            "radio" => Ok(LogBuffer::Radio),
+            "events" => Ok(LogBuffer::Events),
+            "system" => Ok(LogBuffer::System),
+            "crash" => Ok(LogBuffer::Crash),
+            "stats" => Ok(LogBuffer::Stats),
+            "security" => Ok(LogBuffer::Security),
+            "kernel" => Ok(LogBuffer::Kernel),
+            _ => Err(format!("Unknown log buffer: {}", name).into()),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LogBuffer::Main => "main",
+            LogBuffer::Radio => "radio",
+            LogBuffer::Events => "events",
+            LogBuffer::System => "system",
+            LogBuffer::Crash => "crash",
+            LogBuffer::Stats => "stats",
+            LogBuffer::Security => "security",
+            LogBuffer::Kernel => "kernel",
+        }
+    }
+
+    /// Binary buffers carry an event tag number and typed payload instead of priority/tag/message strings.
+    pub fn is_binary(&self) -> bool {
+        matches!(self, LogBuffer::Events | LogBuffer::Stats | LogBuffer::Security)
+    }
+}
+
+impl std::fmt::Display for LogBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
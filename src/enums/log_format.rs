@@ -0,0 +1,142 @@
+use std::error::Error;
+use chrono::Local;
+use serde_json::json;
+use crate::enums::log_priority::LogPriority;
+use crate::models::log_entry::LogEntry;
+
+const FORMAT_THREADTIME: &str = "threadtime";
+const FORMAT_COLOR: &str = "color";
+const FORMAT_JSON: &str = "json";
+const FORMAT_TEMPLATE_PREFIX: &str = "template=";
+
+const THREADTIME_DATE_FORMAT: &str = "%m-%d %H:%M:%S%.3f";
+const TEMPLATE_PLACEHOLDERS: [&str; 12] = ["time", "date", "epoch", "pid", "tid", "uid", "buffer", "priority", "level", "tag", "msg", "message"];
+
+const ANSI_RESET: &str = "\x1b[0m";
+const ANSI_VERBOSE: &str = "\x1b[2m";
+const ANSI_DEBUG: &str = "\x1b[36m";
+const ANSI_INFO: &str = "\x1b[32m";
+const ANSI_WARN: &str = "\x1b[33m";
+const ANSI_ERROR: &str = "\x1b[31m";
+const ANSI_FATAL: &str = "\x1b[1;31m";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogFormat {
+    Threadtime,
+    Color,
+    Json,
+    Template(String),
+}
+
+impl LogFormat {
+    /// Parses the value of `--format`: `threadtime`, `color`, `json` or `template=<TEMPLATE>`.
+    ///
+    /// Templates substitute `{time}`, `{date}`, `{epoch}`, `{pid}`, `{tid}`, `{uid}`, `{buffer}`,
+    /// `{priority}` (or `{level}`), `{tag}` and `{msg}` (or `{message}`).
+    pub fn from_arg(value: &str) -> Result<Self, Box<dyn Error>> {
+        match value {
+            FORMAT_THREADTIME => Ok(LogFormat::Threadtime),
+            FORMAT_COLOR => Ok(LogFormat::Color),
+            FORMAT_JSON => Ok(LogFormat::Json),
+            _ => {
+                let template = value.strip_prefix(FORMAT_TEMPLATE_PREFIX)
+                    .ok_or_else(|| format!("Unknown log format: {} (expected threadtime, color, json or template=...)", value))?;
+                Self::validate_template(template)?;
+                Ok(LogFormat::Template(template.to_string()))
+            }
+        }
+    }
+
+    /// Color only makes sense on a terminal; other formats are returned unchanged.
+    pub fn without_color(self) -> Self {
+        match self {
+            LogFormat::Color => LogFormat::Threadtime,
+            other => other,
+        }
+    }
+
+    pub fn render(&self, entry: &LogEntry) -> String {
+        match self {
+            LogFormat::Threadtime => Self::threadtime(entry),
+            LogFormat::Color => format!("{}{}{}", Self::priority_color(entry.priority), Self::threadtime(entry), ANSI_RESET),
+            LogFormat::Json => Self::json(entry),
+            LogFormat::Template(template) => Self::render_template(template, entry),
+        }
+    }
+
+    fn threadtime(entry: &LogEntry) -> String {
+        format!("{} {:>5} {:>5} {} {:<8}: {}",
+                entry.timestamp.with_timezone(&Local).format(THREADTIME_DATE_FORMAT),
+                entry.pid, entry.tid, entry.priority, entry.tag, entry.message)
+    }
+
+    fn json(entry: &LogEntry) -> String {
+        json!({
+            "timestamp": entry.timestamp.to_rfc3339(),
+            "pid": entry.pid,
+            "tid": entry.tid,
+            "uid": entry.uid,
+            "buffer": entry.buffer.name(),
+            "priority": entry.priority.letter().to_string(),
+            "tag": entry.tag,
+            "message": entry.message,
+        }).to_string()
+    }
+
+    fn priority_color(priority: LogPriority) -> &'static str {
+        match priority {
+            LogPriority::Verbose => ANSI_VERBOSE,
+            LogPriority::Debug => ANSI_DEBUG,
+            LogPriority::Info => ANSI_INFO,
+            LogPriority::Warn => ANSI_WARN,
+            LogPriority::Error => ANSI_ERROR,
+            LogPriority::Fatal => ANSI_FATAL,
+            _ => "",
+        }
+    }
+
+    fn validate_template(template: &str) -> Result<(), Box<dyn Error>> {
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or_else(|| format!("Unclosed placeholder in template: {}", template))? + start;
+            let name = &rest[start + 1..end];
+            if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+                return Err(format!("Unknown template placeholder: {{{}}} (expected one of: {})", name, TEMPLATE_PLACEHOLDERS.join(", ")).into());
+            }
+            rest = &rest[end + 1..];
+        }
+        Ok(())
+    }
+
+    fn render_template(template: &str, entry: &LogEntry) -> String {
+        let mut output = String::with_capacity(template.len() + entry.message.len());
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            output.push_str(&rest[..start]);
+            let Some(end) = rest[start..].find('}').map(|end| end + start) else {
+                output.push_str(&rest[start..]);
+                return output;
+            };
+            match &rest[start + 1..end] {
+                "time" => output.push_str(&entry.timestamp.with_timezone(&Local).format(THREADTIME_DATE_FORMAT).to_string()),
+                "date" => output.push_str(&entry.timestamp.with_timezone(&Local).format("%Y-%m-%d").to_string()),
+                "epoch" => output.push_str(&format!("{}.{:09}", entry.timestamp.timestamp(), entry.timestamp.timestamp_subsec_nanos())),
+                "pid" => output.push_str(&entry.pid.to_string()),
+                "tid" => output.push_str(&entry.tid.to_string()),
+                "uid" => output.push_str(&entry.uid.map(|uid| uid.to_string()).unwrap_or_default()),
+                "buffer" => output.push_str(entry.buffer.name()),
+                "priority" | "level" => output.push(entry.priority.letter()),
+                "tag" => output.push_str(&entry.tag),
+                "msg" | "message" => output.push_str(&entry.message),
+                other => {
+                    output.push('{');
+                    output.push_str(other);
+                    output.push('}');
+                }
+            }
+            rest = &rest[end + 1..];
+        }
+        output.push_str(rest);
+        output
+    }
+}
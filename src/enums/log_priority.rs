@@ -0,0 +1,65 @@
+use std::error::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LogPriority {
+    Unknown = 0,
+    Default = 1,
+    Verbose = 2,
+    Debug = 3,
+    Info = 4,
+    Warn = 5,
+    Error = 6,
+    Fatal = 7,
+    Silent = 8,
+}
+
+impl LogPriority {
+    /// Maps the `android_LogPriority` byte stored in front of the tag.
+    pub fn from_u8(value: u8) -> Self {
+        match value {
+            1 => LogPriority::Default,
+            2 => LogPriority::Verbose,
+            3 => LogPriority::Debug,
+            4 => LogPriority::Info,
+            5 => LogPriority::Warn,
+            6 => LogPriority::Error,
+            7 => LogPriority::Fatal,
+            8 => LogPriority::Silent,
+            _ => LogPriority::Unknown,
+        }
+    }
+
+    /// Parses a logcat priority letter (`V`, `D`, `I`, `W`, `E`, `F`, `S`) or its full name.
+    pub fn from_name(name: &str) -> Result<Self, Box<dyn Error>> {
+        match name.to_ascii_uppercase().as_str() {
+            "V" | "VERBOSE" => Ok(LogPriority::Verbose),
+            "D" | "DEBUG" => Ok(LogPriority::Debug),
+            "I" | "INFO" => Ok(LogPriority::Info),
+            "W" | "WARN" | "WARNING" => Ok(LogPriority::Warn),
+            "E" | "ERROR" => Ok(LogPriority::Error),
+            "F" | "FATAL" | "A" | "ASSERT" => Ok(LogPriority::Fatal),
+            "S" | "SILENT" => Ok(LogPriority::Silent),
+            _ => Err(format!("Unknown log priority: {}", name).into()),
+        }
+    }
+
+    /// The single letter logcat uses for this priority in its text formats.
+    pub fn letter(&self) -> char {
+        match self {
+            LogPriority::Unknown | LogPriority::Default => '?',
+            LogPriority::Verbose => 'V',
+            LogPriority::Debug => 'D',
+            LogPriority::Info => 'I',
+            LogPriority::Warn => 'W',
+            LogPriority::Error => 'E',
+            LogPriority::Fatal => 'F',
+            LogPriority::Silent => 'S',
+        }
+    }
+}
+
+impl std::fmt::Display for LogPriority {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.letter())
+    }
+}
@@ -1,3 +1,19 @@
 pub mod device_transport;
 pub mod push_result;
-pub mod pull_result;
\ No newline at end of file
+pub mod pull_result;
+pub mod intent_extra;
+pub mod test_status;
+pub mod test_event;
+pub mod log_priority;
+pub mod log_buffer;
+pub mod log_format;
+pub mod record_event;
+pub mod crash_kind;
+pub mod shell_packet_id;
+pub mod pty_mode;
+pub mod escape_action;
+pub mod expect_pattern;
+pub mod expect_step;
+pub mod bugreport_event;
+pub mod interrupted;
+pub mod stream_direction;
@@ -0,0 +1,11 @@
+/// Whether `adbr shell` asks the device for a pseudo-terminal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PtyMode {
+    /// Allocate a PTY for interactive sessions when stdin is a terminal (no command given).
+    #[default]
+    Auto,
+    /// `-t`: always allocate a PTY, e.g. to run `top` or `vi` as a command.
+    Force,
+    /// `-T`: never allocate a PTY, so binary output passes through unchanged.
+    Disable,
+}
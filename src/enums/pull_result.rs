@@ -7,4 +7,27 @@ pub enum PullResult {
     Success(f64, u64, Duration, u32),
     SuccessDirectory(f64, u64, Duration, u32),
     FailedAllPull(String),
-}
\ No newline at end of file
+}
+
+impl std::fmt::Display for PullResult {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PullResult::Success(transfer_rate, bytes_transferred, duration, _) => write!(
+                f,
+                "1 file pulled. {:.1} MB/s ({} bytes in {:.3}s)",
+                transfer_rate,
+                bytes_transferred,
+                duration.as_secs_f64()
+            ),
+            PullResult::SuccessDirectory(transfer_rate, bytes_transferred, duration, file_count) => write!(
+                f,
+                "{} files pulled. {:.1} MB/s ({} bytes in {:.3}s)",
+                file_count,
+                transfer_rate,
+                bytes_transferred,
+                duration.as_secs_f64()
+            ),
+            PullResult::FailedAllPull(err_msg) => write!(f, "{}", err_msg)
+        }
+    }
+}
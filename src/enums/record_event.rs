@@ -0,0 +1,10 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub enum RecordEvent {
+    /// Logcat is streaming; `resumed` is set when recording continues a boot seen before.
+    SessionStarted { boot_id: String, resumed: bool },
+    FileOpened(PathBuf),
+    Disconnected { reason: String },
+    WaitingForDevice,
+}
@@ -0,0 +1,35 @@
+/// Packet ids of the shell v2 protocol (`shell,v2,...:` services).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShellPacketId {
+    Stdin,
+    Stdout,
+    Stderr,
+    Exit,
+    CloseStdin,
+    WindowSizeChange,
+}
+
+impl ShellPacketId {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(ShellPacketId::Stdin),
+            1 => Some(ShellPacketId::Stdout),
+            2 => Some(ShellPacketId::Stderr),
+            3 => Some(ShellPacketId::Exit),
+            4 => Some(ShellPacketId::CloseStdin),
+            5 => Some(ShellPacketId::WindowSizeChange),
+            _ => None,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            ShellPacketId::Stdin => 0,
+            ShellPacketId::Stdout => 1,
+            ShellPacketId::Stderr => 2,
+            ShellPacketId::Exit => 3,
+            ShellPacketId::CloseStdin => 4,
+            ShellPacketId::WindowSizeChange => 5,
+        }
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+
+/// What happened on a recorded server connection, seen from the client.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StreamDirection {
+    Connect,
+    /// Bytes written by the client.
+    Send,
+    /// Bytes read from the server.
+    Receive,
+    /// The server closed its side; the client read end of stream.
+    ServerClosed,
+    /// The client shut the connection down or dropped it.
+    ClientClosed,
+}
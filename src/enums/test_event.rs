@@ -0,0 +1,8 @@
+use crate::models::test_case_result::TestCaseResult;
+
+#[derive(Debug, Clone)]
+pub enum TestEvent {
+    Started { class_name: String, test_name: String, current: usize, total: usize },
+    Finished(TestCaseResult),
+    RunFinished { code: Option<i32>, crashed: bool, message: Option<String> },
+}
@@ -0,0 +1,51 @@
+use serde::Serialize;
+
+const STATUS_CODE_START: i32 = 1;
+const STATUS_CODE_OK: i32 = 0;
+const STATUS_CODE_ERROR: i32 = -1;
+const STATUS_CODE_FAILURE: i32 = -2;
+const STATUS_CODE_IGNORED: i32 = -3;
+const STATUS_CODE_ASSUMPTION_FAILURE: i32 = -4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TestStatus {
+    Started,
+    Passed,
+    Failed,
+    Error,
+    Ignored,
+    AssumptionFailure,
+}
+
+impl TestStatus {
+    /// Maps an `INSTRUMENTATION_STATUS_CODE` value to a test status.
+    pub fn from_status_code(code: i32) -> Option<Self> {
+        match code {
+            STATUS_CODE_START => Some(TestStatus::Started),
+            STATUS_CODE_OK => Some(TestStatus::Passed),
+            STATUS_CODE_ERROR => Some(TestStatus::Error),
+            STATUS_CODE_FAILURE => Some(TestStatus::Failed),
+            STATUS_CODE_IGNORED => Some(TestStatus::Ignored),
+            STATUS_CODE_ASSUMPTION_FAILURE => Some(TestStatus::AssumptionFailure),
+            _ => None,
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, TestStatus::Failed | TestStatus::Error)
+    }
+}
+
+impl std::fmt::Display for TestStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TestStatus::Started => write!(f, "STARTED"),
+            TestStatus::Passed => write!(f, "PASSED"),
+            TestStatus::Failed => write!(f, "FAILED"),
+            TestStatus::Error => write!(f, "ERROR"),
+            TestStatus::Ignored => write!(f, "IGNORED"),
+            TestStatus::AssumptionFailure => write!(f, "ASSUMPTION FAILURE"),
+        }
+    }
+}
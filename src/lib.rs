@@ -7,25 +7,94 @@ pub mod utils;
 pub mod models;
 
 pub use self::adb::client::Client;
+pub use self::adb::client_builder::ClientBuilder;
+pub use self::adb::transport::transport::{AdbStream, Transport};
+pub use self::adb::transport::tcp_transport::TcpTransport;
+pub use self::adb::transport::unix_transport::UnixTransport;
+pub use self::adb::transport::memory_transport::{MemoryListener, MemoryTransport};
+#[cfg(feature = "mock")]
+pub use self::adb::mock_server::mock_server::MockServer;
+#[cfg(feature = "mock")]
+pub use self::adb::mock_server::virtual_device::VirtualDevice;
+#[cfg(feature = "mock")]
+pub use self::adb::mock_server::mock_adbd::MockAdbd;
+pub use self::adb::server::adb_server::AdbServer;
+pub use self::adb::wire::device_link::{DeviceLink, IncomingStream};
+pub use self::adb::wire::adb_key::{AdbKey, AdbPublicKey};
+pub use self::adb::wire::auth::{DeviceAuth, HostAuth};
+pub use self::adb::wire::packet::Packet;
+pub use self::adb::recording::session_recorder::SessionRecorder;
+pub use self::adb::recording::recording_transport::RecordingTransport;
+pub use self::adb::recording::replay_server::ReplayServer;
 pub use self::enums::device_transport::DeviceTransport;
 pub use self::enums::pull_result::PullResult;
 pub use self::enums::push_result::PushResult;
+pub use self::enums::intent_extra::IntentExtra;
+pub use self::enums::test_event::TestEvent;
+pub use self::enums::test_status::TestStatus;
+pub use self::enums::log_priority::LogPriority;
+pub use self::enums::log_buffer::LogBuffer;
+pub use self::enums::log_format::LogFormat;
+pub use self::enums::record_event::RecordEvent;
+pub use self::enums::bugreport_event::BugreportEvent;
+pub use self::enums::interrupted::Interrupted;
+pub use self::enums::crash_kind::CrashKind;
+pub use self::enums::shell_packet_id::ShellPacketId;
+pub use self::enums::pty_mode::PtyMode;
+pub use self::enums::escape_action::EscapeAction;
+pub use self::enums::expect_pattern::ExpectPattern;
+pub use self::enums::expect_step::ExpectStep;
+pub use self::enums::stream_direction::StreamDirection;
 
+pub use self::adb::activity_manager;
 pub use self::adb::app_installation;
 pub use self::adb::debugging;
 pub use self::adb::file_transfer;
+pub use self::adb::instrument;
+pub use self::adb::expect;
 pub use self::adb::io;
+#[cfg(feature = "mock")]
+pub use self::adb::mock_server;
 pub use self::adb::network;
 pub use self::adb::protocol;
+pub use self::adb::recording;
 pub use self::adb::scripting;
 pub use self::adb::security;
+pub use self::adb::server;
 pub use self::adb::shell;
+pub use self::adb::transport;
+pub use self::adb::wire;
 
 pub use self::adb::app_installation::{install, uninstall};
 pub use self::adb::file_transfer::{push, pull};
+pub use self::adb::activity_manager::intent_builder::IntentBuilder;
+pub use self::adb::instrument::instrumentation_parser::InstrumentationParser;
+pub use self::adb::debugging::log_recorder::LogRecorder;
+pub use self::adb::debugging::crash_detector::CrashDetector;
+pub use self::adb::shell::shell_session::ShellSession;
+pub use self::adb::expect::expect::ExpectSession;
+pub use self::adb::expect::expect_script::ExpectScript;
 
-pub use self::utils::strip_adb_prefix;
+pub use self::utils::{strip_adb_prefix, shell_quote};
 
 pub use self::models::remote_dir_entry::RemoteDirEntry;
 pub use self::models::remote_metadata::RemoteMetadata;
 pub use self::models::stat_data::StatData;
+pub use self::models::launch_result::LaunchResult;
+pub use self::models::broadcast_result::BroadcastResult;
+pub use self::models::test_case_result::TestCaseResult;
+pub use self::models::instrumentation_report::InstrumentationReport;
+pub use self::models::device_info::DeviceInfo;
+pub use self::models::log_entry::LogEntry;
+pub use self::models::log_filter::LogFilter;
+pub use self::models::boot_session::BootSession;
+pub use self::models::crash_incident::CrashIncident;
+pub use self::models::bugreport_section::BugreportSection;
+pub use self::models::expect_match::ExpectMatch;
+pub use self::models::retry_policy::RetryPolicy;
+pub use self::models::config_profile::ConfigProfile;
+pub use self::models::adbr_config::AdbrConfig;
+pub use self::models::recorded_event::RecordedEvent;
+pub use self::models::session_recording::SessionRecording;
+pub use self::models::device_banner::DeviceBanner;
+pub use self::models::bugreport_summary::{BatterySummary, BugreportSummary, KernelPanic, LowMemoryKill, WakelockUsage};
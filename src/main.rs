@@ -1,14 +1,56 @@
 use std::env::args;
 use std::io;
 use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
-use adbr::Client;
+use adbr::{AdbServer, AdbrConfig, Client, ClientBuilder, HostAuth, ReplayServer, SessionRecording};
 use adbr::DeviceTransport;
-use adbr::constants::{FLAG_SERVER_ADDRESS, FLAG_VERSION, FLAG_EMULATOR, FLAG_SERVER_PORT, UNINSTALL_FLAG_KEEP_DATA, FLAG_SERIAL, FLAG_USB, OPTION_LIST, OPTION_NO_REBIND, OPTION_REMOVE, OPTION_REMOVE_ALL, USER_CONNECT_COMMAND, USER_DEVICES_COMMAND, USER_SHELL_COMMAND, USER_FORWARD_COMMAND, USER_REBOOT_COMMAND, USER_PUSH_COMMAND, USER_PULL_COMMAND, INSTALL_FLAG_SDCARD, INSTALL_FLAG_INTERNAL, USER_INSTALL_COMMAND, INSTALL_FLAG_DOWNGRADE, INSTALL_FLAG_REPLACE, INSTALL_FLAG_GRANT_PERMISSIONS, INSTALL_FLAG_TEST, INSTALL_FLAG_FORWARD_LOCK, USER_SERIALNO_COMMAND, USER_GET_DEVPATH_COMMAND, USER_ROOT_COMMAND, USER_UNROOT_COMMAND, USER_REMOUNT_COMMAND, FLAG_HELP_SHORT, VERSION, PROGRAM_NAME, FLAG_HELP_LONG, REFRESH_INTERVAL_SECS, FLAG_WATCH_DEVICES, USER_DISABLE_VERITY_COMMAND, USER_LOGCAT_COMMAND, USER_BUGREPORT_COMMAND, USER_TCPIP_COMMAND, USER_USB_COMMAND, USER_ENABLE_VERITY_COMMAND, USER_KEYGEN_COMMAND, USER_REVERSE_COMMAND, USER_GET_STATE_COMMAND, DEFAULT_WAIT_STATE, USER_WAIT_FOR_COMMAND, FLAG_TIMEOUT, USER_UNINSTALL_COMMAND};
+use adbr::constants::{FLAG_SERVER_ADDRESS, FLAG_VERSION, FLAG_EMULATOR, FLAG_SERVER_PORT, UNINSTALL_FLAG_KEEP_DATA, FLAG_SERIAL, FLAG_USB, OPTION_LIST, OPTION_NO_REBIND, OPTION_REMOVE, OPTION_REMOVE_ALL, USER_CONNECT_COMMAND, USER_DEVICES_COMMAND, USER_SHELL_COMMAND, USER_FORWARD_COMMAND, USER_REBOOT_COMMAND, USER_PUSH_COMMAND, USER_PULL_COMMAND, INSTALL_FLAG_SDCARD, INSTALL_FLAG_INTERNAL, USER_INSTALL_COMMAND, INSTALL_FLAG_DOWNGRADE, INSTALL_FLAG_REPLACE, INSTALL_FLAG_GRANT_PERMISSIONS, INSTALL_FLAG_TEST, INSTALL_FLAG_FORWARD_LOCK, USER_SERIALNO_COMMAND, USER_GET_DEVPATH_COMMAND, USER_ROOT_COMMAND, USER_UNROOT_COMMAND, USER_REMOUNT_COMMAND, FLAG_HELP_SHORT, VERSION, PROGRAM_NAME, FLAG_HELP_LONG, REFRESH_INTERVAL_SECS, FLAG_WATCH_DEVICES, FLAG_LONG_DEVICES, USER_DISABLE_VERITY_COMMAND, USER_LOGCAT_COMMAND, USER_BUGREPORT_COMMAND, USER_TCPIP_COMMAND, USER_USB_COMMAND, USER_ENABLE_VERITY_COMMAND, USER_KEYGEN_COMMAND, USER_REVERSE_COMMAND, USER_GET_STATE_COMMAND, DEFAULT_WAIT_STATE, USER_WAIT_FOR_COMMAND, FLAG_TIMEOUT, USER_UNINSTALL_COMMAND, USER_AM_COMMAND, AM_START, AM_BROADCAST, AM_START_SERVICE, AM_FORCE_STOP, USER_INSTRUMENT_COMMAND, OPTION_JUNIT, OPTION_JSON, USER_TEST_COMMAND, OPTION_SHARDS, OPTION_RETRIES, DEFAULT_TEST_RETRIES, FLAG_ALL_DEVICES, FLAG_MATCH_DEVICES, FLAG_TRANSPORT_ID, USER_FEATURES_COMMAND, OPTION_FORMAT, OPTION_PACKAGE, OPTION_GREP, OPTION_EXCLUDE_TAG, OPTION_MIN_LEVEL, OPTION_SINCE, PACKAGE_PID_REFRESH_SECS, OPTION_RECORD, OPTION_ROTATE_SIZE, OPTION_ROTATE_TIME, DEFAULT_RECORD_FILE_SIZE, DEFAULT_RECORD_FILE_AGE_SECS, USER_CRASHES_COMMAND, OPTION_OUT, OPTION_NO_PULL, DEFAULT_CRASH_ARTIFACT_DIR, LOGCAT_MERGE_WINDOW_MS, OPTION_EXTRACT, USER_BUGREPORT_SUMMARY_COMMAND, FLAG_FORCE_PTY, FLAG_DISABLE_PTY, FLAG_ESCAPE_CHAR, DEFAULT_ESCAPE_CHAR, USER_EXPECT_COMMAND, FLAG_VERBOSE, FLAG_VERY_VERBOSE, FLAG_LOG_LEVEL, ADBR_TRACE_ENV, FRAME_TRACE_TARGET, FLAG_DEADLINE, FLAG_IO_TIMEOUT, DEFAULT_IO_TIMEOUT_SECS, FLAG_PROFILE, ADBR_PROFILE_ENV, FLAG_SERVER_SOCKET, ADBR_RECORD_ENV, ADBR_FAN_OUT_CHILD_ENV, USER_REPLAY_COMMAND, USER_REDACT_SERIALS_COMMAND, OPTION_PORT, OPTION_REALTIME, OPTION_SERIAL, USER_SERVER_COMMAND, USER_KILL_SERVER_COMMAND, USER_DISCONNECT_COMMAND};
+use adbr::{BugreportEvent, BugreportSummary, CrashIncident, ExpectScript, DeviceInfo, PtyMode, IntentBuilder, IntentExtra, InstrumentationReport, LogFilter, LogFormat, LogPriority, LogRecorder, RecordEvent, TestEvent, TestStatus};
+use adbr::utils::{parse_byte_size, parse_duration};
+use adbr::shell::shell_escape::parse_escape_char;
+use futures::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
+use tracing_subscriber::filter::{EnvFilter, ParseError};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::Layer;
 use adbr::PushResult;
 use adbr::PullResult;
 
+const INITIAL_PROGRESS_BAR_LENGTH: u64 = 100;
+const BUGREPORT_SPINNER_TICK_MS: u64 = 100;
+// Time a cancelled command gets to clean up (e.g. remove a partly pulled file) after Ctrl-C.
+const CANCEL_GRACE_PERIOD_MS: u64 = 500;
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+const REPLAY_POLL_INTERVAL_MS: u64 = 100;
+const LOGCAT_SERIAL_PREFIX_LEN: usize = 6;
+const PM_FAILURE_PREFIX: &str = "Failure";
+const DEVICE_PREFIX_COLORS: [&str; 6] = ["\x1b[1;34m", "\x1b[1;35m", "\x1b[1;36m", "\x1b[1;32m", "\x1b[1;33m", "\x1b[1;31m"];
+
+static COMMAND_FAILED: AtomicBool = AtomicBool::new(false);
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+macro_rules! report_error {
+    ($($arg:tt)*) => {{
+        eprintln!($($arg)*);
+        COMMAND_FAILED.store(true, Ordering::SeqCst);
+    }};
+}
+
+/// Prints a device command's failure; the exit status only reflects it in a fan-out child, whose
+/// parent summarises every device from the exit statuses.
+macro_rules! report_device_error {
+    ($($arg:tt)*) => {{
+        eprintln!($($arg)*);
+        mark_device_failure();
+    }};
+}
+
 #[tokio::main]
 async fn main() {
     let args: Vec<String> = args().collect();
@@ -25,25 +67,54 @@ async fn main() {
     }
 
     handle_commands(args).await;
+
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        std::process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    if COMMAND_FAILED.load(Ordering::SeqCst) {
+        std::process::exit(1);
+    }
 }
 
 fn print_usage() {
     println!("Usage: {} [options] <command> [command args]", PROGRAM_NAME);
     println!();
     println!("global options:");
-    println!("  -s <serial>     Use device with given serial number");
+    println!("  -s <serial>     Use device with given serial number (overrides $ANDROID_SERIAL)");
     println!("  -d              Use USB device (error if multiple devices connected)");
     println!("  -e              Use TCP/IP device (error if multiple TCP/IP devices available)");
+    println!("  -t <id>         Use device with given transport id");
     println!("  -H <host>       Name of adb server host [default=localhost]");
     println!("  -P <port>       Port of adb server [default=5037]");
+    println!("  -L <socket>     Socket of adb server: tcp:[HOST:]PORT, localfilesystem:PATH or localabstract:NAME");
+    println!("  --all           Run the command on every ready device");
+    println!("  --match <KEY>=<VALUE>");
+    println!("                  Run the command on every device matching the selector (repeatable)");
+    println!("                  KEY: serial | state | model | product | device | transport | transport_id");
+    println!("                  logcat output from several devices is merged into one view ordered by timestamp");
+    println!("  -v, -vv         Log protocol requests to stderr (-vv also dumps raw protocol frames)");
+    println!("  --log-level <FILTER>");
+    println!("                  Log filter such as debug or adbr::frames=trace (default: warn)");
+    println!("  --timeout <DURATION>");
+    println!("                  Fail the command if it has not finished within DURATION (e.g. 30s, 5m)");
+    println!("  --io-timeout <DURATION>");
+    println!("                  Fail when the server or device stops responding for DURATION [default={}s, 0=never]", DEFAULT_IO_TIMEOUT_SECS);
+    println!("                  Streaming output such as logcat or shell is only bound by --timeout");
+    println!("  --profile <NAME>");
+    println!("                  Use a profile from ~/.config/adbr/config.toml (overrides $ADBR_PROFILE)");
+    println!("                  Settings come from the profile, then environment variables, then these options");
     println!();
     println!("general commands:");
-    println!("  devices [-w]    List connected devices");
+    println!("  devices [-l] [-w] List connected devices (-l for long output)");
     println!("                  -w: continuously monitors devices, refreshing every {} seconds", REFRESH_INTERVAL_SECS);
     println!("  --version   Print the version of the adbr client");
 
     println!();
     println!("networking:");
+    println!("  connect HOST[:PORT]");
+    println!("                  Connect to a device via TCP/IP [default port=5555]");
+    println!("  disconnect [HOST[:PORT]]");
+    println!("                  Disconnect from the given TCP/IP device, or all of them");
     println!("  forward         Forward socket connections");
     println!("    forward --list");
     println!("      List all forward socket connections");
@@ -74,7 +145,12 @@ fn print_usage() {
     println!("     -a: preserve file timestamp and mode");
     println!();
     println!("shell:");
-    println!("  shell [<cmd>]   Run remote shell command (interactive shell if no command given)");
+    println!("  shell [-t|-T] [-e ESCAPE] [<cmd>]");
+    println!("                  Run remote shell command (interactive shell if no command given)");
+    println!("                  -t: allocate a PTY even with a command (e.g. top, vi)");
+    println!("                  -T: disable PTY allocation");
+    println!("                  -e: escape character at the start of a line (default: ~, ^X for Ctrl-X, none to disable)");
+    println!("                      ~. disconnect, ~^Z suspend, ~~ literal ~, ~> push/pull prompt, ~? help");
     println!();
     println!("app installation:");
     println!("  install [<flags>] <file>");
@@ -94,6 +170,35 @@ fn print_usage() {
     println!("    Note: -s and -f flags are mutually exclusive");
     println!("          -d and -r flags may not work together on some Android versions");
     println!();
+    println!("activity manager:");
+    println!("  am start [-W] <INTENT>");
+    println!("    Start an activity; -W waits for launch and prints ThisTime/TotalTime/WaitTime");
+    println!("  am broadcast <INTENT>");
+    println!("    Send a broadcast intent");
+    println!("  am startservice <INTENT>");
+    println!("    Start a service");
+    println!("  am force-stop PACKAGE");
+    println!("    Force stop everything associated with PACKAGE");
+    println!("    intent:");
+    println!("      -a <ACTION> -n <COMPONENT> -d <DATA_URI> -t <MIME_TYPE> -c <CATEGORY> -f <FLAGS> --user <USER>");
+    println!("      --es|--eu <KEY> <VALUE>   string / uri extra");
+    println!("      --esn <KEY>               null string extra");
+    println!("      --ei|--el|--ef <KEY> <N>  int / long / float extra");
+    println!("      --ez <KEY> true|false     boolean extra");
+    println!("      --esa|--eia|--ela|--efa <KEY> <V1>[,<V2>...]  array extras");
+    println!();
+    println!("testing:");
+    println!("  instrument [-e <KEY> <VALUE>]... [--junit FILE] [--json FILE] RUNNER");
+    println!("    Run instrumentation tests (am instrument -w -r) with live progress");
+    println!("    RUNNER: <test package>/<runner class>");
+    println!("    --junit FILE: write a JUnit XML report");
+    println!("    --json FILE:  write a JSON report");
+    println!("    Exits with a non-zero status when tests fail or the instrumentation crashes");
+    println!("  test [--shards N] [--retries N] [-e <KEY> <VALUE>]... [--junit FILE] [--json FILE] RUNNER");
+    println!("    Shard instrumentation across all ready devices and merge the results");
+    println!("    --shards N:  number of shards (default: number of ready devices)");
+    println!("    --retries N: retry failed tests on another device up to N times (default: {})", DEFAULT_TEST_RETRIES);
+    println!();
     println!("debugging:");
     println!("  logcat [<options>] [<filterspecs>]");
     println!("    View device log");
@@ -107,11 +212,35 @@ fn print_usage() {
     println!("      -d            Dump the log and then exit (don't block)");
     println!("      -t <count>    Print only the most recent <count> lines (implies -d)");
     println!("      -T <time>     Print most recent lines since specified time (implies -d)");
+    println!("      --format <FORMAT>");
+    println!("                    Decode the binary log on the client and print it as:");
+    println!("                    threadtime, color (falls back to threadtime when not a TTY), json,");
+    println!("                    or template=<TEMPLATE> with {{time}} {{date}} {{epoch}} {{pid}} {{tid}} {{uid}}");
+    println!("                    {{buffer}} {{priority}} {{tag}} {{msg}}");
+    println!("      --package <PACKAGE>   Only show the package's processes, following restarts");
+    println!("      --grep <REGEX>        Only show messages matching REGEX");
+    println!("      --exclude-tag <TAG>   Hide entries with TAG (repeatable, comma separated)");
+    println!("      --min-level <LEVEL>   Hide entries below LEVEL (V, D, I, W, E, F)");
+    println!("      --since <TIME>        Hide entries older than TIME ('YYYY-MM-DD HH:MM:SS', epoch, or 10m/2h/1d)");
+    println!("                    Client-side filters imply --format color unless another format is given");
+    println!("      --record <DIR>        Record to rotating files in DIR, reconnecting across reboots and");
+    println!("                            disconnects; DIR/index.json lists the boot sessions and their files");
+    println!("      --rotate-size <SIZE>  Start a new file after SIZE (default: 16M)");
+    println!("      --rotate-time <AGE>   Start a new file after AGE (default: 1h)");
     println!("    filterspecs:");
     println!("      <tag>[:priority]");
-    println!("  bugreport [PATH]");
+    println!("  crashes [--out DIR] [--no-pull]");
+    println!("    Watch the log for Java crashes, native crashes and ANRs and print one JSON summary per incident");
+    println!("    Matching tombstones and ANR traces are pulled into DIR (default: {})", DEFAULT_CRASH_ARTIFACT_DIR);
+    println!("  bugreport [--extract] [PATH]");
     println!("    Generate a bug report and save to PATH (default: bugreport.zip)");
     println!("    The report includes system logs, stack traces, and other diagnostic information");
+    println!("    Devices without bugreportz (before Android 7.0) produce a plain-text report instead");
+    println!("      --extract   Unzip the report next to PATH and split its dumpsys, logcat and ANR");
+    println!("                  sections into separate files listed in index.json");
+    println!("  bugreport-summary [--json] FILE");
+    println!("    Summarize a bugreport zip (or plain-text report) without a device: build, uptime, battery,");
+    println!("    ANRs, crashes, top wakelocks, low-memory kills and kernel panics");
     println!("");
     println!("security:");
     println!("  disable-verity    Disable dm-verity checking on userdebug builds");
@@ -127,6 +256,25 @@ fn print_usage() {
     println!("    -t TIMEOUT: Maximum time in seconds to wait for the device state");
     println!("  get-state");
     println!("    Prints the current state of the connected device");
+    println!("  features");
+    println!("    Lists the features supported by both the device and the adb server");
+    println!("  expect SCRIPT");
+    println!("    Drive a shell command with an expect script, printing its output as it arrives");
+    println!("    Statements, one per line (# starts a comment):");
+    println!("      spawn [-t] COMMAND        Command to drive (default: sh), -t on a PTY; must come first");
+    println!("      send TEXT / sendline TEXT Send TEXT (sendline adds a newline); $1..$9 insert groups");
+    println!("                                captured by the last expect");
+    println!("      expect [-t SECS] PATTERN  Wait for \"literal\", /regex/ or eof; fails the script on timeout");
+    println!("      timeout SECS              Default expect timeout (default: 10)");
+    println!("      sleep SECS");
+    println!("    Exits with the command's status, or 1 when an expect fails");
+    println!("  replay [--port PORT] [--realtime] FILE");
+    println!("    Serve a session recorded with ADBR_RECORD on 127.0.0.1:PORT (default: a free port, printed)");
+    println!("    Exits once every recorded connection was replayed, with 1 when the client strayed from it");
+    println!("    --realtime: reply with the recorded delays instead of as fast as possible");
+    println!("  redact-serials [--serial SERIAL]... FILE [OUT]");
+    println!("    Replace device serials in a recording with placeholders of the same length (in place");
+    println!("    unless OUT is given); --serial adds serials the recording does not reveal on its own");
     println!("  reboot [bootloader|recovery|sideload|sideload-auto-reboot]");
     println!("    Reboot the device; defaults to booting system image but");
     println!("    supports bootloader and recovery too. sideload reboots");
@@ -138,8 +286,19 @@ fn print_usage() {
     println!("  usb               Restart adb server listening on USB");
     println!("  tcpip PORT        Restart adb server listening on TCP on PORT");
     println!();
+    println!("server:");
+    println!("  server            Run adbr's own ADB server in the foreground on -P PORT [default=5037]");
+    println!("                    Serves devices reached over TCP/IP: connect targets and local emulators");
+    println!("  kill-server       Stop the ADB server (Ctrl-C also stops a foreground server)");
+    println!();
     println!("environment variables:");
     println!("  ADB_ADDRESS       IP:PORT of ADB server (default: 127.0.0.1:5037)");
+    println!("  ADB_SERVER_SOCKET Socket of ADB server, as for -L (overrides ADB_ADDRESS)");
+    println!("  ANDROID_SERIAL    Serial number of the device to use when -s/-d/-e/-t are not given");
+    println!("  ADBR_PROFILE      Config profile to use when --profile is not given");
+    println!("  ADBR_CONFIG       Path of the config file (default: ~/.config/adbr/config.toml)");
+    println!("  ADBR_TRACE        Set to 1 to dump raw protocol frames to stderr");
+    println!("  ADBR_RECORD       Record every byte exchanged with the server to this file (see replay)");
     println!();
     println!("Examples:");
     println!("  {} devices", PROGRAM_NAME);
@@ -150,10 +309,23 @@ fn print_usage() {
     println!("  {} keygen ~/.android/my_custom_adbkey", PROGRAM_NAME);
     println!("  {} bugreport", PROGRAM_NAME);
     println!("  {} bugreport /path/to/save/bugreport.zip", PROGRAM_NAME);
+    println!("  {} bugreport --extract ./reports", PROGRAM_NAME);
+    println!("  {} bugreport-summary --json bugreport.zip", PROGRAM_NAME);
     println!("  {} logcat", PROGRAM_NAME);
     println!("  {} logcat *:E", PROGRAM_NAME);
     println!("  {} logcat -c", PROGRAM_NAME);
     println!("  {} logcat -v time ActivityManager:I *:S", PROGRAM_NAME);
+    println!("  {} logcat --format json -b all", PROGRAM_NAME);
+    println!("  {} logcat --format 'template={{time}} {{tag}}: {{msg}}'", PROGRAM_NAME);
+    println!("  {} logcat --package com.example --min-level W --since 10m", PROGRAM_NAME);
+    println!("  {} logcat --record ./logs --rotate-size 64M -b all", PROGRAM_NAME);
+    println!("  {} crashes --out ./crashes > incidents.jsonl", PROGRAM_NAME);
+    println!("  {} --match model=Pixel_7 logcat --min-level W   # merged by timestamp", PROGRAM_NAME);
+    println!("  {} am start -W -n com.example/.MainActivity", PROGRAM_NAME);
+    println!("  {} am broadcast -a com.example.ACTION --es name value --ei count 3", PROGRAM_NAME);
+    println!("  {} am force-stop com.example", PROGRAM_NAME);
+    println!("  {} instrument --junit report.xml com.example.test/androidx.test.runner.AndroidJUnitRunner", PROGRAM_NAME);
+    println!("  {} test --shards 8 --junit report.xml com.example.test/androidx.test.runner.AndroidJUnitRunner", PROGRAM_NAME);
     println!("  {} reboot", PROGRAM_NAME);
     println!("  {} remount", PROGRAM_NAME);
     println!("  {} root", PROGRAM_NAME);
@@ -179,9 +351,18 @@ fn print_usage() {
 }
 
 async fn handle_commands(mut args: Vec<String>) {
-    let mut device_type = DeviceTransport::default();
+    let mut device_type = None;
+    let mut profile = None;
     let mut server_address = None;
     let mut server_port = None;
+    let mut server_socket = None;
+    let mut fan_out = false;
+    let mut selectors: Vec<(String, String)> = Vec::new();
+    let mut verbosity = 0;
+    let mut log_level = None;
+    let mut forwarded_flags = Vec::new();
+    let mut deadline = None;
+    let mut io_timeout = None;
     let mut i = 1;
     while i < args.len() {
         match args[i].as_str() {
@@ -191,7 +372,7 @@ async fn handle_commands(mut args: Vec<String>) {
                     return;
                 }
                 server_address = Some(args[i + 1].clone());
-                args.drain(i..=i + 1);
+                forwarded_flags.extend(args.drain(i..=i + 1));
                 i = 1;
                 continue;
             }
@@ -207,7 +388,17 @@ async fn handle_commands(mut args: Vec<String>) {
                         std::process::exit(1);
                     }
                 }
-                args.drain(i..=i + 1);
+                forwarded_flags.extend(args.drain(i..=i + 1));
+                i = 1;
+                continue;
+            }
+            FLAG_SERVER_SOCKET => {
+                if i + 1 >= args.len() {
+                    report_error!("Invalid command: missing socket after {}", FLAG_SERVER_SOCKET);
+                    return;
+                }
+                server_socket = Some(args[i + 1].clone());
+                forwarded_flags.extend(args.drain(i..=i + 1));
                 i = 1;
                 continue;
             }
@@ -217,51 +408,220 @@ async fn handle_commands(mut args: Vec<String>) {
                     return;
                 }
                 let serial = args[i + 1].clone();
-                device_type = DeviceTransport::usb(serial);
+                device_type = Some(DeviceTransport::serial(serial));
                 args.drain(i..=i + 1);
                 i = 1;
                 continue;
             }
             FLAG_USB => {
-                device_type = DeviceTransport::default_usb();
+                device_type = Some(DeviceTransport::default_usb());
                 args.remove(i);
                 i = 1;
                 continue;
             }
             FLAG_EMULATOR => {
-                device_type = DeviceTransport::default_emulator();
+                device_type = Some(DeviceTransport::default_emulator());
+                args.remove(i);
+                i = 1;
+                continue;
+            }
+            FLAG_TRANSPORT_ID => {
+                if i + 1 >= args.len() {
+                    report_error!("Invalid command: missing transport id after {}", FLAG_TRANSPORT_ID);
+                    return;
+                }
+                match args[i + 1].parse::<u64>() {
+                    Ok(transport_id) => device_type = Some(DeviceTransport::transport_id(transport_id)),
+                    Err(_) => {
+                        report_error!("Invalid transport id: {}", args[i + 1]);
+                        return;
+                    }
+                }
+                args.drain(i..=i + 1);
+                i = 1;
+                continue;
+            }
+            FLAG_ALL_DEVICES => {
+                fan_out = true;
                 args.remove(i);
                 i = 1;
                 continue;
             }
-            _ => i += 1,
+            FLAG_MATCH_DEVICES => {
+                match args.get(i + 1).and_then(|selector| selector.split_once('=')) {
+                    Some((key, value)) => selectors.push((key.to_string(), value.to_string())),
+                    None => {
+                        report_error!("Invalid command: expected <KEY>=<VALUE> after {}", FLAG_MATCH_DEVICES);
+                        return;
+                    }
+                }
+                fan_out = true;
+                args.drain(i..=i + 1);
+                i = 1;
+                continue;
+            }
+            FLAG_VERBOSE | FLAG_VERY_VERBOSE => {
+                verbosity += if args[i] == FLAG_VERBOSE { 1 } else { 2 };
+                forwarded_flags.push(args.remove(i));
+                i = 1;
+                continue;
+            }
+            FLAG_LOG_LEVEL => {
+                if i + 1 >= args.len() {
+                    report_error!("Invalid command: missing level after {}", FLAG_LOG_LEVEL);
+                    return;
+                }
+                log_level = Some(args[i + 1].clone());
+                forwarded_flags.extend(args.drain(i..=i + 1));
+                i = 1;
+                continue;
+            }
+            FLAG_PROFILE => {
+                if i + 1 >= args.len() {
+                    report_error!("Invalid command: missing profile name after {}", FLAG_PROFILE);
+                    return;
+                }
+                profile = Some(args[i + 1].clone());
+                forwarded_flags.extend(args.drain(i..=i + 1));
+                i = 1;
+                continue;
+            }
+            FLAG_DEADLINE | FLAG_IO_TIMEOUT => {
+                let Some(value) = args.get(i + 1) else {
+                    report_error!("Invalid command: missing duration after {}", args[i]);
+                    return;
+                };
+                match parse_duration(value) {
+                    Ok(duration) if args[i] == FLAG_DEADLINE => deadline = Some(Instant::now() + duration),
+                    Ok(duration) => io_timeout = Some(duration),
+                    Err(err) => {
+                        report_error!("{}", err);
+                        return;
+                    }
+                }
+                forwarded_flags.extend(args.drain(i..=i + 1));
+                i = 1;
+                continue;
+            }
+            // Global options come before the command; later arguments such as `shell -e none` belong to it.
+            _ => break,
+        }
+    }
+
+    if let Err(err) = init_logging(verbosity, log_level.as_deref()) {
+        report_error!("Invalid log level: {}", err);
+        return;
+    }
+
+    if args.len() < 2 {
+        report_error!("Invalid command: no command given");
+        return;
+    }
+
+    if server_socket.is_some() && (server_address.is_some() || server_port.is_some()) {
+        report_error!("Invalid command: {} cannot be combined with {} or {}", FLAG_SERVER_SOCKET, FLAG_SERVER_ADDRESS, FLAG_SERVER_PORT);
+        return;
+    }
+    let server = match client_builder(profile.as_deref(), server_address, server_port, server_socket, device_type, io_timeout) {
+        Ok(server) => server,
+        Err(err) => {
+            report_error!("{}", err);
+            return;
         }
+    };
+
+    let span = tracing::info_span!("command", name = %args[1], device = server.default_device().get_device_transport());
+    run_command(&args[1..], &server, fan_out, &selectors, &forwarded_flags, deadline).instrument(span).await;
+}
+
+/// Layers the connection settings: defaults, then the config profile, then environment variables,
+/// then command-line flags.
+fn client_builder(profile: Option<&str>, server_address: Option<String>, server_port: Option<u16>, server_socket: Option<String>, device: Option<DeviceTransport>, io_timeout: Option<Duration>) -> Result<ClientBuilder, Box<dyn std::error::Error>> {
+    let profile = profile.map(str::to_string).or_else(|| std::env::var(ADBR_PROFILE_ENV).ok().filter(|name| !name.is_empty()));
+    let config = AdbrConfig::load_default()?;
+    let mut builder = ClientBuilder::new();
+    if let Some(profile) = config.profile(profile.as_deref())? {
+        builder = builder.profile(profile)?;
+    }
+    builder = builder.env()?;
+
+    if let Some(server_address) = server_address {
+        builder = builder.server_address(&server_address);
+    }
+    if let Some(server_port) = server_port {
+        builder = builder.server_port(server_port);
+    }
+    if let Some(server_socket) = server_socket {
+        builder = builder.server_socket(&server_socket)?;
+    }
+    if let Some(device) = device {
+        builder = builder.device(device);
+    }
+    if let Some(io_timeout) = io_timeout {
+        builder = builder.io_timeout(Some(io_timeout).filter(|timeout| !timeout.is_zero()));
+    }
+    Ok(builder)
+}
+
+async fn run_command(command_line: &[String], server: &ClientBuilder, fan_out: bool, selectors: &[(String, String)], forwarded_flags: &[String], deadline: Option<Instant>) {
+    let command = command_line[0].clone();
+    let command_args = command_line[1..].to_vec();
+
+    // Works on files attached to tickets, so no server or device is needed.
+    if command == USER_BUGREPORT_SUMMARY_COMMAND {
+        summarize_bugreport(&command_args);
+        return;
+    }
+    if command == USER_REPLAY_COMMAND {
+        replay_recording(&command_args).await;
+        return;
+    }
+    if command == USER_REDACT_SERIALS_COMMAND {
+        redact_recording(&command_args);
+        return;
+    }
+    if command == USER_SERVER_COMMAND {
+        run_server(server).await;
+        return;
     }
 
-    let command = args[1].clone();
-    let command_args = args[2..].to_vec();
+    if fan_out {
+        run_on_matching_devices(command_line, server, selectors, forwarded_flags).await;
+        return;
+    }
 
-    let mut client = match Client::new(server_address.clone(), server_port).await {
+    let device_type = server.default_device();
+    let mut client = match server.clone().build().await {
         Ok(client) => client,
         Err(err) => {
             eprintln!("{}", err);
             std::process::exit(1);
         }
     };
+    client.set_deadline(deadline);
+    // The interactive shell forwards Ctrl-C to the device itself.
+    if command != USER_SHELL_COMMAND {
+        client.set_cancellation_token(Some(cancel_on_ctrl_c()));
+    }
 
     match command.as_str() {
         devices_cmd if devices_cmd.starts_with(USER_DEVICES_COMMAND) => {
             let mut watch_flag = false;
+            let mut long_flag = false;
             let mut devices_args = command_args.clone();
 
-            if !devices_args.is_empty() && devices_args[0] == FLAG_WATCH_DEVICES {
-                watch_flag = true;
+            while !devices_args.is_empty() && (devices_args[0] == FLAG_WATCH_DEVICES || devices_args[0] == FLAG_LONG_DEVICES) {
+                if devices_args[0] == FLAG_WATCH_DEVICES {
+                    watch_flag = true;
+                } else {
+                    long_flag = true;
+                }
                 devices_args.remove(0);
             }
 
             if watch_flag {
                 loop {
-                    let mut client = match Client::new(None, None).await {
+                    let mut client = match server.clone().build().await {
                         Ok(client) => client,
                         Err(err) => {
                             eprintln!("Error connecting to server: {}", err);
@@ -269,7 +629,8 @@ async fn handle_commands(mut args: Vec<String>) {
                         }
                     };
 
-                    match client.adb_devices().await {
+                    let devices = if long_flag { client.adb_devices_long().await } else { client.adb_devices().await };
+                    match devices {
                         Ok(result) => {
                             print!("\x1B[2J\x1B[H");
                             println!("{}", result);
@@ -285,7 +646,8 @@ async fn handle_commands(mut args: Vec<String>) {
                     tokio::time::sleep(Duration::from_secs(REFRESH_INTERVAL_SECS)).await;
                 }
             } else {
-                match client.adb_devices().await {
+                let devices = if long_flag { client.adb_devices_long().await } else { client.adb_devices().await };
+                match devices {
                     Ok(result) => {
                         println!("{}", result);
                     }
@@ -295,17 +657,64 @@ async fn handle_commands(mut args: Vec<String>) {
                 }
             }
         }
-        connect_cmd if connect_cmd.starts_with(USER_CONNECT_COMMAND) => {
-            println!("Please set the ADB server address by exporting the ADB_ADDRESS environment variable before running the ADB client.");
+        USER_CONNECT_COMMAND => {
+            let [address] = command_args.as_slice() else {
+                report_error!("Usage: {} {} HOST[:PORT]", PROGRAM_NAME, USER_CONNECT_COMMAND);
+                return;
+            };
+            match client.adb_connect(address).await {
+                Ok(message) => println!("{}", message),
+                Err(err) => report_error!("{}", err),
+            }
+        }
+        USER_DISCONNECT_COMMAND => {
+            let address = command_args.first().map_or("", String::as_str);
+            match client.adb_disconnect(address).await {
+                Ok(message) => println!("{}", message),
+                Err(err) => report_error!("{}", err),
+            }
+        }
+        USER_KILL_SERVER_COMMAND => {
+            if let Err(err) = client.adb_kill_server().await {
+                report_error!("{}", err);
+            }
         }
         shell_cmd if shell_cmd.starts_with(USER_SHELL_COMMAND) => {
-            let shell_args = if !command_args.is_empty() {
-                command_args.join(" ")
-            } else {
-                String::new()
-            };
-            if let Err(err) = client.adb_shell(device_type, &shell_args).await {
-                eprintln!("{}", err);
+            let mut pty_mode = PtyMode::Auto;
+            let mut escape_char = Some(DEFAULT_ESCAPE_CHAR);
+            let mut shell_args = command_args.as_slice();
+            while let Some(flag) = shell_args.first() {
+                match flag.as_str() {
+                    FLAG_FORCE_PTY => pty_mode = PtyMode::Force,
+                    FLAG_DISABLE_PTY => pty_mode = PtyMode::Disable,
+                    FLAG_ESCAPE_CHAR => {
+                        let Some(value) = shell_args.get(1) else {
+                            report_error!("Invalid command: missing escape character after {}", FLAG_ESCAPE_CHAR);
+                            return;
+                        };
+                        match parse_escape_char(value) {
+                            Ok(value) => escape_char = value,
+                            Err(err) => {
+                                report_error!("{}", err);
+                                return;
+                            }
+                        }
+                        shell_args = &shell_args[1..];
+                    }
+                    _ => break,
+                }
+                shell_args = &shell_args[1..];
+            }
+            let shell_command = shell_args.join(" ");
+
+            if shell_command.is_empty() || pty_mode != PtyMode::Auto {
+                match client.adb_interactive_shell(device_type, &shell_command, pty_mode, escape_char).await {
+                    Ok(Some(status)) if status != 0 => std::process::exit(status as i32),
+                    Ok(_) => {}
+                    Err(err) => report_error!("{}", err),
+                }
+            } else if let Err(err) = client.adb_shell_to_writer(device_type, &shell_command, &mut tokio::io::stdout()).await {
+                report_error!("{}", err);
             }
         }
         forward_command if forward_command.starts_with(USER_FORWARD_COMMAND) => {
@@ -562,6 +971,9 @@ async fn handle_commands(mut args: Vec<String>) {
                         0.0
                     };
 
+                    if total_files_failed > 0 {
+                        mark_device_failure();
+                    }
                     println!("{} file{} pushed. {} file{} skipped. {} file{} failed.",
                              total_files_pushed,
                              if total_files_pushed == 1 { "" } else { "s" },
@@ -574,7 +986,10 @@ async fn handle_commands(mut args: Vec<String>) {
                              total_bytes_transferred,
                              total_duration.as_secs_f64());
                 }
-                Err(err) => println!("{}", err),
+                Err(err) => {
+                    println!("{}", err);
+                    mark_device_failure();
+                }
             }
         }
         pull_command if pull_command.starts_with(USER_PULL_COMMAND) => {
@@ -637,6 +1052,9 @@ async fn handle_commands(mut args: Vec<String>) {
                         0.0
                     };
 
+                    if total_files_failed > 0 {
+                        mark_device_failure();
+                    }
                     println!("{} file{} pulled. {} file{} failed.",
                              total_files_pulled,
                              if total_files_pulled == 1 { "" } else { "s" },
@@ -647,7 +1065,10 @@ async fn handle_commands(mut args: Vec<String>) {
                              total_bytes_transferred,
                              total_duration.as_secs_f64());
                 }
-                Err(err) => println!("{}", err),
+                Err(err) => {
+                    println!("{}", err);
+                    mark_device_failure();
+                }
             }
         }
         USER_DISABLE_VERITY_COMMAND => {
@@ -698,15 +1119,223 @@ async fn handle_commands(mut args: Vec<String>) {
             }
         }
         bugreport_cmd if bugreport_cmd == USER_BUGREPORT_COMMAND => {
-            let path = command_args.get(0).map(|s| s.as_str());
-            if let Err(err) = client.adb_bugreport(device_type, path).await {
-                eprintln!("{}", err);
+            let extract = command_args.iter().any(|arg| arg == OPTION_EXTRACT);
+            let paths: Vec<&String> = command_args.iter().filter(|arg| *arg != OPTION_EXTRACT).collect();
+            if paths.len() > 1 {
+                report_error!("Error: bugreport accepts at most one path");
+                return;
+            }
+
+            let mut progress_bar: Option<ProgressBar> = None;
+            let result = client.adb_bugreport(device_type, paths.first().map(|s| s.as_str()), |event| match event {
+                BugreportEvent::PlainTextFallback => println!("bugreportz is not available on this device (requires Android 7.0 or later), trying a plain-text bugreport instead."),
+                BugreportEvent::Generating { progress } => {
+                    println!("Generating bugreport. This may take a while...");
+                    progress_bar = Some(bugreport_progress_bar(*progress));
+                }
+                BugreportEvent::Progress { done, total } => {
+                    if let Some(pb) = &progress_bar {
+                        if *total > 0 {
+                            pb.set_length(*total);
+                        }
+                        pb.set_position((*done).min(pb.length().unwrap_or(*done)));
+                    }
+                }
+                BugreportEvent::Message(line) => match &progress_bar {
+                    Some(pb) => pb.suspend(|| println!("Info: {}", line)),
+                    None => println!("Info: {}", line),
+                },
+                BugreportEvent::Pulling => {
+                    if let Some(pb) = progress_bar.take() {
+                        pb.finish_with_message("Bugreport generated");
+                    }
+                    println!("\nPulling bugreport file...");
+                }
+            }).await;
+            if let Some(pb) = progress_bar {
+                pb.finish_and_clear();
+            }
+            let saved = match result {
+                Ok(saved) => saved,
+                Err(err) => {
+                    report_error!("{}", err);
+                    return;
+                }
+            };
+            println!("Bugreport saved to: {}", saved.display());
+            if !extract {
+                return;
+            }
+            if saved.extension().is_none_or(|extension| extension != "zip") {
+                report_error!("Error: {} is a plain-text bugreport and cannot be extracted", saved.display());
+                return;
+            }
+
+            let out_dir = saved.with_extension("");
+            match adbr::debugging::bugreport::extract_bugreport(&saved, &out_dir) {
+                Ok(index) => println!(
+                    "Extracted {} sections and {} ANR traces to {}",
+                    index.sections.iter().filter(|section| section.file.is_some()).count(),
+                    index.anr_files.len(),
+                    out_dir.display()
+                ),
+                Err(err) => report_error!("Error extracting bugreport: {}", err),
             }
         }
         logcat_cmd if logcat_cmd == USER_LOGCAT_COMMAND => {
-            let logcat_args = command_args.join(" ");
-            if let Err(err) = client.adb_logcat(device_type, &logcat_args).await {
-                eprintln!("{}", err);
+            let options = match parse_logcat_options(&command_args) {
+                Ok(options) => options,
+                Err(err) => {
+                    report_error!("{}", err);
+                    return;
+                }
+            };
+
+            let logcat_args = options.logcat_args.join(" ");
+            let result = match (&options.record_dir, options.format.clone()) {
+                (Some(_), _) => record_logcat(&mut client, device_type, options).await,
+                (None, Some(format)) => print_log_entries(&mut client, device_type, &logcat_args, format, options.filter).await,
+                (None, None) => client.adb_logcat(device_type, &logcat_args, &mut tokio::io::stdout()).await,
+            };
+            if let Err(err) = result {
+                report_error!("{}", err);
+            }
+        }
+        USER_AM_COMMAND => {
+            if command_args.is_empty() {
+                eprintln!("Error: am command requires a subcommand (start, broadcast, startservice, force-stop)");
+                return;
+            }
+            let am_args = &command_args[1..];
+
+            match command_args[0].as_str() {
+                AM_START => {
+                    let wait = am_args.first().map(|arg| arg == "-W").unwrap_or(false);
+                    let intent_args = if wait { &am_args[1..] } else { am_args };
+                    let intent = match parse_intent_args(intent_args) {
+                        Ok(intent) => intent,
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            return;
+                        }
+                    };
+                    match client.adb_am_start(device_type, &intent, wait).await {
+                        Ok(result) => {
+                            for warning in &result.warnings {
+                                println!("Warning: {}", warning);
+                            }
+                            if let Some(status) = &result.status {
+                                println!("Status: {}", status);
+                            }
+                            if let Some(launch_state) = &result.launch_state {
+                                println!("LaunchState: {}", launch_state);
+                            }
+                            if let Some(activity) = &result.activity {
+                                println!("Activity: {}", activity);
+                            }
+                            if let Some(this_time) = result.this_time_ms {
+                                println!("ThisTime: {}", this_time);
+                            }
+                            if let Some(total_time) = result.total_time_ms {
+                                println!("TotalTime: {}", total_time);
+                            }
+                            if let Some(wait_time) = result.wait_time_ms {
+                                println!("WaitTime: {}", wait_time);
+                            }
+                        }
+                        Err(err) => eprintln!("{}", err),
+                    }
+                }
+                AM_BROADCAST => {
+                    let intent = match parse_intent_args(am_args) {
+                        Ok(intent) => intent,
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            return;
+                        }
+                    };
+                    match client.adb_am_broadcast(device_type, &intent).await {
+                        Ok(result) => match result.data {
+                            Some(data) => println!("Broadcast completed: result={}, data=\"{}\"", result.result_code, data),
+                            None => println!("Broadcast completed: result={}", result.result_code),
+                        },
+                        Err(err) => eprintln!("{}", err),
+                    }
+                }
+                AM_START_SERVICE => {
+                    let intent = match parse_intent_args(am_args) {
+                        Ok(intent) => intent,
+                        Err(err) => {
+                            eprintln!("{}", err);
+                            return;
+                        }
+                    };
+                    match client.adb_am_start_service(device_type, &intent).await {
+                        Ok(result) => print!("{}", result),
+                        Err(err) => eprintln!("{}", err),
+                    }
+                }
+                AM_FORCE_STOP => {
+                    if am_args.len() != 1 {
+                        eprintln!("Error: am force-stop requires exactly one argument (PACKAGE)");
+                        return;
+                    }
+                    if let Err(err) = client.adb_am_force_stop(device_type, &am_args[0]).await {
+                        eprintln!("{}", err);
+                    }
+                }
+                other => {
+                    eprintln!("Unknown am subcommand: {}", other);
+                }
+            }
+        }
+        USER_INSTRUMENT_COMMAND => {
+            let options = match parse_instrument_options(&command_args, false) {
+                Ok(options) => options,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    eprintln!("Usage: instrument [-e <KEY> <VALUE>]... [--junit FILE] [--json FILE] RUNNER");
+                    return;
+                }
+            };
+
+            match client.adb_instrument(device_type, &options.runner, &options.instrument_args, |event| print_test_event("", event)).await {
+                Ok(report) => finish_instrumentation(&report, options.junit_path.as_deref(), options.json_path.as_deref()),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            }
+        }
+        USER_TEST_COMMAND => {
+            let options = match parse_instrument_options(&command_args, true) {
+                Ok(options) => options,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    eprintln!("Usage: test [--shards N] [--retries N] [-e <KEY> <VALUE>]... [--junit FILE] [--json FILE] RUNNER");
+                    return;
+                }
+            };
+
+            let serials: Vec<String> = match client.adb_list_devices().await {
+                Ok(devices) => devices.into_iter().filter(|device| device.is_ready()).map(|device| device.serial).collect(),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
+            };
+            let num_shards = options.shards.unwrap_or(serials.len());
+            println!("Running {} shard{} on {} device{}", num_shards, if num_shards == 1 { "" } else { "s" }, serials.len(), if serials.len() == 1 { "" } else { "s" });
+
+            let result = client.adb_instrument_sharded(&serials, &options.runner, &options.instrument_args, num_shards, options.retries, |serial, event| {
+                print_test_event(&format!("[{}] ", serial), event)
+            }).await;
+            match result {
+                Ok(report) => finish_instrumentation(&report, options.junit_path.as_deref(), options.json_path.as_deref()),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    std::process::exit(1);
+                }
             }
         }
         install_cmd if install_cmd.starts_with(USER_INSTALL_COMMAND) => {
@@ -734,8 +1363,14 @@ async fn handle_commands(mut args: Vec<String>) {
                 eprintln!("Error: No APK file specified");
                 return;
             }
-            if let Err(err) = client.adb_install(device_type, &apk_file, &install_flags).await {
-                eprintln!("{}", err);
+            match client.adb_install(device_type, &apk_file, &install_flags).await {
+                Ok(output) => {
+                    print!("{}", output);
+                    if output.starts_with(PM_FAILURE_PREFIX) {
+                        mark_device_failure();
+                    }
+                }
+                Err(err) => report_device_error!("{}", err),
             }
         }
         uninstall_cmd if uninstall_cmd.starts_with(USER_UNINSTALL_COMMAND) => {
@@ -763,8 +1398,14 @@ async fn handle_commands(mut args: Vec<String>) {
                 return;
             }
 
-            if let Err(err) = client.adb_uninstall(device_type.clone(), &package_name, &uninstall_flags).await {
-                eprintln!("{}", err);
+            match client.adb_uninstall(device_type.clone(), &package_name, &uninstall_flags).await {
+                Ok(output) => {
+                    print!("{}", output);
+                    if output.starts_with(PM_FAILURE_PREFIX) {
+                        mark_device_failure();
+                    }
+                }
+                Err(err) => report_device_error!("{}", err),
             }
         }
         reboot_command if reboot_command.starts_with(USER_REBOOT_COMMAND) => {
@@ -774,7 +1415,7 @@ async fn handle_commands(mut args: Vec<String>) {
                 return;
             }
             if let Err(err) = client.adb_reboot(device_type, reboot_target).await {
-                eprintln!("{}", err);
+                report_device_error!("{}", err);
             }
         }
         get_devpath_cmd if get_devpath_cmd.starts_with(USER_GET_DEVPATH_COMMAND) => {
@@ -907,6 +1548,47 @@ async fn handle_commands(mut args: Vec<String>) {
             }
         }
 
+        USER_CRASHES_COMMAND => {
+            let mut artifact_dir = Some(DEFAULT_CRASH_ARTIFACT_DIR.to_string());
+            let mut args_iter = command_args.iter();
+            while let Some(arg) = args_iter.next() {
+                match arg.as_str() {
+                    OPTION_OUT => match args_iter.next() {
+                        Some(dir) => artifact_dir = Some(dir.clone()),
+                        None => {
+                            report_error!("Error: missing directory after {}", OPTION_OUT);
+                            return;
+                        }
+                    },
+                    OPTION_NO_PULL => artifact_dir = None,
+                    _ => {
+                        report_error!("Unknown option: {}", arg);
+                        return;
+                    }
+                }
+            }
+
+            eprintln!("Watching for crashes and ANRs (press Ctrl+C to stop)");
+            let artifact_dir = artifact_dir.as_deref().map(std::path::Path::new);
+            let result = client.adb_watch_crashes(device_type, artifact_dir, |incident| {
+                println!("{}", incident.to_json());
+                let _ = io::stdout().flush();
+            }).await;
+            if let Err(err) = result {
+                report_error!("{}", err);
+            }
+        }
+        USER_EXPECT_COMMAND => run_expect_script(&client, device_type, &command_args).await,
+        USER_FEATURES_COMMAND => {
+            match client.adb_features(device_type).await {
+                Ok(features) => {
+                    for feature in features {
+                        println!("{}", feature);
+                    }
+                }
+                Err(err) => report_error!("{}", err),
+            }
+        }
         USER_GET_STATE_COMMAND => {
             match client.adb_get_state(device_type).await {
                 Ok(state) => println!("{}", state),
@@ -920,3 +1602,876 @@ async fn handle_commands(mut args: Vec<String>) {
     client.close().await;
 }
 
+fn parse_intent_args(args: &[String]) -> Result<IntentBuilder, String> {
+    let mut intent = IntentBuilder::new();
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        let mut next_value = |name: &str| -> Result<String, String> {
+            args_iter.next().cloned().ok_or_else(|| format!("Error: missing {} after {}", name, arg))
+        };
+
+        intent = match arg.as_str() {
+            "-a" => intent.action(&next_value("action")?),
+            "-n" => intent.component(&next_value("component")?),
+            "-d" => intent.data_uri(&next_value("data URI")?),
+            "-t" => intent.mime_type(&next_value("mime type")?),
+            "-c" => intent.category(&next_value("category")?),
+            "--user" => intent.user(&next_value("user")?),
+            "-f" => {
+                let value = next_value("flags")?;
+                let flags = match value.strip_prefix("0x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => value.parse::<u32>(),
+                }.map_err(|_| format!("Error: invalid flags: {}", value))?;
+                intent.flags(flags)
+            }
+            "--esn" => intent.extra(IntentExtra::NullString(next_value("key")?)),
+            "--es" | "--eu" | "--ei" | "--el" | "--ez" | "--ef" | "--esa" | "--eia" | "--ela" | "--efa" => {
+                let key = next_value("key")?;
+                let value = next_value("value")?;
+                let invalid = || format!("Error: invalid value for {} {}: {}", arg, key, value);
+                let extra = match arg.as_str() {
+                    "--es" => IntentExtra::String(key.clone(), value.clone()),
+                    "--eu" => IntentExtra::Uri(key.clone(), value.clone()),
+                    "--ei" => IntentExtra::Int(key.clone(), value.parse().map_err(|_| invalid())?),
+                    "--el" => IntentExtra::Long(key.clone(), value.parse().map_err(|_| invalid())?),
+                    "--ez" => IntentExtra::Bool(key.clone(), value.parse().map_err(|_| invalid())?),
+                    "--ef" => IntentExtra::Float(key.clone(), value.parse().map_err(|_| invalid())?),
+                    "--esa" => IntentExtra::StringArray(key.clone(), value.split(',').map(String::from).collect()),
+                    "--eia" => IntentExtra::IntArray(key.clone(), parse_list(&value).map_err(|_| invalid())?),
+                    "--ela" => IntentExtra::LongArray(key.clone(), parse_list(&value).map_err(|_| invalid())?),
+                    _ => IntentExtra::FloatArray(key.clone(), parse_list(&value).map_err(|_| invalid())?),
+                };
+                intent.extra(extra)
+            }
+            other if !other.starts_with('-') => intent.data_uri(other),
+            other => return Err(format!("Error: unknown intent option: {}", other)),
+        };
+    }
+
+    Ok(intent)
+}
+
+fn parse_list<T: std::str::FromStr>(value: &str) -> Result<Vec<T>, T::Err> {
+    value.split(',').map(|item| item.trim().parse::<T>()).collect()
+}
+
+/// Returns a token cancelled on Ctrl-C. The command then gets a short grace period to clean up
+/// before the process exits.
+fn cancel_on_ctrl_c() -> CancellationToken {
+    let token = CancellationToken::new();
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            INTERRUPTED.store(true, Ordering::SeqCst);
+            cancel.cancel();
+            tokio::time::sleep(Duration::from_millis(CANCEL_GRACE_PERIOD_MS)).await;
+            std::process::exit(INTERRUPTED_EXIT_CODE);
+        }
+    });
+    token
+}
+
+async fn run_on_matching_devices(command_line: &[String], server: &ClientBuilder, selectors: &[(String, String)], forwarded_flags: &[String]) {
+    let command = command_line[0].as_str();
+    let is_interactive_shell = command == USER_SHELL_COMMAND && command_line.len() == 1;
+    if is_interactive_shell || [USER_DEVICES_COMMAND, USER_CONNECT_COMMAND, USER_DISCONNECT_COMMAND, USER_KILL_SERVER_COMMAND, USER_KEYGEN_COMMAND, USER_TEST_COMMAND].contains(&command) {
+        report_error!("Error: {} cannot be run on multiple devices", if is_interactive_shell { "interactive shell" } else { command });
+        return;
+    }
+
+    if command == USER_LOGCAT_COMMAND {
+        run_merged_logcat(&command_line[1..], server, selectors).await;
+        return;
+    }
+
+    let Some(targets) = resolve_matching_devices(server, selectors).await else {
+        return;
+    };
+
+    let executable = match std::env::current_exe() {
+        Ok(executable) => executable,
+        Err(err) => {
+            report_error!("Error: cannot locate the {} executable: {}", PROGRAM_NAME, err);
+            return;
+        }
+    };
+
+    let mut runs = Vec::new();
+    for target in &targets {
+        let mut child_command = tokio::process::Command::new(&executable);
+        match target.transport_id {
+            Some(transport_id) => child_command.args([FLAG_TRANSPORT_ID.to_string(), transport_id.to_string()]),
+            None => child_command.args([FLAG_SERIAL, &target.serial]),
+        };
+        // One recording per device, as every child numbers its connections from 0.
+        if let Some(path) = std::env::var_os(ADBR_RECORD_ENV).filter(|path| !path.is_empty()) {
+            let mut path = path;
+            path.push(format!(".{}", target.serial));
+            child_command.env(ADBR_RECORD_ENV, path);
+        }
+        child_command.env(ADBR_FAN_OUT_CHILD_ENV, "1");
+        child_command.args(forwarded_flags).args(command_line)
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped());
+
+        let serial = target.serial.clone();
+        runs.push(tokio::spawn(async move {
+            let mut child = child_command.spawn().map_err(|e| e.to_string())?;
+            let prefix = format!("[{}] ", serial);
+            let stdout = forward_prefixed_lines(child.stdout.take(), prefix.clone(), false);
+            let stderr = forward_prefixed_lines(child.stderr.take(), prefix, true);
+            let (_, _, status) = tokio::join!(stdout, stderr, child.wait());
+            status.map_err(|e| e.to_string())
+        }));
+    }
+
+    let mut failed = 0;
+    let mut summary = Vec::new();
+    for (target, run) in targets.iter().zip(runs) {
+        let outcome = match run.await {
+            Ok(Ok(status)) if status.success() => "OK".to_string(),
+            Ok(Ok(status)) => format!("FAILED ({})", status),
+            Ok(Err(err)) => format!("FAILED ({})", err),
+            Err(err) => format!("FAILED ({})", err),
+        };
+        if outcome != "OK" {
+            failed += 1;
+        }
+        summary.push((&target.serial, outcome));
+    }
+
+    println!();
+    println!("Summary:");
+    for (serial, outcome) in summary {
+        println!("  {}\t{}", serial, outcome);
+    }
+    println!("{} device{}, {} succeeded, {} failed", targets.len(), if targets.len() == 1 { "" } else { "s" }, targets.len() - failed, failed);
+    if failed > 0 {
+        COMMAND_FAILED.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Fails the process when it runs one device's share of a fan-out, see [`report_device_error`].
+fn mark_device_failure() {
+    if std::env::var_os(ADBR_FAN_OUT_CHILD_ENV).is_some() {
+        COMMAND_FAILED.store(true, Ordering::SeqCst);
+    }
+}
+
+async fn resolve_matching_devices(server: &ClientBuilder, selectors: &[(String, String)]) -> Option<Vec<DeviceInfo>> {
+    let mut client = match server.clone().build().await {
+        Ok(client) => client,
+        Err(err) => {
+            report_error!("{}", err);
+            return None;
+        }
+    };
+    let devices = match client.adb_list_devices().await {
+        Ok(devices) => devices,
+        Err(err) => {
+            report_error!("{}", err);
+            return None;
+        }
+    };
+    client.close().await;
+
+    let mut targets = Vec::new();
+    for device in devices {
+        match device_matches(&device, selectors) {
+            Ok(true) => targets.push(device),
+            Ok(false) => {}
+            Err(err) => {
+                report_error!("{}", err);
+                return None;
+            }
+        }
+    }
+    if targets.is_empty() {
+        report_error!("Error: no devices match the given selectors");
+        return None;
+    }
+    Some(targets)
+}
+
+/// Streams logcat from every matching device over its own connection and prints one view
+/// merged by timestamp, with each line prefixed by its device.
+async fn run_merged_logcat(args: &[String], server: &ClientBuilder, selectors: &[(String, String)]) {
+    let options = match parse_logcat_options(args) {
+        Ok(options) => options,
+        Err(err) => {
+            report_error!("{}", err);
+            return;
+        }
+    };
+    if options.record_dir.is_some() {
+        report_error!("Error: {} cannot be run on multiple devices", OPTION_RECORD);
+        return;
+    }
+    let Some(targets) = resolve_matching_devices(server, selectors).await else {
+        return;
+    };
+
+    let format = options.format.unwrap_or(if atty::is(atty::Stream::Stdout) { LogFormat::Color } else { LogFormat::Threadtime });
+    let colored = format == LogFormat::Color;
+    let logcat_args = options.logcat_args.join(" ");
+    let devices: Vec<DeviceTransport> = targets.iter().map(|target| match target.transport_id {
+        Some(transport_id) => DeviceTransport::transport_id(transport_id),
+        None => DeviceTransport::serial(target.serial.clone()),
+    }).collect();
+
+    let mut filters = Vec::new();
+    for device in &devices {
+        let mut filter = options.filter.clone();
+        if let Some(package) = options.filter.package_name() {
+            match resolve_package_pids(server, device.clone(), package).await {
+                Ok(pids) => filter.set_package_pids(pids),
+                Err(err) => report_error!("[{}] {}", targets[filters.len()].serial, err),
+            }
+        }
+        filters.push(filter);
+    }
+
+    let mut clients = Vec::new();
+    for target in &targets {
+        match server.clone().build().await {
+            Ok(client) => clients.push(client),
+            Err(err) => {
+                report_error!("[{}] {}", target.serial, err);
+                return;
+            }
+        }
+    }
+    let mut streams = Vec::new();
+    for ((client, device), target) in clients.iter_mut().zip(&devices).zip(&targets) {
+        match client.adb_logcat_entries(device.clone(), &logcat_args).await {
+            Ok(entries) => streams.push(Box::pin(entries)),
+            Err(err) => {
+                report_error!("[{}] {}", target.serial, err);
+                return;
+            }
+        }
+    }
+
+    let prefixes: Vec<String> = targets.iter().map(logcat_device_prefix).collect();
+    let merged = adbr::debugging::logcat::merge_log_streams(streams, Duration::from_millis(LOGCAT_MERGE_WINDOW_MS));
+    futures::pin_mut!(merged);
+    let mut last_pid_refresh = vec![std::time::Instant::now(); targets.len()];
+    let mut stdout = io::stdout().lock();
+
+    while let Some((index, entry)) = merged.next().await {
+        let (serial, prefix) = (&targets[index].serial, &prefixes[index]);
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                report_error!("[{}] {}", prefix, err);
+                continue;
+            }
+        };
+        if let Some(package) = options.filter.package_name() {
+            if last_pid_refresh[index].elapsed() >= Duration::from_secs(PACKAGE_PID_REFRESH_SECS) {
+                if let Ok(pids) = resolve_package_pids(server, devices[index].clone(), package).await {
+                    filters[index].set_package_pids(pids);
+                }
+                last_pid_refresh[index] = std::time::Instant::now();
+            }
+        }
+        if !filters[index].accept(&entry) {
+            continue;
+        }
+
+        let line = match format {
+            LogFormat::Json => {
+                let mut value: serde_json::Value = serde_json::from_str(&format.render(&entry)).unwrap_or_default();
+                value["device"] = serde_json::Value::from(serial.as_str());
+                value.to_string()
+            }
+            _ if colored => format!("{}[{}]\x1b[0m {}", DEVICE_PREFIX_COLORS[index % DEVICE_PREFIX_COLORS.len()], prefix, format.render(&entry)),
+            _ => format!("[{}] {}", prefix, format.render(&entry)),
+        };
+        if writeln!(stdout, "{}", line).is_err() {
+            break;
+        }
+    }
+}
+
+/// A short tag for a device's lines in the merged logcat: its transport id, as `-t` takes it,
+/// or the end of its serial when the server reports none.
+fn logcat_device_prefix(target: &DeviceInfo) -> String {
+    match target.transport_id {
+        Some(transport_id) => format!("t{}", transport_id),
+        None => {
+            let chars: Vec<char> = target.serial.chars().collect();
+            chars[chars.len().saturating_sub(LOGCAT_SERIAL_PREFIX_LEN)..].iter().collect()
+        }
+    }
+}
+
+fn device_matches(device: &DeviceInfo, selectors: &[(String, String)]) -> Result<bool, Box<dyn std::error::Error>> {
+    let selects_state = selectors.iter().any(|(key, _)| key == "state");
+    if !selects_state && !device.is_ready() {
+        return Ok(false);
+    }
+    for (key, value) in selectors {
+        if !device.matches_selector(key, value)? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+async fn forward_prefixed_lines<R: tokio::io::AsyncRead + Unpin>(reader: Option<R>, prefix: String, to_stderr: bool) {
+    use tokio::io::AsyncBufReadExt;
+
+    let Some(reader) = reader else { return };
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if to_stderr {
+            eprintln!("{}{}", prefix, line);
+        } else {
+            println!("{}{}", prefix, line);
+        }
+    }
+}
+
+struct LogcatCliOptions {
+    format: Option<LogFormat>,
+    filter: LogFilter,
+    logcat_args: Vec<String>,
+    record_dir: Option<String>,
+    rotate_size: u64,
+    rotate_age: Duration,
+}
+
+fn parse_logcat_options(args: &[String]) -> Result<LogcatCliOptions, String> {
+    let mut format = None;
+    let mut filter = LogFilter::new();
+    let mut filtered = false;
+    let mut logcat_args = Vec::new();
+    let mut record_dir = None;
+    let mut rotate_size = DEFAULT_RECORD_FILE_SIZE;
+    let mut rotate_age = Duration::from_secs(DEFAULT_RECORD_FILE_AGE_SECS);
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if ![OPTION_FORMAT, OPTION_PACKAGE, OPTION_GREP, OPTION_EXCLUDE_TAG, OPTION_MIN_LEVEL, OPTION_SINCE, OPTION_RECORD, OPTION_ROTATE_SIZE, OPTION_ROTATE_TIME].contains(&name) {
+            logcat_args.push(arg.clone());
+            continue;
+        }
+
+        let value = match inline_value {
+            Some(value) => value,
+            None => args_iter.next().ok_or(format!("Error: missing value after {}", name))?.clone(),
+        };
+        let invalid = |err: Box<dyn std::error::Error>| format!("Error: {}", err);
+        filtered |= ![OPTION_FORMAT, OPTION_RECORD, OPTION_ROTATE_SIZE, OPTION_ROTATE_TIME].contains(&name);
+        match name {
+            OPTION_FORMAT => format = Some(LogFormat::from_arg(&value).map_err(invalid)?),
+            OPTION_RECORD => record_dir = Some(value),
+            OPTION_ROTATE_SIZE => rotate_size = parse_byte_size(&value).map_err(invalid)?,
+            OPTION_ROTATE_TIME => rotate_age = parse_duration(&value).map_err(invalid)?,
+            OPTION_PACKAGE => filter = filter.package(&value),
+            OPTION_GREP => filter = filter.grep(&value).map_err(invalid)?,
+            OPTION_EXCLUDE_TAG => {
+                for tag in value.split(',') {
+                    filter = filter.exclude_tag(tag);
+                }
+            }
+            OPTION_MIN_LEVEL => filter = filter.min_level(LogPriority::from_name(&value).map_err(invalid)?),
+            _ => filter = filter.since(LogFilter::parse_time(&value).map_err(invalid)?),
+        }
+    }
+
+    if record_dir.is_some() && filter.package_name().is_some() {
+        return Err(format!("Error: {} cannot be combined with {}", OPTION_PACKAGE, OPTION_RECORD));
+    }
+
+    // Client-side filters need the parsed stream, so they imply the default colored output.
+    if format.is_none() && filtered && record_dir.is_none() {
+        format = Some(LogFormat::Color);
+    }
+    if !atty::is(atty::Stream::Stdout) {
+        format = format.map(LogFormat::without_color);
+    }
+
+    Ok(LogcatCliOptions { format, filter, logcat_args, record_dir, rotate_size, rotate_age })
+}
+
+async fn record_logcat(client: &mut Client, device: DeviceTransport, options: LogcatCliOptions) -> Result<(), Box<dyn std::error::Error>> {
+    let record_dir = options.record_dir.unwrap_or_default();
+    let format = options.format.unwrap_or(LogFormat::Threadtime).without_color();
+    let mut recorder = LogRecorder::open(std::path::Path::new(&record_dir), options.rotate_size, options.rotate_age, format)?.with_filter(options.filter);
+    println!("Recording logcat to {} (press Ctrl+C to stop)", record_dir);
+
+    client.adb_logcat_record(device, &options.logcat_args.join(" "), &mut recorder, |event| match event {
+        RecordEvent::WaitingForDevice => println!("Waiting for device..."),
+        RecordEvent::SessionStarted { boot_id, resumed: true } => println!("Resuming boot session {}", boot_id),
+        RecordEvent::SessionStarted { boot_id, resumed: false } => println!("Started boot session {}", boot_id),
+        RecordEvent::FileOpened(path) => println!("Writing {}", path.display()),
+        RecordEvent::Disconnected { reason } => println!("Disconnected: {}", reason),
+    }).await
+}
+
+async fn print_log_entries(client: &mut Client, device: DeviceTransport, logcat_args: &str, format: LogFormat, mut filter: LogFilter) -> Result<(), Box<dyn std::error::Error>> {
+    let server = client.config().clone();
+    let package = filter.package_name().map(str::to_string);
+    if let Some(package) = &package {
+        filter.set_package_pids(resolve_package_pids(&server, device.clone(), package).await?);
+    }
+    let mut last_pid_refresh = std::time::Instant::now();
+
+    let entries = client.adb_logcat_entries(device.clone(), logcat_args).await?;
+    futures::pin_mut!(entries);
+    let mut stdout = io::stdout().lock();
+
+    while let Some(entry) = entries.next().await {
+        let entry = entry?;
+        // ActivityManager lines catch most restarts; polling covers processes started before the stream.
+        if let Some(package) = &package {
+            if last_pid_refresh.elapsed() >= Duration::from_secs(PACKAGE_PID_REFRESH_SECS) {
+                if let Ok(pids) = resolve_package_pids(&server, device.clone(), package).await {
+                    filter.set_package_pids(pids);
+                }
+                last_pid_refresh = std::time::Instant::now();
+            }
+        }
+        if !filter.accept(&entry) {
+            continue;
+        }
+        if writeln!(stdout, "{}", format.render(&entry)).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+async fn resolve_package_pids(server: &ClientBuilder, device: DeviceTransport, package: &str) -> Result<Vec<i32>, Box<dyn std::error::Error>> {
+    let mut pid_client = server.clone().build().await?;
+    pid_client.adb_package_pids(device, package).await
+}
+
+struct InstrumentCliOptions {
+    runner: String,
+    instrument_args: Vec<(String, String)>,
+    junit_path: Option<String>,
+    json_path: Option<String>,
+    shards: Option<usize>,
+    retries: u32,
+}
+
+fn parse_instrument_options(args: &[String], allow_sharding: bool) -> Result<InstrumentCliOptions, String> {
+    let mut options = InstrumentCliOptions {
+        runner: String::new(),
+        instrument_args: Vec::new(),
+        junit_path: None,
+        json_path: None,
+        shards: None,
+        retries: DEFAULT_TEST_RETRIES,
+    };
+    let mut args_iter = args.iter();
+
+    while let Some(arg) = args_iter.next() {
+        match arg.as_str() {
+            "-e" => match (args_iter.next(), args_iter.next()) {
+                (Some(key), Some(value)) => options.instrument_args.push((key.clone(), value.clone())),
+                _ => return Err("Error: -e requires a key and a value".to_string()),
+            },
+            OPTION_JUNIT => options.junit_path = Some(args_iter.next().ok_or("Error: missing file path after --junit")?.clone()),
+            OPTION_JSON => options.json_path = Some(args_iter.next().ok_or("Error: missing file path after --json")?.clone()),
+            OPTION_SHARDS if allow_sharding => {
+                let value = args_iter.next().ok_or("Error: missing shard count after --shards")?;
+                match value.parse::<usize>() {
+                    Ok(shards) if shards > 0 => options.shards = Some(shards),
+                    _ => return Err(format!("Error: invalid shard count: {}", value)),
+                }
+            }
+            OPTION_RETRIES if allow_sharding => {
+                let value = args_iter.next().ok_or("Error: missing retry count after --retries")?;
+                options.retries = value.parse::<u32>().map_err(|_| format!("Error: invalid retry count: {}", value))?;
+            }
+            _ if options.runner.is_empty() && !arg.starts_with('-') => options.runner = arg.clone(),
+            _ => return Err(format!("Unknown option: {}", arg)),
+        }
+    }
+
+    if options.runner.is_empty() {
+        return Err("Error: No instrumentation runner specified".to_string());
+    }
+    Ok(options)
+}
+
+fn finish_instrumentation(report: &InstrumentationReport, junit_path: Option<&str>, json_path: Option<&str>) {
+    print_instrumentation_summary(report);
+    if let Err(err) = write_instrumentation_reports(report, junit_path, json_path) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+    if !report.is_success() {
+        std::process::exit(1);
+    }
+}
+
+fn print_test_event(prefix: &str, event: &TestEvent) {
+    match event {
+        TestEvent::Started { class_name, test_name, current, total } => {
+            println!("{}[{}/{}] {}#{}", prefix, current, total, class_name, test_name);
+        }
+        TestEvent::Finished(result) => {
+            println!("{}{} {} ({:.3}s)", prefix, result.status, result.full_name(), result.duration_ms as f64 / 1000.0);
+            if result.status.is_failure() {
+                if let Some(stack) = &result.stack_trace {
+                    for line in stack.lines() {
+                        println!("{}    {}", prefix, line);
+                    }
+                }
+            }
+        }
+        TestEvent::RunFinished { crashed: true, message, .. } => {
+            eprintln!("{}Instrumentation crashed: {}", prefix, message.as_deref().unwrap_or("unknown error"));
+        }
+        TestEvent::RunFinished { .. } => {}
+    }
+}
+
+fn print_instrumentation_summary(report: &InstrumentationReport) {
+    println!();
+    println!("{} tests, {} passed, {} failed, {} errors, {} ignored, {} assumption failures ({:.3}s)",
+             report.tests.len(),
+             report.count(TestStatus::Passed),
+             report.count(TestStatus::Failed),
+             report.count(TestStatus::Error),
+             report.count(TestStatus::Ignored),
+             report.count(TestStatus::AssumptionFailure),
+             report.duration_ms as f64 / 1000.0);
+    for failed in report.failed_tests() {
+        println!("  {} {}", failed.status, failed.full_name());
+    }
+}
+
+async fn run_expect_script(client: &Client, device_type: DeviceTransport, args: &[String]) {
+    let [path] = args else {
+        report_error!("Usage: {} {} SCRIPT", PROGRAM_NAME, USER_EXPECT_COMMAND);
+        return;
+    };
+    let script = match std::fs::read_to_string(path).map_err(|err| err.into()).and_then(|text| ExpectScript::parse(&text)) {
+        Ok(script) => script,
+        Err(err) => {
+            report_error!("Error reading expect script {}: {}", path, err);
+            return;
+        }
+    };
+
+    let on_output = |output: &[u8]| {
+        let mut stdout = std::io::stdout();
+        let _ = stdout.write_all(output);
+        let _ = stdout.flush();
+    };
+    match client.run_expect_script(device_type, &script, on_output).await {
+        Ok(Some(status)) if status != 0 => std::process::exit(status as i32),
+        Ok(_) => {}
+        Err(err) => report_error!("{}", err),
+    }
+}
+
+/// A progress bar for `bugreportz -p`, or a spinner when the device does not report progress.
+fn bugreport_progress_bar(progress: bool) -> ProgressBar {
+    if !progress {
+        let spinner = ProgressBar::new_spinner();
+        spinner.enable_steady_tick(Duration::from_millis(BUGREPORT_SPINNER_TICK_MS));
+        return spinner;
+    }
+
+    let pb = ProgressBar::new(INITIAL_PROGRESS_BAR_LENGTH);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {percent}% ({eta})")
+            .unwrap_or_else(|_| ProgressStyle::default_bar())
+            .progress_chars("#>-")
+    );
+    pb
+}
+
+/// Sends library diagnostics to stderr: warnings by default, `-v` adds debug events and `-vv` also
+/// dumps raw protocol frames, as does `ADBR_TRACE` at any level. `--log-level` takes `RUST_LOG`-style
+/// directives such as `debug` or `adbr::frames=trace`.
+fn init_logging(verbosity: u8, log_level: Option<&str>) -> Result<(), String> {
+    let default_filter = match verbosity {
+        0 => "warn",
+        1 => "warn,adbr=debug",
+        _ => "warn,adbr=trace",
+    };
+    let mut filter = EnvFilter::try_new(log_level.unwrap_or(default_filter)).map_err(|err| err.to_string())?;
+    if std::env::var(ADBR_TRACE_ENV).is_ok_and(|value| !value.is_empty() && value != "0") {
+        let directive = format!("{}=trace", FRAME_TRACE_TARGET).parse().map_err(|err: ParseError| err.to_string())?;
+        filter = filter.add_directive(directive);
+    }
+
+    let verbose = verbosity > 0 || log_level.is_some();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(io::stderr)
+        .with_target(verbose)
+        .with_ansi(atty::is(atty::Stream::Stderr));
+    let layer = if verbose { layer.boxed() } else { layer.without_time().boxed() };
+    tracing_subscriber::registry().with(filter).with(layer).init();
+    Ok(())
+}
+
+async fn replay_recording(args: &[String]) {
+    let mut port = 0;
+    let mut realtime = false;
+    let mut path = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            OPTION_PORT => match args.get(i + 1).and_then(|port| port.parse::<u16>().ok()) {
+                Some(value) => {
+                    port = value;
+                    i += 1;
+                }
+                None => {
+                    report_error!("Invalid command: expected a port after {}", OPTION_PORT);
+                    return;
+                }
+            },
+            OPTION_REALTIME => realtime = true,
+            file if path.is_none() => path = Some(file.to_string()),
+            other => {
+                report_error!("Unexpected argument: {}", other);
+                return;
+            }
+        }
+        i += 1;
+    }
+    let Some(path) = path else {
+        report_error!("Usage: {} {} [{} PORT] [{}] FILE", PROGRAM_NAME, USER_REPLAY_COMMAND, OPTION_PORT, OPTION_REALTIME);
+        return;
+    };
+
+    let server = match ReplayServer::load(std::path::Path::new(&path)) {
+        Ok(server) => server.realtime(realtime),
+        Err(err) => {
+            report_error!("{}", err);
+            return;
+        }
+    };
+    match server.listen_on(port).await {
+        Ok(address) => println!("Replaying {} on {} ({} connections)", path, address, server.remaining()),
+        Err(err) => {
+            report_error!("Cannot listen on port {}: {}", port, err);
+            return;
+        }
+    }
+
+    let mut mismatches = 0;
+    loop {
+        for mismatch in server.mismatches().iter().skip(mismatches) {
+            eprintln!("{}", mismatch);
+        }
+        mismatches = server.mismatches().len();
+        if server.is_done() || mismatches > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(REPLAY_POLL_INTERVAL_MS)).await;
+    }
+    if let Err(err) = server.finish() {
+        report_error!("{}", err);
+    }
+}
+
+/// Runs the native server in the foreground until `kill-server` or Ctrl-C.
+async fn run_server(settings: &ClientBuilder) {
+    let Some(port) = settings.tcp_port() else {
+        report_error!("{} {} only listens on TCP; select the port with {} or {} tcp:PORT", PROGRAM_NAME, USER_SERVER_COMMAND, FLAG_SERVER_PORT, FLAG_SERVER_SOCKET);
+        return;
+    };
+    let auth = match HostAuth::load(settings.key_paths()) {
+        Ok(auth) => auth,
+        Err(err) => {
+            report_error!("{}", err);
+            return;
+        }
+    };
+    let server = AdbServer::with_auth(auth);
+    match server.listen_on(port).await {
+        Ok(address) => println!("{} server listening on {}", PROGRAM_NAME, address),
+        Err(err) => {
+            report_error!("Cannot listen on port {}: {}", port, err);
+            return;
+        }
+    }
+    for serial in server.scan_emulators().await {
+        println!("Attached {}", serial);
+    }
+
+    let interrupted = cancel_on_ctrl_c();
+    tokio::select! {
+        _ = server.wait() => {}
+        _ = interrupted.cancelled() => server.shutdown(),
+    }
+}
+
+fn redact_recording(args: &[String]) {
+    let mut serials = Vec::new();
+    let mut paths = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == OPTION_SERIAL {
+            let Some(serial) = args.get(i + 1) else {
+                report_error!("Invalid command: missing serial after {}", OPTION_SERIAL);
+                return;
+            };
+            serials.push(serial.clone());
+            i += 1;
+        } else {
+            paths.push(args[i].as_str());
+        }
+        i += 1;
+    }
+    let (input, output) = match paths.as_slice() {
+        [input] => (*input, *input),
+        [input, output] => (*input, *output),
+        _ => {
+            report_error!("Usage: {} {} [{} SERIAL]... FILE [OUT]", PROGRAM_NAME, USER_REDACT_SERIALS_COMMAND, OPTION_SERIAL);
+            return;
+        }
+    };
+
+    let mut recording = match SessionRecording::load(std::path::Path::new(input)) {
+        Ok(recording) => recording,
+        Err(err) => {
+            report_error!("{}", err);
+            return;
+        }
+    };
+    let replacements = adbr::recording::redact::redact_serials(&mut recording, &serials);
+    if let Err(err) = recording.save(std::path::Path::new(output)) {
+        report_error!("Error writing {}: {}", output, err);
+        return;
+    }
+    for (serial, replacement) in &replacements {
+        println!("{} -> {}", serial, replacement);
+    }
+}
+
+fn summarize_bugreport(args: &[String]) {
+    let json = args.iter().any(|arg| arg == OPTION_JSON);
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != OPTION_JSON).collect();
+    let [path] = paths.as_slice() else {
+        report_error!("Usage: {} {} [{}] FILE", PROGRAM_NAME, USER_BUGREPORT_SUMMARY_COMMAND, OPTION_JSON);
+        return;
+    };
+
+    match adbr::debugging::bugreport_analyzer::summarize_bugreport(std::path::Path::new(path)) {
+        Ok(summary) if json => match serde_json::to_string_pretty(&summary) {
+            Ok(text) => println!("{}", text),
+            Err(err) => report_error!("Error encoding summary: {}", err),
+        },
+        Ok(summary) => print_bugreport_summary(&summary),
+        Err(err) => report_error!("Error reading bugreport {}: {}", path, err),
+    }
+}
+
+fn print_bugreport_summary(summary: &BugreportSummary) {
+    let unknown = "unknown";
+    println!("Build fingerprint: {}", summary.build_fingerprint.as_deref().unwrap_or(unknown));
+    println!("Generated:         {}", summary.generated_at.as_deref().unwrap_or(unknown));
+    println!("Uptime:            {}", summary.uptime.as_deref().unwrap_or(unknown));
+    match &summary.battery {
+        Some(battery) => {
+            let mut parts = Vec::new();
+            if let Some(level) = battery.level {
+                parts.push(format!("{}%", level));
+            }
+            parts.extend(battery.status.clone());
+            if let Some(health) = &battery.health {
+                parts.push(format!("health {}", health));
+            }
+            if let Some(temperature) = battery.temperature_celsius {
+                parts.push(format!("{:.1}°C", temperature));
+            }
+            parts.extend(battery.history.clone());
+            println!("Battery:           {}", parts.join(", "));
+        }
+        None => println!("Battery:           {}", unknown),
+    }
+    println!("Sections:          {}", summary.section_count);
+
+    let print_incidents = |title: &str, incidents: &[CrashIncident]| {
+        println!();
+        println!("{} ({}):", title, incidents.len());
+        for incident in incidents {
+            println!("  {} {} {} ({}): {}",
+                     incident.timestamp.with_timezone(&chrono::Local).format("%m-%d %H:%M:%S"),
+                     incident.kind,
+                     incident.process.as_deref().unwrap_or("?"),
+                     incident.pid.map_or_else(|| "?".to_string(), |pid| pid.to_string()),
+                     incident.summary);
+        }
+    };
+    print_incidents("ANRs", &summary.anrs);
+    print_incidents("Crashes", &summary.crashes);
+
+    if !summary.anr_files.is_empty() {
+        println!();
+        println!("ANR traces ({}):", summary.anr_files.len());
+        for file in &summary.anr_files {
+            println!("  {}", file);
+        }
+    }
+
+    println!();
+    println!("Top wakelocks:");
+    for wakelock in &summary.top_wakelocks {
+        let duration = format_duration_ms(wakelock.duration_ms);
+        let kind = if wakelock.kernel { "kernel" } else { "partial" };
+        println!("  {:>14} {:>7}x {:<7} {}", duration, wakelock.count, kind, wakelock.name);
+    }
+
+    println!();
+    println!("Low-memory kills ({}):", summary.low_memory_kills.len());
+    for kill in &summary.low_memory_kills {
+        let time = kill.timestamp.map_or_else(|| "-".to_string(), |time| time.with_timezone(&chrono::Local).format("%m-%d %H:%M:%S").to_string());
+        let adj = kill.oom_score_adj.map_or_else(String::new, |adj| format!(" adj {}", adj));
+        println!("  {} {} ({}){}", time, kill.process, kill.pid, adj);
+    }
+
+    println!();
+    println!("Kernel panics ({}):", summary.kernel_panics.len());
+    for panic in &summary.kernel_panics {
+        println!("  [{}] {}", panic.section, panic.message);
+    }
+}
+
+fn format_duration_ms(duration_ms: u64) -> String {
+    let units = [("d", 86_400_000), ("h", 3_600_000), ("m", 60_000), ("s", 1_000)];
+    let mut remaining = duration_ms;
+    let mut parts = Vec::new();
+    for (unit, size) in units {
+        if remaining >= size {
+            parts.push(format!("{}{}", remaining / size, unit));
+            remaining %= size;
+        }
+    }
+    if parts.is_empty() {
+        parts.push(format!("{}ms", remaining));
+    }
+    parts.join(" ")
+}
+
+fn write_instrumentation_reports(report: &InstrumentationReport, junit_path: Option<&str>, json_path: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(path) = junit_path {
+        std::fs::write(path, report.to_junit_xml()).map_err(|e| format!("Failed to write JUnit report to {}: {}", path, e))?;
+        println!("JUnit report written to {}", path);
+    }
+    if let Some(path) = json_path {
+        std::fs::write(path, report.to_json()?).map_err(|e| format!("Failed to write JSON report to {}: {}", path, e))?;
+        println!("JSON report written to {}", path);
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use dirs::home_dir;
+use serde::Deserialize;
+use crate::constants::{ADBR_CONFIG_DIR_NAME, ADBR_CONFIG_ENV, CONFIG_DIR_NAME, CONFIG_FILE_NAME, XDG_CONFIG_HOME_ENV};
+use crate::models::config_profile::ConfigProfile;
+
+/// Contents of `~/.config/adbr/config.toml`:
+///
+/// ```toml
+/// default_profile = "local"
+///
+/// [profiles.local]
+/// port = 5037
+///
+/// [profiles.lab]
+/// host = "10.0.0.5"
+/// serial = "R58M123ABC"
+/// io_timeout = "30s"
+/// retries = 3
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AdbrConfig {
+    /// Profile used when none is selected with `--profile` or `ADBR_PROFILE`.
+    pub default_profile: Option<String>,
+    pub profiles: BTreeMap<String, ConfigProfile>,
+}
+
+impl AdbrConfig {
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        Self::parse(&text).map_err(|err| format!("Invalid config file {}: {}", path.display(), err).into())
+    }
+
+    /// `$ADBR_CONFIG`, else `config.toml` in `$XDG_CONFIG_HOME/adbr` or `~/.config/adbr`.
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = std::env::var_os(ADBR_CONFIG_ENV).filter(|path| !path.is_empty()) {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = std::env::var_os(XDG_CONFIG_HOME_ENV)
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| home_dir().map(|home| home.join(CONFIG_DIR_NAME)))?;
+        Some(config_dir.join(ADBR_CONFIG_DIR_NAME).join(CONFIG_FILE_NAME))
+    }
+
+    /// Loads the file at [`AdbrConfig::default_path`]; a missing file is an empty config.
+    pub fn load_default() -> Result<Self, Box<dyn Error>> {
+        match Self::default_path() {
+            Some(path) if path.exists() => Self::load(&path),
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The profile called `name`, or the default profile when `name` is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Result<Option<&ConfigProfile>, Box<dyn Error>> {
+        let Some(name) = name.or(self.default_profile.as_deref()) else {
+            return Ok(None);
+        };
+        match self.profiles.get(name) {
+            Some(profile) => Ok(Some(profile)),
+            None if self.profiles.is_empty() => Err(format!("Unknown profile '{}': no profiles are configured", name).into()),
+            None => Err(format!("Unknown profile '{}' (known: {})", name, self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")).into()),
+        }
+    }
+}
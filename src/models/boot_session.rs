@@ -0,0 +1,28 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// One uninterrupted device boot within a logcat recording, as listed in the recording index.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootSession {
+    pub boot_id: String,
+    pub started_at: DateTime<Utc>,
+    pub first_entry: Option<DateTime<Utc>>,
+    pub last_entry: Option<DateTime<Utc>>,
+    pub entries: u64,
+    pub reconnects: u32,
+    pub files: Vec<String>,
+}
+
+impl BootSession {
+    pub fn new(boot_id: &str) -> Self {
+        BootSession {
+            boot_id: boot_id.to_string(),
+            started_at: Utc::now(),
+            first_entry: None,
+            last_entry: None,
+            entries: 0,
+            reconnects: 0,
+            files: Vec::new(),
+        }
+    }
+}
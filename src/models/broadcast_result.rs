@@ -0,0 +1,38 @@
+use std::error::Error;
+
+const BROADCAST_COMPLETED_PREFIX: &str = "Broadcast completed:";
+const RESULT_KEY: &str = "result=";
+const DATA_KEY: &str = "data=";
+
+#[derive(Debug, Clone)]
+pub struct BroadcastResult {
+    pub result_code: i32,
+    pub data: Option<String>,
+}
+
+impl BroadcastResult {
+    /// Parses the `Broadcast completed: result=0, data="..."` line printed by `am broadcast`.
+    pub fn from_output(output: &str) -> Result<Self, Box<dyn Error>> {
+        let completed = output.lines()
+            .map(str::trim)
+            .find_map(|line| line.strip_prefix(BROADCAST_COMPLETED_PREFIX))
+            .ok_or_else(|| format!("Broadcast did not complete: {}", output.trim()))?;
+
+        let result_start = completed.find(RESULT_KEY).ok_or("Missing broadcast result code")? + RESULT_KEY.len();
+        let result_code = completed[result_start..]
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .next()
+            .unwrap_or_default()
+            .parse::<i32>()?;
+
+        let data = completed.find(DATA_KEY).map(|idx| {
+            let value = &completed[idx + DATA_KEY.len()..];
+            match value.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').map_or(quoted, |(inner, _)| inner).to_string(),
+                None => value.split(',').next().unwrap_or_default().trim().to_string(),
+            }
+        });
+
+        Ok(BroadcastResult { result_code, data })
+    }
+}
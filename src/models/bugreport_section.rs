@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// A titled block of a bugreport's main text file, such as `SYSTEM LOG` or one `DUMP OF SERVICE`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BugreportSection {
+    pub title: String,
+    pub command: Option<String>,
+    pub category: String,
+    /// First and last line of the section body, 1-based and inclusive.
+    pub start_line: usize,
+    pub end_line: usize,
+    /// File the section was written to when the bugreport was extracted.
+    pub file: Option<String>,
+}
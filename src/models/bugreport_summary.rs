@@ -0,0 +1,58 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::models::crash_incident::CrashIncident;
+
+/// The headline facts of a bugreport, extracted offline by `summarize_bugreport`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BugreportSummary {
+    /// Name of the main text file inside the zip, or `None` for a plain-text bugreport.
+    pub main_file: Option<String>,
+    pub generated_at: Option<String>,
+    pub build_fingerprint: Option<String>,
+    pub uptime: Option<String>,
+    pub battery: Option<BatterySummary>,
+    pub anrs: Vec<CrashIncident>,
+    pub crashes: Vec<CrashIncident>,
+    /// ANR trace files shipped in the zip under `FS/data/anr/`.
+    pub anr_files: Vec<String>,
+    pub top_wakelocks: Vec<WakelockUsage>,
+    pub low_memory_kills: Vec<LowMemoryKill>,
+    pub kernel_panics: Vec<KernelPanic>,
+    pub section_count: usize,
+}
+
+/// `dumpsys battery` state plus the `Battery History` line of `dumpsys batterystats`.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BatterySummary {
+    pub level: Option<u32>,
+    pub status: Option<String>,
+    pub health: Option<String>,
+    pub temperature_celsius: Option<f32>,
+    pub history: Option<String>,
+}
+
+/// One entry of the `All kernel wake locks` or `All partial wake locks` tables.
+#[derive(Debug, Clone, Serialize)]
+pub struct WakelockUsage {
+    pub name: String,
+    pub kernel: bool,
+    pub duration_ms: u64,
+    pub count: u64,
+}
+
+/// A process killed by lmkd or the kernel low memory killer.
+#[derive(Debug, Clone, Serialize)]
+pub struct LowMemoryKill {
+    /// Only known when the kill was reported through logcat.
+    pub timestamp: Option<DateTime<Utc>>,
+    pub process: String,
+    pub pid: i32,
+    pub oom_score_adj: Option<i32>,
+}
+
+/// A `Kernel panic - not syncing` message and the section (kernel log, last kmsg, pstore) it was found in.
+#[derive(Debug, Clone, Serialize)]
+pub struct KernelPanic {
+    pub section: String,
+    pub message: String,
+}
@@ -0,0 +1,24 @@
+use std::path::PathBuf;
+use serde::Deserialize;
+
+/// One `[profiles.<name>]` table of the config file. Every setting is optional; durations are
+/// written like `30s` or `500ms`.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConfigProfile {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    /// Path of a unix socket to reach the server on, instead of host and port.
+    pub socket: Option<PathBuf>,
+    pub connect_timeout: Option<String>,
+    /// `0` waits forever.
+    pub io_timeout: Option<String>,
+    pub serial: Option<String>,
+    pub transport_id: Option<u64>,
+    pub usb: bool,
+    pub emulator: bool,
+    /// Private keys used to authenticate to devices; `~/` is expanded.
+    pub keys: Vec<PathBuf>,
+    pub retries: Option<u32>,
+    pub retry_delay: Option<String>,
+}
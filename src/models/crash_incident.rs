@@ -0,0 +1,39 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use crate::enums::crash_kind::CrashKind;
+
+/// A crash, native crash or ANR reassembled from the log lines that describe it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CrashIncident {
+    pub kind: CrashKind,
+    pub timestamp: DateTime<Utc>,
+    pub pid: Option<i32>,
+    pub process: Option<String>,
+    pub summary: String,
+    pub trace: Vec<String>,
+    /// Tombstone or ANR trace file on the device, when one could be identified.
+    pub remote_artifact: Option<String>,
+    /// Local copy of `remote_artifact` after it was pulled.
+    pub artifact: Option<String>,
+    pub artifact_error: Option<String>,
+}
+
+impl CrashIncident {
+    pub fn new(kind: CrashKind, timestamp: DateTime<Utc>) -> Self {
+        CrashIncident {
+            kind,
+            timestamp,
+            pid: None,
+            process: None,
+            summary: String::new(),
+            trace: Vec::new(),
+            remote_artifact: None,
+            artifact: None,
+            artifact_error: None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
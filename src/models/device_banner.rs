@@ -0,0 +1,58 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+const FEATURES_KEY: &str = "features";
+
+/// The identity both ends send in their CNXN packet, e.g.
+/// `device::ro.product.name=sdk;ro.product.model=Pixel;ro.product.device=emu64;features=shell_v2,cmd`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DeviceBanner {
+    /// `device`, `host`, `recovery`, `sideload`, ...
+    pub kind: String,
+    pub props: BTreeMap<String, String>,
+    pub features: Vec<String>,
+}
+
+impl DeviceBanner {
+    pub fn new(kind: &str) -> Self {
+        DeviceBanner { kind: kind.to_string(), ..Default::default() }
+    }
+
+    pub fn prop(mut self, key: &str, value: &str) -> Self {
+        self.props.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    pub fn features(mut self, features: &[String]) -> Self {
+        self.features = features.to_vec();
+        self
+    }
+
+    pub fn parse(banner: &str) -> Self {
+        let banner = banner.trim_end_matches('\0');
+        let (kind, rest) = banner.split_once("::").unwrap_or((banner, ""));
+        let mut parsed = DeviceBanner::new(kind);
+        for (key, value) in rest.split(';').filter_map(|pair| pair.split_once('=')) {
+            if key == FEATURES_KEY {
+                parsed.features = value.split(',').filter(|feature| !feature.is_empty()).map(str::to_string).collect();
+            } else {
+                parsed.props.insert(key.to_string(), value.to_string());
+            }
+        }
+        parsed
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.props.get(key).map(String::as_str)
+    }
+}
+
+impl fmt::Display for DeviceBanner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}::", self.kind)?;
+        for (key, value) in &self.props {
+            write!(f, "{}={};", key, value)?;
+        }
+        write!(f, "{}={}", FEATURES_KEY, self.features.join(","))
+    }
+}
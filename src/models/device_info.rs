@@ -0,0 +1,83 @@
+use std::error::Error;
+use serde::Serialize;
+
+const PRODUCT_KEY: &str = "product:";
+const MODEL_KEY: &str = "model:";
+const DEVICE_KEY: &str = "device:";
+const TRANSPORT_ID_KEY: &str = "transport_id:";
+const USB_KEY: &str = "usb:";
+const READY_STATE: &str = "device";
+const EMULATOR_SERIAL_PREFIX: &str = "emulator-";
+const TRANSPORT_USB: &str = "usb";
+const TRANSPORT_LOCAL: &str = "local";
+
+pub const SELECTOR_KEYS: [&str; 7] = ["serial", "state", "model", "product", "device", "transport", "transport_id"];
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DeviceInfo {
+    pub serial: String,
+    pub state: String,
+    pub product: Option<String>,
+    pub model: Option<String>,
+    pub device: Option<String>,
+    pub usb: Option<String>,
+    pub transport_id: Option<u64>,
+}
+
+impl DeviceInfo {
+    /// Parses one line of `host:devices` or `host:devices-l` output.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let mut parts = line.split_whitespace();
+        let serial = parts.next()?.to_string();
+        let state = parts.next()?.to_string();
+        let mut info = DeviceInfo { serial, state, ..Default::default() };
+
+        for part in parts {
+            if let Some(value) = part.strip_prefix(PRODUCT_KEY) {
+                info.product = Some(value.to_string());
+            } else if let Some(value) = part.strip_prefix(MODEL_KEY) {
+                info.model = Some(value.to_string());
+            } else if let Some(value) = part.strip_prefix(DEVICE_KEY) {
+                info.device = Some(value.to_string());
+            } else if let Some(value) = part.strip_prefix(USB_KEY) {
+                info.usb = Some(value.to_string());
+            } else if let Some(value) = part.strip_prefix(TRANSPORT_ID_KEY) {
+                info.transport_id = value.parse().ok();
+            }
+        }
+
+        Some(info)
+    }
+
+    pub fn parse_list(output: &str) -> Vec<Self> {
+        output.lines().filter_map(Self::from_line).collect()
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.state == READY_STATE
+    }
+
+    /// `usb` for devices attached over USB, `local` for emulators and TCP/IP devices.
+    pub fn transport_kind(&self) -> &str {
+        if self.usb.is_some() || !(self.serial.starts_with(EMULATOR_SERIAL_PREFIX) || self.serial.contains(':')) {
+            TRANSPORT_USB
+        } else {
+            TRANSPORT_LOCAL
+        }
+    }
+
+    /// Checks a `key=value` device selector such as `model=Pixel_7` or `transport=usb`.
+    pub fn matches_selector(&self, key: &str, value: &str) -> Result<bool, Box<dyn Error>> {
+        let actual = match key {
+            "serial" => Some(self.serial.clone()),
+            "state" => Some(self.state.clone()),
+            "model" => self.model.clone(),
+            "product" => self.product.clone(),
+            "device" => self.device.clone(),
+            "transport" => Some(self.transport_kind().to_string()),
+            "transport_id" => self.transport_id.map(|id| id.to_string()),
+            _ => return Err(format!("Unknown device selector '{}', expected one of: {}", key, SELECTOR_KEYS.join(", ")).into()),
+        };
+        Ok(actual.as_deref() == Some(value))
+    }
+}
@@ -0,0 +1,15 @@
+/// Output consumed by a successful expect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExpectMatch {
+    /// Output between the previous match and this one.
+    pub before: String,
+    pub matched: String,
+    /// Capture groups; group 0 is the whole match, unmatched optional groups are `None`.
+    pub captures: Vec<Option<String>>,
+}
+
+impl ExpectMatch {
+    pub fn group(&self, index: usize) -> Option<&str> {
+        self.captures.get(index).and_then(|group| group.as_deref())
+    }
+}
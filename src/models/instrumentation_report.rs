@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use serde::Serialize;
+use crate::enums::test_event::TestEvent;
+use crate::enums::test_status::TestStatus;
+use crate::models::test_case_result::TestCaseResult;
+use crate::utils::xml_escape;
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct InstrumentationReport {
+    pub runner: String,
+    pub tests: Vec<TestCaseResult>,
+    pub duration_ms: u64,
+    pub result_code: Option<i32>,
+    pub crashed: bool,
+    pub crash_message: Option<String>,
+}
+
+impl InstrumentationReport {
+    pub fn new(runner: &str) -> Self {
+        InstrumentationReport {
+            runner: runner.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Folds a parser event into the report.
+    pub fn record(&mut self, event: &TestEvent) {
+        match event {
+            TestEvent::Finished(result) => self.tests.push(result.clone()),
+            TestEvent::RunFinished { code, crashed, message } => {
+                self.result_code = *code;
+                self.crashed = *crashed;
+                if *crashed {
+                    self.crash_message = message.clone();
+                }
+            }
+            TestEvent::Started { .. } => {}
+        }
+    }
+
+    /// Appends another run's results, keeping the crash state of both.
+    pub fn merge(&mut self, other: InstrumentationReport) {
+        self.tests.extend(other.tests);
+        if other.crashed {
+            self.crashed = true;
+            self.crash_message = match (self.crash_message.take(), other.crash_message) {
+                (Some(existing), Some(new)) => Some(format!("{}\n{}", existing, new)),
+                (existing, new) => existing.or(new),
+            };
+        }
+        if self.result_code.is_none() {
+            self.result_code = other.result_code;
+        }
+    }
+
+    /// Replaces the result of a test with the same class and name, or appends it.
+    pub fn replace_test(&mut self, result: TestCaseResult) {
+        match self.tests.iter_mut().find(|test| test.class_name == result.class_name && test.test_name == result.test_name) {
+            Some(existing) => *existing = result,
+            None => self.tests.push(result),
+        }
+    }
+
+    pub fn count(&self, status: TestStatus) -> usize {
+        self.tests.iter().filter(|test| test.status == status).count()
+    }
+
+    pub fn failed_tests(&self) -> Vec<&TestCaseResult> {
+        self.tests.iter().filter(|test| test.status.is_failure()).collect()
+    }
+
+    pub fn is_success(&self) -> bool {
+        !self.crashed && self.failed_tests().is_empty()
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// Renders the report as JUnit XML with one `<testsuite>` per test class.
+    pub fn to_junit_xml(&self) -> String {
+        let mut suites: BTreeMap<&str, Vec<&TestCaseResult>> = BTreeMap::new();
+        for test in &self.tests {
+            suites.entry(test.class_name.as_str()).or_default().push(test);
+        }
+
+        let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        xml.push_str(&format!(
+            "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+            xml_escape(&self.runner),
+            self.tests.len(),
+            self.count(TestStatus::Failed),
+            self.count(TestStatus::Error) + usize::from(self.crashed),
+            self.count(TestStatus::Ignored) + self.count(TestStatus::AssumptionFailure),
+            self.duration_ms as f64 / 1000.0,
+        ));
+
+        for (class_name, tests) in &suites {
+            let suite_time: u64 = tests.iter().map(|test| test.duration_ms).sum();
+            let count = |status: TestStatus| tests.iter().filter(|test| test.status == status).count();
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" errors=\"{}\" skipped=\"{}\" time=\"{:.3}\">\n",
+                xml_escape(class_name),
+                tests.len(),
+                count(TestStatus::Failed),
+                count(TestStatus::Error),
+                count(TestStatus::Ignored) + count(TestStatus::AssumptionFailure),
+                suite_time as f64 / 1000.0,
+            ));
+            for test in tests {
+                xml.push_str(&Self::junit_test_case(test));
+            }
+            xml.push_str("  </testsuite>\n");
+        }
+
+        if self.crashed {
+            let message = self.crash_message.as_deref().unwrap_or("Instrumentation run crashed");
+            xml.push_str(&format!(
+                "  <testsuite name=\"{}\" tests=\"1\" failures=\"0\" errors=\"1\" skipped=\"0\" time=\"0.000\">\n",
+                xml_escape(&self.runner),
+            ));
+            xml.push_str(&format!(
+                "    <testcase classname=\"{}\" name=\"instrumentation\" time=\"0.000\">\n      <error message=\"{}\">{}</error>\n    </testcase>\n",
+                xml_escape(&self.runner),
+                xml_escape(message),
+                xml_escape(message),
+            ));
+            xml.push_str("  </testsuite>\n");
+        }
+
+        xml.push_str("</testsuites>\n");
+        xml
+    }
+
+    fn junit_test_case(test: &TestCaseResult) -> String {
+        let open = format!(
+            "    <testcase classname=\"{}\" name=\"{}\" time=\"{:.3}\"",
+            xml_escape(&test.class_name),
+            xml_escape(&test.test_name),
+            test.duration_ms as f64 / 1000.0,
+        );
+        let message = xml_escape(test.message().unwrap_or_default());
+        let stack = xml_escape(test.stack_trace.as_deref().unwrap_or_default());
+
+        match test.status {
+            TestStatus::Failed => format!("{}>\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n", open, message, stack),
+            TestStatus::Error | TestStatus::Started => format!("{}>\n      <error message=\"{}\">{}</error>\n    </testcase>\n", open, message, stack),
+            TestStatus::Ignored => format!("{}>\n      <skipped/>\n    </testcase>\n", open),
+            TestStatus::AssumptionFailure => format!("{}>\n      <skipped message=\"{}\"/>\n    </testcase>\n", open, message),
+            TestStatus::Passed => format!("{}/>\n", open),
+        }
+    }
+}
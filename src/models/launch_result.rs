@@ -0,0 +1,58 @@
+use std::error::Error;
+
+const STATUS_PREFIX: &str = "Status:";
+const LAUNCH_STATE_PREFIX: &str = "LaunchState:";
+const ACTIVITY_PREFIX: &str = "Activity:";
+const THIS_TIME_PREFIX: &str = "ThisTime:";
+const TOTAL_TIME_PREFIX: &str = "TotalTime:";
+const WAIT_TIME_PREFIX: &str = "WaitTime:";
+const ERROR_PREFIX: &str = "Error";
+const WARNING_PREFIX: &str = "Warning:";
+
+#[derive(Debug, Clone, Default)]
+pub struct LaunchResult {
+    pub status: Option<String>,
+    pub launch_state: Option<String>,
+    pub activity: Option<String>,
+    pub this_time_ms: Option<u64>,
+    pub total_time_ms: Option<u64>,
+    pub wait_time_ms: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+impl LaunchResult {
+    /// Parses the output of `am start [-W]`. Without `-W` only errors and warnings are reported.
+    pub fn from_output(output: &str) -> Result<Self, Box<dyn Error>> {
+        let mut result = LaunchResult::default();
+        let mut errors = Vec::new();
+
+        for line in output.lines().map(str::trim) {
+            if let Some(value) = line.strip_prefix(STATUS_PREFIX) {
+                result.status = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix(LAUNCH_STATE_PREFIX) {
+                result.launch_state = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix(ACTIVITY_PREFIX) {
+                result.activity = Some(value.trim().to_string());
+            } else if let Some(value) = line.strip_prefix(THIS_TIME_PREFIX) {
+                result.this_time_ms = value.trim().parse().ok();
+            } else if let Some(value) = line.strip_prefix(TOTAL_TIME_PREFIX) {
+                result.total_time_ms = value.trim().parse().ok();
+            } else if let Some(value) = line.strip_prefix(WAIT_TIME_PREFIX) {
+                result.wait_time_ms = value.trim().parse().ok();
+            } else if let Some(value) = line.strip_prefix(WARNING_PREFIX) {
+                result.warnings.push(value.trim().to_string());
+            } else if line.starts_with(ERROR_PREFIX) {
+                errors.push(line.to_string());
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(errors.join("\n").into());
+        }
+
+        match result.status.as_deref() {
+            Some("ok") | None => Ok(result),
+            Some(status) => Err(format!("Activity launch failed with status: {}", status).into()),
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use std::error::Error;
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use crate::enums::log_buffer::LogBuffer;
+use crate::enums::log_priority::LogPriority;
+
+const ENTRY_PREFIX_SIZE: usize = 4;
+const HEADER_SIZE_V1: usize = 20;
+const HEADER_SIZE_V3: usize = 24;
+const HEADER_SIZE_V4: usize = 28;
+const MAX_PAYLOAD_SIZE: usize = 5 * 1024;
+
+const THREADTIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.f";
+const TAG_SEPARATOR: &str = ": ";
+
+const EVENT_TYPE_INT: u8 = 0;
+const EVENT_TYPE_LONG: u8 = 1;
+const EVENT_TYPE_STRING: u8 = 2;
+const EVENT_TYPE_LIST: u8 = 3;
+const EVENT_TYPE_FLOAT: u8 = 4;
+
+#[derive(Debug, Clone)]
+pub struct LogEntry {
+    pub pid: i32,
+    pub tid: u32,
+    pub timestamp: DateTime<Utc>,
+    pub uid: Option<u32>,
+    pub buffer: LogBuffer,
+    pub priority: LogPriority,
+    pub tag: String,
+    pub message: String,
+}
+
+impl LogEntry {
+    /// Returns the full size of the entry (header plus payload) announced by its first four bytes.
+    pub fn entry_size(prefix: &[u8]) -> Result<usize, Box<dyn Error>> {
+        if prefix.len() < ENTRY_PREFIX_SIZE {
+            return Err("Truncated logger entry header".into());
+        }
+
+        let payload_len = u16::from_le_bytes([prefix[0], prefix[1]]) as usize;
+        let header_size = Self::header_size(u16::from_le_bytes([prefix[2], prefix[3]]) as usize)?;
+        if payload_len > MAX_PAYLOAD_SIZE {
+            return Err(format!("Invalid logger entry payload length: {}", payload_len).into());
+        }
+
+        Ok(header_size + payload_len)
+    }
+
+    /// Decodes one `logger_entry` record (v1 to v4) as written by `logcat -B`.
+    pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
+        let entry_size = Self::entry_size(data)?;
+        if data.len() < entry_size {
+            return Err(format!("Truncated logger entry: expected {} bytes, got {}", entry_size, data.len()).into());
+        }
+
+        let header_size = Self::header_size(u16::from_le_bytes([data[2], data[3]]) as usize)?;
+        let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+        let pid = read_u32(4) as i32;
+        let tid = read_u32(8);
+        let seconds = read_u32(12);
+        let nanoseconds = read_u32(16);
+
+        // A 24 byte header is either v2 (euid) or v3 (log id); logd only ever produced v3,
+        // so only values outside the known log ids are treated as an euid.
+        let (buffer, uid) = match header_size {
+            HEADER_SIZE_V1 => (LogBuffer::Main, None),
+            HEADER_SIZE_V3 => match LogBuffer::from_id(read_u32(20)) {
+                Some(buffer) => (buffer, None),
+                None => (LogBuffer::Main, Some(read_u32(20))),
+            },
+            size if size >= HEADER_SIZE_V4 => (LogBuffer::from_id(read_u32(20)).unwrap_or(LogBuffer::Main), Some(read_u32(24))),
+            size => return Err(format!("Unsupported logger entry header size: {}", size).into()),
+        };
+
+        let timestamp = DateTime::from_timestamp(seconds as i64, nanoseconds)
+            .ok_or_else(|| format!("Invalid logger entry timestamp: {}.{}", seconds, nanoseconds))?;
+
+        let payload = &data[header_size..entry_size];
+        let (priority, tag, message) = if buffer.is_binary() {
+            Self::parse_binary_payload(payload)?
+        } else {
+            Self::parse_text_payload(payload)
+        };
+
+        Ok(LogEntry { pid, tid, timestamp, uid, buffer, priority, tag, message })
+    }
+
+    /// Parses one line of `logcat -v threadtime` text, optionally with the `-v uid` column, as
+    /// found in bugreports. Logcat omits the year, so the caller supplies it.
+    pub fn from_threadtime_line(line: &str, year: i32) -> Option<Self> {
+        let fields: Vec<(usize, &str)> = line.split_whitespace()
+            .take(6)
+            .map(|field| (field.as_ptr() as usize - line.as_ptr() as usize, field))
+            .collect();
+        let (date, time) = (fields.first()?.1, fields.get(1)?.1);
+        let naive = NaiveDateTime::parse_from_str(&format!("{}-{} {}", year, date, time), THREADTIME_FORMAT).ok()?;
+        let timestamp = Local.from_local_datetime(&naive).earliest()?.with_timezone(&Utc);
+
+        // [uid] pid tid priority, where uid may be a name such as "system" or "u0_a123".
+        let priority_index = (4..fields.len()).find(|&index| {
+            let field = fields[index].1;
+            field.len() == 1 && !field.starts_with(|c: char| c.is_ascii_digit())
+        })?;
+        let (uid, pid, tid) = match &fields[2..priority_index] {
+            [(_, pid), (_, tid)] => (None, *pid, *tid),
+            [(_, uid), (_, pid), (_, tid)] => (uid.parse().ok(), *pid, *tid),
+            _ => return None,
+        };
+        let (priority_offset, priority) = fields[priority_index];
+        let priority = LogPriority::from_name(priority).ok()?;
+
+        let rest = line[priority_offset + 1..].trim_start();
+        let (tag, message) = rest.split_once(TAG_SEPARATOR).unwrap_or((rest.trim_end_matches(':'), ""));
+
+        Some(LogEntry {
+            pid: pid.parse().ok()?,
+            tid: tid.parse().ok()?,
+            timestamp,
+            uid,
+            buffer: LogBuffer::Main,
+            priority,
+            tag: tag.trim_end().to_string(),
+            message: message.to_string(),
+        })
+    }
+
+    fn header_size(value: usize) -> Result<usize, Box<dyn Error>> {
+        match value {
+            // v1 entries have a padding field where later versions store the header size.
+            0 => Ok(HEADER_SIZE_V1),
+            HEADER_SIZE_V1.. => Ok(value),
+            _ => Err(format!("Invalid logger entry header size: {}", value).into()),
+        }
+    }
+
+    fn parse_text_payload(payload: &[u8]) -> (LogPriority, String, String) {
+        let Some((&priority, rest)) = payload.split_first() else {
+            return (LogPriority::Unknown, String::new(), String::new());
+        };
+
+        let mut fields = rest.splitn(2, |&b| b == 0);
+        let tag = String::from_utf8_lossy(fields.next().unwrap_or_default()).into_owned();
+        let message = fields.next().unwrap_or_default();
+        let message = message.split(|&b| b == 0).next().unwrap_or_default();
+        let message = String::from_utf8_lossy(message).trim_end_matches('\n').to_string();
+
+        (LogPriority::from_u8(priority), tag, message)
+    }
+
+    fn parse_binary_payload(payload: &[u8]) -> Result<(LogPriority, String, String), Box<dyn Error>> {
+        if payload.len() < 4 {
+            return Err("Truncated binary log payload".into());
+        }
+
+        let tag = u32::from_le_bytes(payload[0..4].try_into()?).to_string();
+        let mut offset = 4;
+        let mut message = String::new();
+        while offset < payload.len() {
+            if !message.is_empty() {
+                message.push(' ');
+            }
+            message.push_str(&Self::parse_event_value(payload, &mut offset)?);
+        }
+
+        Ok((LogPriority::Info, tag, message))
+    }
+
+    fn parse_event_value(payload: &[u8], offset: &mut usize) -> Result<String, Box<dyn Error>> {
+        let event_type = *payload.get(*offset).ok_or("Truncated event value")?;
+        *offset += 1;
+
+        let mut take = |len: usize| -> Result<&[u8], Box<dyn Error>> {
+            let bytes = payload.get(*offset..*offset + len).ok_or("Truncated event value")?;
+            *offset += len;
+            Ok(bytes)
+        };
+
+        match event_type {
+            EVENT_TYPE_INT => Ok(i32::from_le_bytes(take(4)?.try_into()?).to_string()),
+            EVENT_TYPE_LONG => Ok(i64::from_le_bytes(take(8)?.try_into()?).to_string()),
+            EVENT_TYPE_FLOAT => Ok(f32::from_le_bytes(take(4)?.try_into()?).to_string()),
+            EVENT_TYPE_STRING => {
+                let len = u32::from_le_bytes(take(4)?.try_into()?) as usize;
+                Ok(String::from_utf8_lossy(take(len)?).into_owned())
+            }
+            EVENT_TYPE_LIST => {
+                let count = take(1)?[0];
+                let mut values = Vec::with_capacity(count as usize);
+                for _ in 0..count {
+                    values.push(Self::parse_event_value(payload, offset)?);
+                }
+                Ok(format!("[{}]", values.join(",")))
+            }
+            _ => Err(format!("Unknown event value type: {}", event_type).into()),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::error::Error;
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use crate::enums::log_priority::LogPriority;
+use crate::models::log_entry::LogEntry;
+use crate::utils::parse_duration;
+
+const ACTIVITY_MANAGER_TAG: &str = "ActivityManager";
+const START_PROC_PREFIX: &str = "Start proc ";
+const PROCESS_PREFIX: &str = "Process ";
+const PROCESS_DIED_MARKER: &str = " has died";
+const DATE_FORMATS: [&str; 2] = ["%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M:%S"];
+
+/// Client-side filter over parsed log entries.
+///
+/// When a package is set, entries are limited to its processes: the initial pids come from
+/// [`LogFilter::set_package_pids`] and ActivityManager `Start proc`/`has died` lines seen in the
+/// stream keep the set current across app restarts.
+#[derive(Debug, Clone, Default)]
+pub struct LogFilter {
+    package: Option<String>,
+    pids: HashSet<i32>,
+    grep: Option<Regex>,
+    exclude_tags: Vec<String>,
+    min_level: Option<LogPriority>,
+    since: Option<DateTime<Utc>>,
+}
+
+impl LogFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn package(mut self, package: &str) -> Self {
+        self.package = Some(package.to_string());
+        self
+    }
+
+    pub fn grep(mut self, pattern: &str) -> Result<Self, Box<dyn Error>> {
+        self.grep = Some(Regex::new(pattern)?);
+        Ok(self)
+    }
+
+    pub fn exclude_tag(mut self, tag: &str) -> Self {
+        self.exclude_tags.push(tag.to_string());
+        self
+    }
+
+    pub fn min_level(mut self, priority: LogPriority) -> Self {
+        self.min_level = Some(priority);
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn package_name(&self) -> Option<&str> {
+        self.package.as_deref()
+    }
+
+    pub fn set_package_pids(&mut self, pids: impl IntoIterator<Item = i32>) {
+        self.pids = pids.into_iter().collect();
+    }
+
+    pub fn package_pids(&self) -> &HashSet<i32> {
+        &self.pids
+    }
+
+    /// Updates the tracked pids from `entry`, then reports whether it passes every filter.
+    pub fn accept(&mut self, entry: &LogEntry) -> bool {
+        self.track_package_process(entry);
+        self.matches(entry)
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if self.package.is_some() && !self.pids.contains(&entry.pid) {
+            return false;
+        }
+        if self.min_level.is_some_and(|level| entry.priority < level) {
+            return false;
+        }
+        if self.since.is_some_and(|since| entry.timestamp < since) {
+            return false;
+        }
+        if self.exclude_tags.contains(&entry.tag) {
+            return false;
+        }
+        self.grep.as_ref().is_none_or(|regex| regex.is_match(&entry.message))
+    }
+
+    fn track_package_process(&mut self, entry: &LogEntry) {
+        let Some(package) = self.package.as_deref() else {
+            return;
+        };
+        if entry.tag != ACTIVITY_MANAGER_TAG {
+            return;
+        }
+
+        // "Start proc 4321:com.foo/u0a123 for activity {...}"
+        if let Some(rest) = entry.message.strip_prefix(START_PROC_PREFIX) {
+            if let Some((pid, process)) = rest.split_once(':') {
+                let process = process.split('/').next().unwrap_or_default();
+                if let (Ok(pid), true) = (pid.parse::<i32>(), is_package_process(process, package)) {
+                    self.pids.insert(pid);
+                }
+            }
+        // "Process com.foo (pid 4321) has died: ..."
+        } else if let Some(rest) = entry.message.strip_prefix(PROCESS_PREFIX) {
+            if !rest.contains(PROCESS_DIED_MARKER) {
+                return;
+            }
+            if let Some((process, pid)) = rest.split_once(" (pid ") {
+                let pid = pid.split(')').next().unwrap_or_default();
+                if let (Ok(pid), true) = (pid.parse::<i32>(), is_package_process(process, package)) {
+                    self.pids.remove(&pid);
+                }
+            }
+        }
+    }
+
+    /// Parses a `--since` value: `YYYY-MM-DD HH:MM:SS[.mmm]`, logcat's `MM-DD HH:MM:SS[.mmm]`
+    /// (current year), epoch seconds such as `1700000000.5`, or a relative age like `30s`, `10m`, `2h`, `1d`.
+    pub fn parse_time(value: &str) -> Result<DateTime<Utc>, Box<dyn Error>> {
+        let value = value.trim();
+
+        if value.ends_with(|c: char| "smhd".contains(c)) {
+            if let Ok(age) = parse_duration(value) {
+                return Ok(Utc::now() - chrono::Duration::from_std(age)?);
+            }
+        }
+
+        if let Ok(epoch) = value.parse::<f64>() {
+            let seconds = epoch.trunc() as i64;
+            let nanoseconds = (epoch.fract() * 1e9).round() as u32;
+            return DateTime::from_timestamp(seconds, nanoseconds).ok_or_else(|| format!("Invalid time: {}", value).into());
+        }
+
+        // logcat's own timestamps omit the year.
+        let with_year = format!("{}-{}", Local::now().year(), value);
+        for candidate in [value, with_year.as_str()] {
+            if let Some(time) = DATE_FORMATS.iter().find_map(|format| Self::parse_local_time(candidate, format)) {
+                return Ok(time);
+            }
+        }
+
+        Err(format!("Invalid time: {} (expected 'YYYY-MM-DD HH:MM:SS', 'MM-DD HH:MM:SS.mmm', epoch seconds or an age like 10m)", value).into())
+    }
+
+    fn parse_local_time(value: &str, format: &str) -> Option<DateTime<Utc>> {
+        let naive = NaiveDateTime::parse_from_str(value, format).ok()?;
+        Local.from_local_datetime(&naive).earliest().map(|local| local.with_timezone(&Utc))
+    }
+}
+
+/// Matches the package's main process and its `com.foo:service` subprocesses.
+pub(crate) fn is_package_process(process: &str, package: &str) -> bool {
+    process == package || process.strip_prefix(package).is_some_and(|rest| rest.starts_with(':'))
+}
@@ -1,3 +1,21 @@
 pub(crate) mod stat_data;
 pub(crate) mod remote_dir_entry;
-pub(crate) mod remote_metadata;
\ No newline at end of file
+pub(crate) mod remote_metadata;
+pub(crate) mod launch_result;
+pub(crate) mod broadcast_result;
+pub(crate) mod test_case_result;
+pub(crate) mod instrumentation_report;
+pub(crate) mod device_info;
+pub(crate) mod log_entry;
+pub(crate) mod log_filter;
+pub(crate) mod boot_session;
+pub(crate) mod crash_incident;
+pub(crate) mod bugreport_section;
+pub(crate) mod bugreport_summary;
+pub(crate) mod expect_match;
+pub(crate) mod retry_policy;
+pub(crate) mod config_profile;
+pub(crate) mod adbr_config;
+pub(crate) mod recorded_event;
+pub(crate) mod session_recording;
+pub(crate) mod device_banner;
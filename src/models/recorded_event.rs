@@ -0,0 +1,29 @@
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::enums::stream_direction::StreamDirection;
+
+/// One line of an `ADBR_RECORD` recording.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Microseconds since the recording started.
+    pub time_us: u64,
+    /// Connections are numbered from 0 in the order they were opened.
+    pub connection: u64,
+    pub direction: StreamDirection,
+    /// Bytes sent or received, hex encoded in the file.
+    #[serde(default, skip_serializing_if = "Vec::is_empty", serialize_with = "to_hex", deserialize_with = "from_hex")]
+    pub data: Vec<u8>,
+}
+
+fn to_hex<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&data.iter().map(|byte| format!("{:02x}", byte)).collect::<String>())
+}
+
+fn from_hex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let hex = String::deserialize(deserializer)?;
+    if hex.len() % 2 != 0 || !hex.is_ascii() {
+        return Err(serde::de::Error::custom("odd length or non-ASCII hex data"));
+    }
+    (0..hex.len()).step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).map_err(serde::de::Error::custom))
+        .collect()
+}
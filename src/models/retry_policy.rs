@@ -0,0 +1,23 @@
+use std::time::Duration;
+use crate::constants::DEFAULT_CONNECT_RETRY_DELAY_MS;
+
+/// How often a [`Client`](crate::Client) retries connecting to the ADB server, e.g. while the
+/// server is still starting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub retries: u32,
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, delay: Duration) -> Self {
+        RetryPolicy { retries, delay }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy::new(0, Duration::from_millis(DEFAULT_CONNECT_RETRY_DELAY_MS))
+    }
+}
@@ -0,0 +1,55 @@
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use crate::enums::stream_direction::StreamDirection;
+use crate::models::recorded_event::RecordedEvent;
+
+/// A recording written with `ADBR_RECORD`: every byte exchanged with the server, one JSON event per line.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SessionRecording {
+    pub events: Vec<RecordedEvent>,
+}
+
+impl SessionRecording {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        Self::parse(&fs::read_to_string(path)?).map_err(|err| format!("Invalid recording {}: {}", path.display(), err).into())
+    }
+
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let mut events = Vec::new();
+        for (index, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            events.push(serde_json::from_str(line).map_err(|err| format!("line {}: {}", index + 1, err))?);
+        }
+        Ok(SessionRecording { events })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut text = String::new();
+        for event in &self.events {
+            text.push_str(&serde_json::to_string(event)?);
+            text.push('\n');
+        }
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Events of each connection, in the order the connections were opened.
+    pub fn connections(&self) -> Vec<Vec<RecordedEvent>> {
+        let mut connections: Vec<(u64, Vec<RecordedEvent>)> = Vec::new();
+        for event in &self.events {
+            match connections.iter_mut().find(|(connection, _)| *connection == event.connection) {
+                Some((_, events)) => events.push(event.clone()),
+                None => connections.push((event.connection, vec![event.clone()])),
+            }
+        }
+        connections.into_iter().map(|(_, events)| events).collect()
+    }
+
+    /// Everything sent (or received) on `connection`, concatenated.
+    pub fn stream_data(&self, connection: u64, direction: StreamDirection) -> Vec<u8> {
+        self.events.iter()
+            .filter(|event| event.connection == connection && event.direction == direction)
+            .flat_map(|event| event.data.iter().copied())
+            .collect()
+    }
+}
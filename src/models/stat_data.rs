@@ -11,8 +11,7 @@ pub struct StatData {
 impl StatData {
     pub fn from_bytes(data: &[u8]) -> Result<Self, Box<dyn Error>> {
         if data.len() != 12 {
-            println!("Invalid stat data length: expected 12, got {}", data.len());
-            return Err("Invalid stat data length".into());
+            return Err(format!("Invalid stat data length: expected 12, got {}", data.len()).into());
         }
 
         Ok(StatData {
@@ -0,0 +1,24 @@
+use serde::Serialize;
+use crate::enums::test_status::TestStatus;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TestCaseResult {
+    pub class_name: String,
+    pub test_name: String,
+    pub status: TestStatus,
+    pub stack_trace: Option<String>,
+    pub duration_ms: u64,
+    pub device: Option<String>,
+    pub retries: u32,
+}
+
+impl TestCaseResult {
+    pub fn full_name(&self) -> String {
+        format!("{}#{}", self.class_name, self.test_name)
+    }
+
+    /// First line of the stack trace, used as the failure message.
+    pub fn message(&self) -> Option<&str> {
+        self.stack_trace.as_deref().and_then(|stack| stack.lines().next())
+    }
+}
@@ -4,4 +4,83 @@ pub fn strip_adb_prefix(response: String) -> String {
     } else {
         response
     }
-}
\ No newline at end of file
+}
+
+pub fn shell_quote(value: &str) -> String {
+    let is_safe = !value.is_empty() && value.chars().all(|c| {
+        c.is_ascii_alphanumeric() || matches!(c, '_' | '@' | '%' | '+' | '=' | ':' | ',' | '.' | '/' | '-')
+    });
+
+    if is_safe {
+        value.to_string()
+    } else {
+        format!("'{}'", value.replace('\'', "'\\''"))
+    }
+}
+
+pub fn xml_escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c if (c as u32) < 0x20 && !matches!(c, '\n' | '\r' | '\t') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Parses a duration such as `500ms`, `30s`, `10m`, `2h` or `1d`; a bare number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<std::time::Duration, Box<dyn std::error::Error>> {
+    let value = value.trim();
+    if let Some(millis) = value.strip_suffix("ms") {
+        let millis = millis.parse::<u64>().map_err(|_| format!("Invalid duration: {} (expected e.g. 500ms, 30s, 10m)", value))?;
+        return Ok(std::time::Duration::from_millis(millis));
+    }
+    let (amount, multiplier) = match value.char_indices().last() {
+        Some((idx, 's')) => (&value[..idx], 1),
+        Some((idx, 'm')) => (&value[..idx], 60),
+        Some((idx, 'h')) => (&value[..idx], 3600),
+        Some((idx, 'd')) => (&value[..idx], 86400),
+        _ => (value, 1),
+    };
+    let amount = amount.parse::<u64>().map_err(|_| format!("Invalid duration: {} (expected e.g. 30s, 10m, 2h, 1d)", value))?;
+    Ok(std::time::Duration::from_secs(amount * multiplier))
+}
+
+/// Parses a byte size such as `512K`, `16M` or `1G`; a bare number is taken as bytes.
+pub fn parse_byte_size(value: &str) -> Result<u64, Box<dyn std::error::Error>> {
+    let value = value.trim();
+    let (amount, multiplier) = match value.char_indices().last().map(|(idx, c)| (idx, c.to_ascii_uppercase())) {
+        Some((idx, 'K')) => (&value[..idx], 1024),
+        Some((idx, 'M')) => (&value[..idx], 1024 * 1024),
+        Some((idx, 'G')) => (&value[..idx], 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    match amount.parse::<u64>() {
+        Ok(amount) if amount > 0 => Ok(amount * multiplier),
+        _ => Err(format!("Invalid size: {} (expected e.g. 512K, 16M, 1G)", value).into()),
+    }
+}
+
+/// Renders protocol bytes for logs: printable ASCII as is, everything else as `\xNN`. Output past
+/// `limit` bytes is cut off with a note of the full length.
+pub fn escape_bytes(data: &[u8], limit: usize) -> String {
+    let mut escaped = String::with_capacity(data.len().min(limit));
+    for &byte in data.iter().take(limit) {
+        match byte {
+            b'\\' => escaped.push_str("\\\\"),
+            b'\n' => escaped.push_str("\\n"),
+            0x20..=0x7e => escaped.push(byte as char),
+            _ => escaped.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    if data.len() > limit {
+        escaped.push_str(&format!("... ({} bytes)", data.len()));
+    }
+    escaped
+}
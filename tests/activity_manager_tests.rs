@@ -0,0 +1,68 @@
+use adbr::{BroadcastResult, IntentBuilder, LaunchResult};
+use std::error::Error;
+
+#[test]
+fn test_intent_builder_renders_quoted_command() {
+    let intent = IntentBuilder::new()
+        .action("android.intent.action.VIEW")
+        .data_uri("https://example.com/?a=1&b=2")
+        .category("android.intent.category.BROWSABLE")
+        .extra_string("message", "it's a test")
+        .extra_int("count", 3)
+        .extra_bool("enabled", true)
+        .extra_string_array("names", &["a,b", "c"])
+        .flags(0x10000000)
+        .component("com.example/.MainActivity");
+
+    let command = intent.render("start", &["-W"]);
+
+    assert_eq!(
+        command,
+        "am start -W -a android.intent.action.VIEW -d 'https://example.com/?a=1&b=2' \
+         -c android.intent.category.BROWSABLE --es message 'it'\\''s a test' --ei count 3 \
+         --ez enabled true --esa names 'a\\,b,c' -f 0x10000000 -n com.example/.MainActivity"
+    );
+}
+
+#[test]
+fn test_launch_result_parses_wait_output() -> Result<(), Box<dyn Error>> {
+    let output = "Starting: Intent { cmp=com.example/.MainActivity }\n\
+                  Status: ok\n\
+                  LaunchState: COLD\n\
+                  Activity: com.example/.MainActivity\n\
+                  ThisTime: 412\n\
+                  TotalTime: 412\n\
+                  WaitTime: 430\n\
+                  Complete\n";
+
+    let result = LaunchResult::from_output(output)?;
+    assert_eq!(result.status.as_deref(), Some("ok"));
+    assert_eq!(result.launch_state.as_deref(), Some("COLD"));
+    assert_eq!(result.this_time_ms, Some(412));
+    assert_eq!(result.total_time_ms, Some(412));
+    assert_eq!(result.wait_time_ms, Some(430));
+
+    Ok(())
+}
+
+#[test]
+fn test_launch_result_reports_errors() {
+    let output = "Starting: Intent { cmp=com.example/.Missing }\n\
+                  Error type 3\n\
+                  Error: Activity class {com.example/com.example.Missing} does not exist.\n";
+
+    let err = LaunchResult::from_output(output).unwrap_err();
+    assert!(err.to_string().contains("does not exist"));
+}
+
+#[test]
+fn test_broadcast_result_parses_code_and_data() -> Result<(), Box<dyn Error>> {
+    let output = "Broadcasting: Intent { act=com.example.PING flg=0x400000 }\n\
+                  Broadcast completed: result=-1, data=\"pong\", extras: Bundle[{k=\"v\"}]\n";
+
+    let result = BroadcastResult::from_output(output)?;
+    assert_eq!(result.result_code, -1);
+    assert_eq!(result.data.as_deref(), Some("pong"));
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use adbr::CrashKind;
+use adbr::debugging::bugreport_analyzer::{summarize_bugreport, summarize_bugreport_text};
+use adbr::debugging::bugreport::{extract_bugreport, parse_bugreport_sections, read_bugreport_text, CATEGORY_ANR, CATEGORY_DUMPSYS, CATEGORY_LOGCAT, CATEGORY_OTHER};
+use zip::write::FileOptions;
+use zip::ZipWriter;
+
+const SAMPLE_BUGREPORT: &str = "\
+========================================================
+== dumpstate: 2024-05-01 10:00:00
+========================================================
+------ UPTIME (uptime) ------
+up 2 days
+------ 0.010s was the duration of 'UPTIME' ------
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+05-01 09:59:00.000  1000  1000 I Test: hello
+05-01 09:59:01.000  1000  1000 W Test: world
+------ VM TRACES AT LAST ANR (/data/anr/anr_2024) ------
+----- pid 1234 at 2024-05-01 09:58:00 -----
+------ DUMPSYS CRITICAL (/system/bin/dumpsys) ------
+DUMP OF SERVICE CRITICAL SurfaceFlinger:
+Display 0
+DUMP OF SERVICE activity:
+ACTIVITY MANAGER
+--------- 0.001s was the duration of dumpsys activity
+";
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adbr-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn write_zip(path: &PathBuf, entries: &[(&str, &str)]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut writer = ZipWriter::new(fs::File::create(path)?);
+    for (name, contents) in entries {
+        writer.start_file(*name, FileOptions::default())?;
+        writer.write_all(contents.as_bytes())?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_bugreport_sections() {
+    let sections = parse_bugreport_sections(SAMPLE_BUGREPORT);
+    let titles: Vec<&str> = sections.iter().map(|section| section.title.as_str()).collect();
+    assert_eq!(titles, vec![
+        "UPTIME",
+        "SYSTEM LOG",
+        "VM TRACES AT LAST ANR",
+        "DUMPSYS CRITICAL",
+        "DUMP OF SERVICE SurfaceFlinger",
+        "DUMP OF SERVICE activity",
+    ]);
+
+    assert_eq!(sections[0].category, CATEGORY_OTHER);
+    assert_eq!(sections[0].command.as_deref(), Some("uptime"));
+    assert_eq!((sections[0].start_line, sections[0].end_line), (5, 6));
+
+    assert_eq!(sections[1].category, CATEGORY_LOGCAT);
+    assert_eq!((sections[1].start_line, sections[1].end_line), (8, 9));
+    assert_eq!(sections[2].category, CATEGORY_ANR);
+    assert_eq!(sections[4].category, CATEGORY_DUMPSYS);
+    assert_eq!(sections[4].command.as_deref(), Some("dumpsys SurfaceFlinger"));
+    assert_eq!((sections[4].start_line, sections[4].end_line), (14, 14));
+    assert_eq!(sections[5].end_line, 17);
+}
+
+#[test]
+fn test_extract_bugreport_writes_sections_and_index() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = temp_dir("bugreport-extract");
+    let zip_path = dir.join("bugreport.zip");
+    write_zip(&zip_path, &[
+        ("main_entry.txt", "bugreport-sdk-2024-05-01.txt"),
+        ("bugreport-sdk-2024-05-01.txt", SAMPLE_BUGREPORT),
+        ("FS/data/anr/anr_2024", "----- pid 1234 -----"),
+    ])?;
+
+    let (main_file, text) = read_bugreport_text(&zip_path)?;
+    assert_eq!(main_file, "bugreport-sdk-2024-05-01.txt");
+    assert_eq!(text, SAMPLE_BUGREPORT);
+
+    let out_dir = dir.join("bugreport");
+    let index = extract_bugreport(&zip_path, &out_dir)?;
+    assert_eq!(index.anr_files, vec!["FS/data/anr/anr_2024".to_string()]);
+    assert!(out_dir.join("FS/data/anr/anr_2024").exists());
+    assert!(out_dir.join("index.json").exists());
+
+    let files: Vec<&str> = index.sections.iter().filter_map(|section| section.file.as_deref()).collect();
+    assert_eq!(files, vec![
+        "sections/logcat-system-log.txt",
+        "sections/anr-vm-traces-at-last-anr.txt",
+        "sections/dumpsys-surfaceflinger.txt",
+        "sections/dumpsys-activity.txt",
+    ]);
+    assert_eq!(
+        fs::read_to_string(out_dir.join("sections/logcat-system-log.txt"))?,
+        "05-01 09:59:00.000  1000  1000 I Test: hello\n05-01 09:59:01.000  1000  1000 W Test: world"
+    );
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_read_bugreport_text_without_main_entry() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = temp_dir("bugreport-fallback");
+    let zip_path = dir.join("old.zip");
+    write_zip(&zip_path, &[("version.txt", "1.0"), ("bugreport-old.txt", "------ UPTIME (uptime) ------\n")])?;
+
+    let (main_file, _) = read_bugreport_text(&zip_path)?;
+    assert_eq!(main_file, "bugreport-old.txt");
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+const ANALYZER_BUGREPORT: &str = "\
+========================================================
+== dumpstate: 2024-05-01 10:00:00
+========================================================
+
+Build: UQ1A.240105.004
+Build fingerprint: 'google/oriole/oriole:14/UQ1A.240105.004/11206848:user/release-keys'
+------ UPTIME (uptime) ------
+ 10:00:00 up 2 days,  3:04,  0 users,  load average: 1.00, 0.50, 0.20
+------ KERNEL LOG (dmesg) ------
+[  100.000000] lowmemorykiller: Killing 'com.example.cache' (4321) (tgid 4321), adj 906,
+[  200.000000] Kernel panic - not syncing: Watchdog detected hard LOCKUP on cpu 2
+------ SYSTEM LOG (logcat -v threadtime -v printable -v uid -d *:v) ------
+05-01 09:50:00.000  root   500   500 I lowmemorykiller: Kill 'com.example.bg' (2345), uid 10123, oom_score_adj 900 to free 51200kB rss
+05-01 09:55:00.000 10150  3000  3000 E AndroidRuntime: FATAL EXCEPTION: main
+05-01 09:55:00.000 10150  3000  3000 E AndroidRuntime: Process: com.example.app, PID: 3000
+05-01 09:55:00.000 10150  3000  3000 E AndroidRuntime: java.lang.IllegalStateException: boom
+05-01 09:55:00.000 10150  3000  3000 E AndroidRuntime: \tat com.example.app.Main.run(Main.java:10)
+05-01 09:58:00.000 system  1000  1100 E ActivityManager: ANR in com.example.slow
+05-01 09:58:00.000 system  1000  1100 E ActivityManager: PID: 4000
+05-01 09:58:00.000 system  1000  1100 E ActivityManager: Reason: Input dispatching timed out
+------ DUMPSYS (/system/bin/dumpsys) ------
+DUMP OF SERVICE battery:
+Current Battery Service state:
+  AC powered: false
+  status: 3
+  health: 2
+  level: 85
+  scale: 100
+  temperature: 251
+DUMP OF SERVICE batterystats:
+Battery History (1% used, 40KB used of 4096KB, 45 strings using 2526):
+All kernel wake locks:
+  Kernel Wake lock PowerManagerService.Display: 1h 4m 2s 71ms (12 times) realtime
+  Kernel Wake lock qcom_rx_wakelock: 3m 1s 0ms (900 times) realtime
+
+All partial wake locks:
+  Wake lock u0a57 NlpWakeLock: 2h 0m 0s 5ms (789 times) max=3000 realtime
+";
+
+#[test]
+fn test_summarize_bugreport_text() {
+    let summary = summarize_bugreport_text(ANALYZER_BUGREPORT);
+
+    assert_eq!(summary.build_fingerprint.as_deref(), Some("google/oriole/oriole:14/UQ1A.240105.004/11206848:user/release-keys"));
+    assert_eq!(summary.generated_at.as_deref(), Some("2024-05-01 10:00:00"));
+    assert_eq!(summary.uptime.as_deref(), Some("2 days, 3:04"));
+
+    let battery = summary.battery.expect("battery");
+    assert_eq!(battery.level, Some(85));
+    assert_eq!(battery.status.as_deref(), Some("discharging"));
+    assert_eq!(battery.health.as_deref(), Some("good"));
+    assert_eq!(battery.temperature_celsius, Some(25.1));
+    assert_eq!(battery.history.as_deref(), Some("Battery History (1% used, 40KB used of 4096KB, 45 strings using 2526)"));
+
+    assert_eq!(summary.crashes.len(), 1);
+    assert_eq!(summary.crashes[0].kind, CrashKind::JavaCrash);
+    assert_eq!(summary.crashes[0].process.as_deref(), Some("com.example.app"));
+    assert_eq!(summary.crashes[0].summary, "java.lang.IllegalStateException: boom");
+    assert_eq!(summary.anrs.len(), 1);
+    assert_eq!(summary.anrs[0].process.as_deref(), Some("com.example.slow"));
+    assert_eq!(summary.anrs[0].pid, Some(4000));
+
+    let wakelocks: Vec<(&str, bool, u64, u64)> = summary.top_wakelocks.iter()
+        .map(|wakelock| (wakelock.name.as_str(), wakelock.kernel, wakelock.duration_ms, wakelock.count))
+        .collect();
+    assert_eq!(wakelocks, vec![
+        ("u0a57 NlpWakeLock", false, 7_200_005, 789),
+        ("PowerManagerService.Display", true, 3_842_071, 12),
+        ("qcom_rx_wakelock", true, 181_000, 900),
+    ]);
+
+    let kills: Vec<(&str, i32, Option<i32>, bool)> = summary.low_memory_kills.iter()
+        .map(|kill| (kill.process.as_str(), kill.pid, kill.oom_score_adj, kill.timestamp.is_some()))
+        .collect();
+    assert_eq!(kills, vec![("com.example.cache", 4321, Some(906), false), ("com.example.bg", 2345, Some(900), true)]);
+
+    assert_eq!(summary.kernel_panics.len(), 1);
+    assert_eq!(summary.kernel_panics[0].section, "KERNEL LOG");
+    assert_eq!(summary.kernel_panics[0].message, "Kernel panic - not syncing: Watchdog detected hard LOCKUP on cpu 2");
+}
+
+#[test]
+fn test_summarize_bugreport_zip_lists_anr_files() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = temp_dir("bugreport-summary");
+    let zip_path = dir.join("bugreport.zip");
+    write_zip(&zip_path, &[
+        ("main_entry.txt", "bugreport-sdk.txt"),
+        ("bugreport-sdk.txt", ANALYZER_BUGREPORT),
+        ("FS/data/anr/anr_2", "trace"),
+        ("FS/data/anr/anr_1", "trace"),
+    ])?;
+
+    let summary = summarize_bugreport(&zip_path)?;
+    assert_eq!(summary.main_file.as_deref(), Some("bugreport-sdk.txt"));
+    assert_eq!(summary.anr_files, vec!["FS/data/anr/anr_1".to_string(), "FS/data/anr/anr_2".to_string()]);
+    assert_eq!(summary.crashes.len(), 1);
+
+    let text_path = dir.join("bugreport.txt");
+    fs::write(&text_path, ANALYZER_BUGREPORT)?;
+    let summary = summarize_bugreport(&text_path)?;
+    assert!(summary.main_file.is_none());
+    assert_eq!(summary.anrs.len(), 1);
+
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -1,961 +1,552 @@
-use adbr::{Client, PullResult, PushResult};
-use adbr::DeviceTransport;
 use std::error::Error;
-use std::process::Command;
-use tokio;
-use std::fs;
-use std::path::Path;
-use ctor::ctor;
-
-#[ctor]
-fn init() {
-    std::env::set_var("RUST_TEST_THREADS", "1");
-}
-
+use std::path::PathBuf;
+use std::time::Duration;
+use adbr::{Client, DeviceTransport, MockServer, PullResult, PushResult, VirtualDevice};
+use tokio::io::AsyncReadExt;
 
-fn run_adb_command(args: &[&str]) -> Result<String, Box<dyn Error>> {
-    let cmd_string = format!("adbr {}", args.join(" "));
-    println!("Executing: {}", cmd_string);
+const USB_SERIAL: &str = "R58M123ABC";
+const EMULATOR_SERIAL: &str = "emulator-5554";
 
-    let output = Command::new("adbr")
-        .args(args)
-        .output()?;
-
-    if output.status.success() {
-        let result = String::from_utf8(output.stdout)?;
-        Ok(result)
-    } else {
-        let error = String::from_utf8(output.stderr)?;
-        Err(error.into())
-    }
+fn usb() -> DeviceTransport {
+    DeviceTransport::serial(USB_SERIAL.to_string())
 }
 
-#[tokio::test]
-async fn test_adb_devices() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    let result = client.adb_devices().await?;
-    println!("result {}", result);
-    assert!(result.contains("List of devices attached"));
-
-    client.close().await;
-
-    Ok(())
+/// A local scratch directory unique to one test.
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adbr-client-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
 }
 
-#[tokio::test]
-async fn test_adb_devices_with_device() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    let result = client.adb_devices().await?;
-    println!("result: {}", result);
-
-    if !result.contains("List of devices attached") {
-        return Err("ADB not responding correctly".into());
-    }
-
-    let lines: Vec<&str> = result.lines().collect();
-
-    let devices: Vec<&str> = lines.iter()
-        .skip(1)
-        .map(|&line| line)
-        .filter(|line| !line.trim().is_empty())
-        .collect();
-
-    if devices.is_empty() {
-        return Err("No devices connected".into());
-    }
-
-    // Check if any device is in 'device' state
-    let ready_devices: Vec<&str> = devices.iter()
-        .filter(|&&line| line.contains("device"))
-        .copied()
-        .collect();
-
-    if ready_devices.is_empty() {
-        return Err("No authorized devices found".into());
-    }
-
-    println!("Found {} ready device(s)", ready_devices.len());
-    for device in ready_devices {
-        println!("Device: {}", device);
-    }
-
-    client.close().await;
+/// The shell command `adb_logcat` runs for `args`.
+fn logcat_command(args: &str) -> String {
+    format!("export ANDROID_LOG_TAGS=\"''\"; exec logcat {}", args)
+}
 
-    Ok(())
+/// A server with a single USB device.
+async fn single_device() -> Result<(MockServer, VirtualDevice, Client), Box<dyn Error>> {
+    let server = MockServer::new();
+    let device = VirtualDevice::new(USB_SERIAL);
+    server.add_device(&device);
+    let client = server.client().await?;
+    Ok((server, device, client))
 }
 
 #[tokio::test]
-async fn test_adb_shell() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    let result = client.adb_shell(DeviceTransport::default(), "echo test").await?;
-
-
-    assert_eq!(result.trim(), "test");
+async fn test_adb_devices() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL).prop("ro.product.model", "Pixel 7"));
+    let emulator_id = server.add_device(&VirtualDevice::new(EMULATOR_SERIAL));
+    server.add_device(&VirtualDevice::new("0123456789").state("unauthorized"));
+    let mut client = server.client().await?;
 
-    client.close().await;
+    let result = client.adb_devices().await?;
+    assert_eq!(result, "List of devices attached\nR58M123ABC\tdevice\nemulator-5554\tdevice\n0123456789\tunauthorized\n");
 
+    client.reconnect().await?;
+    let devices = client.adb_list_devices().await?;
+    assert_eq!(devices.len(), 3);
+    assert_eq!(devices[0].model.as_deref(), Some("Pixel_7"));
+    assert_eq!(devices[0].usb.as_deref(), Some("1-1"));
+    assert_eq!(devices[1].transport_id, Some(emulator_id));
+    assert_eq!(devices[1].transport_kind(), "local");
+    assert!(!devices[2].is_ready());
+    assert_eq!(devices[2].model, None);
     Ok(())
 }
 
-
 #[tokio::test]
-async fn test_apk_installation() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    let package_name = "com.example.myapplication";
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let apk_path = std::path::Path::new(project_root)
-        .join("tests")
-        .join("resources")
-        .join("test_app.apk")
-        .to_str()
-        .ok_or("Failed to convert APK path to string")?
-        .to_string();
-
-    let _ = client.adb_uninstall(DeviceTransport::default(), package_name, &[]).await;
-    client.reconnect().await?;
-
-    let install_options = vec![
-        "-r".to_string(),
-        "-d".to_string(),
-        "-g".to_string(),
-    ];
-
-    let install_result = client
-        .adb_install(DeviceTransport::default(), &apk_path, &install_options)
-        .await?;
-
-    if !install_result.contains("Success") {
-        return Err(format!("Installation failed: {}", install_result).into());
-    }
+async fn test_adb_shell() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
 
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "echo 'hello  world'").await?, "hello  world\n");
     client.reconnect().await?;
-
-    let packages = client
-        .adb_shell(DeviceTransport::default(), "pm list packages")
-        .await?;
-    assert!(
-        packages.contains(package_name),
-        "Package not found after installation"
-    );
-
+    let props = client.adb_shell(DeviceTransport::default(), "getprop ro.product.model && getprop ro.build.version.sdk").await?;
+    assert_eq!(props, "Mock_Device\n34\n");
     client.reconnect().await?;
+    assert!(client.adb_shell(DeviceTransport::default(), "invalid_command").await?.contains("inaccessible or not found"));
 
-    let uninstall_result = client
-        .adb_uninstall(DeviceTransport::default(), package_name, &[])
-        .await?;
-
-    if !uninstall_result.contains("Success") {
-        return Err(format!("Uninstallation failed: {}", uninstall_result).into());
-    }
-
-    client.reconnect().await?;
-
-    let packages_after = client
-        .adb_shell(DeviceTransport::default(), "pm list packages")
-        .await?;
-    assert!(
-        !packages_after.contains(package_name),
-        "Package still exists after uninstallation"
-    );
-
-    client.close().await;
+    assert_eq!(device.shell_history().len(), 3);
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_get_state() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    let state = client.adb_get_state(DeviceTransport::default()).await?;
-    assert!(!state.is_empty());
-
-    client.close().await;
+async fn test_adb_battery_status() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    device.clone().shell_output("dumpsys battery", "Current Battery Service state:\n  AC powered: false\n  status: 2\n  level: 85\n  scale: 100\n");
 
+    let battery_info = client.adb_shell(DeviceTransport::default(), "dumpsys battery").await?;
+    assert!(battery_info.contains("level: 85"), "{}", battery_info);
+    assert!(battery_info.contains("scale: 100"), "{}", battery_info);
+    assert!(battery_info.contains("status: 2"), "{}", battery_info);
     Ok(())
 }
 
-
 #[tokio::test]
-async fn test_adb_reverse() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
+async fn test_adb_package_manager() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    device.clone().package("com.example.notes");
 
-    client.send_reverse_command_set(DeviceTransport::default(), "tcp:8000", "tcp:9000", false).await?;
+    let packages = client.adb_shell(DeviceTransport::default(), "pm list packages -f").await?;
+    assert_eq!(packages.lines().filter(|line| line.starts_with("package:")).count(), device.installed_packages().len());
+    for package in ["android", "com.android.systemui", "com.android.settings", "com.example.notes"] {
+        assert!(packages.contains(&format!(".apk={}\n", package)), "{} not listed in {}", package, packages);
+    }
 
     client.reconnect().await?;
-
-    let reverse_list = client.send_reverse_command_list(DeviceTransport::default()).await?;
-
-    assert!(reverse_list.contains("tcp:8000"));
-
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "pm list packages example").await?, "package:com.example.notes\n");
     client.reconnect().await?;
-
-    client.send_reverse_command_remove(DeviceTransport::default(), "tcp:8000").await?;
-
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "pm path com.example.notes").await?, "package:/data/app/com.example.notes/base.apk\n");
     Ok(())
 }
 
-
 #[tokio::test]
-async fn test_adb_screencap() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-    println!("Starting screencap test...");
+async fn test_adb_device_info() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL).prop("ro.product.model", "Pixel 7").prop("ro.build.version.release", "15"));
+    let mut client = server.client().await?;
 
-    // Take screenshot
-    client.adb_shell(DeviceTransport::default(), "screencap -p /data/local/tmp/screenshot.png").await?;
+    assert_eq!(client.adb_serialno(DeviceTransport::default()).await?, USB_SERIAL);
     client.reconnect().await?;
-
-
-    client.adb_pull(
-        DeviceTransport::default(),
-        &["/data/local/tmp/screenshot.png".to_string()],
-        "local_screenshot.png",
-        false,
-    ).await?;
+    let props = client.adb_shell(DeviceTransport::default(), "getprop ro.product.model && getprop ro.build.version.release").await?;
+    assert_eq!(props, "Pixel 7\n15\n");
     client.reconnect().await?;
-
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-
-    let metadata = fs::metadata("local_screenshot.png")?;
-    assert!(metadata.len() > 0, "Screenshot file is empty");
-
-    // Cleanup
-    fs::remove_file("local_screenshot.png")?;
-
-    client.adb_shell(DeviceTransport::default(), "rm /data/local/tmp/screenshot.png").await?;
-
-    client.close().await;
-
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "getprop ro.serialno").await?, "R58M123ABC\n");
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_battery_status() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    let battery_info = client.adb_shell(DeviceTransport::default(), "dumpsys battery").await?;
-
-
-    // Verify basic battery information is present
-    assert!(battery_info.contains("level:"));
-    assert!(battery_info.contains("scale:"));
-    assert!(battery_info.contains("status:"));
+async fn test_adb_logcat_clear_and_dump() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    let device = device
+        .shell_output(&logcat_command("-c"), "")
+        .shell_output(&logcat_command("-d"), "--------- beginning of main\n10-18 12:00:00.000  1234  1234 I ActivityManager: Start proc\n");
 
-    client.close().await;
+    client.adb_logcat(DeviceTransport::default(), "-c", &mut tokio::io::sink()).await?;
+    client.reconnect().await?;
+    let mut output = Vec::new();
+    client.adb_logcat(DeviceTransport::default(), "-d", &mut output).await?;
+    let output = String::from_utf8(output)?;
+    assert_eq!(output.lines().count(), 2);
+    assert!(output.contains("ActivityManager: Start proc"), "{}", output);
 
+    assert_eq!(device.shell_history(), [logcat_command("-c"), logcat_command("-d")]);
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_package_manager() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    // List packages
-    let packages = client.adb_shell(DeviceTransport::default(), "pm list packages -f").await?;
-
-    // Check that we have a meaningful number of packages (typical Android devices have >50)
-    let package_count = packages.lines().filter(|line| line.starts_with("package:")).count();
-    assert!(
-        package_count > 50,
-        "Found only {} packages, expected more than 50 on a typical Android device",
-        package_count
-    );
-
-    // Check for some essential packages that should exist on any Android device
-    let essential_packages = [
-        "android", // Framework
-        "com.android.systemui", // System UI
-        "com.android.settings", // Settings
-    ];
-
-    for package in essential_packages {
-        assert!(
-            packages.contains(&format!(".apk={}", package)),
-            "Essential package '{}' not found",
-            package
-        );
-    }
-
-    client.close().await;
+async fn test_open_shell_reports_exit_status() -> Result<(), Box<dyn Error>> {
+    let (server, device, client) = single_device().await?;
+    device.clone().shell_failure("false_alarm", "it broke\n", 3);
+
+    let mut session = client.open_shell(usb(), "false_alarm", false).await?;
+    let mut stderr = String::new();
+    session.stderr.take().unwrap().read_to_string(&mut stderr).await?;
+    assert_eq!(stderr, "it broke\n");
+    assert_eq!(session.wait().await?, Some(3));
+    assert!(server.requests().contains(&"shell,v2,raw:false_alarm".to_string()));
+
+    // Without shell v2 the client falls back to exec: and gets no exit status.
+    device.features(&["cmd"]);
+    let mut session = client.open_shell(usb(), "echo legacy", false).await?;
+    let mut stdout = String::new();
+    session.stdout.take().unwrap().read_to_string(&mut stdout).await?;
+    assert_eq!(stdout, "legacy\n");
+    assert_eq!(session.wait().await?, None);
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_multiple_devices() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
+async fn test_adb_get_state_serialno_and_devpath() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL));
+    server.add_device(&VirtualDevice::new(EMULATOR_SERIAL).state("offline"));
+    let mut client = server.client().await?;
 
-    // Get list of devices
-    let devices_output = client.adb_devices().await?;
+    assert_eq!(client.adb_get_state(usb()).await?, "device");
     client.reconnect().await?;
-
-    // Parse the devices from output
-    let device_lines: Vec<&str> = devices_output
-        .lines()
-        .skip(1) // Skip "List of devices attached" header
-        .filter(|line| !line.is_empty())
-        .collect();
-
-    // Skip test if only one device is connected
-    if device_lines.len() <= 1 {
-        println!("Skipping test: not enough devices connected");
-        return Ok(());
-    }
-
-    // Get first device serial
-    if let Some(device_line) = device_lines.first() {
-        let device_serial = device_line.split_whitespace().next()
-            .ok_or("Failed to parse device serial")?;
-
-        let transport = DeviceTransport::usb(device_serial.to_string());
-
-        // Try a simple command with the specific device
-        let result = client
-            .adb_shell(transport, "echo test")
-            .await?;
-
-        assert!(!result.is_empty(), "Command execution failed");
-    }
-
-    client.close().await;
-    Ok(())
-}
-
-#[tokio::test]
-async fn test_adb_error_handling() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let nonexistent_file = std::path::Path::new(project_root)
-        .join("tests")
-        .join("resources")
-        .join("nonexistent_file")
-        .to_str()
-        .ok_or("Failed to convert path to string")?
-        .to_string();
-
-    let result = client
-        .adb_shell(DeviceTransport::default(), "invalid_command")
-        .await?;
+    assert_eq!(client.adb_get_state(DeviceTransport::default_emulator()).await?, "offline");
     client.reconnect().await?;
-
-    assert!(result.contains("inaccessible or not found"));
-
-    // Test invalid device serial
-    let result = client
-        .adb_shell(DeviceTransport::usb("nonexistent".to_string()), "echo test")
-        .await;
+    assert_eq!(client.adb_serialno(DeviceTransport::default_usb()).await?, USB_SERIAL);
     client.reconnect().await?;
-
-    assert!(result.is_err());
-
-    let result = client
-        .adb_pull(
-            DeviceTransport::default(),
-            &["/nonexistent/file".to_string()],
-            &nonexistent_file,
-            false,
-        )
-        .await;
-
-    assert!(result.is_err());
-
-    if Path::new(&nonexistent_file).exists() {
-        fs::remove_file(&nonexistent_file)?;
-    }
-
-    client.close().await;
-
+    assert_eq!(client.adb_get_devpath(usb()).await?, "usb:1-1");
+    client.reconnect().await?;
+    let features = client.adb_features(usb()).await?;
+    assert!(features.contains(&"shell_v2".to_string()));
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_push_single_file() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
+async fn test_device_selection_errors() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    let mut client = server.client().await?;
+    let error = client.adb_shell(DeviceTransport::default(), "echo test").await.expect_err("no devices");
+    assert_eq!(error.to_string(), "no devices/emulators found");
 
-    // Create a test file
-    let test_content = "test content";
-    std::fs::write("test_file.txt", test_content)?;
+    server.add_device(&VirtualDevice::new(USB_SERIAL));
+    server.add_device(&VirtualDevice::new(EMULATOR_SERIAL).state("offline"));
+    server.add_device(&VirtualDevice::new("0123456789").state("unauthorized"));
 
-    println!("Pushing single file to device...");
-    let result = client.adb_push(
-        DeviceTransport::default(),
-        &["test_file.txt".to_string()],
-        "/data/local/tmp/",
-        false,
-    ).await?;
+    client.reconnect().await?;
+    let error = client.adb_shell(DeviceTransport::default(), "echo test").await.expect_err("ambiguous");
+    assert_eq!(error.to_string(), "more than one device/emulator");
 
     client.reconnect().await?;
+    let error = client.adb_shell(DeviceTransport::serial("nonexistent".to_string()), "echo test").await.expect_err("unknown serial");
+    assert_eq!(error.to_string(), "device 'nonexistent' not found");
 
+    client.reconnect().await?;
+    let error = client.adb_shell(DeviceTransport::default_emulator(), "echo test").await.expect_err("offline");
+    assert_eq!(error.to_string(), "device offline");
 
-    for (path, result) in result {
-        match result {
-            Ok(push_result) => {
-                println!("Push result for {}: {:?}", path, push_result);
-                assert!(matches!(push_result, PushResult::Success(_, _, _, 1)));
-            }
-            Err(e) => panic!("Push failed: {}", e),
-        }
-    }
+    client.reconnect().await?;
+    let error = client.adb_push(DeviceTransport::serial("0123456789".to_string()), &["x".to_string()], "/sdcard/", false).await.expect_err("unauthorized");
+    assert!(error.to_string().starts_with("device unauthorized."), "{}", error);
 
-    // Cleanup
-    fs::remove_file("test_file.txt")?;
-    client.adb_shell(DeviceTransport::default(), "rm /data/local/tmp/test_file.txt").await?;
+    client.reconnect().await?;
+    let error = client.adb_shell(DeviceTransport::default_usb(), "echo test").await.expect_err("two usb devices");
+    assert_eq!(error.to_string(), "more than one device");
 
-    client.close().await;
+    assert!(server.remove_device("0123456789"));
+    client.reconnect().await?;
+    assert_eq!(client.adb_shell(DeviceTransport::default_usb(), "echo test").await?, "test\n");
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_logcat_clear_and_dump() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    println!("Clearing logcat...");
-    client.adb_logcat(DeviceTransport::default(), "-c").await?;
-
+async fn test_adb_forward_commands() -> Result<(), Box<dyn Error>> {
+    let (server, _device, mut client) = single_device().await?;
 
-    // Sleep briefly to ensure logcat clear takes effect
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    client.send_forward_command_set(DeviceTransport::default(), "tcp:8000", "tcp:8001", false).await?;
+    client.reconnect().await?;
+    client.send_forward_command_set(usb(), "tcp:8002", "localabstract:chrome_devtools_remote", false).await?;
+    client.reconnect().await?;
+    let list = client.send_forward_command_list(DeviceTransport::default()).await?;
+    assert_eq!(list, "R58M123ABC tcp:8000 tcp:8001\nR58M123ABC tcp:8002 localabstract:chrome_devtools_remote\n");
 
     client.reconnect().await?;
-    let result = client.adb_logcat(DeviceTransport::default(), "-d").await?;
+    let error = client.send_forward_command_set(DeviceTransport::default(), "tcp:8000", "tcp:9000", true).await.expect_err("no rebind");
+    assert_eq!(error.to_string(), "cannot rebind existing socket");
 
-    // Count the lines in the output
-    let line_count = result.lines().count();
+    client.reconnect().await?;
+    client.send_forward_command_remove(DeviceTransport::default(), "tcp:8000").await?;
+    client.reconnect().await?;
+    let error = client.send_forward_command_remove(DeviceTransport::default(), "tcp:8000").await.expect_err("already removed");
+    assert_eq!(error.to_string(), "listener 'tcp:8000' not found");
 
-    // Should be relatively small after clear
-    assert!(line_count < 1000, "Logcat should have fewer lines after clear, got {}", line_count);
+    client.reconnect().await?;
+    client.send_forward_command_remove_all(DeviceTransport::default()).await?;
+    client.reconnect().await?;
+    assert_eq!(client.send_forward_command_list(DeviceTransport::default()).await?, "");
 
-    client.close().await;
+    assert!(server.requests().contains(&"host-serial:R58M123ABC:forward:tcp:8002;localabstract:chrome_devtools_remote".to_string()));
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_forward_commands() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    println!("Testing port forwarding...");
+async fn test_adb_reverse() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
 
-    // Setup forward
-    client.send_forward_command_set(
-        DeviceTransport::default(),
-        "tcp:8000",
-        "tcp:8001",
-        false,
-    ).await?;
+    client.send_reverse_command_set(DeviceTransport::default(), "tcp:8000", "tcp:9000", false).await?;
+    client.reconnect().await?;
+    client.send_reverse_command_set(DeviceTransport::default(), "tcp:8001", "tcp:9001", false).await?;
     client.reconnect().await?;
+    let reverse_list = client.send_reverse_command_list(DeviceTransport::default()).await?;
+    assert!(reverse_list.contains("tcp:8000 tcp:9000"), "{}", reverse_list);
 
-    // List forwards
-    let list = client.send_forward_command_list(DeviceTransport::default()).await?;
-    assert!(list.contains("8000"), "Forward not found in list");
-    println!("Forward list: {}", list);
     client.reconnect().await?;
+    let error = client.send_reverse_command_set(DeviceTransport::default(), "tcp:8000", "tcp:7000", true).await.expect_err("no rebind");
+    assert_eq!(error.to_string(), "cannot rebind existing socket");
 
-    // Remove forward
-    client.send_forward_command_remove(
-        DeviceTransport::default(),
-        "tcp:8000",
-    ).await?;
     client.reconnect().await?;
+    client.send_reverse_command_remove(DeviceTransport::default(), "tcp:8000").await?;
+    assert_eq!(device.reverse_forwards(), [("tcp:8001".to_string(), "tcp:9001".to_string())]);
 
-    // Verify removal
-    let list_after = client.send_forward_command_list(DeviceTransport::default()).await?;
-    assert!(!list_after.contains("8000"), "Forward still exists after removal");
+    client.reconnect().await?;
+    let error = client.send_reverse_command_remove(DeviceTransport::default(), "tcp:8000").await.expect_err("already removed");
+    assert_eq!(error.to_string(), "listener 'tcp:8000' not found");
 
-    client.close().await;
+    client.reconnect().await?;
+    client.send_reverse_command_remove_all(DeviceTransport::default()).await?;
+    assert!(device.reverse_forwards().is_empty());
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_device_info() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
+async fn test_adb_push_pull_single_file() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    let dir = scratch_dir("single");
+    let local = dir.join("test_file.txt");
+    std::fs::write(&local, "test content")?;
 
-    println!("Getting device information...");
+    let results = client.adb_push(DeviceTransport::default(), &[local.to_string_lossy().into_owned()], "/data/local/tmp/", false).await?;
+    assert!(matches!(results[0].1, Ok(PushResult::Success(_, 12, _, 1))));
+    assert_eq!(device.read_file("/data/local/tmp/test_file.txt").as_deref(), Some(&b"test content"[..]));
 
-    // Get device serial
-    let serial = client.adb_serialno(DeviceTransport::default()).await?;
-    assert!(!serial.trim().is_empty(), "Serial number should not be empty");
-    println!("Device serial: {}", serial);
     client.reconnect().await?;
+    let pulled = dir.join("pulled.txt");
+    let results = client.adb_pull(DeviceTransport::default(), &["/data/local/tmp/test_file.txt".to_string()], &pulled.to_string_lossy(), false).await?;
+    assert!(matches!(results[0].1, Ok(PullResult::Success(_, 12, _, 1))));
+    assert_eq!(std::fs::read_to_string(&pulled)?, "test content");
 
-
-    // Get some basic device properties
-    let props = client.adb_shell(
-        DeviceTransport::default(),
-        "getprop ro.product.model && getprop ro.build.version.release",
-    ).await?;
-    println!("Device properties: {}", props);
-    assert!(!props.trim().is_empty(), "Device properties should not be empty");
-
-    client.close().await;
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_push_pull_large_file() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let test_dir = std::path::Path::new(project_root)
-        .join("tests")
-        .join("resources");
-
-    // Create a large file (1MB)
-    let large_file_path = test_dir.join("large_test_file.dat");
-    let large_data = vec![0x55; 1024 * 1024]; // 1MB of data
-    std::fs::write(&large_file_path, &large_data)?;
-
-    // Push large file
-    let push_results = client.adb_push(
-        DeviceTransport::default(),
-        &[large_file_path.to_str().unwrap().to_string()],
-        "/data/local/tmp/large_test_file.dat",
-        false,
-    ).await?;
-
-    for (_, result) in &push_results {
-        match result {
-            Ok(push_result) => {
-                assert!(
-                    matches!(push_result, PushResult::Success(_, bytes, _, 1) if *bytes == 1024 * 1024),
-                    "Push failed or wrong file size"
-                );
-            }
-            Err(e) => panic!("Push failed: {}", e),
-        }
-    }
+async fn test_adb_push_pull_large_binary_file() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    let dir = scratch_dir("large");
+    let local = dir.join("large_test_file.dat");
+    // Several sync DATA chunks, with every byte value.
+    let data: Vec<u8> = (0..1024 * 1024 + 17).map(|i| (i * 7 % 256) as u8).collect();
+    std::fs::write(&local, &data)?;
+
+    let results = client.adb_push(DeviceTransport::default(), &[local.to_string_lossy().into_owned()], "/data/local/tmp/large_test_file.dat", false).await?;
+    assert!(matches!(results[0].1, Ok(PushResult::Success(_, bytes, _, 1)) if bytes == data.len() as u64));
+    assert_eq!(device.read_file("/data/local/tmp/large_test_file.dat"), Some(data.clone()));
 
     client.reconnect().await?;
+    let pulled = dir.join("pulled_large_file.dat");
+    client.adb_pull(DeviceTransport::default(), &["/data/local/tmp/large_test_file.dat".to_string()], &pulled.to_string_lossy(), false).await?;
+    assert_eq!(std::fs::read(&pulled)?, data);
 
-    // Pull and verify
-    let pull_path = test_dir.join("pulled_large_file.dat");
-    let pull_results = client.adb_pull(
-        DeviceTransport::default(),
-        &["/data/local/tmp/large_test_file.dat".to_string()],
-        pull_path.to_str().unwrap(),
-        false,
-    ).await?;
-
-    // Verify pull results
-    for (_, result) in &pull_results {
-        match result {
-            Ok(pull_result) => {
-                assert!(
-                    matches!(pull_result, PullResult::Success(_, bytes, _, 1) if *bytes == 1024 * 1024),
-                    "Pull failed or wrong file size"
-                );
-            }
-            Err(e) => panic!("Pull failed: {}", e),
-        }
-    }
-
-    client.reconnect().await?;
-
-    // Cleanup
-    std::fs::remove_file(large_file_path)?;
-    std::fs::remove_file(pull_path)?;
-    client.adb_shell(DeviceTransport::default(), "rm /data/local/tmp/large_test_file.dat").await?;
-
-    client.close().await;
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_push_pull_multiple_files() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let test_dir = std::path::Path::new(project_root)
-        .join("tests")
-        .join("resources");
-
-    // Create multiple test files
-    let file_contents = [
-        ("test1.txt", "content1\n"),
-        ("test2.txt", "content2\n"),
-        ("test3.txt", "content3\n"),
-    ];
-
-    let file_paths: Vec<String> = file_contents
-        .iter()
-        .map(|(name, content)| {
-            let path = test_dir.join(name);
-            std::fs::write(&path, content).unwrap();
-            path.to_str().unwrap().to_string()
-        })
-        .collect();
-
-    // Push all files at once
-    let push_results = client.adb_push(
-        DeviceTransport::default(),
-        &file_paths,
-        "/data/local/tmp/",
-        false,
-    ).await?;
-
-    for (_, result) in &push_results {
-        match result {
-            Ok(push_result) => {
-                assert!(
-                    matches!(push_result, PushResult::Success(_, _, _, 1)),
-                    "Push failed"
-                );
-            }
-            Err(e) => panic!("Push failed: {}", e),
-        }
-    }
-
-    // Create pulled files directory
-    let pull_dir = test_dir.join("pulled_files");
-    std::fs::create_dir_all(&pull_dir)?;
-
-    // Pull all files at once
-    let remote_paths: Vec<String> = file_contents
-        .iter()
-        .map(|(name, _)| format!("/data/local/tmp/{}", name))
-        .collect();
-
-    client.reconnect().await?;
-
-    let pull_results = client.adb_pull(
-        DeviceTransport::default(),
-        &remote_paths,
-        pull_dir.to_str().unwrap(),
-        false,
-    ).await?;
-
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-
-    // Verify all pulls succeeded
-    for (path, result) in &pull_results {
-        match result {
-            Ok(pull_result) => {
-                assert!(
-                    matches!(pull_result, PullResult::Success(_, _, _, 1)),
-                    "Pull failed for {}", path
-                );
-            }
-            Err(e) => panic!("Pull failed for {}: {}", path, e),
-        }
+async fn test_adb_push_pull_multiple_files_and_directories() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    let dir = scratch_dir("multiple");
+    let file_contents = [("test1.txt", "content1\n"), ("test2.txt", "content2\n"), ("test3.txt", "content3\n")];
+    let local_paths: Vec<String> = file_contents.iter().map(|(name, content)| {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path.to_string_lossy().into_owned()
+    }).collect();
+
+    let results = client.adb_push(DeviceTransport::default(), &local_paths, "/sdcard/Download/", false).await?;
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|(_, result)| matches!(result, Ok(PushResult::Success(_, _, _, 1)))));
+    for (name, content) in &file_contents {
+        assert_eq!(device.read_file(&format!("/sdcard/Download/{}", name)), Some(content.as_bytes().to_vec()));
     }
 
-    // Verify content of all files
+    client.reconnect().await?;
+    let pull_dir = dir.join("pulled_files");
+    let remote_paths: Vec<String> = file_contents.iter().map(|(name, _)| format!("/sdcard/Download/{}", name)).collect();
+    client.adb_pull(DeviceTransport::default(), &remote_paths, &pull_dir.to_string_lossy(), false).await?;
     for (name, content) in &file_contents {
-        let pulled_content = std::fs::read_to_string(pull_dir.join(name))?;
-        assert_eq!(
-            pulled_content,
-            *content,
-            "Content mismatch for file: {}",
-            name
-        );
+        assert_eq!(std::fs::read_to_string(pull_dir.join(name))?, *content);
     }
 
+    // A whole directory, including a nested one.
+    let device = device.file("/sdcard/DCIM/Camera/IMG_0001.jpg", b"\xff\xd8jpeg".to_vec());
+    client.reconnect().await?;
+    let results = client.adb_pull(DeviceTransport::default(), &["/sdcard/DCIM".to_string()], &dir.join("DCIM").to_string_lossy(), false).await?;
+    assert!(matches!(results[0].1, Ok(PullResult::SuccessDirectory(_, 6, _, 1))));
+    assert_eq!(std::fs::read(dir.join("DCIM/Camera/IMG_0001.jpg"))?, b"\xff\xd8jpeg");
+    assert!(device.read_file("/sdcard/DCIM/Camera/IMG_0001.jpg").is_some());
 
-    // Cleanup
-    for (name, _) in &file_contents {
-        client.reconnect().await?;
-        std::fs::remove_file(test_dir.join(name))?;
-        std::fs::remove_file(pull_dir.join(name))?;
-        client.adb_shell(
-            DeviceTransport::default(),
-            &format!("rm /data/local/tmp/{}", name),
-        ).await?;
-    }
-    std::fs::remove_dir(pull_dir)?;
-
-    client.close().await;
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn test_adb_push_pull_binary_file() -> Result<(), Box<dyn Error>> {
-    let mut client = Client::new(None, None).await?;
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let test_dir = std::path::Path::new(project_root)
-        .join("tests")
-        .join("resources");
-
-    let binary_data: Vec<u8> = (0..1024).map(|_| rand::random::<u8>()).collect();
-    let binary_file = test_dir.join("test.bin");
-    std::fs::write(&binary_file, &binary_data)?;
-
-    let push_results = client.adb_push(
-        DeviceTransport::default(),
-        &[binary_file.to_str().unwrap().to_string()],
-        "/data/local/tmp/test.bin",
-        false,
-    ).await?;
-
-    for (_, result) in &push_results {
-        match result {
-            Ok(push_result) => {
-                assert!(
-                    matches!(push_result, PushResult::Success(_, bytes, _, 1) if *bytes == 1024),
-                    "Push failed or wrong file size"
-                );
-            }
-            Err(e) => panic!("Push failed: {}", e),
-        }
-    }
+async fn test_adb_screencap() -> Result<(), Box<dyn Error>> {
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\x00\x00\x00\rIHDR";
+    let (_server, device, mut client) = single_device().await?;
+    // The virtual device has no screen; the capture is scripted as a file the command leaves behind.
+    let device = device.shell_output("screencap -p /data/local/tmp/screenshot.png", "").file("/data/local/tmp/screenshot.png", PNG);
+    let dir = scratch_dir("screencap");
+    let local = dir.join("local_screenshot.png");
 
+    client.adb_shell(DeviceTransport::default(), "screencap -p /data/local/tmp/screenshot.png").await?;
     client.reconnect().await?;
-
-
-    let pulled_file = test_dir.join("pulled.bin");
-    let pull_results = client.adb_pull(
-        DeviceTransport::default(),
-        &["/data/local/tmp/test.bin".to_string()],
-        pulled_file.to_str().unwrap(),
-        false,
-    ).await?;
-
-    for (_, result) in &pull_results {
-        match result {
-            Ok(pull_result) => {
-                assert!(
-                    matches!(pull_result, PullResult::Success(_, bytes, _, 1) if *bytes == 1024),
-                    "Pull failed or wrong file size"
-                );
-            }
-            Err(e) => panic!("Pull failed: {}", e),
-        }
-    }
-
-    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-    // Verify content matches
-    let pulled_data = std::fs::read(&pulled_file)?;
-    assert_eq!(pulled_data, binary_data, "Binary content mismatch");
-
-    // Cleanup
-    std::fs::remove_file(binary_file)?;
-    std::fs::remove_file(pulled_file)?;
+    client.adb_pull(DeviceTransport::default(), &["/data/local/tmp/screenshot.png".to_string()], &local.to_string_lossy(), false).await?;
+    assert_eq!(std::fs::read(&local)?, PNG);
 
     client.reconnect().await?;
+    client.adb_shell(DeviceTransport::default(), "rm /data/local/tmp/screenshot.png").await?;
+    assert!(device.read_file("/data/local/tmp/screenshot.png").is_none());
 
-    client.adb_shell(DeviceTransport::default(), "rm /data/local/tmp/test.bin").await?;
-
-    client.close().await;
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn cli_test_list_devices() -> Result<(), Box<dyn Error>> {
-    let result = run_adb_command(&["devices"])?;
-    println!("result {}", result);
-    assert!(result.contains("List of devices attached"));
+async fn test_adb_push_pull_errors() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
+    let dir = scratch_dir("errors");
+    let local = dir.join("a.txt");
+    std::fs::write(&local, "a")?;
+    let local = local.to_string_lossy().into_owned();
+
+    let error = client.adb_push(DeviceTransport::default(), &[local.clone(), local.clone()], "/data/local/tmp/missing", false).await.expect_err("not a directory");
+    assert_eq!(error.to_string(), "adbr: error: target '/data/local/tmp/missing' is not a directory");
+
+    // A file where a parent directory should be.
+    let device = device.file("/sdcard/blocker", "x");
+    client.reconnect().await?;
+    let results = client.adb_push(DeviceTransport::default(), &[local], "/sdcard/blocker/a.txt", false).await?;
+    assert!(matches!(&results[0].1, Ok(PushResult::FailedAllPush(message)) if message.contains("Not a directory")));
+
+    client.reconnect().await?;
+    let pulled = dir.join("missing");
+    let error = client.adb_pull(DeviceTransport::default(), &["/nonexistent/file".to_string()], &pulled.to_string_lossy(), false).await.expect_err("missing remote file");
+    assert!(error.to_string().contains("No such file or directory"), "{}", error);
+    assert!(!pulled.exists());
+
+    assert!(device.read_file("/sdcard/blocker").is_some());
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn cli_test_device_status() -> Result<(), Box<dyn Error>> {
-    let result = run_adb_command(&["devices"])?;
-    println!("result: {}", result);
-
-    if !result.contains("List of devices attached") {
-        return Err("ADB not responding correctly".into());
-    }
-
-    let lines: Vec<&str> = result.lines().collect();
-    let devices: Vec<&str> = lines.iter()
-        .skip(1)
-        .map(|&line| line)
-        .filter(|line| !line.trim().is_empty())
-        .collect();
+async fn test_apk_installation() -> Result<(), Box<dyn Error>> {
+    let package_name = "com.example.myapplication";
+    let (_server, device, mut client) = single_device().await?;
+    let device = device.apk("test_app.apk", package_name);
+    let dir = scratch_dir("install");
+    let apk = dir.join("test_app.apk");
+    std::fs::write(&apk, b"PK\x03\x04")?;
+    let apk = apk.to_string_lossy().into_owned();
+
+    let install_options = ["-r".to_string(), "-d".to_string(), "-g".to_string()];
+    let install_result = client.adb_install(DeviceTransport::default(), &apk, &install_options).await?;
+    assert_eq!(install_result, "Success\n");
+    assert!(device.shell_history().contains(&"pm install -r -d -g /data/local/tmp/test_app.apk".to_string()));
+    assert!(device.read_file("/data/local/tmp/test_app.apk").is_some());
 
-    if devices.is_empty() {
-        return Err("No devices connected".into());
-    }
+    client.reconnect().await?;
+    let packages = client.adb_shell(DeviceTransport::default(), "pm list packages").await?;
+    assert!(packages.contains(&format!("package:{}\n", package_name)));
 
-    let ready_devices: Vec<&str> = devices.iter()
-        .filter(|&&line| line.contains("device"))
-        .copied()
-        .collect();
+    client.reconnect().await?;
+    assert_eq!(client.adb_uninstall(DeviceTransport::default(), package_name, &[]).await?, "Success\n");
+    assert!(!device.installed_packages().contains(&package_name.to_string()));
 
-    if ready_devices.is_empty() {
-        return Err("No authorized devices found".into());
-    }
+    client.reconnect().await?;
+    let uninstall_again = client.adb_uninstall(DeviceTransport::default(), package_name, &[]).await?;
+    assert!(uninstall_again.starts_with("Failure [DELETE_FAILED_INTERNAL_ERROR]"));
 
-    println!("Found {} ready device(s)", ready_devices.len());
-    for device in ready_devices {
-        println!("Device: {}", device);
-    }
+    let unknown = dir.join("unknown.apk");
+    std::fs::write(&unknown, b"not an apk")?;
+    client.reconnect().await?;
+    let install_result = client.adb_install(DeviceTransport::default(), &unknown.to_string_lossy(), &[]).await?;
+    assert!(install_result.starts_with("Failure [INSTALL_PARSE_FAILED_NOT_APK"), "{}", install_result);
 
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
 }
 
 #[tokio::test]
-async fn cli_test_shell_echo() -> Result<(), Box<dyn Error>> {
-    let result = run_adb_command(&["shell", "echo", "test"])?;
-    assert_eq!(result.trim(), "test");
-    Ok(())
-}
+async fn test_adb_reboot() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    let device = VirtualDevice::new(USB_SERIAL).boot_duration(Duration::from_millis(300));
+    server.add_device(&device);
+    let mut client = server.client().await?;
 
-#[tokio::test]
-async fn cli_test_file_transfer() -> Result<(), Box<dyn Error>> {
-    println!("Starting push/pull test...");
-
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let test_dir = Path::new(project_root).join("tests").join("resources");
-    fs::create_dir_all(&test_dir)?;
-
-    // Create test file
-    let test_content = "test_file\n";
-    let local_path = test_dir.join("test_file.txt");
-    fs::write(&local_path, test_content)?;
-
-    let remote_path = "/data/local/tmp/test_file.txt";
-
-    // Push file
-    run_adb_command(&[
-        "push",
-        local_path.to_str().unwrap(),
-        remote_path
-    ])?;
-
-    // Verify file exists and content
-    let ls_output = run_adb_command(&[
-        "shell",
-        "ls",
-        "-l",
-        remote_path
-    ])?;
-
-    let cat_output = run_adb_command(&[
-        "shell",
-        "cat",
-        remote_path
-    ])?;
-
-    assert!(!ls_output.contains("No such file"));
-    assert_eq!(cat_output.trim(), test_content.trim());
-
-    // Pull file
-    let pull_path = test_dir.join("pulled_test_file.txt");
-    run_adb_command(&[
-        "pull",
-        remote_path,
-        pull_path.to_str().unwrap()
-    ])?;
-
-    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
-
-    let pulled_content = fs::read_to_string(&pull_path)?;
-    assert_eq!(pulled_content, test_content);
-
-    fs::remove_file(&local_path)?;
-    fs::remove_file(&pull_path)?;
-    run_adb_command(&["shell", "rm", remote_path])?;
+    client.send_reverse_command_set(DeviceTransport::default(), "tcp:8000", "tcp:9000", false).await?;
+    client.reconnect().await?;
+    client.adb_reboot(DeviceTransport::default(), None).await?;
+    assert_eq!(device.connection_state(), "offline");
+    assert!(device.reverse_forwards().is_empty(), "adbd restarts without reverse forwards");
 
-    Ok(())
-}
+    client.reconnect().await?;
+    let error = client.adb_shell(DeviceTransport::default(), "echo test").await.expect_err("still booting");
+    assert_eq!(error.to_string(), "device offline");
+    client.reconnect().await?;
+    client.adb_wait_for(DeviceTransport::default(), "device", Some(Duration::from_secs(10))).await?;
 
-#[tokio::test]
-async fn cli_test_apk_install() -> Result<(), Box<dyn Error>> {
-    let package_name = "com.example.myapplication";
-    let project_root = env!("CARGO_MANIFEST_DIR");
-    let apk_path = Path::new(project_root)
-        .join("tests")
-        .join("resources")
-        .join("test_app.apk");
-
-    // Uninstall if exists
-    let _ = run_adb_command(&["uninstall", package_name]);
-
-    // Install APK
-    let install_result = run_adb_command(&[
-        "install",
-        apk_path.to_str().unwrap()
-    ])?;
-
-    assert!(install_result.contains("Success"));
-
-    // // Verify installation
-    // let packages = run_adb_command(&["shell", "pm", "list", "packages"])?;
-    // assert!(packages.contains(package_name));
-    //
-    // // Uninstall
-    // let uninstall_result = run_adb_command(&["uninstall", package_name])?;
-    // assert!(uninstall_result.contains("Success"));
+    client.reconnect().await?;
+    let error = client.adb_reboot(DeviceTransport::default(), Some("fastboot".to_string())).await.expect_err("invalid target");
+    assert_eq!(error.to_string(), "Invalid reboot target: fastboot");
 
+    client.reconnect().await?;
+    client.adb_reboot(DeviceTransport::default(), Some("bootloader".to_string())).await?;
+    client.reconnect().await?;
+    assert_eq!(client.adb_devices().await?, "List of devices attached\n");
+    assert_eq!(device.reboots(), ["", "bootloader"]);
     Ok(())
 }
 
 #[tokio::test]
-async fn cli_test_screenshot() -> Result<(), Box<dyn Error>> {
-    println!("Starting screencap test...");
-
-    // Take screenshot
-    run_adb_command(&[
-        "shell",
-        "screencap",
-        "-p",
-        "/data/local/tmp/screenshot.png"
-    ])?;
-
-    // Pull screenshot
-    run_adb_command(&[
-        "pull",
-        "/data/local/tmp/screenshot.png",
-        "local_screenshot.png"
-    ])?;
-
-    // Verify file exists and has content
-    let metadata = fs::metadata("local_screenshot.png")?;
-    assert!(metadata.len() > 0);
-
-    // Cleanup
-    fs::remove_file("local_screenshot.png")?;
-    run_adb_command(&["shell", "rm", "/data/local/tmp/screenshot.png"])?;
+async fn test_adb_root_and_unroot() -> Result<(), Box<dyn Error>> {
+    let (_server, device, mut client) = single_device().await?;
 
+    assert_eq!(client.adb_root(DeviceTransport::default()).await?, "restarting adbd as root\n");
+    client.reconnect().await?;
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "whoami").await?, "root\n");
+    client.reconnect().await?;
+    assert_eq!(client.adb_root(DeviceTransport::default()).await?, "adbd is already running as root\n");
+    client.reconnect().await?;
+    assert_eq!(client.adb_unroot(DeviceTransport::default()).await?, "restarting adbd as non root\n");
+    assert!(!device.is_root());
     Ok(())
 }
 
-
-// #[tokio::test]
-// async fn cli_test_logcat_operations() -> Result<(), Box<dyn Error>> {
-//     // Clear logcat
-//     run_adb_command(&["logcat", "-c"])?;
-//
-//     // Wait briefly
-//     tokio::time::sleep(std::time::Duration::from_secs(1)).await;
-//
-//     // Dump logcat
-//     let result = run_adb_command(&["logcat", "-d"])?;
-//     let line_count = result.lines().count();
-//     assert!(line_count < 1000, "Logcat should have fewer lines after clear");
-//
-//     Ok(())
-// }
-
 #[tokio::test]
-async fn cli_test_port_forwarding() -> Result<(), Box<dyn Error>> {
-    run_adb_command(&["forward", "tcp:9000", "tcp:9001"])?;
-
-    // List forwards
-    let list = run_adb_command(&["forward", "--list"])?;
-    assert!(list.contains("9000"));
-
-    // Remove forward
-    run_adb_command(&["forward", "--remove", "tcp:9000"])?;
-
-    // Verify removal
-    let list_after = run_adb_command(&["forward", "--list"])?;
-    assert!(!list_after.contains("9000"));
-
+async fn test_cli_against_mock_server() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL).file("/sdcard/hello.txt", "hi\n"));
+    server.add_device(&VirtualDevice::new(EMULATOR_SERIAL));
+    let port = server.listen().await?.port().to_string();
+
+    let run = |args: &[&str]| {
+        tokio::process::Command::new(env!("CARGO_BIN_EXE_adbr"))
+            .args(["-P", &port])
+            .args(args)
+            .env("ADBR_CONFIG", "/nonexistent/adbr.toml")
+            .env_remove("ADB_ADDRESS")
+            .env_remove("ADB_SERVER_SOCKET")
+            .env_remove("ANDROID_SERIAL")
+            .env_remove("ADBR_PROFILE")
+            .output()
+    };
+
+    let output = run(&["devices"]).await?;
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stdout)?.contains("R58M123ABC\tdevice"));
+
+    let output = run(&["-d", "shell", "cat", "/sdcard/hello.txt"]).await?;
+    assert_eq!(String::from_utf8(output.stdout)?, "hi\n");
+
+    let output = run(&["shell", "echo", "hi"]).await?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr)?.contains("more than one device/emulator"));
     Ok(())
 }
 
 #[tokio::test]
-async fn cli_test_device_properties() -> Result<(), Box<dyn Error>> {
-    // Get device serial
-    let serial = run_adb_command(&["get-serialno"])?;
-    assert!(!serial.trim().is_empty());
-
-    // Get device properties
-    let props = run_adb_command(&[
-        "shell",
-        "getprop ro.product.model && getprop ro.build.version.release"
-    ])?;
-    assert!(!props.trim().is_empty());
-
+async fn test_cli_fan_out_summary_reports_failed_devices() -> Result<(), Box<dyn Error>> {
+    let server = MockServer::new();
+    let accepting = VirtualDevice::new(USB_SERIAL).apk("app.apk", "com.example.app");
+    server.add_device(&accepting);
+    server.add_device(&VirtualDevice::new(EMULATOR_SERIAL));
+    let port = server.listen().await?.port().to_string();
+    let dir = scratch_dir("fan-out");
+    let apk = dir.join("app.apk");
+    std::fs::write(&apk, b"PK\x03\x04")?;
+
+    let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_adbr"))
+        .args(["-P", &port, "--all", "install", &apk.to_string_lossy()])
+        .env("ADBR_CONFIG", "/nonexistent/adbr.toml")
+        .env_remove("ADB_ADDRESS")
+        .env_remove("ADB_SERVER_SOCKET")
+        .env_remove("ANDROID_SERIAL")
+        .env_remove("ADBR_PROFILE")
+        .output()
+        .await?;
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(!output.status.success(), "{}", stdout);
+    assert!(stdout.contains("  R58M123ABC\tOK\n"), "{}", stdout);
+    assert!(stdout.contains("  emulator-5554\tFAILED"), "{}", stdout);
+    assert!(stdout.contains("2 devices, 1 succeeded, 1 failed"), "{}", stdout);
+    assert!(accepting.installed_packages().contains(&"com.example.app".to_string()));
+
+    std::fs::remove_dir_all(&dir)?;
     Ok(())
-}
\ No newline at end of file
+}
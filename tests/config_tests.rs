@@ -0,0 +1,128 @@
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+use adbr::{AdbrConfig, Client, ClientBuilder, MockServer, RetryPolicy, VirtualDevice};
+
+const CONFIG: &str = r#"
+default_profile = "local"
+
+[profiles.local]
+port = 5037
+
+[profiles.lab]
+host = "10.0.0.5"
+port = 5038
+serial = "R58M123ABC"
+io_timeout = "0"
+connect_timeout = "2s"
+retries = 3
+retry_delay = "250ms"
+keys = ["~/.android/lab_adbkey", "/etc/adbkey"]
+"#;
+
+#[test]
+fn test_parse_config_and_select_profiles() -> Result<(), Box<dyn std::error::Error>> {
+    let config = AdbrConfig::parse(CONFIG)?;
+    assert_eq!(config.profile(None)?.and_then(|profile| profile.port), Some(5037));
+
+    let lab = config.profile(Some("lab"))?.expect("lab profile");
+    assert_eq!(lab.host.as_deref(), Some("10.0.0.5"));
+    assert_eq!(lab.retries, Some(3));
+
+    let error = config.profile(Some("staging")).expect_err("unknown profile");
+    assert_eq!(error.to_string(), "Unknown profile 'staging' (known: lab, local)");
+    assert!(AdbrConfig::parse("").unwrap().profile(None)?.is_none());
+    Ok(())
+}
+
+#[test]
+fn test_config_rejects_unknown_settings() {
+    let error = AdbrConfig::parse("[profiles.lab]\nhots = \"10.0.0.5\"\n").expect_err("typo should be reported");
+    assert!(error.to_string().contains("unknown field `hots`"), "{}", error);
+}
+
+#[test]
+fn test_profile_rejects_conflicting_device_selectors() -> Result<(), Box<dyn std::error::Error>> {
+    let config = AdbrConfig::parse("[profiles.both]\nserial = \"abc\"\nusb = true\n")?;
+    let profile = config.profile(Some("both"))?.unwrap();
+    assert!(ClientBuilder::new().profile(profile).is_err());
+    Ok(())
+}
+
+/// A server with one emulator.
+fn mock_server() -> MockServer {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("emulator-5554"));
+    server
+}
+
+#[tokio::test]
+async fn test_builder_applies_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let port = mock_server().listen().await?.port();
+    let config = AdbrConfig::parse(&format!("[profiles.test]\nhost = \"127.0.0.1\"\nport = {}\nemulator = true\nio_timeout = \"5s\"\nkeys = [\"/tmp/adbkey\"]\n", port))?;
+
+    let mut client = ClientBuilder::new().profile(config.profile(Some("test"))?.unwrap())?.build().await?;
+    assert_eq!(client.server_port(), Some(port));
+    assert_eq!(client.default_device().get_device_transport(), "host:transport-local");
+    assert_eq!(client.limits().io_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(client.key_paths(), [PathBuf::from("/tmp/adbkey")]);
+    assert!(client.adb_devices().await?.contains("emulator-5554"));
+
+    // Later settings win over the profile.
+    let client = ClientBuilder::new().profile(config.profile(Some("test"))?.unwrap())?.io_timeout(None).build().await?;
+    assert_eq!(client.limits().io_timeout, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_builder_retries_until_the_server_is_up() -> Result<(), Box<dyn std::error::Error>> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    let port = listener.local_addr()?.port();
+    drop(listener);
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        mock_server().listen_on(("127.0.0.1", port)).await.unwrap();
+    });
+
+    let builder = Client::builder().server_address("127.0.0.1").server_port(port);
+    assert!(builder.clone().build().await.is_err());
+    let mut client = builder.retry_policy(RetryPolicy::new(20, Duration::from_millis(50))).build().await?;
+    assert!(client.adb_devices().await?.contains("emulator-5554"));
+    Ok(())
+}
+
+#[test]
+fn test_cli_precedence_of_flags_env_and_profile() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("adbr-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let config_path = dir.join("config.toml");
+    // Nothing listens on port 1, so each run reports the address it tried.
+    std::fs::write(&config_path, "default_profile = \"lab\"\n[profiles.lab]\nhost = \"127.0.0.2\"\nport = 1\n[profiles.other]\nhost = \"127.0.0.3\"\nport = 1\n")?;
+
+    let attempted_address = |args: &[&str], env: &[(&str, &str)]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_adbr"))
+            .args(args)
+            .arg("get-state")
+            .env("ADBR_CONFIG", &config_path)
+            .env_remove("ADB_ADDRESS")
+            .env_remove("ADBR_PROFILE")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        match stderr.split_once("at address ") {
+            Some((_, rest)) => rest.split(": ").next().unwrap_or_default().to_string(),
+            None => stderr,
+        }
+    };
+
+    assert_eq!(attempted_address(&[], &[]), "127.0.0.2:1");
+    assert_eq!(attempted_address(&[], &[("ADBR_PROFILE", "other")]), "127.0.0.3:1");
+    assert_eq!(attempted_address(&["--profile", "other"], &[("ADBR_PROFILE", "lab")]), "127.0.0.3:1");
+    assert_eq!(attempted_address(&[], &[("ADB_ADDRESS", "127.0.0.4:1")]), "127.0.0.4:1");
+    assert_eq!(attempted_address(&["-H", "127.0.0.5"], &[("ADB_ADDRESS", "127.0.0.4:1")]), "127.0.0.5:1");
+    assert!(attempted_address(&["--profile", "missing"], &[]).contains("Unknown profile 'missing'"));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
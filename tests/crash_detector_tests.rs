@@ -0,0 +1,86 @@
+use adbr::{CrashDetector, CrashKind, LogEntry};
+
+fn entry(pid: i32, millis: u32, priority: u8, tag: &str, message: &str) -> LogEntry {
+    let mut payload = vec![priority];
+    payload.extend_from_slice(tag.as_bytes());
+    payload.push(0);
+    payload.extend_from_slice(message.as_bytes());
+    payload.push(0);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&28u16.to_le_bytes());
+    for value in [pid as u32, pid as u32, 1_700_000_000 + millis / 1000, (millis % 1000) * 1_000_000, 4, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&payload);
+    LogEntry::from_bytes(&bytes).unwrap()
+}
+
+#[test]
+fn test_java_crash_grouped_from_separate_lines() {
+    let mut detector = CrashDetector::new();
+    let lines = [
+        "FATAL EXCEPTION: main",
+        "Process: com.example, PID: 4321",
+        "java.lang.IllegalStateException: boom",
+        "\tat com.example.Main.onCreate(Main.java:10)",
+        "Caused by: java.lang.NullPointerException",
+    ];
+    for (i, line) in lines.iter().enumerate() {
+        assert!(detector.feed(&entry(4321, i as u32, 6, "AndroidRuntime", line)).is_empty());
+    }
+    // Unrelated output from another process does not end the report.
+    assert!(detector.feed(&entry(1, 10, 4, "Other", "noise")).is_empty());
+
+    let incidents = detector.feed(&entry(1, 5000, 4, "Other", "much later"));
+    assert_eq!(incidents.len(), 1);
+    let crash = &incidents[0];
+    assert_eq!(crash.kind, CrashKind::JavaCrash);
+    assert_eq!(crash.process.as_deref(), Some("com.example"));
+    assert_eq!(crash.pid, Some(4321));
+    assert_eq!(crash.summary, "java.lang.IllegalStateException: boom");
+    assert_eq!(crash.trace.len(), 5);
+}
+
+#[test]
+fn test_native_crash_with_tombstone() {
+    let mut detector = CrashDetector::new();
+    let report = "*** *** *** *** *** *** *** *** *** *** *** *** *** *** *** ***\n\
+                  Build fingerprint: 'google/panther/panther:14/AP1A/1:user/release-keys'\n\
+                  pid: 5555, tid: 5560, name: RenderThread  >>> com.example <<<\n\
+                  signal 6 (SIGABRT), code -1 (SI_QUEUE), fault addr --------\n\
+                  Abort message: 'bad state'\n\
+                  #00 pc 0000000000089d8c  /apex/com.android.runtime/lib64/bionic/libc.so (abort+164)";
+    assert!(detector.feed(&entry(777, 0, 7, "DEBUG", report)).is_empty());
+    assert!(detector.feed(&entry(600, 50, 6, "tombstoned", "Tombstone written to: /data/tombstones/tombstone_07")).is_empty());
+
+    let incidents = detector.flush();
+    assert_eq!(incidents.len(), 1);
+    let crash = &incidents[0];
+    assert_eq!(crash.kind, CrashKind::NativeCrash);
+    assert_eq!(crash.pid, Some(5555));
+    assert_eq!(crash.process.as_deref(), Some("com.example"));
+    assert_eq!(crash.summary, "signal 6 (SIGABRT), code -1 (SI_QUEUE), fault addr -------- (bad state)");
+    assert_eq!(crash.remote_artifact.as_deref(), Some("/data/tombstones/tombstone_07"));
+}
+
+#[test]
+fn test_anr_and_back_to_back_reports() {
+    let mut detector = CrashDetector::new();
+    let anr = "ANR in com.example (com.example/.MainActivity)\nPID: 9999\nReason: Input dispatching timed out\nLoad: 1.0 / 1.0 / 1.0";
+    assert!(detector.feed(&entry(1000, 0, 6, "ActivityManager", anr)).is_empty());
+
+    let incidents = detector.feed(&entry(1000, 100, 6, "ActivityManager", "ANR in com.other"));
+    assert_eq!(incidents.len(), 1);
+    assert_eq!(incidents[0].kind, CrashKind::Anr);
+    assert_eq!(incidents[0].process.as_deref(), Some("com.example"));
+    assert_eq!(incidents[0].pid, Some(9999));
+    assert_eq!(incidents[0].summary, "Input dispatching timed out");
+
+    let remaining = detector.flush();
+    assert_eq!(remaining.len(), 1);
+    assert_eq!(remaining[0].process.as_deref(), Some("com.other"));
+    assert_eq!(remaining[0].summary, "ANR in com.other");
+    assert!(remaining[0].to_json().contains("\"kind\":\"anr\""));
+}
@@ -0,0 +1,33 @@
+use adbr::DeviceInfo;
+
+#[test]
+fn test_parse_long_device_list() {
+    let output = "emulator-5554          device product:sdk_gphone64_x86_64 model:sdk_gphone64_x86_64 device:emu64x transport_id:1\n\
+                  R58M123ABC             unauthorized usb:1-1 transport_id:2\n";
+
+    let devices = DeviceInfo::parse_list(output);
+
+    assert_eq!(devices.len(), 2);
+    assert_eq!(devices[0].serial, "emulator-5554");
+    assert!(devices[0].is_ready());
+    assert_eq!(devices[0].model.as_deref(), Some("sdk_gphone64_x86_64"));
+    assert_eq!(devices[0].transport_id, Some(1));
+    assert_eq!(devices[1].state, "unauthorized");
+    assert!(!devices[1].is_ready());
+    assert_eq!(devices[1].usb.as_deref(), Some("1-1"));
+}
+
+#[test]
+fn test_device_selectors() -> Result<(), Box<dyn std::error::Error>> {
+    let usb = DeviceInfo::from_line("R58M123ABC device usb:1-1 product:p1 model:Pixel_7 device:panther transport_id:3").unwrap();
+    let emulator = DeviceInfo::from_line("emulator-5554 device product:sdk model:sdk_gphone transport_id:1").unwrap();
+
+    assert!(usb.matches_selector("model", "Pixel_7")?);
+    assert!(usb.matches_selector("transport", "usb")?);
+    assert!(emulator.matches_selector("transport", "local")?);
+    assert!(!emulator.matches_selector("model", "Pixel_7")?);
+    assert!(emulator.matches_selector("transport_id", "1")?);
+    assert!(usb.matches_selector("color", "blue").is_err());
+
+    Ok(())
+}
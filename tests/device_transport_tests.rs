@@ -0,0 +1,28 @@
+use adbr::DeviceTransport;
+
+#[test]
+fn test_serial_transport_commands() {
+    let transport = DeviceTransport::serial("R58M123ABC".to_string());
+
+    assert_eq!(transport.get_device_transport(), "host:transport:R58M123ABC");
+    assert_eq!(transport.host_prefix(), "host-serial:R58M123ABC:");
+    assert_eq!(transport.tport_command().as_deref(), Some("host:tport:serial:R58M123ABC"));
+}
+
+#[test]
+fn test_transport_id_commands() {
+    let transport = DeviceTransport::transport_id(7);
+
+    assert_eq!(transport.get_device_transport(), "host:transport-id:7");
+    assert_eq!(transport.host_prefix(), "host-transport-id:7:");
+    assert_eq!(transport.tport_command(), None);
+    assert_eq!(transport.selected_transport_id(), Some(7));
+}
+
+#[test]
+fn test_default_transport_host_prefixes() {
+    assert_eq!(DeviceTransport::default().host_prefix(), "host:");
+    assert_eq!(DeviceTransport::default_usb().host_prefix(), "host-usb:");
+    assert_eq!(DeviceTransport::default_emulator().host_prefix(), "host-local:");
+    assert_eq!(DeviceTransport::default_usb().tport_command().as_deref(), Some("host:tport:usb"));
+}
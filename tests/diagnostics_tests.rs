@@ -0,0 +1,121 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use adbr::utils::escape_bytes;
+use adbr::{BugreportEvent, DeviceTransport, MockServer, VirtualDevice};
+use tracing_subscriber::filter::EnvFilter;
+use tracing_subscriber::fmt::MakeWriter;
+
+#[test]
+fn test_escape_bytes() {
+    assert_eq!(escape_bytes(b"OKAY0004abcd", 64), "OKAY0004abcd");
+    assert_eq!(escape_bytes(b"DATA\x05\x00\x00\x00a\\b\n", 64), "DATA\\x05\\x00\\x00\\x00a\\\\b\\n");
+    assert_eq!(escape_bytes(b"0123456789", 4), "0123... (10 bytes)");
+}
+
+/// A server with one device without `bugreportz`, which only has the plain-text `bugreport`.
+fn start_server() -> MockServer {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("emulator-5554")
+        .shell_output("bugreportz -v 2>&1", "/system/bin/sh: bugreportz: not found\n")
+        .shell_output("bugreport", "== dumpstate\n------ MEMORY INFO ------\n"));
+    server
+}
+
+#[tokio::test]
+async fn test_shell_output_goes_to_the_given_writer() -> Result<(), Box<dyn std::error::Error>> {
+    let server = start_server();
+    let mut client = server.client().await?;
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "echo hello").await?, "hello\n");
+
+    client.reconnect().await?;
+    let mut output = Vec::new();
+    client.adb_shell_to_writer(DeviceTransport::default(), "echo hello", &mut output).await?;
+    assert_eq!(output, b"hello\n");
+
+    client.reconnect().await?;
+    let error = client.adb_shell_to_writer(DeviceTransport::serial("R58M123ABC".to_string()), "echo hello", &mut output).await.expect_err("a missing device should be reported");
+    assert_eq!(error.to_string(), "device 'R58M123ABC' not found");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_plain_bugreport_reports_events() -> Result<(), Box<dyn std::error::Error>> {
+    let server = start_server();
+    let dir = std::env::temp_dir().join(format!("adbr-bugreport-events-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("report.zip");
+
+    let mut client = server.client().await?;
+    let mut events = Vec::new();
+    let saved = client.adb_bugreport(DeviceTransport::default(), path.to_str(), |event| events.push(event.clone())).await?;
+
+    assert_eq!(events, vec![BugreportEvent::PlainTextFallback, BugreportEvent::Generating { progress: false }]);
+    assert_eq!(saved, dir.join("report.txt"));
+    assert!(std::fs::read_to_string(&saved)?.contains("MEMORY INFO"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_bugreport_path_without_extension_gets_the_report_kind() -> Result<(), Box<dyn std::error::Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("emulator-5554")
+        .shell_output("bugreportz -v 2>&1", "1.2\n")
+        .shell_output("bugreportz -p", "BEGIN:/bugreports/report.zip\nOK:/bugreports/report.zip\n")
+        .file("/bugreports/report.zip", b"PK\x05\x06".to_vec()));
+    let dir = std::env::temp_dir().join(format!("adbr-bugreport-kind-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+
+    let mut client = server.client().await?;
+    let saved = client.adb_bugreport(DeviceTransport::default(), dir.join("out").to_str(), |_| {}).await?;
+    assert_eq!(saved, dir.join("out.zip"));
+    assert_eq!(std::fs::read(&saved)?, b"PK\x05\x06");
+
+    let saved = start_server().client().await?.adb_bugreport(DeviceTransport::default(), dir.join("out").to_str(), |_| {}).await?;
+    assert_eq!(saved, dir.join("out.txt"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[derive(Clone, Default)]
+struct CapturedLog(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CapturedLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for CapturedLog {
+    type Writer = CapturedLog;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn test_frames_are_traced() -> Result<(), Box<dyn std::error::Error>> {
+    let server = start_server();
+    let log = CapturedLog::default();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::new("adbr::frames=trace"))
+        .with_writer(log.clone())
+        .with_ansi(false)
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut client = server.client().await?;
+    client.adb_devices().await?;
+
+    let log = String::from_utf8(log.0.lock().unwrap().clone())?;
+    assert!(log.contains(">> 000chost:devices"), "{}", log);
+    assert!(log.contains("<< OKAY"), "{}", log);
+    assert!(log.contains("<< emulator-5554\\x09device\\n"), "{}", log);
+    Ok(())
+}
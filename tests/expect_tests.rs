@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use adbr::expect::expect_script::substitute_captures;
+use adbr::{Client, DeviceTransport, ExpectMatch, ExpectPattern, ExpectScript, ExpectStep, MockServer, VirtualDevice};
+
+#[test]
+fn test_parse_script() {
+    let script = ExpectScript::parse(r#"
+        # log in to a fake console
+        spawn login
+        timeout 2.5
+        expect "login: "
+        sendline root
+        expect -t 1 /uid=(\d+)\((\w+)\)/
+        send "echo $1\x21\n"
+        sleep 0.1
+        expect eof
+    "#).unwrap();
+
+    assert_eq!(script.command(), "login");
+    assert_eq!(script.steps.len(), 8);
+    assert!(matches!(&script.steps[1], ExpectStep::SetTimeout(timeout) if *timeout == Duration::from_millis(2500)));
+    assert!(matches!(&script.steps[2], ExpectStep::Expect { pattern: ExpectPattern::Literal(text), timeout: None } if text == "login: "));
+    assert!(matches!(&script.steps[3], ExpectStep::Send(text) if text == "root\n"));
+    match &script.steps[4] {
+        ExpectStep::Expect { pattern: ExpectPattern::Regex(regex), timeout } => {
+            assert_eq!(regex.as_str(), r"uid=(\d+)\((\w+)\)");
+            assert_eq!(*timeout, Some(Duration::from_secs(1)));
+        }
+        other => panic!("unexpected step {:?}", other),
+    }
+    assert!(matches!(&script.steps[5], ExpectStep::Send(text) if text == "echo $1!\n"));
+    assert!(matches!(&script.steps[7], ExpectStep::ExpectEof { timeout: None }));
+}
+
+#[test]
+fn test_parse_script_defaults_to_shell() {
+    let script = ExpectScript::parse("sendline id\n").unwrap();
+    assert_eq!(script.command(), "sh");
+}
+
+#[test]
+fn test_parse_spawn_on_a_pty() {
+    let script = ExpectScript::parse("spawn -t su\nexpect \"Password:\"\n").unwrap();
+    assert_eq!(script.command(), "su");
+    assert!(script.pty());
+    assert!(!ExpectScript::parse("spawn su\n").unwrap().pty());
+    assert_eq!(ExpectScript::parse("spawn -trace\n").unwrap().command(), "-trace");
+}
+
+#[test]
+fn test_parse_script_errors_name_the_line() {
+    let cases = [
+        ("expect \"unterminated", "line 1: missing closing quote"),
+        ("\nsendline ok\nfrobnicate", "line 3: unknown statement: frobnicate"),
+        ("expect /([a-/", "line 1:"),
+        ("expect /no-end", "line 1: regex must end with /"),
+        ("timeout soon", "line 1: invalid number of seconds: soon"),
+        ("send \"\\q\"", "line 1: invalid escape: \\q"),
+        ("sendline id\nspawn sh", "line 2: spawn must come before other statements"),
+    ];
+    for (text, expected) in cases {
+        let error = ExpectScript::parse(text).err().unwrap_or_else(|| panic!("{:?} should not parse", text));
+        assert!(error.to_string().starts_with(expected), "{:?}: {}", text, error);
+    }
+}
+
+#[test]
+fn test_pattern_find() {
+    let literal = ExpectPattern::literal("$ ");
+    assert_eq!(literal.find(b"a\n$ b").map(|(start, end, _)| (start, end)), Some((2, 4)));
+    assert!(literal.find(b"nothing").is_none());
+
+    let regex = ExpectPattern::regex(r"version (\d+)(-beta)?").unwrap();
+    let (start, end, captures) = regex.find(b"app version 42 ready").unwrap();
+    assert_eq!((start, end), (4, 14));
+    assert_eq!(captures, vec![Some("version 42".to_string()), Some("42".to_string()), None]);
+    assert_eq!(regex.to_string(), r"/version (\d+)(-beta)?/");
+}
+
+#[test]
+fn test_substitute_captures() {
+    let last_match = ExpectMatch {
+        before: String::new(),
+        matched: "uid=0(root)".to_string(),
+        captures: vec![Some("uid=0(root)".to_string()), Some("0".to_string()), Some("root".to_string())],
+    };
+    assert_eq!(substitute_captures("id $2 is $1, cost $$5, $9.", Some(&last_match)), "id root is 0, cost $5, .");
+    assert_eq!(substitute_captures("$1 $", None), " $");
+}
+
+/// A device with a `login` prompt: answers `root` with an id line, anything else with an error on
+/// stderr, and exits with status 5 when stdin closes.
+async fn connect() -> Result<Client, Box<dyn std::error::Error>> {
+    let device = VirtualDevice::new("emulator-5554").interactive("login", "Welcome\nlogin: ", 5, |input| match input {
+        b"root\n" => (b"uid=0(root) gid=0\n# ".to_vec(), Vec::new()),
+        _ => (Vec::new(), format!("unknown: {}", String::from_utf8_lossy(input)).into_bytes()),
+    });
+    let server = MockServer::new();
+    server.add_device(&device);
+    server.client().await
+}
+
+#[tokio::test]
+async fn test_expect_session_matches_and_captures() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect().await?;
+    let mut session = client.open_expect(DeviceTransport::default(), "login", false).await?;
+
+    let prompt = session.expect(&ExpectPattern::literal("login: "), None).await?;
+    assert_eq!(prompt.before, "Welcome\n");
+    assert_eq!(prompt.matched, "login: ");
+
+    session.send_line("root").await?;
+    let id = session.expect(&ExpectPattern::regex(r"uid=(\d+)\((\w+)\)")?, None).await?;
+    assert_eq!(id.group(1), Some("0"));
+    assert_eq!(id.group(2), Some("root"));
+
+    session.send_line("guest").await?;
+    let error = session.expect(&ExpectPattern::literal("unknown: guest"), None).await?;
+    assert_eq!(error.before, " gid=0\n# ");
+
+    assert_eq!(session.wait().await?, Some(5));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_expect_session_times_out_with_pending_output() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect().await?;
+    let mut session = client.open_expect(DeviceTransport::default(), "login", false).await?;
+
+    let error = session.expect(&ExpectPattern::literal("password:"), Some(Duration::from_millis(200))).await.expect_err("expect should time out");
+    assert!(error.to_string().contains("Timed out"), "{}", error);
+    assert!(error.to_string().contains("login: "), "{}", error);
+
+    // The unmatched output is still there for the next expect.
+    session.expect(&ExpectPattern::literal("login: "), None).await?;
+    session.kill();
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_run_expect_script_reports_output_and_status() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect().await?;
+    let script = ExpectScript::parse("spawn login\nexpect \"login: \"\nsendline root\nexpect /gid=(\\d+)/\nsendline $1\nexpect \"unknown: 0\"\n")?;
+
+    let transcript = Arc::new(Mutex::new(Vec::new()));
+    let sink = transcript.clone();
+    let status = client.run_expect_script(DeviceTransport::default(), &script, move |output| sink.lock().unwrap().extend_from_slice(output)).await?;
+    assert_eq!(status, Some(5));
+    let transcript = String::from_utf8(transcript.lock().unwrap().clone())?;
+    assert_eq!(transcript, "Welcome\nlogin: uid=0(root) gid=0\n# unknown: 0\n");
+
+    // The login prompt never ends its output while stdin is open.
+    let failing = ExpectScript::parse("spawn login\ntimeout 0.2\nexpect eof\n")?;
+    let error = client.run_expect_script(DeviceTransport::default(), &failing, |_| {}).await.expect_err("script should fail");
+    assert!(error.to_string().contains("end of output"), "{}", error);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_spawn_on_a_pty_gets_the_prompt() -> Result<(), Box<dyn std::error::Error>> {
+    let device = VirtualDevice::new("emulator-5554").tty_interactive("sqlite3", "sqlite> ", 0, |input| match input {
+        b"select 1;\n" => (b"1\nsqlite> ".to_vec(), Vec::new()),
+        _ => (Vec::new(), Vec::new()),
+    });
+    let server = MockServer::new();
+    server.add_device(&device);
+    let client = server.client().await?;
+
+    // Without a terminal the program stays quiet.
+    let mut session = client.open_expect(DeviceTransport::default(), "sqlite3", false).await?;
+    session.expect(&ExpectPattern::literal("sqlite> "), Some(Duration::from_millis(200))).await.expect_err("no prompt without a PTY");
+    session.kill();
+
+    let script = ExpectScript::parse("spawn -t sqlite3\nexpect \"sqlite> \"\nsendline select 1;\nexpect /(\\d+)\\n/\n")?;
+    let transcript = Arc::new(Mutex::new(Vec::new()));
+    let sink = transcript.clone();
+    assert_eq!(client.run_expect_script(DeviceTransport::default(), &script, move |output| sink.lock().unwrap().extend_from_slice(output)).await?, Some(0));
+    assert_eq!(String::from_utf8(transcript.lock().unwrap().clone())?, "sqlite> 1\nsqlite> ");
+    assert!(server.requests().iter().any(|request| request.starts_with("shell,v2,") && request.ends_with("pty:sqlite3")));
+    Ok(())
+}
@@ -0,0 +1,203 @@
+use adbr::{shell_quote, InstrumentationParser, InstrumentationReport, MockServer, TestEvent, TestStatus, VirtualDevice};
+
+const RUNNER: &str = "com.example.test/androidx.test.runner.AndroidJUnitRunner";
+
+const PASSING_AND_FAILING_RUN: &str = "\
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: id=AndroidJUnitRunner
+INSTRUMENTATION_STATUS: numtests=2
+INSTRUMENTATION_STATUS: stream=
+com.example.LoginTest:
+INSTRUMENTATION_STATUS: test=validLogin
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: numtests=2
+INSTRUMENTATION_STATUS: test=validLogin
+INSTRUMENTATION_STATUS_CODE: 0
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=2
+INSTRUMENTATION_STATUS: numtests=2
+INSTRUMENTATION_STATUS: test=invalidLogin
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=2
+INSTRUMENTATION_STATUS: numtests=2
+INSTRUMENTATION_STATUS: stack=java.lang.AssertionError: expected <error>
+\tat com.example.LoginTest.invalidLogin(LoginTest.java:42)
+INSTRUMENTATION_STATUS: test=invalidLogin
+INSTRUMENTATION_STATUS_CODE: -2
+INSTRUMENTATION_RESULT: stream=
+
+Time: 1.2
+
+FAILURES!!!
+Tests run: 2,  Failures: 1
+
+INSTRUMENTATION_CODE: -1
+";
+
+const RETRIED_RUN: &str = "\
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: numtests=1
+INSTRUMENTATION_STATUS: test=invalidLogin
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_STATUS: class=com.example.LoginTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: numtests=1
+INSTRUMENTATION_STATUS: test=invalidLogin
+INSTRUMENTATION_STATUS_CODE: 0
+INSTRUMENTATION_RESULT: stream=
+
+OK (1 test)
+
+INSTRUMENTATION_CODE: -1
+";
+
+/// The shell command `adb_instrument` sends for a single `-e class` run.
+fn instrument_command(class: &str) -> String {
+    format!("am instrument -w -r -e class {} {}", shell_quote(class), shell_quote(RUNNER))
+}
+
+fn parse(output: &str) -> Vec<TestEvent> {
+    let mut parser = InstrumentationParser::new();
+    let mut events: Vec<TestEvent> = output.lines().flat_map(|line| parser.feed_line(line)).collect();
+    events.extend(parser.finish());
+    events
+}
+
+fn build_report(events: &[TestEvent]) -> InstrumentationReport {
+    let mut report = InstrumentationReport::new(RUNNER);
+    for event in events {
+        report.record(event);
+    }
+    report
+}
+
+#[test]
+fn test_parser_reports_started_passed_and_failed_tests() {
+    let events = parse(PASSING_AND_FAILING_RUN);
+    let report = build_report(&events);
+
+    let started = events.iter().filter(|e| matches!(e, TestEvent::Started { .. })).count();
+    assert_eq!(started, 2);
+    assert_eq!(report.tests.len(), 2);
+    assert_eq!(report.tests[0].status, TestStatus::Passed);
+    assert_eq!(report.tests[1].status, TestStatus::Failed);
+    assert_eq!(report.tests[1].message(), Some("java.lang.AssertionError: expected <error>"));
+    assert!(report.tests[1].stack_trace.as_deref().unwrap().contains("LoginTest.java:42"));
+    assert_eq!(report.result_code, Some(-1));
+    assert!(!report.crashed);
+    assert!(!report.is_success());
+}
+
+#[test]
+fn test_parser_reports_process_crash() {
+    let output = "\
+INSTRUMENTATION_STATUS: class=com.example.CrashTest
+INSTRUMENTATION_STATUS: current=1
+INSTRUMENTATION_STATUS: numtests=1
+INSTRUMENTATION_STATUS: test=crashes
+INSTRUMENTATION_STATUS_CODE: 1
+INSTRUMENTATION_RESULT: shortMsg=Process crashed.
+INSTRUMENTATION_CODE: 0
+";
+    let report = build_report(&parse(output));
+
+    assert!(report.crashed);
+    assert_eq!(report.crash_message.as_deref(), Some("Process crashed."));
+    assert_eq!(report.tests.len(), 1);
+    assert_eq!(report.tests[0].status, TestStatus::Error);
+}
+
+#[test]
+fn test_parser_reports_truncated_stream_as_crash() {
+    let output = "\
+INSTRUMENTATION_STATUS: class=com.example.HangTest
+INSTRUMENTATION_STATUS: test=hangs
+INSTRUMENTATION_STATUS_CODE: 1
+";
+    let report = build_report(&parse(output));
+
+    assert!(report.crashed);
+    assert_eq!(report.tests[0].status, TestStatus::Error);
+}
+
+#[test]
+fn test_junit_xml_contains_failure_and_escapes_message() {
+    let report = build_report(&parse(PASSING_AND_FAILING_RUN));
+    let xml = report.to_junit_xml();
+
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"));
+    assert!(xml.contains("<testsuite name=\"com.example.LoginTest\" tests=\"2\" failures=\"1\" errors=\"0\" skipped=\"0\""));
+    assert!(xml.contains("<testcase classname=\"com.example.LoginTest\" name=\"validLogin\""));
+    assert!(xml.contains("<failure message=\"java.lang.AssertionError: expected &lt;error&gt;\">"));
+}
+
+#[test]
+fn test_json_report_lists_tests() -> Result<(), Box<dyn std::error::Error>> {
+    let report = build_report(&parse(PASSING_AND_FAILING_RUN));
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+
+    assert_eq!(json["tests"][1]["status"], "failed");
+    assert_eq!(json["crashed"], false);
+
+    Ok(())
+}
+
+#[test]
+fn test_report_replace_test_keeps_single_entry_per_test() {
+    let mut report = build_report(&parse(PASSING_AND_FAILING_RUN));
+    let mut retried = report.tests[1].clone();
+    retried.status = TestStatus::Passed;
+    retried.stack_trace = None;
+    retried.retries = 1;
+
+    report.replace_test(retried);
+
+    assert_eq!(report.tests.len(), 2);
+    assert!(report.is_success());
+    assert_eq!(report.tests[1].retries, 1);
+}
+
+#[tokio::test]
+async fn test_sharded_retry_replaces_the_class_and_clears_the_crash() -> Result<(), Box<dyn std::error::Error>> {
+    // The first device fails a test and then dies before reporting a result code.
+    let crashing_run = PASSING_AND_FAILING_RUN.split("INSTRUMENTATION_RESULT").next().unwrap();
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("a").shell_output(&instrument_command("com.example.LoginTest"), crashing_run));
+    let retry_device = VirtualDevice::new("b").shell_output(&instrument_command("com.example.LoginTest#invalidLogin"), RETRIED_RUN);
+    server.add_device(&retry_device);
+    let client = server.client().await?;
+
+    let serials = ["a".to_string(), "b".to_string()];
+    let args = [("class".to_string(), "com.example.LoginTest".to_string())];
+    let report = client.adb_instrument_sharded(&serials, RUNNER, &args, 1, 1, |_, _| {}).await?;
+
+    assert!(retry_device.shell_history().contains(&instrument_command("com.example.LoginTest#invalidLogin")));
+    assert_eq!(report.tests.len(), 2);
+    assert_eq!(report.tests[1].retries, 1);
+    assert!(!report.crashed, "{:?}", report.crash_message);
+    assert!(report.is_success());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_sharded_retry_that_crashes_is_reported() -> Result<(), Box<dyn std::error::Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("a").shell_output(&instrument_command("com.example.LoginTest"), PASSING_AND_FAILING_RUN));
+    // The retry device does not know the command, so its run ends without results.
+    server.add_device(&VirtualDevice::new("b"));
+    let client = server.client().await?;
+
+    let serials = ["a".to_string(), "b".to_string()];
+    let args = [("class".to_string(), "com.example.LoginTest".to_string())];
+    let report = client.adb_instrument_sharded(&serials, RUNNER, &args, 1, 1, |_, _| {}).await?;
+
+    assert!(report.crashed);
+    assert!(report.crash_message.as_deref().unwrap_or_default().contains("Retry 1 on b"), "{:?}", report.crash_message);
+    assert_eq!(report.tests[1].status, TestStatus::Failed);
+    Ok(())
+}
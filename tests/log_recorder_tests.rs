@@ -0,0 +1,76 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use adbr::utils::{parse_byte_size, parse_duration};
+use adbr::{LogEntry, LogFormat, LogRecorder};
+
+fn entry(sec: u32, message: &str) -> LogEntry {
+    let mut payload = vec![4u8];
+    payload.extend_from_slice(b"Test\0");
+    payload.extend_from_slice(message.as_bytes());
+    payload.push(0);
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&28u16.to_le_bytes());
+    for value in [100u32, 100, sec, 0, 0, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&payload);
+    LogEntry::from_bytes(&bytes).unwrap()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adbr-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn test_recorder_rotates_and_indexes_sessions() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = temp_dir("record-rotate");
+    let format = LogFormat::from_arg("template={msg}")?;
+    let mut recorder = LogRecorder::open(&dir, 10, Duration::from_secs(3600), format.clone())?;
+
+    assert!(!recorder.start_session("boot-a")?);
+    assert!(recorder.record(&entry(1, "first line"))?.is_some());
+    assert!(recorder.record(&entry(2, "second"))?.is_some());
+    assert!(recorder.record(&entry(3, "x"))?.is_none());
+    recorder.finish()?;
+
+    assert_eq!(fs::read_to_string(dir.join("logcat-001-0001.log"))?, "first line\n");
+    assert_eq!(fs::read_to_string(dir.join("logcat-001-0002.log"))?, "second\nx\n");
+
+    // A reconnect to the same boot resumes from the last timestamp and drops replayed entries.
+    assert!(recorder.start_session("boot-a")?);
+    assert_eq!(recorder.resume_time().map(|time| time.timestamp()), Some(3));
+    assert!(recorder.record(&entry(2, "second"))?.is_none());
+    assert!(recorder.record(&entry(3, "x"))?.is_none());
+    assert!(recorder.record(&entry(4, "y"))?.is_none());
+    assert_eq!(recorder.sessions()[0].entries, 4);
+    assert_eq!(recorder.sessions()[0].reconnects, 1);
+
+    assert!(!recorder.start_session("boot-b")?);
+    assert_eq!(recorder.record(&entry(1, "after reboot"))?, Some(dir.join("logcat-002-0001.log")));
+    recorder.finish()?;
+
+    let reopened = LogRecorder::open(&dir, 10, Duration::from_secs(3600), format)?;
+    assert_eq!(reopened.sessions().len(), 2);
+    assert_eq!(reopened.sessions()[0].files, vec!["logcat-001-0001.log", "logcat-001-0002.log"]);
+    assert_eq!(reopened.sessions()[1].boot_id, "boot-b");
+
+    assert_eq!(fs::read_to_string(dir.join("logcat-001-0002.log"))?, "second\nx\ny\n");
+    fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn test_parse_rotation_limits() {
+    assert_eq!(parse_byte_size("16M").unwrap(), 16 * 1024 * 1024);
+    assert_eq!(parse_byte_size("512k").unwrap(), 512 * 1024);
+    assert!(parse_byte_size("0").is_err());
+    assert_eq!(parse_duration("90").unwrap(), Duration::from_secs(90));
+    assert_eq!(parse_duration("2h").unwrap(), Duration::from_secs(7200));
+    assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+    assert!(parse_duration("soon").is_err());
+}
@@ -0,0 +1,192 @@
+use adbr::debugging::logcat::{log_entry_stream, merge_log_streams, parse_package_pids};
+use adbr::{LogBuffer, LogEntry, LogFilter, LogFormat, LogPriority};
+use futures::StreamExt;
+
+fn text_payload(priority: u8, tag: &str, message: &str) -> Vec<u8> {
+    let mut payload = vec![priority];
+    payload.extend_from_slice(tag.as_bytes());
+    payload.push(0);
+    payload.extend_from_slice(message.as_bytes());
+    payload.push(0);
+    payload
+}
+
+fn entry_v4(pid: i32, tid: u32, sec: u32, nsec: u32, lid: u32, uid: u32, payload: &[u8]) -> Vec<u8> {
+    let mut entry = Vec::new();
+    entry.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    entry.extend_from_slice(&28u16.to_le_bytes());
+    for value in [pid as u32, tid, sec, nsec, lid, uid] {
+        entry.extend_from_slice(&value.to_le_bytes());
+    }
+    entry.extend_from_slice(payload);
+    entry
+}
+
+#[test]
+fn test_decode_v4_text_entry() {
+    let bytes = entry_v4(1234, 1240, 1_700_000_000, 500_000_000, 3, 1000, &text_payload(6, "ActivityManager", "Process died\n"));
+    assert_eq!(LogEntry::entry_size(&bytes).unwrap(), bytes.len());
+
+    let entry = LogEntry::from_bytes(&bytes).unwrap();
+    assert_eq!(entry.pid, 1234);
+    assert_eq!(entry.tid, 1240);
+    assert_eq!(entry.uid, Some(1000));
+    assert_eq!(entry.buffer, LogBuffer::System);
+    assert_eq!(entry.priority, LogPriority::Error);
+    assert_eq!(entry.tag, "ActivityManager");
+    assert_eq!(entry.message, "Process died");
+    assert_eq!(entry.timestamp.timestamp(), 1_700_000_000);
+    assert_eq!(entry.timestamp.timestamp_subsec_millis(), 500);
+}
+
+#[test]
+fn test_decode_v1_entry() {
+    let payload = text_payload(4, "init", "starting service");
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(&0u16.to_le_bytes());
+    for value in [1u32, 1, 100, 0] {
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&payload);
+
+    let entry = LogEntry::from_bytes(&bytes).unwrap();
+    assert_eq!(entry.uid, None);
+    assert_eq!(entry.buffer, LogBuffer::Main);
+    assert_eq!(entry.priority, LogPriority::Info);
+    assert_eq!(entry.message, "starting service");
+}
+
+#[test]
+fn test_decode_events_entry() {
+    let mut payload = 30014u32.to_le_bytes().to_vec();
+    payload.extend_from_slice(&[3, 2]);
+    payload.push(0);
+    payload.extend_from_slice(&42i32.to_le_bytes());
+    payload.push(2);
+    payload.extend_from_slice(&3u32.to_le_bytes());
+    payload.extend_from_slice(b"foo");
+
+    let entry = LogEntry::from_bytes(&entry_v4(1, 1, 0, 0, 2, 0, &payload)).unwrap();
+    assert_eq!(entry.buffer, LogBuffer::Events);
+    assert_eq!(entry.tag, "30014");
+    assert_eq!(entry.message, "[42,foo]");
+}
+
+#[test]
+fn test_priority_names() {
+    assert_eq!(LogPriority::from_name("w").unwrap(), LogPriority::Warn);
+    assert_eq!(LogPriority::from_name("ERROR").unwrap(), LogPriority::Error);
+    assert!(LogPriority::from_name("loud").is_err());
+    assert!(LogPriority::Warn > LogPriority::Info);
+}
+
+#[tokio::test]
+async fn test_log_entry_stream() {
+    let mut bytes = entry_v4(10, 10, 1, 0, 0, 0, &text_payload(3, "A", "first"));
+    bytes.extend(entry_v4(11, 11, 2, 0, 0, 0, &text_payload(5, "B", "second")));
+    bytes.extend_from_slice(&[0x10, 0x00]);
+
+    let results: Vec<_> = log_entry_stream(bytes.as_slice()).collect().await;
+    assert_eq!(results.len(), 3);
+    assert_eq!(results[0].as_ref().unwrap().message, "first");
+    assert_eq!(results[1].as_ref().unwrap().tag, "B");
+    assert!(results[2].is_err());
+}
+
+#[test]
+fn test_log_formats() {
+    let entry = LogEntry::from_bytes(&entry_v4(42, 43, 1_700_000_000, 0, 0, 10001, &text_payload(5, "Net", "timeout \"x\""))).unwrap();
+
+    let template = LogFormat::from_arg("template={tag}/{priority} [{pid}:{tid}] {msg}").unwrap();
+    assert_eq!(template.render(&entry), "Net/W [42:43] timeout \"x\"");
+
+    let json: serde_json::Value = serde_json::from_str(&LogFormat::Json.render(&entry)).unwrap();
+    assert_eq!(json["tag"], "Net");
+    assert_eq!(json["priority"], "W");
+    assert_eq!(json["uid"], 10001);
+    assert_eq!(json["message"], "timeout \"x\"");
+
+    let colored = LogFormat::Color.render(&entry);
+    assert!(colored.starts_with("\x1b[33m") && colored.ends_with("\x1b[0m"));
+    assert_eq!(LogFormat::Color.without_color().render(&entry), LogFormat::Threadtime.render(&entry));
+}
+
+#[test]
+fn test_invalid_log_formats() {
+    assert!(LogFormat::from_arg("xml").is_err());
+    assert!(LogFormat::from_arg("template={time} {nope}").is_err());
+    assert!(LogFormat::from_arg("template={tag").is_err());
+}
+
+fn text_entry(pid: i32, sec: u32, priority: u8, tag: &str, message: &str) -> LogEntry {
+    LogEntry::from_bytes(&entry_v4(pid, pid as u32, sec, 0, 0, 0, &text_payload(priority, tag, message))).unwrap()
+}
+
+#[test]
+fn test_log_filter_levels_tags_and_grep() {
+    let filter = LogFilter::new()
+        .min_level(LogPriority::Info)
+        .exclude_tag("chatty")
+        .grep("time(out)?")
+        .unwrap();
+
+    assert!(filter.matches(&text_entry(1, 0, 5, "Net", "socket timeout")));
+    assert!(!filter.matches(&text_entry(1, 0, 3, "Net", "socket timeout")));
+    assert!(!filter.matches(&text_entry(1, 0, 5, "chatty", "socket timeout")));
+    assert!(!filter.matches(&text_entry(1, 0, 5, "Net", "connected")));
+    assert!(LogFilter::new().grep("(").is_err());
+}
+
+#[test]
+fn test_log_filter_since() {
+    let filter = LogFilter::new().since(LogFilter::parse_time("1700000000.5").unwrap());
+
+    assert!(!filter.matches(&text_entry(1, 1_700_000_000, 4, "A", "old")));
+    assert!(filter.matches(&text_entry(1, 1_700_000_001, 4, "A", "new")));
+    assert!(LogFilter::parse_time("10m").is_ok());
+    assert!(LogFilter::parse_time("2024-01-02 03:04:05").is_ok());
+    assert!(LogFilter::parse_time("01-02 03:04:05.678").is_ok());
+    assert!(LogFilter::parse_time("yesterday").is_err());
+}
+
+#[test]
+fn test_log_filter_follows_package_restarts() {
+    let mut filter = LogFilter::new().package("com.foo");
+    filter.set_package_pids([100]);
+
+    assert!(filter.accept(&text_entry(100, 0, 4, "Foo", "running")));
+    assert!(!filter.accept(&text_entry(200, 0, 4, "Bar", "other app")));
+
+    assert!(!filter.accept(&text_entry(1000, 0, 4, "ActivityManager", "Process com.foo (pid 100) has died: fg TOP")));
+    assert!(!filter.accept(&text_entry(1000, 0, 4, "ActivityManager", "Start proc 300:com.foo/u0a123 for activity {com.foo/.Main}")));
+    assert!(!filter.accept(&text_entry(1000, 0, 4, "ActivityManager", "Start proc 301:com.foobar/u0a124 for service")));
+    assert!(filter.accept(&text_entry(300, 0, 4, "Foo", "restarted")));
+    assert!(!filter.accept(&text_entry(100, 0, 4, "Foo", "stale pid")));
+    assert!(!filter.accept(&text_entry(301, 0, 4, "FooBar", "different package")));
+}
+
+#[test]
+fn test_parse_package_pids() {
+    let toybox = "  PID NAME\n  100 com.foo\n  101 com.foo:remote\n  102 com.foobar\n";
+    assert_eq!(parse_package_pids(toybox, "com.foo"), vec![100, 101]);
+
+    let toolbox = "USER     PID   PPID  VSIZE  RSS     WCHAN    PC         NAME\n\
+                   u0_a12   2345  190   1234   567   ffffffff 00000000 S com.foo\n";
+    assert_eq!(parse_package_pids(toolbox, "com.foo"), vec![2345]);
+}
+
+#[tokio::test]
+async fn test_merge_log_streams_by_timestamp() {
+    let device_a = futures::stream::iter(vec![Ok(text_entry(1, 10, 4, "A", "a1")), Ok(text_entry(1, 30, 4, "A", "a2"))]);
+    let device_b = futures::stream::iter(vec![Ok(text_entry(2, 20, 4, "B", "b1")), Err("connection reset".into())]);
+
+    let merged: Vec<_> = merge_log_streams(vec![device_a, device_b], std::time::Duration::from_millis(50)).collect().await;
+
+    let errors: Vec<_> = merged.iter().filter(|(_, entry)| entry.is_err()).map(|(source, _)| *source).collect();
+    assert_eq!(errors, vec![1]);
+    let order: Vec<_> = merged.iter()
+        .filter_map(|(source, entry)| entry.as_ref().ok().map(|entry| (*source, entry.message.clone())))
+        .collect();
+    assert_eq!(order, vec![(0, "a1".to_string()), (1, "b1".to_string()), (0, "a2".to_string())]);
+}
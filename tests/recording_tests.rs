@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use adbr::recording::redact::redact_serials;
+use adbr::{Client, ClientBuilder, DeviceTransport, MockServer, ReplayServer, SessionRecording, StreamDirection, VirtualDevice};
+
+const USB_SERIAL: &str = "R58M123ABC";
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adbr-recording-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Lists devices, runs a shell command and pushes then pulls a file, returning what the client saw.
+async fn run_session(client: &mut Client, device: DeviceTransport, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let local = dir.join("notes.txt");
+    std::fs::write(&local, "recorded bytes\n")?;
+    // Pushes send the file's mtime, which has to match the recording.
+    filetime::set_file_mtime(&local, filetime::FileTime::from_unix_time(1_700_000_000, 0))?;
+    let pulled = dir.join("pulled.txt");
+
+    let devices = client.adb_devices().await?;
+    client.reconnect().await?;
+    let shell = client.adb_shell(device.clone(), "getprop ro.product.model").await?;
+    client.reconnect().await?;
+    for (_, result) in client.adb_push(device.clone(), &[local.to_string_lossy().into_owned()], "/sdcard/notes.txt", false).await? {
+        result?;
+    }
+    client.reconnect().await?;
+    for (_, result) in client.adb_pull(device, &["/sdcard/notes.txt".to_string()], &pulled.to_string_lossy(), false).await? {
+        result?;
+    }
+    Ok(vec![devices, shell, std::fs::read_to_string(pulled)?])
+}
+
+async fn record_session(path: &Path, dir: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL));
+    let mut client = ClientBuilder::new().transport(server.transport()).record(path)?.build().await?;
+    let seen = run_session(&mut client, DeviceTransport::serial(USB_SERIAL.to_string()), dir).await?;
+    client.close().await;
+    Ok(seen)
+}
+
+#[tokio::test]
+async fn test_record_and_replay_session() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("replay");
+    let path = dir.join("session.jsonl");
+    let recorded = record_session(&path, &dir).await?;
+    assert_eq!(recorded[1], "Mock_Device\n");
+    assert_eq!(recorded[2], "recorded bytes\n");
+
+    let recording = SessionRecording::load(&path)?;
+    assert_eq!(recording.events[0].direction, StreamDirection::Connect);
+    assert!(recording.events.windows(2).all(|pair| pair[0].time_us <= pair[1].time_us));
+    let devices_connection = recording.events.iter()
+        .find(|event| event.direction == StreamDirection::Send && event.data.ends_with(b"host:devices"))
+        .map(|event| event.connection)
+        .expect("host:devices request");
+    assert!(String::from_utf8(recording.stream_data(devices_connection, StreamDirection::Receive))?.contains("R58M123ABC\tdevice"));
+
+    let replay = ReplayServer::load(&path)?;
+    let mut client = replay.client().await?;
+    let replayed = run_session(&mut client, DeviceTransport::serial(USB_SERIAL.to_string()), &dir).await?;
+    client.close().await;
+    assert_eq!(replayed, recorded);
+    replay.finish()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_replay_reports_mismatch() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("mismatch");
+    let path = dir.join("session.jsonl");
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL));
+    let mut client = ClientBuilder::new().transport(server.transport()).record(&path)?.build().await?;
+    assert_eq!(client.adb_shell(DeviceTransport::default(), "echo recorded").await?, "recorded\n");
+
+    let replay = ReplayServer::load(&path)?;
+    let mut client = replay.client().await?;
+    assert!(client.adb_shell(DeviceTransport::default(), "echo changed").await.is_err());
+    let mismatches = replay.mismatches();
+    assert_eq!(mismatches.len(), 1);
+    assert!(mismatches[0].contains("echo recorded"), "{}", mismatches[0]);
+    assert!(mismatches[0].contains("echo changed"), "{}", mismatches[0]);
+    assert!(replay.finish().is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_redact_serials() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("redact");
+    let path = dir.join("session.jsonl");
+    record_session(&path, &dir).await?;
+
+    let mut recording = SessionRecording::load(&path)?;
+    let replacements = redact_serials(&mut recording, &["Mock_Device".to_string()]);
+    assert_eq!(replacements.get(USB_SERIAL).map(String::as_str), Some("SERIAL0002"));
+    assert_eq!(replacements.get("Mock_Device").map(String::as_str), Some("SERIAL00001"));
+    let redacted_path = dir.join("redacted.jsonl");
+    recording.save(&redacted_path)?;
+    let text = std::fs::read_to_string(&redacted_path)?;
+    let hex = |value: &str| value.bytes().map(|byte| format!("{:02x}", byte)).collect::<String>();
+    assert!(!text.contains(&hex(USB_SERIAL)));
+    assert!(text.contains(&hex("SERIAL0002")));
+
+    let replay = ReplayServer::load(&redacted_path)?;
+    let mut client = replay.client().await?;
+    let replayed = run_session(&mut client, DeviceTransport::serial("SERIAL0002".to_string()), &dir).await?;
+    client.close().await;
+    assert_eq!(replayed[0], "List of devices attached\nSERIAL0002\tdevice\n");
+    assert_eq!(replayed[1], "SERIAL00001\n");
+    replay.finish()?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_record_replay_and_redact() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("cli");
+    let path = dir.join("session.jsonl");
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new(USB_SERIAL).file("/sdcard/hello.txt", "hi\n"));
+    let port = server.listen().await?.port().to_string();
+
+    let run = |port: &str, record: bool, args: &[&str]| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_adbr"));
+        command.args(["-P", port]).args(args)
+            .env("ADBR_CONFIG", "/nonexistent/adbr.toml")
+            .env_remove("ADB_ADDRESS")
+            .env_remove("ADB_SERVER_SOCKET")
+            .env_remove("ANDROID_SERIAL")
+            .env_remove("ADBR_PROFILE")
+            .env_remove("ADBR_RECORD");
+        if record {
+            command.env("ADBR_RECORD", &path);
+        }
+        command.output()
+    };
+
+    let recorded = run(&port, true, &["shell", "cat", "/sdcard/hello.txt"]).await?;
+    assert!(recorded.status.success());
+    assert_eq!(String::from_utf8(recorded.stdout.clone())?, "hi\n");
+
+    let replay = ReplayServer::load(&path)?;
+    let replay_port = replay.listen().await?.port().to_string();
+    let replayed = run(&replay_port, false, &["shell", "cat", "/sdcard/hello.txt"]).await?;
+    assert_eq!(replayed.stdout, recorded.stdout);
+    replay.finish()?;
+
+    let redacted_path = dir.join("redacted.jsonl");
+    let redacted = run(&port, false, &["redact-serials", "--serial", USB_SERIAL, path.to_str().unwrap(), redacted_path.to_str().unwrap()]).await?;
+    assert!(redacted.status.success());
+    assert_eq!(String::from_utf8(redacted.stdout)?, "R58M123ABC -> SERIAL0001\n");
+    assert_eq!(SessionRecording::load(&redacted_path)?.events.len(), SessionRecording::load(&path)?.events.len());
+    Ok(())
+}
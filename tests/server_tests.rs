@@ -0,0 +1,235 @@
+use std::error::Error;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use adbr::{AdbServer, Client, DeviceTransport, MockAdbd, VirtualDevice};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("adbr-server-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A server with one stand-in adbd connected to it, and a client of that server.
+async fn connected_server(device: &VirtualDevice) -> Result<(AdbServer, MockAdbd, SocketAddr, Client), Box<dyn Error>> {
+    let adbd = MockAdbd::new(device);
+    let address = adbd.listen().await?;
+    let server = AdbServer::new();
+    let mut client = server.client().await?;
+    assert_eq!(client.adb_connect(&address.to_string()).await?, format!("connected to {}", address));
+    client.reconnect().await?;
+    Ok((server, adbd, address, client))
+}
+
+async fn free_port() -> Result<u16, Box<dyn Error>> {
+    Ok(TcpListener::bind("127.0.0.1:0").await?.local_addr()?.port())
+}
+
+#[tokio::test]
+async fn test_server_version_and_empty_device_list() -> Result<(), Box<dyn Error>> {
+    let server = AdbServer::new();
+    let mut client = server.client().await?;
+    assert_eq!(client.adb_server_version().await?, 41);
+    client.reconnect().await?;
+    assert_eq!(client.adb_devices().await?, "List of devices attached\n");
+    client.reconnect().await?;
+    let err = client.adb_shell(DeviceTransport::default(), "true").await.unwrap_err();
+    assert!(err.to_string().contains("no devices/emulators found"), "{}", err);
+    client.reconnect().await?;
+    let err = client.adb_connect("127.0.0.1:1").await.unwrap_err();
+    assert!(err.to_string().starts_with("failed to connect to '127.0.0.1:1'"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_and_relay_device_services() -> Result<(), Box<dyn Error>> {
+    let dir = scratch_dir("relay");
+    let device = VirtualDevice::new("adbd").file("/sdcard/hello.txt", "hello\n").prop("ro.product.model", "Pixel 7");
+    let (_server, _adbd, address, mut client) = connected_server(&device).await?;
+    let serial = DeviceTransport::serial(address.to_string());
+
+    assert_eq!(client.adb_connect(&address.to_string()).await?, format!("already connected to {}", address));
+    client.reconnect().await?;
+    let listing = client.adb_devices_long().await?;
+    assert!(listing.contains(&format!("{:<22} device product:mock model:Pixel_7 device:mock transport_id:1\n", address)), "{}", listing);
+    client.reconnect().await?;
+    assert_eq!(client.adb_serialno(serial.clone()).await?, address.to_string());
+    client.reconnect().await?;
+    assert!(client.adb_features(serial.clone()).await?.contains(&"shell_v2".to_string()));
+
+    client.reconnect().await?;
+    assert_eq!(client.adb_shell(serial.clone(), "cat /sdcard/hello.txt").await?, "hello\n");
+
+    // Large enough to take many WRTE packets, each waiting for the device's OKAY.
+    let data: Vec<u8> = (0..3 * 1024 * 1024).map(|index| (index % 251) as u8).collect();
+    let local = dir.join("big.bin");
+    std::fs::write(&local, &data)?;
+    client.reconnect().await?;
+    for (_, result) in client.adb_push(serial.clone(), &[local.to_string_lossy().into_owned()], "/sdcard/big.bin", false).await? {
+        result?;
+    }
+    assert_eq!(device.read_file("/sdcard/big.bin").as_deref(), Some(data.as_slice()));
+
+    let pulled = dir.join("pulled.bin");
+    client.reconnect().await?;
+    for (_, result) in client.adb_pull(serial, &["/sdcard/big.bin".to_string()], &pulled.to_string_lossy(), false).await? {
+        result?;
+    }
+    assert_eq!(std::fs::read(&pulled)?, data);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_emulators_are_selected_with_e() -> Result<(), Box<dyn Error>> {
+    let adbd = MockAdbd::new(&VirtualDevice::new("emulator").shell_output("getprop ro.kernel.qemu", "1\n"));
+    let port = adbd.listen().await?.port();
+    let server = AdbServer::new();
+    assert_eq!(server.connect_emulator(port).await?, format!("emulator-{}", port - 1));
+
+    let mut client = server.client().await?;
+    assert_eq!(client.adb_shell(DeviceTransport::default_emulator(), "getprop ro.kernel.qemu").await?, "1\n");
+    client.reconnect().await?;
+    let err = client.adb_shell(DeviceTransport::default_usb(), "true").await.unwrap_err();
+    assert!(err.to_string().contains("no devices found"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_connect_emulator_rejects_port_zero() {
+    let err = AdbServer::new().connect_emulator(0).await.unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[tokio::test]
+async fn test_track_devices_follows_connects_and_losses() -> Result<(), Box<dyn Error>> {
+    let adbd = MockAdbd::new(&VirtualDevice::new("adbd"));
+    let address = adbd.listen().await?;
+    let server = AdbServer::new();
+    let mut tracker = server.client().await?;
+    tracker.adb_track_devices(false).await?;
+    assert_eq!(tracker.next_device_list().await?, "");
+
+    let mut client = server.client().await?;
+    client.adb_connect(&address.to_string()).await?;
+    assert_eq!(tracker.next_device_list().await?, format!("{}\tdevice\n", address));
+    client.reconnect().await?;
+    assert_eq!(client.adb_disconnect(&address.to_string()).await?, format!("disconnected {}", address));
+    assert_eq!(tracker.next_device_list().await?, "");
+    client.reconnect().await?;
+    let err = client.adb_disconnect(&address.to_string()).await.unwrap_err();
+    assert!(err.to_string().contains(&format!("no such device '{}'", address)), "{}", err);
+
+    // The device going away drops it from the list too.
+    client.reconnect().await?;
+    client.adb_connect(&address.to_string()).await?;
+    assert_eq!(tracker.next_device_list().await?, format!("{}\tdevice\n", address));
+    adbd.disconnect();
+    assert_eq!(tokio::time::timeout(WAIT, tracker.next_device_list()).await??, "");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_forward_relays_to_a_device_port() -> Result<(), Box<dyn Error>> {
+    let (_server, _adbd, address, mut client) = connected_server(&VirtualDevice::new("adbd")).await?;
+    let serial = DeviceTransport::serial(address.to_string());
+
+    // Stands in for a port open on the device.
+    let echo = TcpListener::bind("127.0.0.1:0").await?;
+    let echo_port = echo.local_addr()?.port();
+    tokio::spawn(async move {
+        while let Ok((mut socket, _)) = echo.accept().await {
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = socket.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+
+    let local = format!("tcp:{}", free_port().await?);
+    let remote = format!("tcp:{}", echo_port);
+    client.send_forward_command_set(serial.clone(), &local, &remote, false).await?;
+    let mut socket = TcpStream::connect(("127.0.0.1", local[4..].parse::<u16>()?)).await?;
+    socket.write_all(b"ping").await?;
+    let mut reply = [0u8; 4];
+    tokio::time::timeout(WAIT, socket.read_exact(&mut reply)).await??;
+    assert_eq!(&reply, b"ping");
+
+    client.reconnect().await?;
+    assert_eq!(client.send_forward_command_list(serial.clone()).await?, format!("{} {} {}\n", address, local, remote));
+    client.reconnect().await?;
+    let err = client.send_forward_command_set(serial.clone(), &local, "tcp:1", true).await.unwrap_err();
+    assert!(err.to_string().contains("cannot rebind existing socket"), "{}", err);
+    client.reconnect().await?;
+    client.send_forward_command_remove(serial.clone(), &local).await?;
+    client.reconnect().await?;
+    assert_eq!(client.send_forward_command_list(serial).await?, "");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_kill_server_stops_listening() -> Result<(), Box<dyn Error>> {
+    let (server, _adbd, _, mut client) = connected_server(&VirtualDevice::new("adbd")).await?;
+    let port = server.listen().await?.port();
+    client.adb_kill_server().await?;
+    tokio::time::timeout(WAIT, server.wait()).await?;
+
+    let mut refused = false;
+    for _ in 0..50 {
+        if TcpStream::connect(("127.0.0.1", port)).await.is_err() {
+            refused = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(refused, "server still accepts connections after kill");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cli_server_connect_shell_and_kill() -> Result<(), Box<dyn Error>> {
+    let adbd = MockAdbd::new(&VirtualDevice::new("adbd").file("/sdcard/hello.txt", "hi\n"));
+    let device_address = adbd.listen().await?.to_string();
+    let port = free_port().await?.to_string();
+
+    let command = |args: &[&str]| {
+        let mut command = tokio::process::Command::new(env!("CARGO_BIN_EXE_adbr"));
+        command.args(["-P", &port]).args(args)
+            .env("ADBR_CONFIG", "/nonexistent/adbr.toml")
+            .env_remove("ADB_ADDRESS")
+            .env_remove("ADB_SERVER_SOCKET")
+            .env_remove("ANDROID_SERIAL")
+            .env_remove("ADBR_PROFILE")
+            .env_remove("ADBR_RECORD")
+            .kill_on_drop(true);
+        command
+    };
+
+    let mut server = command(&["server"]).stdout(std::process::Stdio::null()).spawn()?;
+    let mut listening = false;
+    for _ in 0..250 {
+        if TcpStream::connect(("127.0.0.1", port.parse::<u16>()?)).await.is_ok() {
+            listening = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    assert!(listening, "adbr server did not start");
+
+    let connected = command(&["connect", &device_address]).output().await?;
+    assert_eq!(String::from_utf8(connected.stdout)?, format!("connected to {}\n", device_address));
+    let shell = command(&["-s", &device_address, "shell", "cat", "/sdcard/hello.txt"]).output().await?;
+    assert!(shell.status.success());
+    assert_eq!(String::from_utf8(shell.stdout)?, "hi\n");
+    let disconnected = command(&["disconnect"]).output().await?;
+    assert_eq!(String::from_utf8(disconnected.stdout)?, "disconnected everything\n");
+
+    assert!(command(&["kill-server"]).output().await?.status.success());
+    let status = tokio::time::timeout(WAIT, server.wait()).await??;
+    assert!(status.success());
+    Ok(())
+}
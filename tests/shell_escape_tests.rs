@@ -0,0 +1,51 @@
+use adbr::EscapeAction;
+use adbr::shell::shell_escape::{escape_help, parse_escape_char, EscapeParser};
+
+#[test]
+fn test_parse_escape_char() {
+    assert_eq!(parse_escape_char("~"), Ok(Some(b'~')));
+    assert_eq!(parse_escape_char("^]"), Ok(Some(0x1d)));
+    assert_eq!(parse_escape_char("^a"), Ok(Some(0x01)));
+    assert_eq!(parse_escape_char("none"), Ok(None));
+    assert!(parse_escape_char("ab").is_err());
+    assert!(parse_escape_char("").is_err());
+}
+
+#[test]
+fn test_escapes_only_at_line_start() {
+    let mut parser = EscapeParser::new(Some(b'~'));
+    assert_eq!(parser.feed(b"~."), vec![EscapeAction::Disconnect]);
+
+    let mut parser = EscapeParser::new(Some(b'~'));
+    assert_eq!(parser.feed(b"ls ~."), vec![EscapeAction::Send(b"ls ~.".to_vec())]);
+    assert_eq!(parser.feed(b"\r~?"), vec![EscapeAction::Send(b"\r".to_vec()), EscapeAction::Help]);
+    assert_eq!(parser.feed(b"~\x1a"), vec![EscapeAction::Suspend]);
+    assert_eq!(parser.feed(b"~>"), vec![EscapeAction::LocalCommand]);
+}
+
+#[test]
+fn test_literal_and_unknown_escapes_are_forwarded() {
+    let mut parser = EscapeParser::new(Some(b'~'));
+    assert_eq!(parser.feed(b"~~/x"), vec![EscapeAction::Send(b"~/x".to_vec())]);
+    assert_eq!(parser.feed(b"\n~a"), vec![EscapeAction::Send(b"\n~a".to_vec())]);
+}
+
+#[test]
+fn test_escape_split_across_reads() {
+    let mut parser = EscapeParser::new(Some(b'~'));
+    assert_eq!(parser.feed(b"exit\r~"), vec![EscapeAction::Send(b"exit\r".to_vec())]);
+    assert_eq!(parser.feed(b"."), vec![EscapeAction::Disconnect]);
+}
+
+#[test]
+fn test_disabled_escape_passes_everything() {
+    let mut parser = EscapeParser::new(None);
+    assert_eq!(parser.feed(b"~."), vec![EscapeAction::Send(b"~.".to_vec())]);
+}
+
+#[test]
+fn test_escape_help_uses_configured_char() {
+    let help = escape_help(0x1d);
+    assert!(help.contains(" ^]. "));
+    assert!(help.ends_with("\r\n"));
+}
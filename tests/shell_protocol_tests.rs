@@ -0,0 +1,46 @@
+use adbr::ShellPacketId;
+use adbr::shell::shell_protocol::{encode_shell_packet, window_size_payload, ShellPacketDecoder};
+
+#[test]
+fn test_encode_shell_packet() {
+    assert_eq!(encode_shell_packet(ShellPacketId::Stdin, b"ls\n"), vec![0, 3, 0, 0, 0, b'l', b's', b'\n']);
+    assert_eq!(encode_shell_packet(ShellPacketId::CloseStdin, &[]), vec![4, 0, 0, 0, 0]);
+}
+
+#[test]
+fn test_window_size_payload() {
+    assert_eq!(window_size_payload(24, 80, 0, 0), b"24x80,0x0\0".to_vec());
+}
+
+#[test]
+fn test_decoder_reassembles_split_packets() {
+    let mut stream = encode_shell_packet(ShellPacketId::Stdout, b"hello");
+    stream.extend(encode_shell_packet(ShellPacketId::Stderr, b"oops"));
+    stream.extend([9, 1, 0, 0, 0, b'?']);
+    stream.extend(encode_shell_packet(ShellPacketId::Exit, &[3]));
+
+    let mut decoder = ShellPacketDecoder::new();
+    let mut packets = Vec::new();
+    for chunk in stream.chunks(3) {
+        decoder.feed(chunk);
+        while let Some(packet) = decoder.next_packet() {
+            packets.push(packet);
+        }
+    }
+
+    assert_eq!(packets, vec![
+        (ShellPacketId::Stdout, b"hello".to_vec()),
+        (ShellPacketId::Stderr, b"oops".to_vec()),
+        (ShellPacketId::Exit, vec![3]),
+    ]);
+}
+
+#[test]
+fn test_decoder_waits_for_complete_packet() {
+    let packet = encode_shell_packet(ShellPacketId::Stdout, b"abc");
+    let mut decoder = ShellPacketDecoder::new();
+    decoder.feed(&packet[..6]);
+    assert!(decoder.next_packet().is_none());
+    decoder.feed(&packet[6..]);
+    assert_eq!(decoder.next_packet(), Some((ShellPacketId::Stdout, b"abc".to_vec())));
+}
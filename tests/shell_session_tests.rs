@@ -0,0 +1,60 @@
+use adbr::{Client, DeviceTransport, MockServer, VirtualDevice};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+/// A device whose `cat` upper-cases stdin with a warning on stderr and exits with status 3.
+async fn connect(shell_v2: bool) -> Result<Client, Box<dyn std::error::Error>> {
+    let features: &[&str] = if shell_v2 { &["shell_v2", "cmd"] } else { &["cmd"] };
+    let device = VirtualDevice::new("emulator-5554")
+        .features(features)
+        .interactive("cat", "", 3, |input| (input.to_ascii_uppercase(), b"warn\n".to_vec()));
+    let server = MockServer::new();
+    server.add_device(&device);
+    server.client().await
+}
+
+#[tokio::test]
+async fn test_open_shell_v2_pipes_and_exit_status() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect(true).await?;
+    let mut session = client.open_shell(DeviceTransport::default(), "cat", false).await?;
+
+    let mut stdin = session.stdin.take().unwrap();
+    let mut stdout = BufReader::new(session.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(session.stderr.take().unwrap()).lines();
+
+    stdin.write_all(b"hello\n").await?;
+    assert_eq!(stdout.next_line().await?, Some("HELLO".to_string()));
+    assert_eq!(stderr.next_line().await?, Some("warn".to_string()));
+
+    drop(stdin);
+    assert_eq!(session.wait().await?, Some(3));
+    assert_eq!(stdout.next_line().await?, None);
+    assert_eq!(session.wait().await?, Some(3));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_open_shell_without_shell_v2() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect(false).await?;
+    let mut session = client.open_shell(DeviceTransport::default(), "cat", false).await?;
+    assert!(session.stderr.is_none());
+
+    let mut stdout = session.stdout.take().unwrap();
+    let mut stdin = session.stdin.take().unwrap();
+    stdin.write_all(b"raw bytes").await?;
+    stdin.shutdown().await?;
+
+    let mut output = String::new();
+    stdout.read_to_string(&mut output).await?;
+    // exec: carries stdout only.
+    assert_eq!(output, "RAW BYTES");
+    assert_eq!(session.wait().await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_open_shell_reports_failure() -> Result<(), Box<dyn std::error::Error>> {
+    let client = connect(true).await?;
+    let error = client.open_shell(DeviceTransport::serial("R58M123ABC".to_string()), "cat", false).await.err().expect("open_shell should fail");
+    assert_eq!(error.to_string(), "device 'R58M123ABC' not found");
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use std::time::Duration;
+use adbr::{shell_quote, Client, DeviceTransport, Interrupted, MockServer, VirtualDevice};
+use futures::StreamExt;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+
+const RUNNER: &str = "com.example.test/androidx.test.runner.AndroidJUnitRunner";
+
+/// Serves a wedged device: `host:devices` is never answered, `logcat` and the instrumentation of
+/// [`RUNNER`] never finish and pulling `/sdcard/big.bin` stops after its first DATA chunk.
+async fn start_server() -> u16 {
+    let server = MockServer::new();
+    server.stall("host:devices");
+    let quiet = |_: &[u8]| (Vec::new(), Vec::new());
+    server.add_device(&VirtualDevice::new("emulator-5554")
+        .interactive("logcat", "--------- beginning of main\n", 0, quiet)
+        .interactive("export ANDROID_LOG_TAGS=\"''\"; exec logcat -B ", "", 0, quiet)
+        .interactive(&format!("am instrument -w -r {}", shell_quote(RUNNER)), "INSTRUMENTATION_STATUS: numtests=1\n", 0, quiet)
+        .stalled_file("/sdcard/big.bin", "abcd"));
+    server.listen().await.unwrap().port()
+}
+
+async fn connect(port: u16) -> Client {
+    let mut client = Client::new(Some("127.0.0.1".to_string()), Some(port)).await.unwrap();
+    client.set_io_timeout(Some(Duration::from_millis(200)));
+    client
+}
+
+#[tokio::test]
+async fn test_unresponsive_server_times_out() {
+    let port = start_server().await;
+    let mut client = connect(port).await;
+
+    let started = Instant::now();
+    let error = client.adb_devices().await.expect_err("devices should time out");
+    assert!(matches!(error.downcast_ref::<Interrupted>(), Some(Interrupted::TimedOut { .. })), "{}", error);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_streams_are_bound_by_the_deadline_only() {
+    let port = start_server().await;
+    let mut client = connect(port).await;
+    client.set_deadline(Some(Instant::now() + Duration::from_millis(600)));
+
+    let started = Instant::now();
+    let mut output = Vec::new();
+    let error = client.adb_shell_to_writer(DeviceTransport::default(), "logcat", &mut output).await.expect_err("logcat should hit the deadline");
+    assert!(matches!(error.downcast_ref::<Interrupted>(), Some(Interrupted::TimedOut { .. })), "{}", error);
+    // A quiet log is not an I/O timeout.
+    assert!(started.elapsed() >= Duration::from_millis(600));
+    assert_eq!(output, b"--------- beginning of main\n");
+}
+
+#[tokio::test]
+async fn test_logcat_entries_are_bound_by_the_deadline() {
+    let port = start_server().await;
+    let mut client = connect(port).await;
+    client.set_deadline(Some(Instant::now() + Duration::from_millis(400)));
+
+    let started = Instant::now();
+    let entries = client.adb_logcat_entries(DeviceTransport::default(), "").await.unwrap();
+    futures::pin_mut!(entries);
+    let error = entries.next().await.expect("an error, not the end of the log").expect_err("logcat should hit the deadline");
+    assert!(matches!(error.downcast_ref::<Interrupted>(), Some(Interrupted::TimedOut { .. })), "{}", error);
+    assert!(started.elapsed() >= Duration::from_millis(400));
+    assert!(entries.next().await.is_none());
+}
+
+#[tokio::test]
+async fn test_cancellation_interrupts_instrumentation() {
+    let port = start_server().await;
+    let mut client = connect(port).await;
+    let token = CancellationToken::new();
+    client.set_cancellation_token(Some(token.clone()));
+
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        cancel.cancel();
+    });
+    let error = client.adb_instrument(DeviceTransport::default(), RUNNER, &[], |_| {}).await.expect_err("instrumentation should be cancelled");
+    assert!(matches!(error.downcast_ref::<Interrupted>(), Some(Interrupted::Cancelled)), "{}", error);
+}
+
+#[tokio::test]
+async fn test_cancellation_interrupts_pending_reads() {
+    let port = start_server().await;
+    let mut client = connect(port).await;
+    client.set_io_timeout(None);
+    let token = CancellationToken::new();
+    client.set_cancellation_token(Some(token.clone()));
+
+    let cancel = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        cancel.cancel();
+    });
+    let error = client.adb_devices().await.expect_err("devices should be cancelled");
+    assert!(matches!(error.downcast_ref::<Interrupted>(), Some(Interrupted::Cancelled)), "{}", error);
+}
+
+#[tokio::test]
+async fn test_interrupted_pull_removes_partial_file() -> Result<(), Box<dyn std::error::Error>> {
+    let port = start_server().await;
+    let dir = std::env::temp_dir().join(format!("adbr-partial-pull-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("big.bin");
+
+    let mut client = connect(port).await;
+    let error = client.adb_pull(DeviceTransport::default(), &["/sdcard/big.bin".to_string()], path.to_str().unwrap(), false).await
+        .expect_err("pull should time out");
+    assert!(error.to_string().starts_with("Timed out"), "{}", error);
+    assert!(!path.exists(), "partial file was left behind");
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
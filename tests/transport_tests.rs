@@ -0,0 +1,96 @@
+use adbr::{Client, ClientBuilder, DeviceTransport, MemoryTransport, MockServer, VirtualDevice};
+
+/// A server with one emulator.
+fn mock_server() -> MockServer {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("emulator-5554"));
+    server
+}
+
+#[tokio::test]
+async fn test_client_over_memory_transport() -> Result<(), Box<dyn std::error::Error>> {
+    let mut client = ClientBuilder::new().transport(mock_server().transport()).build().await?;
+    assert_eq!(client.server(), "memory");
+    assert_eq!(client.server_port(), None);
+    assert!(client.adb_devices().await?.contains("emulator-5554"));
+
+    // Every service needs a fresh connection from the same transport.
+    client.reconnect().await?;
+    assert_eq!(client.adb_get_state(DeviceTransport::default()).await?, "device");
+    let mut other = client.new_connection().await?;
+    assert!(other.adb_devices().await?.contains("emulator-5554"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_is_connected_notices_closed_server() -> Result<(), Box<dyn std::error::Error>> {
+    let (transport, mut listener) = MemoryTransport::pair();
+    let mut client = ClientBuilder::new().transport(transport).build().await?;
+    let server = listener.accept().await.expect("client connection");
+    assert!(client.is_connected().await);
+
+    drop(server);
+    assert!(!client.is_connected().await);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_memory_transport_refuses_without_listener() {
+    let (transport, listener) = MemoryTransport::pair();
+    drop(listener);
+    assert!(ClientBuilder::new().transport(transport).build().await.is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_client_over_unix_socket() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("adbr-transport-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("adb.sock");
+    let (server, listener) = (mock_server(), tokio::net::UnixListener::bind(&path)?);
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let server = server.clone();
+            tokio::spawn(async move { server.serve(stream).await });
+        }
+    });
+
+    let spec = format!("localfilesystem:{}", path.display());
+    let mut client = Client::builder().server_socket(&spec)?.build().await?;
+    assert_eq!(client.server(), spec);
+    assert!(client.adb_devices().await?.contains("emulator-5554"));
+    client.reconnect().await?;
+    assert_eq!(client.adb_get_state(DeviceTransport::default()).await?, "device");
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_server_socket_specs() -> Result<(), Box<dyn std::error::Error>> {
+    let port = mock_server().listen().await?.port();
+
+    let mut client = Client::builder().server_socket(&format!("tcp:{}", port))?.build().await?;
+    assert_eq!(client.server(), format!("127.0.0.1:{}", port));
+    assert!(client.adb_devices().await?.contains("emulator-5554"));
+    let client = Client::builder().server_socket(&format!("tcp:127.0.0.1:{}", port))?.build().await?;
+    assert_eq!(client.server_port(), Some(port));
+
+    assert!(Client::builder().server_socket("tcp:localhost").is_err());
+    assert!(Client::builder().server_socket("vsock:1:5037").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_switching_transports_records_transport_id() -> Result<(), Box<dyn std::error::Error>> {
+    let server = MockServer::new();
+    server.add_device(&VirtualDevice::new("emulator-5554"));
+    let usb_id = server.add_device(&VirtualDevice::new("R58M123ABC").shell_output("echo hi", "hi\n"));
+    let mut client = server.client().await?;
+    assert_eq!(client.transport_id(), None);
+
+    assert_eq!(client.adb_shell_output(DeviceTransport::serial("R58M123ABC".to_string()), "echo hi").await?, "hi\n");
+    assert_eq!(client.transport_id(), Some(usb_id));
+    assert!(server.requests().iter().any(|request| request == "host:tport:serial:R58M123ABC"));
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use std::error::Error;
+use std::io::ErrorKind;
+use std::time::Duration;
+use openssl::base64::decode_block;
+use openssl::bn::{BigNum, BigNumContext};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+use adbr::constants::{A_AUTH, A_CNXN, A_OKAY, A_OPEN, A_VERSION, A_VERSION_MIN, A_WRTE, ADB_AUTH_TOKEN, DELAYED_ACK_FEATURE};
+use adbr::{AdbKey, AdbPublicKey, AdbServer, DeviceAuth, DeviceBanner, DeviceLink, HostAuth, MockAdbd, Packet, VirtualDevice};
+
+const WAIT: Duration = Duration::from_secs(10);
+
+fn banner(kind: &str, delayed_ack: bool) -> DeviceBanner {
+    let features = if delayed_ack { vec!["shell_v2".to_string(), DELAYED_ACK_FEATURE.to_string()] } else { vec!["shell_v2".to_string()] };
+    DeviceBanner::new(kind).features(&features)
+}
+
+/// A device end that echoes every stream it accepts back to the host.
+async fn echo_device(stream: DuplexStream, delayed_ack: bool) -> DeviceLink {
+    let (link, mut streams) = DeviceLink::accept(stream, &banner("device", delayed_ack), None).await.unwrap();
+    tokio::spawn(async move {
+        while let Some(incoming) = streams.recv().await {
+            tokio::spawn(async move {
+                let stream = incoming.accept();
+                let (mut reader, mut writer) = tokio::io::split(stream);
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    link
+}
+
+async fn read_packet(stream: &mut DuplexStream) -> Result<Packet, Box<dyn Error>> {
+    Ok(tokio::time::timeout(WAIT, Packet::read(stream, A_VERSION)).await??.ok_or("stream ended")?)
+}
+
+#[tokio::test]
+async fn test_packet_header_is_validated() -> Result<(), Box<dyn Error>> {
+    let packet = Packet::new(A_WRTE, 1, 2, b"hello".to_vec());
+    let bytes = packet.encode();
+    assert_eq!(bytes.len(), 24 + 5);
+    assert_eq!(&bytes[16..20], &(b"hello".iter().map(|&byte| byte as u32).sum::<u32>()).to_le_bytes());
+    assert_eq!(&bytes[20..24], &(A_WRTE ^ 0xffff_ffff).to_le_bytes());
+    assert_eq!(Packet::read(&mut bytes.as_slice(), A_VERSION_MIN).await?, Some(packet));
+    assert_eq!(Packet::read(&mut &[][..], A_VERSION_MIN).await?, None);
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[20] ^= 1;
+    let err = Packet::read(&mut bad_magic.as_slice(), A_VERSION).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    assert!(err.to_string().contains("bad magic"), "{}", err);
+
+    // Checksums only count before the version that dropped them.
+    let mut bad_checksum = bytes.clone();
+    bad_checksum[16] ^= 1;
+    let err = Packet::read(&mut bad_checksum.as_slice(), A_VERSION_MIN).await.unwrap_err();
+    assert!(err.to_string().contains("bad checksum"), "{}", err);
+    assert!(Packet::read(&mut bad_checksum.as_slice(), A_VERSION).await?.is_some());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_cnxn_negotiates_version_and_payload() -> Result<(), Box<dyn Error>> {
+    let (mut host, device) = tokio::io::duplex(64 * 1024);
+    let accepted = tokio::spawn(async move { DeviceLink::accept(device, &banner("device", false), None).await });
+    host.write_all(&Packet::new(A_CNXN, A_VERSION_MIN, 4096, b"host::features=shell_v2".to_vec()).encode()).await?;
+    let cnxn = read_packet(&mut host).await?;
+    assert_eq!((cnxn.command, cnxn.arg0, cnxn.arg1), (A_CNXN, A_VERSION_MIN, 4096));
+    assert_eq!(DeviceBanner::parse(&String::from_utf8_lossy(&cnxn.data)).kind, "device");
+    let (link, _streams) = accepted.await??;
+    assert_eq!((link.version(), link.max_payload(), link.delayed_ack()), (A_VERSION_MIN, 4096, false));
+    assert_eq!(link.peer().features, vec!["shell_v2".to_string()]);
+
+    let (mut host, device) = tokio::io::duplex(64 * 1024);
+    let accepted = tokio::spawn(async move { DeviceLink::accept(device, &banner("device", false), None).await });
+    host.write_all(&Packet::new(A_CNXN, 0x00ff_ffff, 4096, b"host::".to_vec()).encode()).await?;
+    let err = accepted.await?.unwrap_err();
+    assert!(err.to_string().contains("unsupported protocol version"), "{}", err);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_streams_move_data_with_and_without_delayed_acks() -> Result<(), Box<dyn Error>> {
+    let data: Vec<u8> = (0..2 * 1024 * 1024).map(|index| (index % 253) as u8).collect();
+    for delayed_ack in [false, true] {
+        let (host, device) = tokio::io::duplex(64 * 1024);
+        let device = tokio::spawn(echo_device(device, delayed_ack));
+        let link = DeviceLink::connect(host, &banner("host", delayed_ack), &HostAuth::new()).await?;
+        let _device = device.await?;
+        assert_eq!(link.delayed_ack(), delayed_ack);
+
+        // Two streams at once, to keep the ids apart.
+        let mut first = link.open("echo:1").await?;
+        let mut second = link.open("echo:2").await?;
+        second.write_all(b"second").await?;
+        let (mut reader, mut writer) = tokio::io::split(first);
+        let sent = data.clone();
+        let writing = tokio::spawn(async move { writer.write_all(&sent).await.map(|_| writer) });
+        let mut echoed = vec![0u8; data.len()];
+        tokio::time::timeout(WAIT, reader.read_exact(&mut echoed)).await??;
+        assert!(echoed == data, "echoed data differs with delayed_ack={}", delayed_ack);
+        let mut writer = writing.await??;
+        let mut reply = [0u8; 6];
+        tokio::time::timeout(WAIT, second.read_exact(&mut reply)).await??;
+        assert_eq!(&reply, b"second");
+
+        writer.shutdown().await?;
+        first = reader.unsplit(writer);
+        let mut rest = Vec::new();
+        tokio::time::timeout(WAIT, first.read_to_end(&mut rest)).await??;
+        assert!(rest.is_empty());
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_delayed_acks_count_bytes() -> Result<(), Box<dyn Error>> {
+    let (mut host, device) = tokio::io::duplex(64 * 1024);
+    let device = tokio::spawn(echo_device(device, true));
+    host.write_all(&Packet::new(A_CNXN, A_VERSION, 1024 * 1024, banner("host", true).to_string().into_bytes()).encode()).await?;
+    assert_eq!(read_packet(&mut host).await?.command, A_CNXN);
+    assert!(device.await?.delayed_ack());
+
+    // arg1 of OPEN is how much the device may send us before our first ack.
+    host.write_all(&Packet::new(A_OPEN, 7, 1000, b"echo:\0".to_vec()).encode()).await?;
+    let okay = read_packet(&mut host).await?;
+    assert_eq!((okay.command, okay.arg1), (A_OKAY, 7));
+    let remote_id = okay.arg0;
+    assert_eq!(okay.data.len(), 4, "the OKAY for OPEN carries the device's window");
+
+    host.write_all(&Packet::new(A_WRTE, 7, remote_id, vec![b'x'; 3000]).encode()).await?;
+    let mut echoed = 0;
+    let mut acked = 0;
+    while echoed < 3000 || acked < 3000 {
+        let packet = read_packet(&mut host).await?;
+        match packet.command {
+            A_OKAY => acked += u32::from_le_bytes(packet.data[..4].try_into()?),
+            A_WRTE => {
+                echoed += packet.data.len();
+                host.write_all(&Packet::new(A_OKAY, 7, remote_id, (packet.data.len() as u32).to_le_bytes().to_vec()).encode()).await?;
+            }
+            other => panic!("unexpected packet {:#010x}", other),
+        }
+    }
+    assert_eq!((echoed, acked), (3000, 3000));
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_public_keys_use_the_android_format() -> Result<(), Box<dyn Error>> {
+    let key = AdbKey::generate()?;
+    let public_key = key.public_key();
+    let bytes = decode_block(&public_key.to_string())?;
+    assert_eq!(bytes.len(), 524);
+    let word = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    assert_eq!(word(0), 64);
+    assert_eq!(word(4).wrapping_mul(word(8)), u32::MAX, "n0inv is -1 / n mod 2^32");
+    assert_eq!(word(520), 65537);
+    let little_endian = |range: std::ops::Range<usize>| {
+        let mut bytes = bytes[range].to_vec();
+        bytes.reverse();
+        BigNum::from_slice(&bytes).unwrap()
+    };
+    let (n, rr) = (little_endian(8..264), little_endian(264..520));
+    let mut r = BigNum::new()?;
+    r.set_bit(4096)?;
+    let mut expected = BigNum::new()?;
+    let mut context = BigNumContext::new()?;
+    expected.nnmod(&r, &n, &mut context)?;
+    assert_eq!(rr, expected);
+
+    let parsed = AdbPublicKey::parse(&format!("{} someone@somewhere\0", public_key))?;
+    assert_eq!(&parsed, public_key);
+    let token = [7u8; 20];
+    let signature = key.sign(&token)?;
+    assert!(parsed.verify(&token, &signature));
+    assert!(!parsed.verify(&[8u8; 20], &signature));
+    assert!(!AdbKey::generate()?.public_key().verify(&token, &signature));
+    assert!(AdbPublicKey::parse("not-a-key").is_err());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auth_signs_tokens_with_known_keys() -> Result<(), Box<dyn Error>> {
+    let dir = std::env::temp_dir().join(format!("adbr-wire-{}", std::process::id()));
+    std::fs::create_dir_all(&dir)?;
+    // A key as `adbr keygen` writes it, tried after one the device does not know.
+    let key_path = dir.join("adbkey");
+    let server = AdbServer::new();
+    server.client().await?.adb_keygen(Some(&key_path.to_string_lossy()))?;
+    let key = AdbKey::load(&key_path)?;
+
+    let auth = DeviceAuth::new();
+    auth.authorize(key.public_key());
+    let adbd = MockAdbd::new(&VirtualDevice::new("secure").file("/sdcard/hello.txt", "hello\n")).auth(auth);
+    let address = adbd.listen().await?.to_string();
+
+    let host_auth = HostAuth::new().key(AdbKey::generate()?).key(key);
+    let link = DeviceLink::dial(&address, &banner("host", true), &host_auth).await?;
+    assert_eq!(link.peer().kind, "device");
+    let mut shell = link.open("shell:cat /sdcard/hello.txt").await?;
+    let mut output = String::new();
+    tokio::time::timeout(WAIT, shell.read_to_string(&mut output)).await??;
+    assert_eq!(output, "hello\n");
+
+    // The server signs with the same keys; without them the device turns it away.
+    let authenticated = AdbServer::with_auth(HostAuth::load(&[key_path])?);
+    assert_eq!(authenticated.connect_device(&address).await?, format!("connected to {}", address));
+    let err = server.connect_device(&address).await.unwrap_err();
+    assert_eq!(err, format!("failed to authenticate to {}", address));
+    let _ = std::fs::remove_dir_all(&dir);
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_auth_offers_the_public_key_when_no_key_is_known() -> Result<(), Box<dyn Error>> {
+    let key = AdbKey::generate()?;
+
+    // The user allows the new key on the device.
+    let auth = DeviceAuth::new().allow_new_keys(true);
+    let adbd = MockAdbd::new(&VirtualDevice::new("new-key")).auth(auth.clone());
+    let address = adbd.listen().await?.to_string();
+    DeviceLink::dial(&address, &banner("host", true), &HostAuth::new().key(key.clone())).await?;
+    assert_eq!(auth.authorized_keys(), vec![key.public_key().clone()]);
+
+    // Nobody allows it; the host gives up once the prompt times out.
+    let adbd = MockAdbd::new(&VirtualDevice::new("locked")).auth(DeviceAuth::new());
+    let address = adbd.listen().await?.to_string();
+    let host_auth = HostAuth::new().key(key).prompt_timeout(Duration::from_millis(200));
+    let err = DeviceLink::dial(&address, &banner("host", true), &host_auth).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+    assert!(err.to_string().contains("not allowed"), "{}", err);
+
+    let err = DeviceLink::dial(&address, &banner("host", true), &HostAuth::new()).await.unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::PermissionDenied);
+
+    // Devices that ask for AUTH start with a 20-byte token.
+    let mut stream = tokio::net::TcpStream::connect(&address).await?;
+    stream.write_all(&Packet::new(A_CNXN, A_VERSION, 4096, b"host::".to_vec()).encode()).await?;
+    let token = tokio::time::timeout(WAIT, Packet::read(&mut stream, A_VERSION)).await??.ok_or("no token")?;
+    assert_eq!((token.command, token.arg0, token.data.len()), (A_AUTH, ADB_AUTH_TOKEN, 20));
+    Ok(())
+}